@@ -0,0 +1,635 @@
+use crate::SessionRecorder;
+use anyhow::Result;
+use cs2_common::policy_protocol::{decode_movement, decode_outputs, encode_inputs, MAX_FRAME_LEN};
+use cs2_common::{
+    CS2Error, ErrorReply, Frame, Handshake, InputVector, MessageType, MovementVector, OutputVector,
+    PROTOCOL_MAGIC, PROTOCOL_VERSION,
+};
+use std::collections::HashMap;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+use tokio::runtime::{Handle, Runtime};
+use tokio::sync::oneshot;
+
+/// Timeouts and reconnect policy of an [`AsyncPolicyClient`]
+#[derive(Debug, Clone)]
+pub struct ClientConfig {
+    pub connect_timeout: Duration, // Per connection attempt, handshake included
+    pub request_timeout: Duration, // From sending a request to its reply
+    pub initial_backoff: Duration, // Wait after the first failed connection attempt
+    pub max_backoff: Duration,     // Cap for the doubling backoff
+    pub connect_attempts: usize,   // Attempts before a request gives up
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Duration::from_secs(2),
+            request_timeout: Duration::from_millis(500),
+            initial_backoff: Duration::from_millis(50),
+            max_backoff: Duration::from_secs(2),
+            connect_attempts: 5,
+        }
+    }
+}
+
+type Reply = std::result::Result<Frame, String>;
+type Pending = Arc<Mutex<HashMap<u32, oneshot::Sender<Reply>>>>;
+
+/// One open framed session; requests are matched to replies by request ID
+struct Connection {
+    writer: tokio::sync::Mutex<OwnedWriteHalf>,
+    pending: Pending,
+    alive: Arc<AtomicBool>,
+    handshake: Handshake,
+    dispatcher: tokio::task::JoinHandle<()>,
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        // Closes the read half of a connection retired while the server is silent
+        self.dispatcher.abort();
+    }
+}
+
+impl Connection {
+    async fn open(addrs: &[SocketAddr], timeout: Duration) -> Result<Self> {
+        let stream = tokio::time::timeout(timeout, TcpStream::connect(addrs))
+            .await
+            .map_err(|_| CS2Error::NetworkError("connect timed out".into()))?
+            .map_err(|e| {
+                CS2Error::NetworkError(format!("Failed to connect to policy server: {}", e))
+            })?;
+        stream.set_nodelay(true)?;
+        let (mut reader, mut writer) = stream.into_split();
+
+        let handshake = tokio::time::timeout(timeout, async {
+            let mut hello = PROTOCOL_MAGIC.to_vec();
+            hello.extend(Frame::new(MessageType::Hello, 0, 0, Vec::new()).encode());
+            writer.write_all(&hello).await?;
+            let reply = read_frame(&mut reader).await?;
+            if reply.kind == MessageType::Error {
+                return Err(ErrorReply::decode(&reply.payload)?.into());
+            }
+            Ok::<_, anyhow::Error>(Handshake::decode(&reply.payload)?)
+        })
+        .await
+        .map_err(|_| CS2Error::NetworkError("handshake timed out".into()))??;
+
+        let pending: Pending = Arc::default();
+        let alive = Arc::new(AtomicBool::new(true));
+        let dispatcher = tokio::spawn(dispatch_replies(reader, pending.clone(), alive.clone()));
+        Ok(Self {
+            writer: tokio::sync::Mutex::new(writer),
+            pending,
+            alive,
+            handshake,
+            dispatcher,
+        })
+    }
+
+    fn is_alive(&self) -> bool {
+        self.alive.load(Ordering::SeqCst)
+    }
+
+    /// Send a request and wait for its reply; other requests may be in flight
+    ///
+    /// A write that fails or times out may leave a partial frame on the wire,
+    /// and a reply that does not arrive in time means the server stalled or
+    /// the link silently died, so both retire the connection. Other requests
+    /// in flight keep waiting for their replies; new ones reconnect.
+    async fn request(&self, frame: Frame, timeout: Duration) -> Result<Frame> {
+        let start = Instant::now();
+        let request_id = frame.request_id;
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().unwrap().insert(request_id, tx);
+
+        let written = tokio::time::timeout(timeout, async {
+            self.writer.lock().await.write_all(&frame.encode()).await
+        })
+        .await;
+        if !matches!(written, Ok(Ok(()))) {
+            self.pending.lock().unwrap().remove(&request_id);
+            self.alive.store(false, Ordering::SeqCst);
+            return Err(
+                CS2Error::NetworkError(format!("Failed to send request {request_id}")).into(),
+            );
+        }
+
+        match tokio::time::timeout(timeout.saturating_sub(start.elapsed()), rx).await {
+            Ok(Ok(Ok(frame))) => Ok(frame),
+            Ok(Ok(Err(e))) => Err(CS2Error::NetworkError(e).into()),
+            Ok(Err(_)) => Err(CS2Error::NetworkError("connection closed".into()).into()),
+            Err(_) => {
+                self.pending.lock().unwrap().remove(&request_id);
+                self.alive.store(false, Ordering::SeqCst);
+                Err(CS2Error::NetworkError(format!(
+                    "request {request_id} timed out after {timeout:?}"
+                ))
+                .into())
+            }
+        }
+    }
+}
+
+async fn read_frame(reader: &mut OwnedReadHalf) -> Result<Frame> {
+    let mut len = [0u8; 4];
+    reader.read_exact(&mut len).await?;
+    let body_len = u32::from_le_bytes(len) as usize;
+    if body_len > MAX_FRAME_LEN {
+        return Err(CS2Error::ProtocolError(format!("frame of {body_len} bytes")).into());
+    }
+    let mut bytes = len.to_vec();
+    bytes.resize(4 + body_len, 0);
+    reader.read_exact(&mut bytes[4..]).await?;
+    Ok(Frame::read_from(&mut bytes.as_slice())?)
+}
+
+/// Route replies to their waiting requests until the connection breaks
+async fn dispatch_replies(mut reader: OwnedReadHalf, pending: Pending, alive: Arc<AtomicBool>) {
+    let error = loop {
+        match read_frame(&mut reader).await {
+            Ok(frame) => {
+                if let Some(tx) = pending.lock().unwrap().remove(&frame.request_id) {
+                    let _ = tx.send(Ok(frame));
+                }
+            }
+            Err(e) => break e.to_string(),
+        }
+    };
+    alive.store(false, Ordering::SeqCst);
+    for (_, tx) in pending.lock().unwrap().drain() {
+        let _ = tx.send(Err(error.clone()));
+    }
+}
+
+/// Async client for the framed policy protocol
+///
+/// Requests from any number of tasks are pipelined over one connection. A
+/// broken connection is reopened on the next request, waiting with a doubling
+/// backoff between failed attempts; requests that were in flight when it broke
+/// fail and are not retried. Server error replies come back as an
+/// [`ErrorReply`] inside the `anyhow::Error`.
+pub struct AsyncPolicyClient {
+    addrs: Vec<SocketAddr>,
+    config: ClientConfig,
+    connection: tokio::sync::Mutex<Option<Arc<Connection>>>,
+    next_request_id: AtomicU32,
+    reconnects: AtomicU32,
+    recorder: Option<SessionRecorder>,
+}
+
+impl AsyncPolicyClient {
+    /// Connect to a policy server with the default [`ClientConfig`]
+    pub async fn connect(addr: impl ToSocketAddrs) -> Result<Self> {
+        Self::connect_with_config(addr, ClientConfig::default()).await
+    }
+
+    pub async fn connect_with_config(
+        addr: impl ToSocketAddrs,
+        config: ClientConfig,
+    ) -> Result<Self> {
+        let addrs: Vec<SocketAddr> = addr.to_socket_addrs()?.collect();
+        let client = Self {
+            addrs,
+            config,
+            connection: tokio::sync::Mutex::new(None),
+            next_request_id: AtomicU32::new(1),
+            reconnects: AtomicU32::new(0),
+            recorder: None,
+        };
+        client.live_connection().await?;
+        Ok(client)
+    }
+
+    /// What the server reported when the current connection opened
+    pub async fn handshake(&self) -> Result<Handshake> {
+        Ok(self.live_connection().await?.handshake.clone())
+    }
+
+    /// Connections reopened after the first one broke
+    pub fn reconnects(&self) -> u32 {
+        self.reconnects.load(Ordering::SeqCst)
+    }
+
+    /// Record every later prediction of this client into a session log
+    pub fn record_to(&mut self, recorder: SessionRecorder) {
+        self.recorder = Some(recorder);
+    }
+
+    pub async fn predict(&self, input: &InputVector) -> Result<OutputVector> {
+        let mut outputs = self.predict_batch(std::slice::from_ref(input)).await?;
+        outputs
+            .pop()
+            .ok_or_else(|| CS2Error::ProtocolError("empty prediction".into()).into())
+    }
+
+    pub async fn predict_batch(&self, inputs: &[InputVector]) -> Result<Vec<OutputVector>> {
+        let reply = self
+            .request(MessageType::Predict, encode_inputs(inputs))
+            .await?;
+        let outputs = decode_outputs(&reply.payload)?;
+        if outputs.len() != inputs.len() {
+            return Err(CS2Error::ProtocolError(format!(
+                "{} predictions for {} inputs",
+                outputs.len(),
+                inputs.len()
+            ))
+            .into());
+        }
+        if let Some(recorder) = &self.recorder {
+            recorder.record(reply.model_id, inputs, &outputs)?;
+        }
+        Ok(outputs)
+    }
+
+    /// Movement for the latest of a player's recent inputs, oldest first
+    pub async fn predict_movement(&self, history: &[InputVector]) -> Result<MovementVector> {
+        let reply = self
+            .request(MessageType::PredictMovement, encode_inputs(history))
+            .await?;
+        Ok(decode_movement(&reply.payload)?)
+    }
+
+    /// Round trip of a heartbeat
+    pub async fn heartbeat(&self) -> Result<Duration> {
+        let start = Instant::now();
+        self.request(MessageType::Heartbeat, Vec::new()).await?;
+        Ok(start.elapsed())
+    }
+
+    async fn request(&self, kind: MessageType, payload: Vec<u8>) -> Result<Frame> {
+        let connection = self.live_connection().await?;
+        let request_id = self.next_request_id.fetch_add(1, Ordering::SeqCst);
+        let reply = connection
+            .request(
+                Frame::new(kind, 0, request_id, payload),
+                self.config.request_timeout,
+            )
+            .await?;
+        if reply.version != PROTOCOL_VERSION {
+            return Err(CS2Error::ProtocolError(format!(
+                "server replied with version {}, expected {PROTOCOL_VERSION}",
+                reply.version
+            ))
+            .into());
+        }
+        if reply.kind == MessageType::Error {
+            return Err(ErrorReply::decode(&reply.payload)?.into());
+        }
+        Ok(reply)
+    }
+
+    /// The open connection, reconnecting with backoff if it broke
+    async fn live_connection(&self) -> Result<Arc<Connection>> {
+        let mut slot = self.connection.lock().await;
+        if let Some(connection) = slot.as_ref().filter(|c| c.is_alive()) {
+            return Ok(connection.clone());
+        }
+        let reconnecting = slot.is_some();
+
+        let mut backoff = self.config.initial_backoff;
+        let mut last_error = None;
+        for attempt in 0..self.config.connect_attempts.max(1) {
+            if attempt > 0 {
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(self.config.max_backoff);
+            }
+            match Connection::open(&self.addrs, self.config.connect_timeout).await {
+                Ok(connection) => {
+                    if reconnecting {
+                        self.reconnects.fetch_add(1, Ordering::SeqCst);
+                    }
+                    let connection = Arc::new(connection);
+                    *slot = Some(connection.clone());
+                    return Ok(connection);
+                }
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(last_error.expect("at least one attempt"))
+    }
+}
+
+/// Runtime shared by every [`BlockingPolicyClient`] of the process
+fn shared_runtime() -> &'static Runtime {
+    static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+    RUNTIME.get_or_init(|| {
+        tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .thread_name("policy-client")
+            .enable_all()
+            .build()
+            .expect("build policy client runtime")
+    })
+}
+
+/// Blocking facade over [`AsyncPolicyClient`] for synchronous callers
+///
+/// All facades of a process share one small runtime, so many agents can each
+/// hold a client without a socket thread of their own. Blocking on that
+/// runtime from a thread that is already inside a Tokio runtime, including
+/// `spawn_blocking` threads, would panic, so every method returns an error
+/// there instead; async code uses [`AsyncPolicyClient`] directly.
+pub struct BlockingPolicyClient {
+    client: AsyncPolicyClient,
+    handle: Handle,
+}
+
+impl BlockingPolicyClient {
+    pub fn connect(addr: impl ToSocketAddrs) -> Result<Self> {
+        Self::connect_with_config(addr, ClientConfig::default())
+    }
+
+    pub fn connect_with_config(addr: impl ToSocketAddrs, config: ClientConfig) -> Result<Self> {
+        let handle = shared_runtime().handle().clone();
+        let client = block_on(
+            &handle,
+            AsyncPolicyClient::connect_with_config(addr, config),
+        )?;
+        Ok(Self { client, handle })
+    }
+
+    pub fn predict(&self, input: &InputVector) -> Result<OutputVector> {
+        block_on(&self.handle, self.client.predict(input))
+    }
+
+    pub fn predict_batch(&self, inputs: &[InputVector]) -> Result<Vec<OutputVector>> {
+        block_on(&self.handle, self.client.predict_batch(inputs))
+    }
+
+    pub fn predict_movement(&self, history: &[InputVector]) -> Result<MovementVector> {
+        block_on(&self.handle, self.client.predict_movement(history))
+    }
+
+    pub fn heartbeat(&self) -> Result<Duration> {
+        block_on(&self.handle, self.client.heartbeat())
+    }
+
+    pub fn handshake(&self) -> Result<Handshake> {
+        block_on(&self.handle, self.client.handshake())
+    }
+
+    pub fn reconnects(&self) -> u32 {
+        self.client.reconnects()
+    }
+
+    pub fn record_to(&mut self, recorder: SessionRecorder) {
+        self.client.record_to(recorder);
+    }
+}
+
+/// Run `future` on the shared runtime, unless this thread is inside a runtime already
+fn block_on<T>(handle: &Handle, future: impl std::future::Future<Output = Result<T>>) -> Result<T> {
+    if Handle::try_current().is_ok() {
+        return Err(CS2Error::NetworkError(
+            "BlockingPolicyClient used inside a Tokio runtime, use AsyncPolicyClient there".into(),
+        )
+        .into());
+    }
+    handle.block_on(future)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytemuck::Zeroable;
+    use cs2_common::policy_protocol::{decode_inputs, encode_outputs};
+    use tokio::net::TcpListener;
+
+    /// Echoes each input's yaw as delta_yaw after `ammo` milliseconds
+    ///
+    /// Negative health drops the connection and negative armor is never
+    /// answered, so tests can provoke reconnects and timeouts.
+    async fn mock_server() -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(mock_session(stream));
+            }
+        });
+        addr
+    }
+
+    async fn mock_session(stream: TcpStream) -> Result<()> {
+        let (mut reader, writer) = stream.into_split();
+        let writer = Arc::new(tokio::sync::Mutex::new(writer));
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic).await?;
+        loop {
+            let frame = read_frame(&mut reader).await?;
+            let writer = writer.clone();
+            let reply = match frame.kind {
+                MessageType::Hello => Handshake {
+                    model_id: 3,
+                    model_name: "echo".to_string(),
+                    input_schema: Vec::new(),
+                    input_dim: 12,
+                    output_dim: 2,
+                    max_batch: 16,
+                    sequence_length: None,
+                }
+                .encode(),
+                MessageType::Predict => {
+                    let inputs = decode_inputs(&frame.payload)?;
+                    if inputs[0].health < 0.0 {
+                        return Ok(());
+                    }
+                    if inputs[0].armor < 0.0 {
+                        continue;
+                    }
+                    let outputs: Vec<OutputVector> = inputs
+                        .iter()
+                        .map(|i| OutputVector {
+                            delta_yaw: i.yaw,
+                            delta_pitch: 0.0,
+                        })
+                        .collect();
+                    let delay = Duration::from_millis(inputs[0].ammo as u64);
+                    tokio::spawn(async move {
+                        tokio::time::sleep(delay).await;
+                        let reply = Frame::new(
+                            MessageType::Prediction,
+                            3,
+                            frame.request_id,
+                            encode_outputs(&outputs),
+                        );
+                        let _ = writer.lock().await.write_all(&reply.encode()).await;
+                    });
+                    continue;
+                }
+                _ => frame.payload,
+            };
+            let kind = match frame.kind {
+                MessageType::Hello => MessageType::Handshake,
+                other => other,
+            };
+            let reply = Frame::new(kind, 3, frame.request_id, reply);
+            writer.lock().await.write_all(&reply.encode()).await?;
+        }
+    }
+
+    fn input(yaw: f32, ammo: f32) -> InputVector {
+        InputVector {
+            yaw,
+            ammo,
+            ..InputVector::zeroed()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pipelined_requests_get_their_own_replies() {
+        let addr = mock_server().await;
+        let client = Arc::new(AsyncPolicyClient::connect(addr).await.unwrap());
+        assert_eq!(client.handshake().await.unwrap().model_name, "echo");
+
+        // Earlier requests are answered last, so replies arrive out of order
+        let mut tasks = tokio::task::JoinSet::new();
+        for i in 0..10 {
+            let client = client.clone();
+            tasks.spawn(async move {
+                let yaw = i as f32;
+                let output = client.predict(&input(yaw, (10 - i) as f32 * 5.0)).await;
+                (yaw, output.unwrap().delta_yaw)
+            });
+        }
+        while let Some(result) = tasks.join_next().await {
+            let (sent, received) = result.unwrap();
+            assert_eq!(sent, received);
+        }
+        assert!(client.heartbeat().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_reconnects_after_dropped_connection() {
+        let addr = mock_server().await;
+        let client = AsyncPolicyClient::connect(addr).await.unwrap();
+        let dropped = InputVector {
+            health: -1.0,
+            ..InputVector::zeroed()
+        };
+        assert!(client.predict(&dropped).await.is_err());
+
+        let output = client.predict(&input(12.0, 0.0)).await.unwrap();
+        assert_eq!(output.delta_yaw, 12.0);
+        assert_eq!(client.reconnects(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_request_timeout() {
+        let addr = mock_server().await;
+        let config = ClientConfig {
+            request_timeout: Duration::from_millis(50),
+            ..Default::default()
+        };
+        let client = AsyncPolicyClient::connect_with_config(addr, config)
+            .await
+            .unwrap();
+        let ignored = InputVector {
+            armor: -1.0,
+            ..InputVector::zeroed()
+        };
+        let start = Instant::now();
+        assert!(client.predict(&ignored).await.is_err());
+        assert!(start.elapsed() < Duration::from_millis(500));
+
+        // A timeout retires the connection, the next request reconnects
+        assert!(client.predict(&input(1.0, 0.0)).await.is_ok());
+        assert_eq!(client.reconnects(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_silent_server_is_reconnected() {
+        // Completes the handshake of every connection, then never answers
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let (mut reader, mut writer) = stream.into_split();
+                    let mut magic = [0u8; 4];
+                    reader.read_exact(&mut magic).await?;
+                    let hello = read_frame(&mut reader).await?;
+                    let handshake = Handshake {
+                        model_id: 9,
+                        model_name: "silent".to_string(),
+                        input_schema: Vec::new(),
+                        input_dim: 12,
+                        output_dim: 2,
+                        max_batch: 16,
+                        sequence_length: None,
+                    };
+                    let reply = Frame::new(
+                        MessageType::Handshake,
+                        9,
+                        hello.request_id,
+                        handshake.encode(),
+                    );
+                    writer.write_all(&reply.encode()).await?;
+                    while read_frame(&mut reader).await.is_ok() {}
+                    Ok::<_, anyhow::Error>(())
+                });
+            }
+        });
+
+        let config = ClientConfig {
+            request_timeout: Duration::from_millis(50),
+            ..Default::default()
+        };
+        let client = AsyncPolicyClient::connect_with_config(addr, config)
+            .await
+            .unwrap();
+        for attempt in 0..3 {
+            assert!(client.heartbeat().await.is_err());
+            assert_eq!(client.reconnects(), attempt);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_blocking_facade_refuses_async_context() {
+        let addr = mock_server().await;
+        let err = BlockingPolicyClient::connect(addr).err().unwrap();
+        assert!(err.to_string().contains("AsyncPolicyClient"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn test_connect_gives_up_after_backoff() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+        let config = ClientConfig {
+            initial_backoff: Duration::from_millis(10),
+            connect_attempts: 3,
+            ..Default::default()
+        };
+        let start = Instant::now();
+        assert!(AsyncPolicyClient::connect_with_config(addr, config)
+            .await
+            .is_err());
+        // Two waits of 10 and 20 ms between the three attempts
+        assert!(start.elapsed() >= Duration::from_millis(30));
+    }
+
+    #[test]
+    fn test_blocking_facade_serves_many_agents() {
+        let addr = shared_runtime().block_on(mock_server());
+        let agents: Vec<_> = (0..10)
+            .map(|agent| {
+                std::thread::spawn(move || {
+                    let client = BlockingPolicyClient::connect(addr).unwrap();
+                    (0..5).all(|step| {
+                        let yaw = (agent * 10 + step) as f32;
+                        client.predict(&input(yaw, 1.0)).unwrap().delta_yaw == yaw
+                    })
+                })
+            })
+            .collect();
+        assert!(agents.into_iter().all(|agent| agent.join().unwrap()));
+    }
+}
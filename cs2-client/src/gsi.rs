@@ -0,0 +1,412 @@
+use crate::PlayerState;
+use anyhow::Result;
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::post;
+use axum::Router;
+use cs2_common::{weapon_id_from_name, InputVector};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+use std::time::Instant;
+use tokio::net::TcpListener;
+
+/// Largest gap between two positions that still yields a velocity
+const MAX_VELOCITY_GAP_SECS: f64 = 1.0;
+/// Vertical speed above which a player counts as airborne
+///
+/// GSI does not report ground contact, so jumps and falls are inferred from
+/// the change in height.
+const AIRBORNE_VERTICAL_SPEED: f32 = 20.0;
+
+/// One Game State Integration POST body
+///
+/// Only the components enabled in the game's `gamestate_integration_*.cfg`
+/// are present, so every section is optional.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GsiPayload {
+    pub provider: Option<GsiProvider>,
+    pub map: Option<GsiMap>,
+    pub round: Option<GsiRound>,
+    pub player: Option<GsiPlayer>,
+    pub allplayers: Option<HashMap<String, GsiPlayer>>, // Keyed by SteamID, only while spectating
+    pub auth: Option<GsiAuth>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GsiProvider {
+    pub name: String,
+    pub appid: u32,
+    pub version: u32,
+    pub steamid: String, // The client sending the payload
+    pub timestamp: u64,  // Unix seconds
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GsiMap {
+    pub mode: Option<String>,
+    pub name: String,
+    pub phase: Option<String>, // "warmup", "live", "intermission" or "gameover"
+    pub round: Option<u32>,
+    pub team_ct: Option<GsiTeam>,
+    pub team_t: Option<GsiTeam>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GsiTeam {
+    pub score: u32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GsiRound {
+    pub phase: Option<String>, // "freezetime", "live" or "over"
+    pub bomb: Option<String>,
+    pub win_team: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GsiPlayer {
+    pub steamid: Option<String>, // Absent inside `allplayers`, where it is the key
+    pub name: Option<String>,
+    pub team: Option<String>,
+    pub activity: Option<String>,
+    pub state: Option<GsiPlayerState>,
+    #[serde(default)]
+    pub weapons: BTreeMap<String, GsiWeapon>,
+    pub position: Option<String>, // "x, y, z"
+    pub forward: Option<String>,  // Unit view vector "x, y, z"
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GsiPlayerState {
+    pub health: f32,
+    pub armor: f32,
+    pub helmet: bool,
+    pub flashed: u8,
+    pub smoked: u8,
+    pub burning: u8,
+    pub money: u32,
+    pub round_kills: u32,
+    pub equip_value: u32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GsiWeapon {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: Option<String>,
+    pub state: String, // "active", "holstered" or "reloading"
+    pub ammo_clip: Option<u32>,
+    pub ammo_reserve: Option<u32>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GsiAuth {
+    pub token: Option<String>,
+}
+
+impl GsiPlayer {
+    pub fn active_weapon(&self) -> Option<&GsiWeapon> {
+        self.weapons
+            .values()
+            .find(|w| w.state == "active" || w.state == "reloading")
+    }
+
+    pub fn position(&self) -> Option<(f32, f32, f32)> {
+        self.position.as_deref().and_then(parse_vector)
+    }
+
+    /// View angles `(yaw, pitch)` in degrees from the forward vector
+    ///
+    /// Pitch is positive when looking down, as in the engine.
+    pub fn view_angles(&self) -> Option<(f32, f32)> {
+        let (x, y, z) = self.forward.as_deref().and_then(parse_vector)?;
+        let yaw = y.atan2(x).to_degrees();
+        let pitch = -z.atan2((x * x + y * y).sqrt()).to_degrees();
+        Some((yaw, pitch))
+    }
+}
+
+fn parse_vector(text: &str) -> Option<(f32, f32, f32)> {
+    let mut parts = text.split(',').map(|p| p.trim().parse::<f32>());
+    match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(Ok(x)), Some(Ok(y)), Some(Ok(z)), None) => Some((x, y, z)),
+        _ => None,
+    }
+}
+
+/// Latest known state of one player
+#[derive(Debug, Clone)]
+pub struct TrackedPlayer {
+    pub player: GsiPlayer,
+    pub velocity: (f32, f32, f32), // Units per second, from the last two positions
+    pub updated_at: f64,           // Receiver clock, seconds
+}
+
+/// Game state assembled from successive GSI payloads
+#[derive(Debug, Clone, Default)]
+pub struct GsiState {
+    pub map: Option<GsiMap>,
+    pub round: Option<GsiRound>,
+    pub local_steamid: Option<u64>, // Player the `player` section described last
+    pub players: BTreeMap<u64, TrackedPlayer>,
+    pub payloads: u64,
+}
+
+impl GsiState {
+    /// Merge a payload received `now` seconds into the session
+    pub fn apply(&mut self, payload: GsiPayload, now: f64) {
+        self.payloads += 1;
+        if payload.map.is_some() {
+            self.map = payload.map;
+        }
+        if payload.round.is_some() {
+            self.round = payload.round;
+        }
+        if let Some(player) = payload.player {
+            self.local_steamid = player.steamid.as_deref().and_then(|id| id.parse().ok());
+            if let Some(steamid) = self.local_steamid {
+                self.track(steamid, player, now);
+            }
+        }
+        for (steamid, player) in payload.allplayers.unwrap_or_default() {
+            if let Ok(steamid) = steamid.parse() {
+                self.track(steamid, player, now);
+            }
+        }
+    }
+
+    fn track(&mut self, steamid: u64, player: GsiPlayer, now: f64) {
+        let velocity = match self.players.get(&steamid) {
+            // One payload can describe a player in both `player` and `allplayers`
+            Some(previous) if now <= previous.updated_at => previous.velocity,
+            Some(previous) if now - previous.updated_at <= MAX_VELOCITY_GAP_SECS => {
+                let dt = (now - previous.updated_at) as f32;
+                match (previous.player.position(), player.position()) {
+                    (Some((x0, y0, z0)), Some((x1, y1, z1))) => {
+                        ((x1 - x0) / dt, (y1 - y0) / dt, (z1 - z0) / dt)
+                    }
+                    _ => (0.0, 0.0, 0.0),
+                }
+            }
+            _ => (0.0, 0.0, 0.0),
+        };
+        self.players.insert(
+            steamid,
+            TrackedPlayer {
+                player,
+                velocity,
+                updated_at: now,
+            },
+        );
+    }
+
+    /// State of a player for the policy client, if position and view are known
+    pub fn player_state(&self, steamid: u64) -> Option<PlayerState> {
+        let tracked = self.players.get(&steamid)?;
+        let player = &tracked.player;
+        let state = player.state.clone().unwrap_or_default();
+        let weapon = player.active_weapon();
+        Some(PlayerState {
+            health: state.health,
+            armor: state.armor,
+            position: player.position()?,
+            velocity: tracked.velocity,
+            view_angles: player.view_angles()?,
+            weapon_id: weapon_id_from_name(weapon.map_or("none", |w| w.name.as_str())),
+            ammo: weapon.and_then(|w| w.ammo_clip).unwrap_or(0) as f32,
+            is_airborne: tracked.velocity.2.abs() > AIRBORNE_VERTICAL_SPEED,
+        })
+    }
+
+    /// State of the player the game client is playing or observing
+    pub fn local_player_state(&self) -> Option<PlayerState> {
+        self.player_state(self.local_steamid?)
+    }
+
+    pub fn input_vector(&self, steamid: u64) -> Option<InputVector> {
+        Some(self.player_state(steamid)?.to_input())
+    }
+}
+
+/// HTTP endpoint the game POSTs its state to
+///
+/// Point a `gamestate_integration_fps_genie.cfg` at the bound address; when
+/// `token` is set, payloads whose `auth.token` differs are rejected.
+#[derive(Clone)]
+pub struct GsiReceiver {
+    state: Arc<RwLock<GsiState>>,
+    token: Option<String>,
+    started: Instant,
+}
+
+impl GsiReceiver {
+    pub fn new(token: Option<String>) -> Self {
+        Self {
+            state: Arc::default(),
+            token,
+            started: Instant::now(),
+        }
+    }
+
+    /// Shared handle to the current state, updated with every payload
+    pub fn state(&self) -> Arc<RwLock<GsiState>> {
+        self.state.clone()
+    }
+
+    pub fn router(&self) -> Router {
+        Router::new()
+            .route("/", post(receive))
+            .with_state(self.clone())
+    }
+
+    /// Accept payloads on `listener` until the task is dropped
+    pub async fn serve(self, listener: TcpListener) -> Result<()> {
+        axum::serve(listener, self.router()).await?;
+        Ok(())
+    }
+
+    /// Bind `addr` and serve in the background, returning the bound address
+    pub async fn spawn(self, addr: SocketAddr) -> Result<SocketAddr> {
+        let listener = TcpListener::bind(addr).await?;
+        let local = listener.local_addr()?;
+        tokio::spawn(async move {
+            if let Err(e) = self.serve(listener).await {
+                eprintln!("GSI receiver stopped: {e}");
+            }
+        });
+        Ok(local)
+    }
+}
+
+async fn receive(State(receiver): State<GsiReceiver>, body: Bytes) -> StatusCode {
+    let payload: GsiPayload = match serde_json::from_slice(&body) {
+        Ok(payload) => payload,
+        Err(_) => return StatusCode::BAD_REQUEST,
+    };
+    if let Some(expected) = &receiver.token {
+        let token = payload.auth.as_ref().and_then(|a| a.token.as_ref());
+        if token != Some(expected) {
+            return StatusCode::UNAUTHORIZED;
+        }
+    }
+    let now = receiver.started.elapsed().as_secs_f64();
+    receiver.state.write().unwrap().apply(payload, now);
+    StatusCode::OK
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    const PLAYING: &str = include_str!("../test_data/gsi/playing.json");
+    const PLAYING_MOVED: &str = include_str!("../test_data/gsi/playing_moved.json");
+    const SPECTATING: &str = include_str!("../test_data/gsi/spectating.json");
+
+    fn payload(json: &str) -> GsiPayload {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_playing_payload() {
+        let mut state = GsiState::default();
+        state.apply(payload(PLAYING), 0.0);
+        assert_eq!(state.map.as_ref().unwrap().name, "de_mirage");
+        assert_eq!(state.local_steamid, Some(76561198000000001));
+
+        let player = state.local_player_state().unwrap();
+        assert_eq!(player.health, 100.0);
+        assert_eq!(player.position, (-1000.0, -1600.0, -168.0));
+        assert!((player.view_angles.0 - 45.0).abs() < 1e-3);
+        assert!(player.view_angles.1.abs() < 1e-3);
+        assert_eq!(player.weapon_id, weapon_id_from_name("weapon_m4a1"));
+        assert_eq!(player.ammo, 27.0);
+        assert_eq!(player.velocity, (0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_velocity_from_successive_payloads() {
+        let mut state = GsiState::default();
+        state.apply(payload(PLAYING), 10.0);
+        state.apply(payload(PLAYING_MOVED), 10.1);
+
+        let player = state.local_player_state().unwrap();
+        assert!((player.velocity.0 - 250.0).abs() < 1e-2);
+        assert_eq!(player.velocity.1, 0.0);
+        assert!(!player.is_airborne);
+        // Looking down along +x, on the pistol that is now drawn
+        assert!(player.view_angles.0.abs() < 1e-3);
+        assert!(player.view_angles.1 > 26.0);
+        assert_eq!(player.weapon_id, weapon_id_from_name("weapon_usp_silencer"));
+        assert_eq!(player.ammo, 9.0);
+
+        let input = state.input_vector(76561198000000001).unwrap();
+        assert_eq!(input.health, 74.0);
+        assert_eq!(input.vel_x, player.velocity.0);
+
+        // Too long a gap gives no velocity rather than a wrong one
+        state.apply(payload(PLAYING), 20.0);
+        assert_eq!(state.local_player_state().unwrap().velocity.0, 0.0);
+    }
+
+    #[test]
+    fn test_spectating_tracks_all_players() {
+        let mut state = GsiState::default();
+        state.apply(payload(SPECTATING), 0.0);
+        assert_eq!(state.players.len(), 3);
+        assert_eq!(state.local_steamid, Some(76561198000000002));
+
+        let observed = state.local_player_state().unwrap();
+        assert_eq!(observed.health, 42.0);
+        assert!((observed.view_angles.0.abs() - 180.0).abs() < 1e-3);
+
+        let ct = state.player_state(76561198000000001).unwrap();
+        assert!((ct.view_angles.0 - 90.0).abs() < 1e-3);
+        assert_eq!(ct.weapon_id, weapon_id_from_name("weapon_m4a1"));
+
+        let dead = state.player_state(76561198000000003).unwrap();
+        assert_eq!(dead.health, 0.0);
+        assert_eq!(dead.weapon_id, weapon_id_from_name("none"));
+
+        // The observed player appears twice per payload and keeps its velocity
+        let moved = SPECTATING.replace("1296.00, -400.00", "1246.00, -400.00");
+        state.apply(payload(&moved), 0.5);
+        let observed = state.local_player_state().unwrap();
+        assert!((observed.velocity.0 + 100.0).abs() < 1e-2);
+    }
+
+    async fn post(addr: SocketAddr, body: &str) -> String {
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let request = format!(
+            "POST / HTTP/1.1\r\nHost: {addr}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn test_receiver_accepts_payloads() {
+        let receiver = GsiReceiver::new(Some("fps-genie".to_string()));
+        let state = receiver.state();
+        let addr = receiver
+            .spawn("127.0.0.1:0".parse().unwrap())
+            .await
+            .unwrap();
+
+        assert!(post(addr, PLAYING).await.starts_with("HTTP/1.1 200"));
+        assert_eq!(state.read().unwrap().payloads, 1);
+        assert!(state.read().unwrap().local_player_state().is_some());
+
+        assert!(post(addr, "not json").await.starts_with("HTTP/1.1 400"));
+        let wrong_token = PLAYING.replace("\"fps-genie\"", "\"someone-else\"");
+        assert!(post(addr, &wrong_token).await.starts_with("HTTP/1.1 401"));
+        assert_eq!(state.read().unwrap().payloads, 1);
+    }
+}
@@ -1,28 +1,187 @@
+pub mod async_client;
+pub mod gsi;
+pub mod replay;
+
+pub use async_client::{AsyncPolicyClient, BlockingPolicyClient, ClientConfig};
+pub use gsi::{GsiPayload, GsiReceiver, GsiState};
+pub use replay::{ReplayReport, SessionRecorder};
+
 use anyhow::Result;
-use cs2_common::{CS2Error, InputVector, OutputVector};
-use std::io::{Read, Write};
+use cs2_common::policy_protocol::{decode_movement, decode_outputs, encode_inputs};
+use cs2_common::{
+    CS2Error, ErrorReply, Frame, Handshake, InputVector, MessageType, MovementVector, OutputVector,
+    PROTOCOL_MAGIC, PROTOCOL_VERSION,
+};
+use std::collections::VecDeque;
+use std::io::{BufReader, Read, Write};
 use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+/// How a client talks to the policy server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WireMode {
+    Framed, // Versioned frames with handshake, errors, batches and heartbeats
+    Legacy, // Raw 56-byte `InputVector` in, raw 8-byte `OutputVector` out
+}
 
 /// A client for connecting to the CS2 ML policy server
 pub struct PolicyClient {
     connection: TcpStream,
+    reader: BufReader<TcpStream>,
+    mode: WireMode,
+    handshake: Option<Handshake>, // Sent by the server when a framed session opens
+    model_id: Option<u32>,        // Model of the latest framed reply
+    next_request_id: u32,
+    recorder: Option<SessionRecorder>,
 }
 
 impl PolicyClient {
-    /// Connect to a policy server at the given address
+    /// Connect to a policy server at the given address and open a framed session
     pub fn connect(addr: impl ToSocketAddrs) -> Result<Self> {
+        let mut client = Self::open(addr, WireMode::Framed)?;
+        client
+            .connection
+            .write_all(&PROTOCOL_MAGIC)
+            .map_err(|e| CS2Error::NetworkError(format!("Failed to send hello: {}", e)))?;
+        let reply = client.request(MessageType::Hello, Vec::new())?;
+        let handshake = Handshake::decode(&reply.payload)?;
+        client.handshake = Some(handshake);
+        Ok(client)
+    }
+
+    /// Connect in the legacy raw mode, without handshake
+    pub fn connect_legacy(addr: impl ToSocketAddrs) -> Result<Self> {
+        Self::open(addr, WireMode::Legacy)
+    }
+
+    fn open(addr: impl ToSocketAddrs, mode: WireMode) -> Result<Self> {
         let connection = TcpStream::connect(addr).map_err(|e| {
             CS2Error::NetworkError(format!("Failed to connect to policy server: {}", e))
         })?;
 
         // Set non-blocking mode
         connection.set_nonblocking(false)?;
+        connection.set_nodelay(true)?;
+        let reader = BufReader::new(connection.try_clone()?);
+
+        Ok(Self {
+            connection,
+            reader,
+            mode,
+            handshake: None,
+            model_id: None,
+            next_request_id: 0,
+            recorder: None,
+        })
+    }
+
+    pub fn mode(&self) -> WireMode {
+        self.mode
+    }
 
-        Ok(Self { connection })
+    /// What the server reported about its model, for framed sessions
+    pub fn handshake(&self) -> Option<&Handshake> {
+        self.handshake.as_ref()
+    }
+
+    /// Model that answered the latest framed request
+    pub fn model_id(&self) -> Option<u32> {
+        self.model_id
+    }
+
+    /// Record every later prediction of this client into a session log
+    pub fn record_to(&mut self, recorder: SessionRecorder) {
+        self.recorder = Some(recorder);
     }
 
     /// Get a policy prediction for the given input
     pub fn predict(&mut self, input: &InputVector) -> Result<OutputVector> {
+        if self.mode == WireMode::Legacy {
+            return self.predict_legacy(input);
+        }
+        let mut outputs = self.predict_batch(std::slice::from_ref(input))?;
+        outputs
+            .pop()
+            .ok_or_else(|| CS2Error::ProtocolError("empty prediction".into()).into())
+    }
+
+    /// Get predictions for several inputs in one request
+    ///
+    /// Legacy sessions send the inputs one after another.
+    pub fn predict_batch(&mut self, inputs: &[InputVector]) -> Result<Vec<OutputVector>> {
+        if self.mode == WireMode::Legacy {
+            return inputs.iter().map(|i| self.predict_legacy(i)).collect();
+        }
+        let reply = self.request(MessageType::Predict, encode_inputs(inputs))?;
+        let outputs = decode_outputs(&reply.payload)?;
+        if outputs.len() != inputs.len() {
+            return Err(CS2Error::ProtocolError(format!(
+                "{} predictions for {} inputs",
+                outputs.len(),
+                inputs.len()
+            ))
+            .into());
+        }
+        if let Some(recorder) = &self.recorder {
+            recorder.record(reply.model_id, inputs, &outputs)?;
+        }
+        Ok(outputs)
+    }
+
+    /// Movement for the latest of a player's recent inputs, oldest first
+    ///
+    /// Framed sessions only; the server needs a movement model.
+    pub fn predict_movement(&mut self, history: &[InputVector]) -> Result<MovementVector> {
+        if self.mode == WireMode::Legacy {
+            return Err(CS2Error::ProtocolError("legacy sessions have no movement".into()).into());
+        }
+        let reply = self.request(MessageType::PredictMovement, encode_inputs(history))?;
+        Ok(decode_movement(&reply.payload)?)
+    }
+
+    /// Round trip of a heartbeat, framed sessions only
+    pub fn heartbeat(&mut self) -> Result<Duration> {
+        if self.mode == WireMode::Legacy {
+            return Err(CS2Error::ProtocolError("legacy sessions have no heartbeat".into()).into());
+        }
+        let start = Instant::now();
+        self.request(MessageType::Heartbeat, Vec::new())?;
+        Ok(start.elapsed())
+    }
+
+    /// Send one frame and wait for the reply carrying the same request ID
+    ///
+    /// Error replies come back as an `ErrorReply` inside the `anyhow::Error`.
+    fn request(&mut self, kind: MessageType, payload: Vec<u8>) -> Result<Frame> {
+        let request_id = self.next_request_id;
+        self.next_request_id = self.next_request_id.wrapping_add(1);
+        Frame::new(kind, 0, request_id, payload)
+            .write_to(&mut self.connection)
+            .map_err(|e| CS2Error::NetworkError(format!("Failed to send request: {}", e)))?;
+
+        let reply = Frame::read_from(&mut self.reader)?;
+        if reply.version != PROTOCOL_VERSION {
+            return Err(CS2Error::ProtocolError(format!(
+                "server replied with version {}, expected {PROTOCOL_VERSION}",
+                reply.version
+            ))
+            .into());
+        }
+        if reply.request_id != request_id {
+            return Err(CS2Error::ProtocolError(format!(
+                "reply to request {} while waiting for {request_id}",
+                reply.request_id
+            ))
+            .into());
+        }
+        self.model_id = Some(reply.model_id);
+        if reply.kind == MessageType::Error {
+            return Err(ErrorReply::decode(&reply.payload)?.into());
+        }
+        Ok(reply)
+    }
+
+    fn predict_legacy(&mut self, input: &InputVector) -> Result<OutputVector> {
         // Convert to bytes and send
         let input_bytes: &[u8] = bytemuck::bytes_of(input);
         self.connection
@@ -31,50 +190,116 @@ impl PolicyClient {
 
         // Read response
         let mut output_bytes = [0u8; std::mem::size_of::<OutputVector>()];
-        self.connection
+        self.reader
             .read_exact(&mut output_bytes)
             .map_err(|e| CS2Error::NetworkError(format!("Failed to read prediction: {}", e)))?;
 
+        // The server answers a failed prediction with the magic and an error frame
+        if output_bytes[..4] == PROTOCOL_MAGIC {
+            let reply = Frame::read_from(&mut (&output_bytes[4..]).chain(&mut self.reader))?;
+            return Err(ErrorReply::decode(&reply.payload)?.into());
+        }
+
         // Convert back to OutputVector
         let output = bytemuck::pod_read_unaligned::<OutputVector>(&output_bytes);
+        if let Some(recorder) = &self.recorder {
+            recorder.record(
+                0,
+                std::slice::from_ref(input),
+                std::slice::from_ref(&output),
+            )?;
+        }
         Ok(output)
     }
 }
 
+/// History length used when the server does not report one
+pub const DEFAULT_SEQUENCE_LENGTH: usize = 32;
+
 /// A higher-level interface for game integration
+///
+/// Talks to the server through a [`BlockingPolicyClient`], so a dropped
+/// connection is reopened on the next call instead of ending the controller.
+/// The controller keeps the player's recent states for movement requests.
 pub struct AIController {
-    client: PolicyClient,
+    client: BlockingPolicyClient,
+    history: VecDeque<InputVector>, // Oldest first, at most `sequence_length` long
+    sequence_length: usize,
 }
 
 impl AIController {
     /// Create a new AI controller connected to a policy server
     pub fn new(server_addr: impl ToSocketAddrs) -> Result<Self> {
-        let client = PolicyClient::connect(server_addr)?;
-        Ok(Self { client })
+        let client = BlockingPolicyClient::connect(server_addr)?;
+        let sequence_length = client
+            .handshake()?
+            .sequence_length
+            .unwrap_or(DEFAULT_SEQUENCE_LENGTH)
+            .max(1);
+        Ok(Self {
+            client,
+            history: VecDeque::with_capacity(sequence_length),
+            sequence_length,
+        })
     }
 
     /// Get aim adjustment based on current game state
     pub fn get_aim_adjustment(&mut self, state: &PlayerState) -> Result<(f32, f32)> {
-        let input = InputVector {
-            health: state.health,
-            armor: state.armor,
-            pos_x: state.position.0,
-            pos_y: state.position.1,
-            pos_z: state.position.2,
-            vel_x: state.velocity.0,
-            vel_y: state.velocity.1,
-            vel_z: state.velocity.2,
-            yaw: state.view_angles.0,
-            pitch: state.view_angles.1,
-            weapon_id_f32: state.weapon_id as f32,
-            ammo: state.ammo,
-            is_airborne: if state.is_airborne { 1.0 } else { 0.0 },
-            padding: 0.0,
-        };
-
-        let output = self.client.predict(&input)?;
+        let output = self.client.predict(&state.to_input())?;
         Ok((output.delta_yaw, output.delta_pitch))
     }
+
+    /// Record the current state and get movement for it from the recent history
+    ///
+    /// Until `sequence_length` states are recorded, the server pads the
+    /// history by repeating its oldest state.
+    pub fn get_movement(&mut self, state: &PlayerState) -> Result<MovementCommand> {
+        if self.history.len() == self.sequence_length {
+            self.history.pop_front();
+        }
+        self.history.push_back(state.to_input());
+        let movement = self
+            .client
+            .predict_movement(self.history.make_contiguous())?;
+        Ok(MovementCommand::from(movement))
+    }
+
+    /// States kept for the next movement request
+    pub fn history_len(&self) -> usize {
+        self.history.len()
+    }
+
+    /// Record every later aim prediction into a session log
+    pub fn record_to(&mut self, recorder: SessionRecorder) {
+        self.client.record_to(recorder);
+    }
+
+    /// Forget the history, e.g. after a death or at the start of a round
+    pub fn reset_history(&mut self) {
+        self.history.clear();
+    }
+}
+
+/// Movement inputs to apply for the next tick
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MovementCommand {
+    pub forward_move: f32, // -1.0 (back) to 1.0 (forward)
+    pub side_move: f32,    // -1.0 (left) to 1.0 (right)
+    pub jump: bool,
+    pub walk: bool,
+    pub confidence: f32,
+}
+
+impl From<MovementVector> for MovementCommand {
+    fn from(movement: MovementVector) -> Self {
+        Self {
+            forward_move: movement.forward_move,
+            side_move: movement.side_move,
+            jump: movement.jump > 0.5,
+            walk: movement.walk > 0.5,
+            confidence: movement.confidence,
+        }
+    }
 }
 
 /// Struct to encapsulate player state for aim adjustment
@@ -89,9 +314,32 @@ pub struct PlayerState {
     pub is_airborne: bool,
 }
 
+impl PlayerState {
+    /// Network input for this state
+    pub fn to_input(&self) -> InputVector {
+        InputVector {
+            health: self.health,
+            armor: self.armor,
+            pos_x: self.position.0,
+            pos_y: self.position.1,
+            pos_z: self.position.2,
+            vel_x: self.velocity.0,
+            vel_y: self.velocity.1,
+            vel_z: self.velocity.2,
+            yaw: self.view_angles.0,
+            pitch: self.view_angles.1,
+            weapon_id_f32: self.weapon_id as f32,
+            ammo: self.ammo,
+            is_airborne: if self.is_airborne { 1.0 } else { 0.0 },
+            padding: 0.0,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use bytemuck::Zeroable;
     use cs2_common::{InputVector, OutputVector};
     use std::io::{Read, Write};
     use std::net::{SocketAddr, TcpListener};
@@ -118,29 +366,11 @@ mod tests {
 
                 while !*shutdown_clone.lock().unwrap() {
                     match listener.accept() {
-                        Ok((mut socket, _)) => {
+                        Ok((socket, _)) => {
                             socket.set_nonblocking(false).unwrap();
 
                             // Handle this client in a new thread
-                            thread::spawn(move || {
-                                loop {
-                                    let mut buf = [0u8; std::mem::size_of::<InputVector>()];
-                                    match socket.read_exact(&mut buf) {
-                                        Ok(_) => {
-                                            // Simulate policy server - always return a fixed response
-                                            let response = OutputVector {
-                                                delta_yaw: 1.0,
-                                                delta_pitch: 0.5,
-                                            };
-                                            let response_bytes = bytemuck::bytes_of(&response);
-                                            if socket.write_all(response_bytes).is_err() {
-                                                break;
-                                            }
-                                        }
-                                        Err(_) => break,
-                                    }
-                                }
-                            });
+                            thread::spawn(move || mock_session(socket));
                         }
                         Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
                             thread::sleep(Duration::from_millis(10));
@@ -161,6 +391,105 @@ mod tests {
         }
     }
 
+    // Simulate policy server - always return a fixed response
+    const MOCK_OUTPUT: OutputVector = OutputVector {
+        delta_yaw: 1.0,
+        delta_pitch: 0.5,
+    };
+    const MOCK_MAX_BATCH: usize = 4;
+    const MOCK_SEQUENCE_LENGTH: usize = 3;
+
+    fn mock_session(mut socket: std::net::TcpStream) {
+        let mut opening = [0u8; 4];
+        if socket.read_exact(&mut opening).is_err() {
+            return;
+        }
+        if opening != PROTOCOL_MAGIC {
+            // Legacy raw mode, the opening bytes start the first input
+            let mut buf = [0u8; std::mem::size_of::<InputVector>()];
+            buf[..4].copy_from_slice(&opening);
+            let mut read = socket.read_exact(&mut buf[4..]);
+            while read.is_ok() {
+                let input: InputVector = bytemuck::pod_read_unaligned(&buf);
+                let written = if input.health.is_nan() {
+                    let error =
+                        cs2_common::ErrorReply::new(cs2_common::ErrorCode::ModelError, "bad input");
+                    socket.write_all(&PROTOCOL_MAGIC).and_then(|()| {
+                        Frame::new(MessageType::Error, 0, 0, error.encode())
+                            .write_to(&mut socket)
+                            .map_err(std::io::Error::other)
+                    })
+                } else {
+                    socket.write_all(bytemuck::bytes_of(&MOCK_OUTPUT))
+                };
+                if written.is_err() {
+                    break;
+                }
+                read = socket.read_exact(&mut buf);
+            }
+            return;
+        }
+        while let Ok(frame) = Frame::read_from(&mut socket) {
+            let (kind, payload) = match frame.kind {
+                MessageType::Hello => (
+                    MessageType::Handshake,
+                    Handshake {
+                        model_id: 7,
+                        model_name: "mock".to_string(),
+                        input_schema: cs2_common::policy_protocol::INPUT_VECTOR_FIELDS
+                            .iter()
+                            .map(|f| f.to_string())
+                            .collect(),
+                        input_dim: 12,
+                        output_dim: 2,
+                        max_batch: MOCK_MAX_BATCH,
+                        sequence_length: Some(MOCK_SEQUENCE_LENGTH),
+                    }
+                    .encode(),
+                ),
+                MessageType::Predict => {
+                    let count = frame.payload.len() / std::mem::size_of::<InputVector>();
+                    if count > MOCK_MAX_BATCH {
+                        let error = cs2_common::ErrorReply::new(
+                            cs2_common::ErrorCode::BatchTooLarge,
+                            "too many inputs",
+                        );
+                        (MessageType::Error, error.encode())
+                    } else {
+                        (
+                            MessageType::Prediction,
+                            cs2_common::policy_protocol::encode_outputs(&vec![MOCK_OUTPUT; count]),
+                        )
+                    }
+                }
+                MessageType::PredictMovement => {
+                    // Report the history length and the latest yaw back
+                    let history =
+                        cs2_common::policy_protocol::decode_inputs(&frame.payload).unwrap();
+                    let movement = MovementVector {
+                        forward_move: history.len() as f32,
+                        side_move: history.last().unwrap().yaw,
+                        jump: 1.0,
+                        walk: 0.0,
+                        confidence: 0.9,
+                        action_index: 0,
+                    };
+                    (
+                        MessageType::Movement,
+                        cs2_common::policy_protocol::encode_movement(&movement),
+                    )
+                }
+                kind => (kind, frame.payload),
+            };
+            if Frame::new(kind, 7, frame.request_id, payload)
+                .write_to(&mut socket)
+                .is_err()
+            {
+                break;
+            }
+        }
+    }
+
     impl Drop for MockPolicyServer {
         fn drop(&mut self) {
             *self.shutdown.lock().unwrap() = true;
@@ -204,6 +533,84 @@ mod tests {
         assert_eq!(output.delta_pitch, 0.5);
     }
 
+    #[test]
+    fn test_client_framed_session() {
+        let server = MockPolicyServer::start();
+        let mut client = PolicyClient::connect(server.addr).unwrap();
+        assert_eq!(client.mode(), WireMode::Framed);
+        let handshake = client.handshake().unwrap();
+        assert_eq!(handshake.model_name, "mock");
+        assert_eq!(handshake.input_schema.len(), 14);
+
+        let inputs = [InputVector::zeroed(); 3];
+        let outputs = client.predict_batch(&inputs).unwrap();
+        assert_eq!(outputs.len(), 3);
+        assert!(outputs.iter().all(|o| o.delta_yaw == 1.0));
+        assert_eq!(client.model_id(), Some(7));
+        assert!(client.heartbeat().is_ok());
+
+        // Error replies surface as `ErrorReply` and leave the session usable
+        let error = client
+            .predict_batch(&[InputVector::zeroed(); 5])
+            .unwrap_err();
+        let reply = error.downcast_ref::<cs2_common::ErrorReply>().unwrap();
+        assert_eq!(reply.code, cs2_common::ErrorCode::BatchTooLarge);
+        assert!(client.predict(&InputVector::zeroed()).is_ok());
+    }
+
+    #[test]
+    fn test_client_legacy_mode() {
+        let server = MockPolicyServer::start();
+        let mut client = PolicyClient::connect_legacy(server.addr).unwrap();
+        assert!(client.handshake().is_none());
+        let outputs = client.predict_batch(&[InputVector::zeroed(); 2]).unwrap();
+        assert_eq!(outputs.len(), 2);
+        assert_eq!(outputs[1].delta_pitch, 0.5);
+        assert!(client.heartbeat().is_err());
+
+        // A failed prediction comes back as an error and the session goes on
+        let bad = InputVector {
+            health: f32::NAN,
+            ..InputVector::zeroed()
+        };
+        let error = client.predict(&bad).unwrap_err();
+        let reply = error.downcast_ref::<cs2_common::ErrorReply>().unwrap();
+        assert_eq!(reply.code, cs2_common::ErrorCode::ModelError);
+        assert!(client.predict(&InputVector::zeroed()).is_ok());
+    }
+
+    #[test]
+    fn test_record_and_replay_session() {
+        let server = MockPolicyServer::start();
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("session.cs2r");
+
+        let mut client = PolicyClient::connect(server.addr).unwrap();
+        client.record_to(SessionRecorder::create(&path).unwrap());
+        client.predict(&InputVector::zeroed()).unwrap();
+        client.predict_batch(&[InputVector::zeroed(); 4]).unwrap();
+        let mut legacy = PolicyClient::connect_legacy(server.addr).unwrap();
+        let recorder = SessionRecorder::create(tmp.path().join("legacy.cs2r")).unwrap();
+        legacy.record_to(recorder.clone());
+        legacy.predict(&InputVector::zeroed()).unwrap();
+        recorder.flush().unwrap();
+        drop(client);
+
+        let session = replay::read_session(&path).unwrap();
+        assert_eq!(session.len(), 5);
+        assert!(session.iter().all(|e| e.model_id == 7));
+        let legacy_session = replay::read_session(tmp.path().join("legacy.cs2r")).unwrap();
+        assert_eq!(legacy_session[0].model_id, 0);
+
+        // The mock answers every input alike, so a replay cannot diverge
+        let mut replayer = PolicyClient::connect(server.addr).unwrap();
+        let report = replay::replay(&session, &mut replayer, 3).unwrap();
+        assert_eq!(report.compared, 5);
+        assert_eq!(report.replay_model, Some(7));
+        assert_eq!(report.max_angular_difference, 0.0);
+        assert_eq!(report.largest.len(), 3);
+    }
+
     #[test]
     fn test_ai_controller_integration() {
         let server = MockPolicyServer::start();
@@ -228,6 +635,36 @@ mod tests {
         assert_eq!(delta_pitch, 0.5);
     }
 
+    #[test]
+    fn test_ai_controller_movement_history() {
+        let server = MockPolicyServer::start();
+        let mut controller = AIController::new(server.addr).unwrap();
+
+        let mut state = PlayerState {
+            health: 100.0,
+            armor: 0.0,
+            position: (0.0, 0.0, 0.0),
+            velocity: (250.0, 0.0, 0.0),
+            view_angles: (0.0, 0.0),
+            weapon_id: 1,
+            ammo: 30.0,
+            is_airborne: false,
+        };
+        for tick in 0..5 {
+            state.view_angles.0 = tick as f32;
+            let movement = controller.get_movement(&state).unwrap();
+            // The history grows to the server's sequence length and then slides
+            assert_eq!(movement.forward_move, (tick + 1).min(3) as f32);
+            assert_eq!(movement.side_move, tick as f32);
+            assert!(movement.jump && !movement.walk);
+        }
+        assert_eq!(controller.history_len(), 3);
+
+        controller.reset_history();
+        let movement = controller.get_movement(&state).unwrap();
+        assert_eq!(movement.forward_move, 1.0);
+    }
+
     // Integration test with testcontainers
     // This is commented out because it requires Docker and would be run in CI
     /*
@@ -0,0 +1,42 @@
+use clap::{Parser, Subcommand};
+use cs2_client::{replay, PolicyClient};
+use std::path::PathBuf;
+
+#[derive(Parser)]
+#[command(name = "cs2-client")]
+#[command(about = "Tools for sessions with the CS2 policy server")]
+struct Cli {
+    #[command(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Re-send a recorded session to a policy server and compare its outputs
+    Replay {
+        /// Session log written by a `SessionRecorder`
+        session: PathBuf,
+        #[arg(long, default_value = "127.0.0.1:8123")]
+        server: String,
+        /// Largest divergences to list
+        #[arg(long, default_value = "10")]
+        top: usize,
+    },
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    match cli.command {
+        Commands::Replay {
+            session,
+            server,
+            top,
+        } => {
+            let recorded = replay::read_session(&session)?;
+            let mut client = PolicyClient::connect(server.as_str())?;
+            let report = replay::replay(&recorded, &mut client, top)?;
+            print!("{report}");
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,334 @@
+use crate::PolicyClient;
+use anyhow::{bail, Result};
+use cs2_common::{InputVector, OutputVector};
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// First bytes of a session log
+pub const SESSION_MAGIC: [u8; 4] = *b"CS2R";
+/// Version written after the magic
+pub const SESSION_VERSION: u8 = 1;
+/// Magic, version and three reserved bytes
+const HEADER_LEN: usize = 8;
+/// Timestamp, model ID, input and output of one exchange
+pub const RECORD_LEN: usize =
+    8 + 4 + std::mem::size_of::<InputVector>() + std::mem::size_of::<OutputVector>();
+
+/// One prediction exchanged with the policy server
+#[derive(Debug, Clone, Copy)]
+pub struct RecordedExchange {
+    pub timestamp_us: u64, // Microseconds since the Unix epoch when the reply arrived
+    pub model_id: u32,     // Zero for legacy sessions, which do not report one
+    pub input: InputVector,
+    pub output: OutputVector,
+}
+
+impl RecordedExchange {
+    fn encode(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&self.timestamp_us.to_le_bytes());
+        bytes.extend_from_slice(&self.model_id.to_le_bytes());
+        bytes.extend_from_slice(bytemuck::bytes_of(&self.input));
+        bytes.extend_from_slice(bytemuck::bytes_of(&self.output));
+    }
+
+    fn decode(bytes: &[u8]) -> Self {
+        let input_end = 12 + std::mem::size_of::<InputVector>();
+        Self {
+            timestamp_us: u64::from_le_bytes(bytes[..8].try_into().unwrap()),
+            model_id: u32::from_le_bytes(bytes[8..12].try_into().unwrap()),
+            input: bytemuck::pod_read_unaligned(&bytes[12..input_end]),
+            output: bytemuck::pod_read_unaligned(&bytes[input_end..RECORD_LEN]),
+        }
+    }
+}
+
+/// Appends every prediction of the clients it is attached to into a session log
+///
+/// Clones share the same file, so one log can cover several clients. Records
+/// are buffered; they reach the disk on [`SessionRecorder::flush`] or when the
+/// last clone is dropped.
+#[derive(Clone)]
+pub struct SessionRecorder {
+    writer: Arc<Mutex<BufWriter<File>>>,
+}
+
+impl SessionRecorder {
+    /// Create a new log at `path`, replacing any existing file
+    pub fn create(path: impl AsRef<Path>) -> Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(&SESSION_MAGIC)?;
+        writer.write_all(&[SESSION_VERSION, 0, 0, 0])?;
+        Ok(Self {
+            writer: Arc::new(Mutex::new(writer)),
+        })
+    }
+
+    /// Record a batch of inputs and the outputs the model answered them with
+    pub fn record(
+        &self,
+        model_id: u32,
+        inputs: &[InputVector],
+        outputs: &[OutputVector],
+    ) -> Result<()> {
+        let timestamp_us = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_micros() as u64)
+            .unwrap_or_default();
+        let mut bytes = Vec::with_capacity(inputs.len() * RECORD_LEN);
+        for (input, output) in inputs.iter().zip(outputs) {
+            RecordedExchange {
+                timestamp_us,
+                model_id,
+                input: *input,
+                output: *output,
+            }
+            .encode(&mut bytes);
+        }
+        self.writer.lock().unwrap().write_all(&bytes)?;
+        Ok(())
+    }
+
+    pub fn flush(&self) -> Result<()> {
+        self.writer.lock().unwrap().flush()?;
+        Ok(())
+    }
+}
+
+/// Read every exchange of a session log
+///
+/// A partial record at the end, left by a recorder that did not flush
+/// cleanly, is ignored.
+pub fn read_session(path: impl AsRef<Path>) -> Result<Vec<RecordedExchange>> {
+    let mut bytes = Vec::new();
+    BufReader::new(File::open(path)?).read_to_end(&mut bytes)?;
+    if bytes.len() < HEADER_LEN || bytes[..4] != SESSION_MAGIC {
+        bail!("not a policy session log");
+    }
+    if bytes[4] != SESSION_VERSION {
+        bail!(
+            "session log version {}, expected {SESSION_VERSION}",
+            bytes[4]
+        );
+    }
+    Ok(bytes[HEADER_LEN..]
+        .chunks_exact(RECORD_LEN)
+        .map(RecordedExchange::decode)
+        .collect())
+}
+
+/// Angle between two aim adjustments, in degrees
+pub fn angular_difference(a: &OutputVector, b: &OutputVector) -> f32 {
+    (a.delta_yaw - b.delta_yaw).hypot(a.delta_pitch - b.delta_pitch)
+}
+
+/// An exchange where the replayed model disagreed with the recording
+#[derive(Debug, Clone, Copy)]
+pub struct Divergence {
+    pub index: usize, // Position of the exchange in the log
+    pub timestamp_us: u64,
+    pub recorded: OutputVector,
+    pub replayed: OutputVector,
+    pub angular_difference: f32,
+}
+
+/// How a replayed model's outputs differ from the recorded ones
+#[derive(Debug, Clone)]
+pub struct ReplayReport {
+    pub compared: usize,
+    pub recorded_models: Vec<u32>, // Distinct model IDs found in the log
+    pub replay_model: Option<u32>,
+    pub mean_angular_difference: f32,
+    pub p95_angular_difference: f32,
+    pub max_angular_difference: f32,
+    pub largest: Vec<Divergence>, // Largest first
+}
+
+impl ReplayReport {
+    /// Compare recorded exchanges with the outputs replayed for their inputs
+    pub fn compare(
+        recorded: &[RecordedExchange],
+        replayed: &[OutputVector],
+        replay_model: Option<u32>,
+        top: usize,
+    ) -> Self {
+        let mut divergences: Vec<Divergence> = recorded
+            .iter()
+            .zip(replayed)
+            .enumerate()
+            .map(|(index, (exchange, output))| Divergence {
+                index,
+                timestamp_us: exchange.timestamp_us,
+                recorded: exchange.output,
+                replayed: *output,
+                angular_difference: angular_difference(&exchange.output, output),
+            })
+            .collect();
+        divergences.sort_by(|a, b| b.angular_difference.total_cmp(&a.angular_difference));
+
+        let compared = divergences.len();
+        let at_rank = |rank: f32| {
+            divergences
+                .get(((compared as f32 * (1.0 - rank)) as usize).min(compared.saturating_sub(1)))
+                .map_or(0.0, |d| d.angular_difference)
+        };
+        let mean_angular_difference = if compared == 0 {
+            0.0
+        } else {
+            divergences
+                .iter()
+                .map(|d| d.angular_difference)
+                .sum::<f32>()
+                / compared as f32
+        };
+        let p95_angular_difference = at_rank(0.95);
+        let max_angular_difference = at_rank(1.0);
+        divergences.truncate(top);
+
+        Self {
+            compared,
+            recorded_models: recorded
+                .iter()
+                .map(|e| e.model_id)
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .collect(),
+            replay_model,
+            mean_angular_difference,
+            p95_angular_difference,
+            max_angular_difference,
+            largest: divergences,
+        }
+    }
+}
+
+impl fmt::Display for ReplayReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let replay_model = self
+            .replay_model
+            .map_or_else(|| "unknown".to_string(), |id| format!("{id:#010x}"));
+        let recorded_models: Vec<String> = self
+            .recorded_models
+            .iter()
+            .map(|id| format!("{id:#010x}"))
+            .collect();
+        writeln!(
+            f,
+            "{} exchanges recorded by [{}] replayed against {replay_model}",
+            self.compared,
+            recorded_models.join(", ")
+        )?;
+        writeln!(
+            f,
+            "angular difference: mean {:.4}° p95 {:.4}° max {:.4}°",
+            self.mean_angular_difference, self.p95_angular_difference, self.max_angular_difference
+        )?;
+        for d in &self.largest {
+            writeln!(
+                f,
+                "  #{:<8} {:>8.4}°  recorded ({:.3}, {:.3})  replayed ({:.3}, {:.3})",
+                d.index,
+                d.angular_difference,
+                d.recorded.delta_yaw,
+                d.recorded.delta_pitch,
+                d.replayed.delta_yaw,
+                d.replayed.delta_pitch
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Re-send the recorded inputs to the client's server and compare the outputs
+///
+/// Framed sessions send the inputs in batches of the server's `max_batch`.
+pub fn replay(
+    recorded: &[RecordedExchange],
+    client: &mut PolicyClient,
+    top: usize,
+) -> Result<ReplayReport> {
+    let batch = client.handshake().map_or(1, |h| h.max_batch.max(1));
+    let inputs: Vec<InputVector> = recorded.iter().map(|e| e.input).collect();
+    let mut replayed = Vec::with_capacity(inputs.len());
+    for chunk in inputs.chunks(batch) {
+        replayed.extend(client.predict_batch(chunk)?);
+    }
+    Ok(ReplayReport::compare(
+        recorded,
+        &replayed,
+        client.model_id(),
+        top,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytemuck::Zeroable;
+
+    fn output(delta_yaw: f32, delta_pitch: f32) -> OutputVector {
+        OutputVector {
+            delta_yaw,
+            delta_pitch,
+        }
+    }
+
+    #[test]
+    fn test_session_roundtrip() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let path = tmp.path().join("session.cs2r");
+        let recorder = SessionRecorder::create(&path)?;
+        let mut input = InputVector::zeroed();
+        input.yaw = 90.0;
+        recorder.record(7, &[input, input], &[output(1.0, 0.5), output(2.0, 0.0)])?;
+        recorder.clone().record(8, &[input], &[output(3.0, 0.0)])?;
+        recorder.flush()?;
+
+        // A torn record at the end is dropped
+        let mut file = std::fs::OpenOptions::new().append(true).open(&path)?;
+        file.write_all(&[0u8; 10])?;
+
+        let session = read_session(&path)?;
+        assert_eq!(session.len(), 3);
+        assert_eq!(session[0].model_id, 7);
+        assert_eq!(session[0].input.yaw, 90.0);
+        assert_eq!(session[1].output.delta_yaw, 2.0);
+        assert_eq!(session[2].model_id, 8);
+        assert!(session[0].timestamp_us > 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_report_ranks_divergences() {
+        let recorded: Vec<RecordedExchange> = [(0.0, 0.0), (1.0, 1.0), (5.0, 0.0), (0.0, 0.0)]
+            .iter()
+            .enumerate()
+            .map(|(i, &(yaw, pitch))| RecordedExchange {
+                timestamp_us: i as u64,
+                model_id: 1,
+                input: InputVector::zeroed(),
+                output: output(yaw, pitch),
+            })
+            .collect();
+        let replayed = [
+            output(0.0, 0.0),
+            output(1.0, 1.0),
+            output(2.0, 4.0),
+            output(0.0, 1.0),
+        ];
+
+        let report = ReplayReport::compare(&recorded, &replayed, Some(2), 2);
+        assert_eq!(report.compared, 4);
+        assert_eq!(report.recorded_models, vec![1]);
+        assert!((report.mean_angular_difference - 1.5).abs() < 1e-6);
+        assert_eq!(report.max_angular_difference, 5.0);
+        assert_eq!(report.largest.len(), 2);
+        assert_eq!(report.largest[0].index, 2);
+        assert_eq!(report.largest[1].index, 3);
+        assert!(report.to_string().contains("max 5.0000°"));
+    }
+}
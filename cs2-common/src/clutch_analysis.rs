@@ -0,0 +1,333 @@
+use crate::feature_extraction::TemporalContextFeatures;
+use crate::match_clock::MatchClock;
+use crate::match_events::{BombAction, BombEvent, DeathEvent, RoundInfo};
+use crate::player_frames::FrameIndex;
+use crate::scoreboard::round_teams;
+use crate::win_probability::BombState;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Largest N tracked separately in 1vN statistics
+pub const MAX_CLUTCH_OPPONENTS: usize = 5;
+
+/// Clutch Analyzer - Detects 1vN situations from round rosters and kill events
+#[derive(Default)]
+pub struct ClutchAnalyzer {
+    pub clock: MatchClock,
+}
+
+/// The moment a player became the last one alive on their team
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClutchSituation {
+    pub tick: u32,
+    pub steamid: u64,
+    pub opponents: u32, // Enemies alive at that moment
+}
+
+/// A 1vN from the moment it started to the end of the round
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Clutch {
+    pub round: u32,
+    pub steamid: u64,
+    pub team_num: u8,
+    pub start_tick: u32,
+    pub end_tick: u32, // Round end
+    pub duration_secs: f32,
+    pub opponents: u32,
+    pub equipment_value: u32, // Clutcher's equipment when the clutch started
+    pub opponents_equipment_value: u32, // Summed over the alive opponents
+    pub bomb: BombState,      // Bomb state when the clutch started
+    pub kills: u32,           // Opponents killed by the clutcher
+    pub survived: bool,
+    pub won: bool,
+}
+
+impl Clutch {
+    /// Opponents' equipment value minus the clutcher's, zero when frames had no economy
+    pub fn equipment_disadvantage(&self) -> i64 {
+        self.opponents_equipment_value as i64 - self.equipment_value as i64
+    }
+}
+
+/// Clutch record of one player
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ClutchStats {
+    pub steamid: u64,
+    pub attempts: u32,
+    pub wins: u32,
+    pub kills: u32,
+    pub attempts_by_opponents: [u32; MAX_CLUTCH_OPPONENTS + 1], // Indexed by N, capped at MAX_CLUTCH_OPPONENTS
+    pub wins_by_opponents: [u32; MAX_CLUTCH_OPPONENTS + 1],
+}
+
+impl ClutchStats {
+    pub fn win_rate(&self) -> f32 {
+        if self.attempts == 0 {
+            return 0.0;
+        }
+        self.wins as f32 / self.attempts as f32
+    }
+
+    /// Win rate where a 1vN counts N times, so harder clutches weigh more
+    pub fn performance(&self) -> f32 {
+        let weighted = |counts: &[u32]| -> u32 {
+            counts
+                .iter()
+                .enumerate()
+                .map(|(n, &count)| n as u32 * count)
+                .sum()
+        };
+        let attempted = weighted(&self.attempts_by_opponents);
+        if attempted == 0 {
+            return 0.0;
+        }
+        weighted(&self.wins_by_opponents) as f32 / attempted as f32
+    }
+
+    /// Replace the isolation-based clutch estimate with the measured clutch record
+    pub fn apply_to(&self, features: &mut TemporalContextFeatures) {
+        if self.attempts > 0 {
+            features.clutch_performance_metrics = self.performance();
+        }
+    }
+
+    fn add(&mut self, clutch: &Clutch) {
+        let n = (clutch.opponents as usize).min(MAX_CLUTCH_OPPONENTS);
+        self.attempts += 1;
+        self.kills += clutch.kills;
+        self.attempts_by_opponents[n] += 1;
+        if clutch.won {
+            self.wins += 1;
+            self.wins_by_opponents[n] += 1;
+        }
+    }
+}
+
+/// Every clutch of a match with per-player aggregates
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ClutchReport {
+    pub clutches: Vec<Clutch>,
+    pub players: HashMap<u64, ClutchStats>,
+}
+
+impl ClutchAnalyzer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an analyzer that converts ticks with the demo's clock
+    pub fn with_clock(clock: &MatchClock) -> Self {
+        Self { clock: *clock }
+    }
+
+    /// Find every 1vN of the match and how it ended
+    ///
+    /// Rosters come from death teams and `frames`, equipment values from the
+    /// latest frame of each player in the round.
+    pub fn analyze(
+        &self,
+        rounds: &[RoundInfo],
+        deaths: &[DeathEvent],
+        bomb_events: &[BombEvent],
+        frames: &FrameIndex,
+    ) -> ClutchReport {
+        let mut report = ClutchReport::default();
+        for round in rounds {
+            let round_deaths: Vec<&DeathEvent> =
+                deaths.iter().filter(|d| round.contains(d.tick)).collect();
+            let teams = round_teams(round, &round_deaths, frames);
+            let equipment = |steamid: u64, tick: u32| {
+                frames
+                    .frame_at(steamid, tick)
+                    .filter(|f| f.tick >= round.start_tick)
+                    .map_or(0, |f| f.equipment_value)
+            };
+
+            for situation in clutch_situations(&teams, &round_deaths) {
+                let team_num = teams[&situation.steamid];
+                let dead: HashSet<u64> = round_deaths
+                    .iter()
+                    .filter(|d| d.tick <= situation.tick)
+                    .map(|d| d.victim)
+                    .collect();
+                let opponents_equipment_value = teams
+                    .iter()
+                    .filter(|&(p, &team)| team != team_num && !dead.contains(p))
+                    .map(|(&p, _)| equipment(p, situation.tick))
+                    .sum();
+                let kills = round_deaths
+                    .iter()
+                    .filter(|d| d.tick >= situation.tick && d.killer() == Some(situation.steamid))
+                    .filter(|d| teams.get(&d.victim).is_some_and(|&t| t != team_num))
+                    .count() as u32;
+
+                report.clutches.push(Clutch {
+                    round: round.number,
+                    steamid: situation.steamid,
+                    team_num,
+                    start_tick: situation.tick,
+                    end_tick: round.end_tick,
+                    duration_secs: self.clock.elapsed_seconds(situation.tick, round.end_tick),
+                    opponents: situation.opponents,
+                    equipment_value: equipment(situation.steamid, situation.tick),
+                    opponents_equipment_value,
+                    bomb: bomb_state_at(bomb_events, round, situation.tick),
+                    kills,
+                    survived: !round_deaths.iter().any(|d| d.victim == situation.steamid),
+                    won: round.winner_team == team_num,
+                });
+            }
+        }
+
+        for clutch in &report.clutches {
+            report
+                .players
+                .entry(clutch.steamid)
+                .or_insert_with(|| ClutchStats {
+                    steamid: clutch.steamid,
+                    ..Default::default()
+                })
+                .add(clutch);
+        }
+        report
+    }
+}
+
+/// Players left as the last one alive on their team, in order of the deaths that caused it
+///
+/// Only the side that is outnumbered or level when it is reduced to one
+/// player is clutching; a player left alone against an opponent who is
+/// already in a clutch is not, so a 1v1 counts once.
+pub fn clutch_situations(teams: &HashMap<u64, u8>, deaths: &[&DeathEvent]) -> Vec<ClutchSituation> {
+    let mut alive: HashSet<u64> = teams.keys().copied().collect();
+    let mut situations: Vec<ClutchSituation> = Vec::new();
+
+    for death in deaths {
+        if !alive.remove(&death.victim) {
+            continue;
+        }
+        let Some(&team) = teams.get(&death.victim) else {
+            continue;
+        };
+        let remaining: Vec<u64> = alive
+            .iter()
+            .copied()
+            .filter(|p| teams.get(p) == Some(&team))
+            .collect();
+        let enemies = alive.iter().filter(|p| teams.get(p) != Some(&team)).count() as u32;
+        let opponent_clutching = situations
+            .iter()
+            .any(|s| teams.get(&s.steamid) != Some(&team));
+        if let [last] = remaining[..] {
+            if enemies > 0 && !opponent_clutching && !situations.iter().any(|s| s.steamid == last) {
+                situations.push(ClutchSituation {
+                    tick: death.tick,
+                    steamid: last,
+                    opponents: enemies,
+                });
+            }
+        }
+    }
+    situations
+}
+
+fn bomb_state_at(bomb_events: &[BombEvent], round: &RoundInfo, tick: u32) -> BombState {
+    bomb_events
+        .iter()
+        .filter(|b| round.contains(b.tick) && b.tick <= tick)
+        .fold(BombState::NotPlanted, |_, b| match b.action {
+            BombAction::Planted => BombState::Planted,
+            BombAction::Defused => BombState::Defused,
+            BombAction::Exploded => BombState::Exploded,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::match_events::BombSite;
+    use crate::player_frames::{PlayerFrame, TEAM_CT, TEAM_T};
+    use crate::test_fixtures::{death, player};
+
+    fn frame(steamid: u64, team_num: u8, equipment_value: u32) -> PlayerFrame {
+        PlayerFrame {
+            equipment_value,
+            ..player(steamid, team_num)
+        }
+    }
+
+    #[test]
+    fn test_one_v_two_after_plant() {
+        // T 1 plants, T 2 dies, then T 1 is left against two rifles and wins by killing both
+        let frames = FrameIndex::new(vec![
+            frame(1, TEAM_T, 800),
+            frame(2, TEAM_T, 800),
+            frame(10, TEAM_CT, 4000),
+            frame(11, TEAM_CT, 4500),
+        ]);
+        let rounds = vec![RoundInfo {
+            number: 3,
+            start_tick: 0,
+            end_tick: 2000,
+            winner_team: TEAM_T,
+            reason: "t_killed".to_string(),
+        }];
+        let bombs = vec![BombEvent {
+            tick: 500,
+            action: BombAction::Planted,
+            steamid: Some(1),
+            site: BombSite::A,
+        }];
+        let deaths = vec![death(600, 2, 10), death(900, 10, 1), death(1000, 11, 1)];
+
+        let report = ClutchAnalyzer::new().analyze(&rounds, &deaths, &bombs, &frames);
+        // CT 11 is left in a 1v1 once CT 10 falls, but T 1 was alone first
+        assert_eq!(report.clutches.len(), 1);
+
+        let clutch = &report.clutches[0];
+        assert_eq!(clutch.steamid, 1);
+        assert_eq!(clutch.start_tick, 600);
+        assert_eq!(clutch.opponents, 2);
+        assert_eq!(clutch.equipment_disadvantage(), 8500 - 800);
+        assert_eq!(clutch.bomb, BombState::Planted);
+        assert_eq!(clutch.kills, 2);
+        assert!(clutch.won && clutch.survived);
+
+        let stats = &report.players[&1];
+        assert_eq!(stats.attempts_by_opponents[2], 1);
+        assert_eq!(stats.performance(), 1.0);
+
+        let mut features = TemporalContextFeatures::default();
+        stats.apply_to(&mut features);
+        assert_eq!(features.clutch_performance_metrics, 1.0);
+    }
+
+    #[test]
+    fn test_one_v_one_counts_once() {
+        // Both openers fall together: CT 10 first, so CT 11 is alone against two Ts.
+        // T 2 dying then leaves T 1 level with CT 11, which is not a clutch for T 1.
+        let teams = HashMap::from([(1, TEAM_T), (2, TEAM_T), (10, TEAM_CT), (11, TEAM_CT)]);
+        let deaths = [death(100, 10, 2), death(101, 2, 11), death(300, 11, 1)];
+        let deaths: Vec<&DeathEvent> = deaths.iter().collect();
+
+        let situations = clutch_situations(&teams, &deaths);
+
+        assert_eq!(situations.len(), 1);
+        assert_eq!((situations[0].steamid, situations[0].opponents), (11, 2));
+    }
+
+    #[test]
+    fn test_performance_weighs_harder_clutches() {
+        // Lost a 1v1, won a 1v3
+        let stats = ClutchStats {
+            attempts: 2,
+            wins: 1,
+            attempts_by_opponents: [0, 1, 0, 1, 0, 0],
+            wins_by_opponents: [0, 0, 0, 1, 0, 0],
+            ..Default::default()
+        };
+
+        assert_eq!(stats.win_rate(), 0.5);
+        assert_eq!(stats.performance(), 0.75);
+    }
+}
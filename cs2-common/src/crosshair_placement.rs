@@ -0,0 +1,211 @@
+use crate::feature_extraction::PlayerMechanicsFeatures;
+use crate::match_events::WeaponClass;
+use crate::player_frames::{first_spotted_events, AimError, FrameIndex};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Crosshair Placement Extractor - Measures where the crosshair was when an enemy appeared
+pub struct CrosshairPlacementExtractor {
+    pub pre_aim_threshold_deg: f32, // Encounters below this total error count as pre-aimed
+}
+
+impl Default for CrosshairPlacementExtractor {
+    fn default() -> Self {
+        Self {
+            pre_aim_threshold_deg: 5.0,
+        }
+    }
+}
+
+/// Crosshair error of one observer at the tick an enemy became visible
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlacementSample {
+    pub tick: u32,
+    pub observer: u64,
+    pub target: u64,
+    pub place: String, // Observer's map zone
+    pub weapon_class: WeaponClass,
+    pub error: AimError,
+}
+
+/// Aggregated crosshair errors over a set of encounters, in degrees
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PlacementStats {
+    pub encounters: usize,
+    pub mean_pitch_error: f32, // Mean absolute vertical error
+    pub mean_yaw_error: f32,   // Mean absolute horizontal error
+    pub mean_total_error: f32,
+    pub median_total_error: f32,
+    pub pre_aimed_ratio: f32,
+}
+
+impl PlacementStats {
+    pub fn from_samples<'a>(
+        samples: impl IntoIterator<Item = &'a PlacementSample>,
+        pre_aim_threshold_deg: f32,
+    ) -> Self {
+        let errors: Vec<AimError> = samples.into_iter().map(|s| s.error).collect();
+        if errors.is_empty() {
+            return Self::default();
+        }
+
+        let n = errors.len() as f32;
+        let mut totals: Vec<f32> = errors.iter().map(|e| e.total_deg).collect();
+        totals.sort_by(|a, b| a.total_cmp(b));
+
+        Self {
+            encounters: errors.len(),
+            mean_pitch_error: errors.iter().map(|e| e.pitch_deg.abs()).sum::<f32>() / n,
+            mean_yaw_error: errors.iter().map(|e| e.yaw_deg.abs()).sum::<f32>() / n,
+            mean_total_error: totals.iter().sum::<f32>() / n,
+            median_total_error: totals[totals.len() / 2],
+            pre_aimed_ratio: totals
+                .iter()
+                .filter(|&&t| t <= pre_aim_threshold_deg)
+                .count() as f32
+                / n,
+        }
+    }
+}
+
+/// Per-player crosshair placement split by map zone and weapon class
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PlacementProfile {
+    pub steamid: u64,
+    pub overall: PlacementStats,
+    pub by_place: HashMap<String, PlacementStats>,
+    pub by_weapon_class: HashMap<WeaponClass, PlacementStats>,
+}
+
+impl PlacementProfile {
+    /// Replace the pitch-average estimates with the measured placement
+    pub fn apply_to(&self, features: &mut PlayerMechanicsFeatures) {
+        if self.overall.encounters > 0 {
+            features.crosshair_placement_height = self.overall.mean_pitch_error;
+            features.pre_aim_accuracy = self.overall.pre_aimed_ratio;
+        }
+    }
+}
+
+impl CrosshairPlacementExtractor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Measure the crosshair error of every observer when an enemy becomes visible
+    pub fn extract_samples(&self, frames: &FrameIndex) -> Vec<PlacementSample> {
+        first_spotted_events(frames)
+            .into_iter()
+            .filter_map(|event| {
+                let observer = frames.frame_at(event.observer, event.tick)?;
+                let target = frames.frame_at(event.target, event.tick)?;
+                Some(PlacementSample {
+                    tick: event.tick,
+                    observer: event.observer,
+                    target: event.target,
+                    place: observer.place.clone(),
+                    weapon_class: WeaponClass::from_weapon_name(&observer.weapon),
+                    error: observer.aim_error_to(target),
+                })
+            })
+            .collect()
+    }
+
+    /// Aggregate placement samples into per-player profiles
+    pub fn extract_profiles(&self, frames: &FrameIndex) -> HashMap<u64, PlacementProfile> {
+        let mut by_player: HashMap<u64, Vec<PlacementSample>> = HashMap::new();
+        for sample in self.extract_samples(frames) {
+            by_player.entry(sample.observer).or_default().push(sample);
+        }
+
+        by_player
+            .into_iter()
+            .map(|(steamid, samples)| {
+                let mut by_place: HashMap<String, Vec<&PlacementSample>> = HashMap::new();
+                let mut by_weapon_class: HashMap<WeaponClass, Vec<&PlacementSample>> =
+                    HashMap::new();
+                for sample in &samples {
+                    by_place
+                        .entry(sample.place.clone())
+                        .or_default()
+                        .push(sample);
+                    by_weapon_class
+                        .entry(sample.weapon_class)
+                        .or_default()
+                        .push(sample);
+                }
+
+                let stats = |s: Vec<&PlacementSample>| {
+                    PlacementStats::from_samples(s, self.pre_aim_threshold_deg)
+                };
+                let profile = PlacementProfile {
+                    steamid,
+                    overall: PlacementStats::from_samples(&samples, self.pre_aim_threshold_deg),
+                    by_place: by_place
+                        .into_iter()
+                        .map(|(place, s)| (place, stats(s)))
+                        .collect(),
+                    by_weapon_class: by_weapon_class
+                        .into_iter()
+                        .map(|(class, s)| (class, stats(s)))
+                        .collect(),
+                };
+                (steamid, profile)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player_frames::{PlayerFrame, TEAM_CT, TEAM_T};
+
+    #[test]
+    fn test_placement_split_by_place_and_weapon_class() {
+        let observer = |tick: u32, pitch: f32, yaw: f32, place: &str, weapon: &str| PlayerFrame {
+            tick,
+            steamid: 1,
+            team_num: TEAM_CT,
+            is_alive: true,
+            yaw,
+            pitch,
+            weapon: weapon.to_string(),
+            place: place.to_string(),
+            ..Default::default()
+        };
+        let enemy = |tick: u32, spotted_by: Vec<u64>| PlayerFrame {
+            tick,
+            steamid: 2,
+            team_num: TEAM_T,
+            is_alive: true,
+            pos_x: 1000.0,
+            spotted_by,
+            ..Default::default()
+        };
+
+        // Spotted once holding the head with an AK on A site, then 30 degrees off with a USP at mid
+        let frames = FrameIndex::new(vec![
+            observer(1, 2.0, 0.0, "BombsiteA", "AK-47"),
+            enemy(1, vec![1]),
+            enemy(2, vec![]),
+            observer(3, 0.0, 30.0, "Middle", "USP-S"),
+            enemy(3, vec![1]),
+        ]);
+
+        let extractor = CrosshairPlacementExtractor::new();
+        let profiles = extractor.extract_profiles(&frames);
+        let profile = &profiles[&1];
+
+        assert_eq!(profile.overall.encounters, 2);
+        assert_eq!(profile.overall.pre_aimed_ratio, 0.5);
+        assert!((profile.by_place["BombsiteA"].mean_pitch_error - 2.0).abs() < 1e-3);
+        assert!(profile.by_place["BombsiteA"].mean_yaw_error < 1e-3);
+        assert!((profile.by_weapon_class[&WeaponClass::Pistol].mean_yaw_error - 30.0).abs() < 1e-3);
+
+        let mut features = PlayerMechanicsFeatures::default();
+        profile.apply_to(&mut features);
+        assert!((features.crosshair_placement_height - 1.0).abs() < 1e-3);
+        assert_eq!(features.pre_aim_accuracy, 0.5);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::crosshair_placement::PlacementProfile;
 use crate::BehavioralVector;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -5,7 +6,7 @@ use std::collections::HashMap;
 /// Comprehensive feature extraction module for CS2 demo analysis
 /// Implements feature extraction checklist from project requirements
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ExtractedFeatures {
     pub player_mechanics: PlayerMechanicsFeatures,
     pub team_dynamics: TeamDynamicsFeatures,
@@ -14,7 +15,7 @@ pub struct ExtractedFeatures {
 }
 
 /// Player Mechanics Features - Individual player skill metrics
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct PlayerMechanicsFeatures {
     // Aim Precision
     pub headshot_percentage: f32,
@@ -45,7 +46,7 @@ pub struct PlayerMechanicsFeatures {
 }
 
 /// Team Dynamics Features - Team coordination and positioning
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct TeamDynamicsFeatures {
     // Team Positioning
     pub formation_spread_vs_stack: f32,
@@ -75,7 +76,7 @@ pub struct TeamDynamicsFeatures {
 }
 
 /// Decision-Making Features - Strategic and tactical decision analysis
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct DecisionMetricsFeatures {
     // Economy Decisions
     pub buy_efficiency_value_per_dollar: f32,
@@ -100,7 +101,7 @@ pub struct DecisionMetricsFeatures {
 }
 
 /// Temporal & Contextual Features - Round and situational analysis
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct TemporalContextFeatures {
     // Round Phase Context
     pub early_round_tendencies: HashMap<String, f32>,
@@ -127,6 +128,7 @@ pub struct PlayerMechanicsExtractor {
     pub headshot_threshold: f32,
     pub flick_distance_threshold: f32,
     pub movement_smoothness_window: usize,
+    pub placement_profiles: HashMap<u64, PlacementProfile>, // Measured placement from CrosshairPlacementExtractor
 }
 
 impl Default for PlayerMechanicsExtractor {
@@ -135,6 +137,7 @@ impl Default for PlayerMechanicsExtractor {
             headshot_threshold: 30.0,       // degrees for headshot angle tolerance
             flick_distance_threshold: 45.0, // degrees for significant flick
             movement_smoothness_window: 10, // ticks for smoothness analysis
+            placement_profiles: HashMap::new(),
         }
     }
 }
@@ -144,6 +147,15 @@ impl PlayerMechanicsExtractor {
         Self::default()
     }
 
+    /// Score crosshair placement from the players' measured placement profiles
+    pub fn with_placement_profiles(
+        mut self,
+        placement_profiles: HashMap<u64, PlacementProfile>,
+    ) -> Self {
+        self.placement_profiles = placement_profiles;
+        self
+    }
+
     /// Extract comprehensive player mechanics features from behavioral vectors
     pub fn extract_features(&self, vectors: &[BehavioralVector]) -> PlayerMechanicsFeatures {
         let mut features = PlayerMechanicsFeatures {
@@ -183,6 +195,11 @@ impl PlayerMechanicsExtractor {
         // Calculate weapon control metrics
         self.extract_weapon_control(&mut features, vectors);
 
+        // Crosshair placement comes from the player's measured encounters
+        if let Some(profile) = self.placement_profiles.get(&vectors[0].steamid) {
+            profile.apply_to(&mut features);
+        }
+
         features
     }
 
@@ -507,6 +524,23 @@ mod tests {
         assert!(features.movement_efficiency >= 0.0);
         assert!(features.recoil_control_consistency >= 0.0);
         assert!(!features.weapon_preference_patterns.is_empty());
+
+        // Measured placement replaces the pitch-average estimates
+        let measured = PlacementProfile {
+            steamid: 76561198123456789,
+            overall: crate::crosshair_placement::PlacementStats {
+                encounters: 4,
+                mean_pitch_error: 3.5,
+                pre_aimed_ratio: 0.75,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let features = PlayerMechanicsExtractor::new()
+            .with_placement_profiles(HashMap::from([(76561198123456789, measured)]))
+            .extract_features(&vectors);
+        assert_eq!(features.crosshair_placement_height, 3.5);
+        assert_eq!(features.pre_aim_accuracy, 0.75);
     }
 
     #[test]
@@ -0,0 +1,668 @@
+use crate::feature_extraction::ExtractedFeatures;
+use crate::CS2Error;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Stable flat feature vector schema for `ExtractedFeatures`
+///
+/// Every scalar field is emitted in declaration order. Map fields (per-weapon,
+/// per-area, ...) have data-dependent keys, so they are summarized into a fixed
+/// set of aggregates (`count`, `mean`, `min`, `max`) to keep the vector length
+/// constant. Bump `FEATURE_SCHEMA_VERSION` whenever the ordering or the set of
+/// features changes.
+pub const FEATURE_SCHEMA_VERSION: u32 = 1;
+
+/// Aggregates emitted for every map-valued feature, in order
+pub const MAP_AGGREGATES: [&str; 4] = ["count", "mean", "min", "max"];
+
+/// Top-level feature group a flattened feature belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FeatureGroup {
+    PlayerMechanics,
+    TeamDynamics,
+    DecisionMetrics,
+    TemporalContext,
+}
+
+impl FeatureGroup {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FeatureGroup::PlayerMechanics => "player_mechanics",
+            FeatureGroup::TeamDynamics => "team_dynamics",
+            FeatureGroup::DecisionMetrics => "decision_metrics",
+            FeatureGroup::TemporalContext => "temporal_context",
+        }
+    }
+}
+
+/// Description of a single entry of the flat feature vector
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeatureDescriptor {
+    /// Fully qualified name, e.g. `player_mechanics.headshot_percentage`
+    /// or `temporal_context.success_rates_by_area.mean`
+    pub name: String,
+    pub group: FeatureGroup,
+    /// Source struct field
+    pub field: String,
+    /// Aggregate applied to a map field, `None` for plain scalars
+    pub aggregate: Option<String>,
+}
+
+/// Published schema describing the ordering of the flat feature vector
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeatureSchema {
+    pub version: u32,
+    pub features: Vec<FeatureDescriptor>,
+}
+
+impl Default for FeatureSchema {
+    fn default() -> Self {
+        Self::current()
+    }
+}
+
+impl FeatureSchema {
+    /// Schema produced by `ExtractedFeatures::to_flat_vector` in this build
+    pub fn current() -> Self {
+        let mut features = Vec::new();
+        visit_features(
+            &ExtractedFeatures::default(),
+            &mut |group, field, aggregate, _| {
+                let name = match aggregate {
+                    Some(agg) => format!("{}.{field}.{agg}", group.as_str()),
+                    None => format!("{}.{field}", group.as_str()),
+                };
+                features.push(FeatureDescriptor {
+                    name,
+                    group,
+                    field: field.to_string(),
+                    aggregate: aggregate.map(String::from),
+                });
+            },
+        );
+
+        Self {
+            version: FEATURE_SCHEMA_VERSION,
+            features,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.features.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.features.is_empty()
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.features.iter().map(|f| f.name.as_str()).collect()
+    }
+
+    /// Position of a feature in the flat vector
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.features.iter().position(|f| f.name == name)
+    }
+
+    /// Pick the given named features out of a flat vector, in the requested order
+    pub fn select(&self, values: &[f32], names: &[&str]) -> Result<Vec<f32>, CS2Error> {
+        if values.len() != self.len() {
+            return Err(CS2Error::ParseError(format!(
+                "feature vector has {} values, schema v{} expects {}",
+                values.len(),
+                self.version,
+                self.len()
+            )));
+        }
+        names
+            .iter()
+            .map(|name| {
+                self.index_of(name)
+                    .map(|i| values[i])
+                    .ok_or_else(|| CS2Error::ParseError(format!("unknown feature: {name}")))
+            })
+            .collect()
+    }
+
+    /// Write the schema as JSON so downstream consumers can validate ordering
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), CS2Error> {
+        write_json(self, path)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, CS2Error> {
+        read_json(path)
+    }
+}
+
+impl ExtractedFeatures {
+    /// Flatten into the fixed-length vector described by `FeatureSchema::current()`
+    pub fn to_flat_vector(&self) -> Vec<f32> {
+        let mut values = Vec::new();
+        visit_features(self, &mut |_, _, _, value| values.push(value));
+        values
+    }
+
+    /// Flatten into `(name, value)` pairs, mostly useful for reports
+    pub fn to_named_values(&self) -> Vec<(String, f32)> {
+        FeatureSchema::current()
+            .features
+            .into_iter()
+            .map(|f| f.name)
+            .zip(self.to_flat_vector())
+            .collect()
+    }
+}
+
+/// Per-feature normalization statistics
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FeatureStats {
+    pub mean: f32,
+    pub std: f32,
+    pub min: f32,
+    pub max: f32,
+    pub p05: f32,
+    pub p25: f32,
+    pub p50: f32,
+    pub p75: f32,
+    pub p95: f32,
+}
+
+impl FeatureStats {
+    /// Summary statistics of a sample, or `None` if it is empty
+    pub fn from_values(values: &[f32]) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+        Some(Self::from_column(&mut values.to_vec()))
+    }
+
+    fn from_column(column: &mut [f32]) -> Self {
+        let n = column.len() as f32;
+        let mean = column.iter().sum::<f32>() / n;
+        let variance = column.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / n;
+        column.sort_by(|a, b| a.total_cmp(b));
+
+        Self {
+            mean,
+            std: variance.sqrt(),
+            min: column[0],
+            max: column[column.len() - 1],
+            p05: quantile(column, 0.05),
+            p25: quantile(column, 0.25),
+            p50: quantile(column, 0.50),
+            p75: quantile(column, 0.75),
+            p95: quantile(column, 0.95),
+        }
+    }
+}
+
+/// Normalization statistics fitted over a set of flat feature vectors
+///
+/// Shared by ML models, Qdrant embeddings and reports so that every consumer
+/// applies the same ordering and scaling.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FeatureNormalizer {
+    pub schema: FeatureSchema,
+    pub stats: Vec<FeatureStats>,
+    pub sample_count: usize,
+}
+
+impl FeatureNormalizer {
+    /// Fit statistics over flat vectors laid out according to `schema`
+    pub fn fit(schema: FeatureSchema, rows: &[Vec<f32>]) -> Result<Self, CS2Error> {
+        if rows.is_empty() {
+            return Err(CS2Error::ParseError(
+                "cannot fit feature normalizer on an empty dataset".to_string(),
+            ));
+        }
+        if let Some(bad) = rows.iter().find(|r| r.len() != schema.len()) {
+            return Err(CS2Error::ParseError(format!(
+                "feature vector has {} values, schema v{} expects {}",
+                bad.len(),
+                schema.version,
+                schema.len()
+            )));
+        }
+
+        let stats = (0..schema.len())
+            .map(|i| {
+                let mut column: Vec<f32> = rows.iter().map(|r| r[i]).collect();
+                FeatureStats::from_column(&mut column)
+            })
+            .collect();
+
+        Ok(Self {
+            schema,
+            stats,
+            sample_count: rows.len(),
+        })
+    }
+
+    /// Flatten and fit in one go using the current schema
+    pub fn fit_features(features: &[ExtractedFeatures]) -> Result<Self, CS2Error> {
+        let rows: Vec<Vec<f32>> = features.iter().map(|f| f.to_flat_vector()).collect();
+        Self::fit(FeatureSchema::current(), &rows)
+    }
+
+    /// Z-score normalization; constant features are only centered
+    pub fn transform(&self, values: &[f32]) -> Result<Vec<f32>, CS2Error> {
+        self.check_len(values)?;
+        Ok(values
+            .iter()
+            .zip(&self.stats)
+            .map(|(v, s)| {
+                if s.std > f32::EPSILON {
+                    (v - s.mean) / s.std
+                } else {
+                    v - s.mean
+                }
+            })
+            .collect())
+    }
+
+    /// Robust scaling using the median and interquartile range
+    pub fn transform_robust(&self, values: &[f32]) -> Result<Vec<f32>, CS2Error> {
+        self.check_len(values)?;
+        Ok(values
+            .iter()
+            .zip(&self.stats)
+            .map(|(v, s)| {
+                let iqr = s.p75 - s.p25;
+                if iqr > f32::EPSILON {
+                    (v - s.p50) / iqr
+                } else {
+                    v - s.p50
+                }
+            })
+            .collect())
+    }
+
+    /// Undo `transform`
+    pub fn inverse_transform(&self, values: &[f32]) -> Result<Vec<f32>, CS2Error> {
+        self.check_len(values)?;
+        Ok(values
+            .iter()
+            .zip(&self.stats)
+            .map(|(v, s)| {
+                if s.std > f32::EPSILON {
+                    v * s.std + s.mean
+                } else {
+                    v + s.mean
+                }
+            })
+            .collect())
+    }
+
+    /// Flatten and z-score normalize in one go
+    pub fn normalize_features(&self, features: &ExtractedFeatures) -> Result<Vec<f32>, CS2Error> {
+        self.transform(&features.to_flat_vector())
+    }
+
+    /// Fail if these statistics were fitted with a different feature layout
+    pub fn check_compatible(&self, schema: &FeatureSchema) -> Result<(), CS2Error> {
+        if &self.schema != schema {
+            return Err(CS2Error::ParseError(format!(
+                "normalizer fitted on feature schema v{} ({} features), got v{} ({} features)",
+                self.schema.version,
+                self.schema.len(),
+                schema.version,
+                schema.len()
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), CS2Error> {
+        write_json(self, path)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, CS2Error> {
+        read_json(path)
+    }
+
+    fn check_len(&self, values: &[f32]) -> Result<(), CS2Error> {
+        if values.len() != self.stats.len() {
+            return Err(CS2Error::ParseError(format!(
+                "feature vector has {} values, normalizer expects {}",
+                values.len(),
+                self.stats.len()
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Linear interpolation quantile over a sorted slice
+fn quantile(sorted: &[f32], q: f32) -> f32 {
+    if sorted.len() == 1 {
+        return sorted[0];
+    }
+    let pos = q * (sorted.len() - 1) as f32;
+    let lo = pos.floor() as usize;
+    let hi = pos.ceil() as usize;
+    let frac = pos - lo as f32;
+    sorted[lo] + (sorted[hi] - sorted[lo]) * frac
+}
+
+pub(crate) fn write_json<T: Serialize>(value: &T, path: impl AsRef<Path>) -> Result<(), CS2Error> {
+    let json =
+        serde_json::to_string_pretty(value).map_err(|e| CS2Error::ParseError(e.to_string()))?;
+    std::fs::write(path, json)?;
+    Ok(())
+}
+
+pub(crate) fn read_json<T: for<'de> Deserialize<'de>>(
+    path: impl AsRef<Path>,
+) -> Result<T, CS2Error> {
+    let json = std::fs::read_to_string(path)?;
+    serde_json::from_str(&json).map_err(|e| CS2Error::ParseError(e.to_string()))
+}
+
+type Emit<'a> = dyn FnMut(FeatureGroup, &'static str, Option<&'static str>, f32) + 'a;
+
+fn emit_map<'a>(
+    emit: &mut Emit,
+    group: FeatureGroup,
+    field: &'static str,
+    values: impl Iterator<Item = &'a f32>,
+) {
+    // Sum in value order, f32 addition depends on the map's iteration order
+    let mut values: Vec<f32> = values.copied().collect();
+    values.sort_by(f32::total_cmp);
+    let count = values.len() as f32;
+    let (mean, min, max) = if values.is_empty() {
+        (0.0, 0.0, 0.0)
+    } else {
+        (
+            values.iter().sum::<f32>() / count,
+            values.iter().copied().fold(f32::INFINITY, f32::min),
+            values.iter().copied().fold(f32::NEG_INFINITY, f32::max),
+        )
+    };
+    for (agg, value) in MAP_AGGREGATES.iter().zip([count, mean, min, max]) {
+        emit(group, field, Some(agg), value);
+    }
+}
+
+fn nested_values(map: &HashMap<String, HashMap<String, f32>>) -> impl Iterator<Item = &f32> {
+    map.values().flat_map(|inner| inner.values())
+}
+
+/// Single source of truth for the flat layout; both the schema and the values
+/// are produced by walking the features through this function.
+fn visit_features(f: &ExtractedFeatures, emit: &mut Emit) {
+    macro_rules! scalars {
+        ($group:expr, $src:expr, [$($field:ident),* $(,)?]) => {
+            $(emit($group, stringify!($field), None, $src.$field);)*
+        };
+    }
+    macro_rules! maps {
+        ($group:expr, $src:expr, [$($field:ident),* $(,)?]) => {
+            $(emit_map(emit, $group, stringify!($field), $src.$field.values());)*
+        };
+    }
+
+    let pm = &f.player_mechanics;
+    let group = FeatureGroup::PlayerMechanics;
+    scalars!(
+        group,
+        pm,
+        [
+            headshot_percentage,
+            flick_accuracy,
+            flick_speed,
+            target_acquisition_time,
+            spray_control_deviation,
+            crosshair_placement_height,
+            pre_aim_accuracy,
+            counter_strafe_effectiveness,
+            peek_technique_score,
+            movement_efficiency,
+            position_transition_smoothness,
+            crouch_usage_pattern,
+            jump_usage_pattern,
+            air_strafe_control,
+            recoil_control_consistency,
+            burst_vs_spray_preference,
+            weapon_switch_speed,
+            positioning_vs_weapon_range,
+            first_bullet_accuracy,
+        ]
+    );
+    maps!(
+        group,
+        pm,
+        [headshot_percentage_per_weapon, weapon_preference_patterns]
+    );
+
+    let td = &f.team_dynamics;
+    let group = FeatureGroup::TeamDynamics;
+    scalars!(
+        group,
+        td,
+        [
+            formation_spread_vs_stack,
+            map_control_percentage,
+            defensive_setup_variations,
+            rotation_timing,
+            rotation_route_efficiency,
+            crossfire_setup_effectiveness,
+            smoke_coverage_effectiveness,
+            flash_effectiveness_enemies,
+            flash_effectiveness_teammates,
+            molotov_area_denial_effectiveness,
+            grenade_damage_efficiency,
+            utility_timing_vs_executes,
+            support_utility_coordination,
+            execute_timing_consistency,
+            role_adherence,
+            trade_efficiency,
+            mid_round_adaptation_frequency,
+        ]
+    );
+    maps!(
+        group,
+        td,
+        [
+            site_approach_patterns,
+            default_strategy_identification,
+            execute_success_rate_by_type,
+        ]
+    );
+
+    let dm = &f.decision_metrics;
+    let group = FeatureGroup::DecisionMetrics;
+    scalars!(
+        group,
+        dm,
+        [
+            buy_efficiency_value_per_dollar,
+            save_decision_quality,
+            force_buy_success_rate,
+            investment_utility_vs_weapons,
+            economic_impact_on_strategy,
+            information_based_rotation_timing,
+            decision_speed_after_first_contact,
+            re_aggression_timing_patterns,
+            post_plant_positioning_decisions,
+            timeout_impact_on_decision_quality,
+            reaction_time_visual_stimuli,
+            reaction_time_audio_stimuli,
+            adjustment_time_after_enemy_spotted,
+            reaction_consistency,
+            threat_prioritization_under_pressure,
+        ]
+    );
+
+    let tc = &f.temporal_context;
+    let group = FeatureGroup::TemporalContext;
+    scalars!(
+        group,
+        tc,
+        [
+            clutch_performance_metrics,
+            counter_strategy_effectiveness,
+            adaptation_to_opponent_patterns,
+            anti_strategy_timing,
+            information_denial_effectiveness,
+        ]
+    );
+    maps!(
+        group,
+        tc,
+        [
+            early_round_tendencies,
+            mid_round_adaptations,
+            late_round_decision_patterns,
+            map_specific_tendencies,
+            success_rates_by_area,
+            route_preference_patterns,
+        ]
+    );
+    emit_map(
+        emit,
+        group,
+        "position_preference_by_map",
+        nested_values(&tc.position_preference_by_map),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_features(scale: f32) -> ExtractedFeatures {
+        let mut features = ExtractedFeatures::default();
+        features.player_mechanics.headshot_percentage = 0.5 * scale;
+        features.player_mechanics.flick_accuracy = scale;
+        features
+            .player_mechanics
+            .weapon_preference_patterns
+            .insert("AK-47".to_string(), 0.75 * scale);
+        features
+            .player_mechanics
+            .weapon_preference_patterns
+            .insert("AWP".to_string(), 0.25 * scale);
+        features.temporal_context.clutch_performance_metrics = 2.0 * scale;
+        features
+    }
+
+    #[test]
+    fn schema_matches_vector_length() {
+        let schema = FeatureSchema::current();
+        let flat = sample_features(1.0).to_flat_vector();
+        assert_eq!(schema.len(), flat.len());
+        assert_eq!(schema.version, FEATURE_SCHEMA_VERSION);
+
+        // Names are unique
+        let mut names = schema.names();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), schema.len());
+    }
+
+    #[test]
+    fn flattening_is_deterministic_for_maps() {
+        let features = sample_features(1.0);
+        let schema = FeatureSchema::current();
+        let flat = features.to_flat_vector();
+
+        let picked = schema
+            .select(
+                &flat,
+                &[
+                    "player_mechanics.headshot_percentage",
+                    "player_mechanics.weapon_preference_patterns.count",
+                    "player_mechanics.weapon_preference_patterns.mean",
+                    "player_mechanics.weapon_preference_patterns.max",
+                    "temporal_context.clutch_performance_metrics",
+                ],
+            )
+            .unwrap();
+        assert_eq!(picked, vec![0.5, 2.0, 0.5, 0.75, 2.0]);
+
+        // The same entries inserted in the opposite order, into maps with their own hashers
+        let weapons = [
+            ("AK-47", 1e8),
+            ("AWP", 0.25),
+            ("M4A4", -1e8),
+            ("Desert Eagle", 3.0),
+            ("USP-S", 0.1),
+            ("MP9", -2.5),
+            ("Galil AR", 7.25),
+        ];
+        let with_weapons = |weapons: Vec<&(&str, f32)>| {
+            let mut features = sample_features(1.0);
+            features.player_mechanics.weapon_preference_patterns = weapons
+                .into_iter()
+                .map(|(name, share)| (name.to_string(), *share))
+                .collect();
+            features.to_flat_vector()
+        };
+        for _ in 0..8 {
+            assert_eq!(
+                with_weapons(weapons.iter().collect()),
+                with_weapons(weapons.iter().rev().collect())
+            );
+        }
+    }
+
+    #[test]
+    fn select_rejects_unknown_feature() {
+        let schema = FeatureSchema::current();
+        let flat = sample_features(1.0).to_flat_vector();
+        assert!(schema.select(&flat, &["player_mechanics.nope"]).is_err());
+        assert!(schema.select(&flat[1..], &[]).is_err());
+    }
+
+    #[test]
+    fn normalizer_fit_and_transform() {
+        let features: Vec<ExtractedFeatures> = (1..=5).map(|i| sample_features(i as f32)).collect();
+        let normalizer = FeatureNormalizer::fit_features(&features).unwrap();
+        let idx = normalizer
+            .schema
+            .index_of("player_mechanics.flick_accuracy")
+            .unwrap();
+
+        let stats = &normalizer.stats[idx];
+        assert!((stats.mean - 3.0).abs() < 1e-6);
+        assert_eq!(stats.min, 1.0);
+        assert_eq!(stats.max, 5.0);
+        assert!((stats.p50 - 3.0).abs() < 1e-6);
+        assert!((stats.p25 - 2.0).abs() < 1e-6);
+
+        let flat = features[2].to_flat_vector();
+        let normalized = normalizer.transform(&flat).unwrap();
+        assert!(normalized[idx].abs() < 1e-6);
+
+        let restored = normalizer.inverse_transform(&normalized).unwrap();
+        for (a, b) in restored.iter().zip(&flat) {
+            assert!((a - b).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn normalizer_roundtrip_through_json() {
+        let features: Vec<ExtractedFeatures> = (1..=3).map(|i| sample_features(i as f32)).collect();
+        let normalizer = FeatureNormalizer::fit_features(&features).unwrap();
+
+        let dir = std::env::temp_dir().join(format!("cs2_norm_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("normalizer.json");
+        normalizer.save(&path).unwrap();
+        let loaded = FeatureNormalizer::load(&path).unwrap();
+        std::fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(loaded, normalizer);
+        assert!(loaded.check_compatible(&FeatureSchema::current()).is_ok());
+    }
+
+    #[test]
+    fn normalizer_rejects_mismatched_rows() {
+        let schema = FeatureSchema::current();
+        assert!(FeatureNormalizer::fit(schema.clone(), &[]).is_err());
+        assert!(FeatureNormalizer::fit(schema, &[vec![0.0; 3]]).is_err());
+    }
+}
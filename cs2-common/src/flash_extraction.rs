@@ -0,0 +1,425 @@
+use crate::feature_extraction::TeamDynamicsFeatures;
+use crate::match_clock::MatchClock;
+use crate::match_events::{BlindEvent, DeathEvent, GrenadeDetonation};
+use crate::player_frames::FrameIndex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Flash Effectiveness Extractor - Scores thrown flashbangs by who they blinded and what followed
+pub struct FlashEffectivenessExtractor {
+    pub clock: MatchClock,
+    pub kill_window_secs: f32, // A kill on a blinded enemy within this time counts for the flash
+    pub blind_match_secs: f32, // Blinds must start this soon after the detonation
+    pub min_blind_secs: f32,   // Shorter blinds are ignored
+}
+
+impl Default for FlashEffectivenessExtractor {
+    fn default() -> Self {
+        Self {
+            clock: MatchClock::default(),
+            kill_window_secs: 2.0,
+            blind_match_secs: 0.25,
+            min_blind_secs: 0.5,
+        }
+    }
+}
+
+/// One player blinded by a flashbang
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Blind {
+    pub steamid: u64,
+    pub seconds: f32,
+}
+
+/// Result of a single flashbang
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FlashOutcome {
+    pub tick: u32, // Detonation tick
+    pub thrower: u64,
+    pub entity_id: i32,
+    pub enemies: Vec<Blind>,
+    pub teammates: Vec<Blind>,
+    pub self_blind_secs: f32,
+    pub led_to_kill: bool,
+    pub exposure_delay_secs: Option<f32>, // Detonation until a blinded enemy was first seen
+    pub pop_quality: Option<f32>,         // Share of the blind left when the enemy was exposed
+}
+
+impl FlashOutcome {
+    pub fn enemy_blind_secs(&self) -> f32 {
+        self.enemies.iter().map(|b| b.seconds).sum()
+    }
+
+    pub fn team_blind_secs(&self) -> f32 {
+        self.teammates.iter().map(|b| b.seconds).sum()
+    }
+}
+
+/// Per-thrower flash statistics
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FlashStats {
+    pub steamid: u64,
+    pub flashes_thrown: u32,
+    pub enemies_blinded: u32,
+    pub enemy_blind_secs: f32,
+    pub team_blind_secs: f32,
+    pub self_blind_secs: f32,
+    pub flashes_blinding_enemies: u32,
+    pub flashes_blinding_teammates: u32,
+    pub flashes_leading_to_kill: u32,
+    pub avg_exposure_delay_secs: Option<f32>,
+    pub pop_flash_quality: Option<f32>, // Mean pop quality of flashes that blinded enemies
+}
+
+impl FlashStats {
+    /// Replace the placeholder flash scores with measured ones
+    ///
+    /// Enemies: share of flashes that blinded an enemy. Teammates: share of
+    /// flashes that did not blind a teammate (higher is better).
+    pub fn apply_to(&self, features: &mut TeamDynamicsFeatures) {
+        if self.flashes_thrown > 0 {
+            let thrown = self.flashes_thrown as f32;
+            features.flash_effectiveness_enemies = self.flashes_blinding_enemies as f32 / thrown;
+            features.flash_effectiveness_teammates =
+                1.0 - self.flashes_blinding_teammates as f32 / thrown;
+        }
+    }
+}
+
+/// Every flashbang outcome plus per-thrower aggregates
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FlashReport {
+    pub flashes: Vec<FlashOutcome>,
+    pub players: HashMap<u64, FlashStats>,
+}
+
+impl FlashEffectivenessExtractor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an extractor that converts ticks with the demo's clock
+    pub fn with_clock(clock: &MatchClock) -> Self {
+        Self {
+            clock: *clock,
+            ..Self::default()
+        }
+    }
+
+    /// Score every flashbang detonation
+    ///
+    /// Blinds come from `player_blind` events. When none were parsed they are
+    /// recovered from increases of `m_flFlashDuration` right after each
+    /// detonation.
+    pub fn extract(
+        &self,
+        detonations: &[GrenadeDetonation],
+        blinds: &[BlindEvent],
+        deaths: &[DeathEvent],
+        frames: &FrameIndex,
+    ) -> FlashReport {
+        let mut flashes: Vec<&GrenadeDetonation> =
+            detonations.iter().filter(|d| d.is_flashbang()).collect();
+        flashes.sort_by_key(|d| d.tick);
+
+        let mut report = FlashReport::default();
+        for detonation in flashes {
+            let blinded = if blinds.is_empty() {
+                self.blinds_from_frames(detonation, frames)
+            } else {
+                self.blinds_from_events(detonation, blinds, frames)
+            };
+            let outcome = self.score_flash(detonation, blinded, deaths, frames);
+
+            let stats = report
+                .players
+                .entry(outcome.thrower)
+                .or_insert_with(|| FlashStats {
+                    steamid: outcome.thrower,
+                    ..Default::default()
+                });
+            stats.flashes_thrown += 1;
+            stats.enemies_blinded += outcome.enemies.len() as u32;
+            stats.enemy_blind_secs += outcome.enemy_blind_secs();
+            stats.team_blind_secs += outcome.team_blind_secs();
+            stats.self_blind_secs += outcome.self_blind_secs;
+            stats.flashes_blinding_enemies += !outcome.enemies.is_empty() as u32;
+            stats.flashes_blinding_teammates += !outcome.teammates.is_empty() as u32;
+            stats.flashes_leading_to_kill += outcome.led_to_kill as u32;
+
+            report.flashes.push(outcome);
+        }
+
+        for (&thrower, stats) in report.players.iter_mut() {
+            let thrown: Vec<&FlashOutcome> = report
+                .flashes
+                .iter()
+                .filter(|f| f.thrower == thrower)
+                .collect();
+            stats.avg_exposure_delay_secs =
+                mean(thrown.iter().filter_map(|f| f.exposure_delay_secs));
+            stats.pop_flash_quality = mean(thrown.iter().filter_map(|f| f.pop_quality));
+        }
+
+        report
+    }
+
+    fn blinds_from_events(
+        &self,
+        detonation: &GrenadeDetonation,
+        blinds: &[BlindEvent],
+        frames: &FrameIndex,
+    ) -> Vec<(u64, f32, u8)> {
+        let match_ticks = self.clock.seconds_to_ticks(self.blind_match_secs);
+        blinds
+            .iter()
+            .filter(|b| {
+                // Entity ids are reused, so the timing has to line up as well
+                let same_flash = match b.entity_id {
+                    Some(entity_id) => entity_id == detonation.entity_id,
+                    None => b.attacker == Some(detonation.thrower),
+                };
+                same_flash && b.tick >= detonation.tick && b.tick <= detonation.tick + match_ticks
+            })
+            .map(|b| {
+                let seconds = if b.blind_duration > 0.0 {
+                    b.blind_duration
+                } else {
+                    frames
+                        .frame_at(b.victim, b.tick)
+                        .map(|f| f.flash_duration)
+                        .unwrap_or(0.0)
+                };
+                (b.victim, seconds, b.victim_team)
+            })
+            .collect()
+    }
+
+    fn blinds_from_frames(
+        &self,
+        detonation: &GrenadeDetonation,
+        frames: &FrameIndex,
+    ) -> Vec<(u64, f32, u8)> {
+        let end = detonation.tick + self.clock.seconds_to_ticks(self.blind_match_secs);
+        frames
+            .players()
+            .filter_map(|steamid| {
+                let before = frames
+                    .frame_at(steamid, detonation.tick.saturating_sub(1))
+                    .map(|f| f.flash_duration)
+                    .unwrap_or(0.0);
+                let after = frames
+                    .frames_between(steamid, detonation.tick, end)
+                    .iter()
+                    .map(|f| f.flash_duration)
+                    .fold(0.0, f32::max);
+                (after > before).then_some((steamid, after, 0))
+            })
+            .collect()
+    }
+
+    fn score_flash(
+        &self,
+        detonation: &GrenadeDetonation,
+        blinded: Vec<(u64, f32, u8)>, // (steamid, seconds, team from the event)
+        deaths: &[DeathEvent],
+        frames: &FrameIndex,
+    ) -> FlashOutcome {
+        let team_at = |steamid: u64| {
+            frames
+                .frame_at(steamid, detonation.tick)
+                .map(|f| f.team_num)
+                .unwrap_or(0)
+        };
+        let thrower_team = team_at(detonation.thrower);
+
+        let mut outcome = FlashOutcome {
+            tick: detonation.tick,
+            thrower: detonation.thrower,
+            entity_id: detonation.entity_id,
+            enemies: Vec::new(),
+            teammates: Vec::new(),
+            self_blind_secs: 0.0,
+            led_to_kill: false,
+            exposure_delay_secs: None,
+            pop_quality: None,
+        };
+        for (steamid, seconds, event_team) in blinded {
+            if seconds < self.min_blind_secs {
+                continue;
+            }
+            let team = match team_at(steamid) {
+                0 => event_team,
+                team => team,
+            };
+            if steamid == detonation.thrower {
+                outcome.self_blind_secs += seconds;
+            } else if thrower_team == 0 || team == 0 {
+                // Without both sides known the blind is neither a teammate nor an enemy
+                continue;
+            } else if team == thrower_team {
+                outcome.teammates.push(Blind { steamid, seconds });
+            } else {
+                outcome.enemies.push(Blind { steamid, seconds });
+            }
+        }
+
+        let kill_end = detonation.tick + self.clock.seconds_to_ticks(self.kill_window_secs);
+        outcome.led_to_kill = deaths.iter().any(|d| {
+            d.tick >= detonation.tick
+                && d.tick <= kill_end
+                && outcome.enemies.iter().any(|b| b.steamid == d.victim)
+                && d.killer().is_some_and(|killer| {
+                    killer == detonation.thrower
+                        || (thrower_team != 0 && team_at(killer) == thrower_team)
+                })
+        });
+
+        // Pop quality: how much of the blind was left when the thrower's team first saw the enemy
+        for enemy in &outcome.enemies {
+            let blind_end = detonation.tick + self.clock.seconds_to_ticks(enemy.seconds);
+            let timeline = frames.frames_between(enemy.steamid, detonation.tick, blind_end);
+            if timeline.is_empty() {
+                continue;
+            }
+            let exposed = timeline.iter().find(|f| {
+                f.spotted_by
+                    .iter()
+                    .any(|&observer| team_at(observer) == thrower_team)
+            });
+            let (delay, quality) = match exposed {
+                Some(frame) => {
+                    let delay = self.clock.elapsed_seconds(detonation.tick, frame.tick);
+                    (Some(delay), (1.0 - delay / enemy.seconds).max(0.0))
+                }
+                None => (None, 0.0),
+            };
+            if let Some(delay) = delay {
+                outcome.exposure_delay_secs =
+                    Some(outcome.exposure_delay_secs.map_or(delay, |d| d.min(delay)));
+            }
+            outcome.pop_quality = Some(outcome.pop_quality.map_or(quality, |q| q.max(quality)));
+        }
+
+        outcome
+    }
+}
+
+fn mean(values: impl Iterator<Item = f32>) -> Option<f32> {
+    let (sum, count) = values.fold((0.0, 0), |(s, c), v| (s + v, c + 1));
+    (count > 0).then(|| sum / count as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player_frames::{PlayerFrame, TEAM_CT, TEAM_T};
+    use crate::test_fixtures::frame;
+
+    fn flash(tick: u32, entity_id: i32, thrower: u64) -> GrenadeDetonation {
+        GrenadeDetonation {
+            tick,
+            entity_id,
+            thrower,
+            grenade_type: "CFlashbangProjectile".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn blind(tick: u32, victim: u64, entity_id: i32, blind_duration: f32) -> BlindEvent {
+        BlindEvent {
+            tick,
+            victim,
+            attacker: Some(1),
+            blind_duration,
+            entity_id: Some(entity_id),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_flash_blinds_kill_and_pop_quality() {
+        // T 1 flashes CT 10 (2s) and teammate T 2 (1s); T 2 sees CT 10 0.5s later and kills him
+        let frames = FrameIndex::new(vec![
+            frame(0, 1, TEAM_T, vec![]),
+            frame(0, 2, TEAM_T, vec![]),
+            frame(0, 10, TEAM_CT, vec![]),
+            frame(132, 10, TEAM_CT, vec![2]),
+        ]);
+        let detonations = vec![
+            flash(100, 50, 1),
+            GrenadeDetonation {
+                grenade_type: "CSmokeGrenadeProjectile".to_string(),
+                ..flash(100, 51, 1)
+            },
+        ];
+        let blinds = vec![
+            blind(100, 10, 50, 2.0),
+            blind(100, 2, 50, 1.0),
+            blind(100, 1, 50, 0.2), // Too short to count
+        ];
+        let deaths = vec![DeathEvent {
+            tick: 200,
+            victim: 10,
+            attacker: Some(2),
+            ..Default::default()
+        }];
+
+        let report =
+            FlashEffectivenessExtractor::new().extract(&detonations, &blinds, &deaths, &frames);
+
+        assert_eq!(report.flashes.len(), 1);
+        let outcome = &report.flashes[0];
+        assert_eq!(outcome.enemy_blind_secs(), 2.0);
+        assert_eq!(outcome.team_blind_secs(), 1.0);
+        assert_eq!(outcome.self_blind_secs, 0.0);
+        assert!(outcome.led_to_kill);
+        assert_eq!(outcome.exposure_delay_secs, Some(0.5));
+        assert_eq!(outcome.pop_quality, Some(0.75));
+
+        let stats = &report.players[&1];
+        assert_eq!(stats.flashes_thrown, 1);
+        assert_eq!(stats.flashes_leading_to_kill, 1);
+
+        let mut features = TeamDynamicsFeatures::default();
+        stats.apply_to(&mut features);
+        assert_eq!(features.flash_effectiveness_enemies, 1.0);
+        assert_eq!(features.flash_effectiveness_teammates, 0.0);
+    }
+
+    #[test]
+    fn test_blinds_recovered_from_flash_duration() {
+        let frames = FrameIndex::new(vec![
+            frame(0, 1, TEAM_T, vec![]),
+            frame(0, 10, TEAM_CT, vec![]),
+            PlayerFrame {
+                flash_duration: 3.0,
+                ..frame(102, 10, TEAM_CT, vec![])
+            },
+        ]);
+
+        let report =
+            FlashEffectivenessExtractor::new().extract(&[flash(100, 50, 1)], &[], &[], &frames);
+
+        assert_eq!(report.flashes[0].enemy_blind_secs(), 3.0);
+        assert!(!report.flashes[0].led_to_kill);
+        assert_eq!(report.flashes[0].pop_quality, Some(0.0));
+    }
+
+    #[test]
+    fn test_unknown_team_blinds_are_not_scored() {
+        // Thrower 1 has no frames, so its side is unknown
+        let frames = FrameIndex::new(vec![
+            frame(0, 10, TEAM_CT, vec![]),
+            frame(0, 11, TEAM_T, vec![]),
+        ]);
+        let blinds = vec![blind(100, 10, 50, 2.0), blind(100, 11, 50, 2.0)];
+
+        let report =
+            FlashEffectivenessExtractor::new().extract(&[flash(100, 50, 1)], &blinds, &[], &frames);
+
+        assert!(report.flashes[0].enemies.is_empty());
+        assert!(report.flashes[0].teammates.is_empty());
+        assert_eq!(report.players[&1].flashes_blinding_teammates, 0);
+    }
+}
@@ -1,15 +1,48 @@
+pub mod clutch_analysis;
+pub mod crosshair_placement;
 pub mod feature_extraction;
+pub mod feature_schema;
+pub mod flash_extraction;
+pub mod match_clock;
+pub mod match_events;
 pub mod parsing_features;
+pub mod player_frames;
+pub mod policy_protocol;
+pub mod reaction_extraction;
+pub mod scoreboard;
 pub mod team_decision_extraction;
 pub mod temporal_extraction;
+#[cfg(test)]
+mod test_fixtures;
+pub mod trade_analysis;
+pub mod win_probability;
 
 // Re-export extractors for easy access
+pub use clutch_analysis::{Clutch, ClutchAnalyzer, ClutchReport, ClutchStats};
+pub use crosshair_placement::{CrosshairPlacementExtractor, PlacementProfile};
 pub use feature_extraction::{
     DecisionMetricsFeatures, ExtractedFeatures, PlayerMechanicsExtractor, PlayerMechanicsFeatures,
     TeamDynamicsFeatures, TemporalContextFeatures,
 };
+pub use feature_schema::{FeatureNormalizer, FeatureSchema, FeatureStats, FEATURE_SCHEMA_VERSION};
+pub use flash_extraction::{FlashEffectivenessExtractor, FlashReport, FlashStats};
+pub use match_clock::MatchClock;
+pub use match_events::{
+    BlindEvent, BombEvent, DeathEvent, GrenadeDetonation, HurtEvent, RoundInfo, ShotEvent,
+    WeaponClass,
+};
+pub use player_frames::{FrameIndex, PlayerFrame};
+pub use policy_protocol::{
+    ErrorCode, ErrorReply, Frame, Handshake, MessageType, PROTOCOL_MAGIC, PROTOCOL_VERSION,
+};
+pub use reaction_extraction::{ReactionProfile, VisualReactionExtractor};
+pub use scoreboard::{PlayerMatchStats, Scoreboard, ScoreboardBuilder};
 pub use team_decision_extraction::{DecisionMetricsExtractor, TeamDynamicsExtractor};
 pub use temporal_extraction::TemporalContextExtractor;
+pub use trade_analysis::{TradeAnalyzer, TradeReport, TradeStats};
+pub use win_probability::{
+    RoundState, RoundStateBuilder, WinProbabilityModel, WinProbabilityTimeline,
+};
 
 use bytemuck::{Pod, Zeroable};
 use serde::{Deserialize, Serialize};
@@ -28,6 +61,117 @@ pub enum CS2Error {
 
     #[error("Model error: {0}")]
     ModelError(String),
+
+    #[error("Protocol error: {0}")]
+    ProtocolError(String),
+}
+
+/// Weapon ID used in behavioral vectors, derived from the item name (`"weapon_ak47"`)
+///
+/// Demos and Game State Integration both report item names, so live inputs
+/// get the same IDs the models were trained on.
+pub fn weapon_id_from_name(name: &str) -> u16 {
+    name.chars().fold(0u16, |a, b| a.wrapping_add(b as u16))
+}
+
+/// Item names of the weapons and equipment players carry
+pub const WEAPON_NAMES: [&str; 44] = [
+    "weapon_ak47",
+    "weapon_m4a1",
+    "weapon_m4a1_silencer",
+    "weapon_awp",
+    "weapon_deagle",
+    "weapon_glock",
+    "weapon_usp_silencer",
+    "weapon_hkp2000",
+    "weapon_p250",
+    "weapon_fiveseven",
+    "weapon_tec9",
+    "weapon_cz75a",
+    "weapon_elite",
+    "weapon_revolver",
+    "weapon_famas",
+    "weapon_galilar",
+    "weapon_aug",
+    "weapon_sg556",
+    "weapon_ssg08",
+    "weapon_scar20",
+    "weapon_g3sg1",
+    "weapon_mac10",
+    "weapon_mp9",
+    "weapon_mp7",
+    "weapon_mp5sd",
+    "weapon_ump45",
+    "weapon_p90",
+    "weapon_bizon",
+    "weapon_nova",
+    "weapon_xm1014",
+    "weapon_mag7",
+    "weapon_sawedoff",
+    "weapon_m249",
+    "weapon_negev",
+    "weapon_knife",
+    "weapon_knife_t",
+    "weapon_taser",
+    "weapon_hegrenade",
+    "weapon_flashbang",
+    "weapon_smokegrenade",
+    "weapon_molotov",
+    "weapon_incgrenade",
+    "weapon_decoy",
+    "weapon_c4",
+];
+
+/// Item name of a weapon ID from [`weapon_id_from_name`], if it is a known weapon
+pub fn weapon_name(weapon_id: u16) -> Option<&'static str> {
+    WEAPON_NAMES
+        .iter()
+        .find(|name| weapon_id_from_name(name) == weapon_id)
+        .copied()
+}
+
+/// Item name of a weapon from the display name demos report (`"AK-47"` is `"weapon_ak47"`)
+///
+/// Item names are returned as they are and every knife finish is `weapon_knife`.
+pub fn weapon_item_name(name: &str) -> Option<&'static str> {
+    let lower = name.to_ascii_lowercase();
+    let key: String = lower
+        .strip_prefix("weapon_")
+        .unwrap_or(&lower)
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect();
+    let item = match key.as_str() {
+        "deserteagle" => "deagle",
+        "dualberettas" => "elite",
+        "glock18" => "glock",
+        "m4a4" => "m4a1",
+        "m4a1s" => "m4a1silencer",
+        "usps" => "uspsilencer",
+        "p2000" => "hkp2000",
+        "sg553" => "sg556",
+        "cz75auto" => "cz75a",
+        "r8revolver" => "revolver",
+        "ppbizon" => "bizon",
+        "zeusx27" => "taser",
+        "highexplosivegrenade" => "hegrenade",
+        "decoygrenade" => "decoy",
+        "incendiarygrenade" => "incgrenade",
+        "c4explosive" => "c4",
+        k if k != "knifet"
+            && (k.contains("knife")
+                || k.contains("bayonet")
+                || k == "karambit"
+                || k == "shadowdaggers") =>
+        {
+            "knife"
+        }
+        k => k,
+    };
+    WEAPON_NAMES
+        .iter()
+        .find(|n| n["weapon_".len()..].replace('_', "") == item)
+        .copied()
 }
 
 /// A behavioral vector representing player state and actions
@@ -53,7 +197,7 @@ pub struct BehavioralVector {
 }
 
 /// Network input vector (C-compatible, for fast binary serialization)
-#[derive(Copy, Clone, Pod, Zeroable)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
 #[repr(C)]
 pub struct InputVector {
     pub health: f32,
@@ -73,13 +217,25 @@ pub struct InputVector {
 }
 
 /// Network output vector (C-compatible, for fast binary serialization)
-#[derive(Copy, Clone, Pod, Zeroable)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
 #[repr(C)]
 pub struct OutputVector {
     pub delta_yaw: f32,
     pub delta_pitch: f32,
 }
 
+/// Network movement output (C-compatible, for fast binary serialization)
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+#[repr(C)]
+pub struct MovementVector {
+    pub forward_move: f32, // -1.0 (back) to 1.0 (forward)
+    pub side_move: f32,    // -1.0 (left) to 1.0 (right)
+    pub jump: f32,         // 1.0 to jump
+    pub walk: f32,         // 1.0 to hold walk
+    pub confidence: f32,   // Probability of the chosen action
+    pub action_index: u32, // Discrete action, 96 for no-op
+}
+
 impl InputVector {
     /// Convert from a behavioral vector to network input format
     pub fn from_behavioral(bv: &BehavioralVector) -> Self {
@@ -103,6 +259,29 @@ impl InputVector {
 }
 
 impl BehavioralVector {
+    /// Rebuild a behavioral vector from network input, without aim deltas
+    pub fn from_input(input: &InputVector, tick: u32, steamid: u64) -> Self {
+        Self {
+            tick,
+            steamid,
+            health: input.health,
+            armor: input.armor,
+            pos_x: input.pos_x,
+            pos_y: input.pos_y,
+            pos_z: input.pos_z,
+            vel_x: input.vel_x,
+            vel_y: input.vel_y,
+            vel_z: input.vel_z,
+            yaw: input.yaw,
+            pitch: input.pitch,
+            weapon_id: input.weapon_id_f32 as u16,
+            ammo: input.ammo,
+            is_airborne: input.is_airborne,
+            delta_yaw: 0.0,
+            delta_pitch: 0.0,
+        }
+    }
+
     /// Create a new behavioral vector with default values
     pub fn new(tick: u32, steamid: u64) -> Self {
         Self {
@@ -160,6 +339,11 @@ mod tests {
         assert_eq!(std::mem::size_of::<OutputVector>(), 2 * 4); // 2 f32 values = 8 bytes
     }
 
+    #[test]
+    fn test_movement_vector_size() {
+        assert_eq!(std::mem::size_of::<MovementVector>(), 6 * 4); // 5 f32 and a u32 = 24 bytes
+    }
+
     #[test]
     fn test_behavioral_vector_new() {
         let bv = BehavioralVector::new(42, 76561198123456789);
@@ -273,4 +457,25 @@ mod tests {
             _ => panic!("Error conversion failed"),
         }
     }
+
+    #[test]
+    fn test_weapon_names_round_trip() {
+        let ids: std::collections::HashSet<u16> = WEAPON_NAMES
+            .iter()
+            .map(|n| weapon_id_from_name(n))
+            .collect();
+        assert_eq!(ids.len(), WEAPON_NAMES.len());
+        for name in WEAPON_NAMES {
+            assert_eq!(weapon_name(weapon_id_from_name(name)), Some(name));
+        }
+        assert_eq!(weapon_name(0), None);
+
+        assert_eq!(weapon_item_name("AK-47"), Some("weapon_ak47"));
+        assert_eq!(weapon_item_name("M4A1-S"), Some("weapon_m4a1_silencer"));
+        assert_eq!(weapon_item_name("Zeus x27"), Some("weapon_taser"));
+        assert_eq!(weapon_item_name("Karambit"), Some("weapon_knife"));
+        assert_eq!(weapon_item_name("knife_t"), Some("weapon_knife_t"));
+        assert_eq!(weapon_item_name("weapon_awp"), Some("weapon_awp"));
+        assert_eq!(weapon_item_name("Kevlar Vest"), None);
+    }
 }
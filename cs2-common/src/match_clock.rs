@@ -0,0 +1,184 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+
+/// Tick rate assumed when a demo does not advertise one
+pub const DEFAULT_TICK_RATE: f32 = 64.0;
+/// `mp_roundtime_defuse` of the competitive config (1.92 minutes)
+pub const DEFAULT_ROUND_TIME_SECS: f32 = 115.0;
+/// `mp_freezetime` of the competitive config
+pub const DEFAULT_FREEZE_TIME_SECS: f32 = 20.0;
+/// `mp_c4timer` of the competitive config
+pub const DEFAULT_BOMB_TIMER_SECS: f32 = 40.0;
+
+/// Header key holding the server tick interval in seconds
+pub const TICK_INTERVAL_HEADER_KEY: &str = "tick_interval";
+
+/// Match timing derived from the demo header and server convars
+///
+/// Converts between ticks, seconds, round time and bomb timer so that
+/// timing-based features stay correct for 128-tick demos and for servers
+/// running non-default round settings.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MatchClock {
+    pub tick_rate: f32,        // Ticks per second
+    pub round_time_secs: f32,  // Playable round time after freeze time
+    pub freeze_time_secs: f32, // Freeze time at the start of each round
+    pub bomb_timer_secs: f32,  // Time from plant to detonation
+}
+
+impl Default for MatchClock {
+    fn default() -> Self {
+        Self::new(DEFAULT_TICK_RATE)
+    }
+}
+
+impl MatchClock {
+    pub fn new(tick_rate: f32) -> Self {
+        Self {
+            tick_rate,
+            round_time_secs: DEFAULT_ROUND_TIME_SECS,
+            freeze_time_secs: DEFAULT_FREEZE_TIME_SECS,
+            bomb_timer_secs: DEFAULT_BOMB_TIMER_SECS,
+        }
+    }
+
+    /// Build a clock from the parsed demo header and the server convars
+    ///
+    /// Missing or malformed values fall back to the competitive defaults.
+    pub fn from_demo_metadata<H: BuildHasher, C: BuildHasher>(
+        header: Option<&HashMap<String, String, H>>,
+        convars: &HashMap<String, String, C>,
+    ) -> Self {
+        let mut clock = Self::default();
+
+        if let Some(interval) = header
+            .and_then(|h| h.get(TICK_INTERVAL_HEADER_KEY))
+            .and_then(|v| v.parse::<f32>().ok())
+            .filter(|v| *v > 0.0)
+        {
+            clock.tick_rate = (1.0 / interval).round();
+        }
+
+        // Apply the generic round time first so the defuse-specific value wins
+        for name in [
+            "mp_roundtime",
+            "mp_roundtime_defuse",
+            "mp_freezetime",
+            "mp_c4timer",
+        ] {
+            if let Some(value) = convars.get(name) {
+                clock.apply_convar(name, value);
+            }
+        }
+
+        clock
+    }
+
+    /// Update the clock from a single convar; returns whether it was used
+    pub fn apply_convar(&mut self, name: &str, value: &str) -> bool {
+        let Some(value) = value.trim().parse::<f32>().ok().filter(|v| *v >= 0.0) else {
+            return false;
+        };
+
+        match name {
+            // Round time convars are expressed in minutes
+            "mp_roundtime" | "mp_roundtime_defuse" | "mp_roundtime_hostage" => {
+                self.round_time_secs = value * 60.0;
+            }
+            "mp_freezetime" => self.freeze_time_secs = value,
+            "mp_c4timer" => self.bomb_timer_secs = value,
+            _ => return false,
+        }
+        true
+    }
+
+    /// Duration of a number of ticks in seconds
+    pub fn ticks_to_seconds(&self, ticks: u32) -> f32 {
+        ticks as f32 / self.tick_rate
+    }
+
+    /// Number of whole ticks closest to a duration in seconds
+    pub fn seconds_to_ticks(&self, seconds: f32) -> u32 {
+        (seconds * self.tick_rate).round().max(0.0) as u32
+    }
+
+    /// Seconds elapsed between two ticks (zero if `to_tick` is earlier)
+    pub fn elapsed_seconds(&self, from_tick: u32, to_tick: u32) -> f32 {
+        self.ticks_to_seconds(to_tick.saturating_sub(from_tick))
+    }
+
+    /// Tick at which freeze time ends for a round starting at `round_start_tick`
+    pub fn freeze_end_tick(&self, round_start_tick: u32) -> u32 {
+        round_start_tick + self.seconds_to_ticks(self.freeze_time_secs)
+    }
+
+    /// Seconds of playable round time elapsed since freeze time ended
+    pub fn round_time_elapsed(&self, freeze_end_tick: u32, tick: u32) -> f32 {
+        self.elapsed_seconds(freeze_end_tick, tick)
+    }
+
+    /// Seconds left on the round clock (zero once it runs out)
+    pub fn round_time_remaining(&self, freeze_end_tick: u32, tick: u32) -> f32 {
+        (self.round_time_secs - self.round_time_elapsed(freeze_end_tick, tick)).max(0.0)
+    }
+
+    /// Seconds left until a bomb planted at `plant_tick` detonates
+    pub fn bomb_time_remaining(&self, plant_tick: u32, tick: u32) -> f32 {
+        (self.bomb_timer_secs - self.elapsed_seconds(plant_tick, tick)).max(0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_clock_is_64_tick() {
+        let clock = MatchClock::default();
+
+        assert_eq!(clock.tick_rate, 64.0);
+        assert_eq!(clock.ticks_to_seconds(320), 5.0);
+        assert_eq!(clock.seconds_to_ticks(2.0), 128);
+    }
+
+    #[test]
+    fn test_clock_from_header_and_convars() {
+        let header: HashMap<String, String> = HashMap::from([(
+            TICK_INTERVAL_HEADER_KEY.to_string(),
+            "0.0078125".to_string(),
+        )]);
+        let convars: HashMap<String, String> = HashMap::from([
+            ("mp_roundtime".to_string(), "2".to_string()),
+            ("mp_roundtime_defuse".to_string(), "1.5".to_string()),
+            ("mp_freezetime".to_string(), "10".to_string()),
+            ("mp_c4timer".to_string(), "35".to_string()),
+        ]);
+
+        let clock = MatchClock::from_demo_metadata(Some(&header), &convars);
+
+        assert_eq!(clock.tick_rate, 128.0);
+        assert_eq!(clock.round_time_secs, 90.0);
+        assert_eq!(clock.freeze_time_secs, 10.0);
+        assert_eq!(clock.bomb_timer_secs, 35.0);
+        assert_eq!(clock.ticks_to_seconds(640), 5.0);
+    }
+
+    #[test]
+    fn test_round_and_bomb_timers() {
+        let clock = MatchClock::default();
+        let freeze_end = clock.freeze_end_tick(1000);
+
+        assert_eq!(freeze_end, 1000 + 20 * 64);
+        assert_eq!(
+            clock.round_time_remaining(freeze_end, freeze_end + 15 * 64),
+            100.0
+        );
+        assert_eq!(
+            clock.round_time_remaining(freeze_end, freeze_end + 200 * 64),
+            0.0
+        );
+        assert_eq!(clock.bomb_time_remaining(5000, 5000 + 10 * 64), 30.0);
+        assert!(!MatchClock::default().apply_convar("sv_cheats", "1"));
+    }
+}
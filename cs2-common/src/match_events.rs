@@ -0,0 +1,214 @@
+use serde::{Deserialize, Serialize};
+
+/// A `weapon_fire` event
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ShotEvent {
+    pub tick: u32,
+    pub steamid: u64,
+    pub weapon: String,
+}
+
+/// A `player_death` event
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DeathEvent {
+    pub tick: u32,
+    pub victim: u64,
+    pub attacker: Option<u64>, // None for world damage, suicides and bots
+    pub assister: Option<u64>,
+    pub victim_team: u8,   // 0 when the team prop was not parsed
+    pub attacker_team: u8, // 0 when the team prop was not parsed
+    pub weapon: String,
+    pub headshot: bool,
+    pub flash_assist: bool,
+}
+
+impl DeathEvent {
+    /// Attacker that is neither missing nor the victim
+    pub fn killer(&self) -> Option<u64> {
+        self.attacker.filter(|&a| a != self.victim)
+    }
+}
+
+/// A `player_hurt` event
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HurtEvent {
+    pub tick: u32,
+    pub victim: u64,
+    pub attacker: Option<u64>,
+    pub health_damage: u32,
+    pub armor_damage: u32,
+    pub remaining_health: u32,
+    pub weapon: String,
+}
+
+/// A `player_blind` event
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BlindEvent {
+    pub tick: u32,
+    pub victim: u64,
+    pub attacker: Option<u64>,  // Thrower of the flashbang
+    pub victim_team: u8,        // 0 when the team prop was not parsed
+    pub blind_duration: f32,    // Seconds
+    pub entity_id: Option<i32>, // Flashbang projectile entity
+}
+
+/// A thrown grenade at its last recorded position, which is where it detonated
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GrenadeDetonation {
+    pub tick: u32,
+    pub entity_id: i32,
+    pub thrower: u64,
+    pub grenade_type: String, // Projectile class, e.g. "CFlashbangProjectile"
+    pub pos_x: f32,
+    pub pos_y: f32,
+    pub pos_z: f32,
+}
+
+impl GrenadeDetonation {
+    pub fn is_flashbang(&self) -> bool {
+        self.grenade_type.to_ascii_lowercase().contains("flashbang")
+    }
+}
+
+/// What happened to the bomb in a `bomb_planted`, `bomb_defused` or `bomb_exploded` event
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BombAction {
+    Planted,
+    Defused,
+    Exploded,
+}
+
+/// Bombsite of a plant, taken from the planter's callout
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum BombSite {
+    A,
+    B,
+    #[default]
+    Unknown,
+}
+
+impl BombSite {
+    /// Site from an `m_szLastPlaceName` callout such as `BombsiteA`
+    pub fn from_place(place: &str) -> Self {
+        match place.to_ascii_lowercase().as_str() {
+            "bombsitea" => BombSite::A,
+            "bombsiteb" => BombSite::B,
+            _ => BombSite::Unknown,
+        }
+    }
+}
+
+/// A bomb plant, defuse or detonation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BombEvent {
+    pub tick: u32,
+    pub action: BombAction,
+    pub steamid: Option<u64>, // Planter or defuser, None for detonations
+    pub site: BombSite,
+}
+
+/// A round from `round_start` to `round_end`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RoundInfo {
+    pub number: u32,
+    pub start_tick: u32,
+    pub end_tick: u32,
+    pub winner_team: u8, // TEAM_T, TEAM_CT or 0 when unknown
+    pub reason: String,
+}
+
+impl RoundInfo {
+    pub fn contains(&self, tick: u32) -> bool {
+        tick >= self.start_tick && tick <= self.end_tick
+    }
+}
+
+/// Broad weapon category used to split aim and reaction statistics
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum WeaponClass {
+    Pistol,
+    Smg,
+    Rifle,
+    Sniper,
+    Shotgun,
+    MachineGun,
+    Knife,
+    Utility,
+    #[default]
+    Other,
+}
+
+impl WeaponClass {
+    /// Classify either an item name (`"AK-47"`) or an event weapon (`"weapon_ak47"`, `"ak47"`)
+    pub fn from_weapon_name(name: &str) -> Self {
+        let name = name.to_ascii_lowercase();
+        let name = name.strip_prefix("weapon_").unwrap_or(&name);
+        let key: String = name.chars().filter(|c| c.is_ascii_alphanumeric()).collect();
+
+        match key.as_str() {
+            "glock" | "glock18" | "hkp2000" | "p2000" | "usp" | "usps" | "uspsilencer" | "p250"
+            | "fiveseven" | "tec9" | "cz75a" | "cz75auto" | "deagle" | "deserteagle" | "elite"
+            | "dualberettas" | "revolver" | "r8revolver" => Self::Pistol,
+            "mac10" | "mp9" | "mp7" | "mp5sd" | "ump45" | "p90" | "bizon" | "ppbizon" => Self::Smg,
+            "ak47" | "m4a1" | "m4a4" | "m4a1s" | "m4a1silencer" | "famas" | "galilar" | "aug"
+            | "sg556" | "sg553" => Self::Rifle,
+            "awp" | "ssg08" | "scar20" | "g3sg1" => Self::Sniper,
+            "nova" | "xm1014" | "mag7" | "sawedoff" => Self::Shotgun,
+            "m249" | "negev" => Self::MachineGun,
+            "flashbang"
+            | "inferno"
+            | "hegrenade"
+            | "highexplosivegrenade"
+            | "smokegrenade"
+            | "molotov"
+            | "incgrenade"
+            | "incendiarygrenade"
+            | "decoy"
+            | "decoygrenade"
+            | "c4"
+            | "c4explosive"
+            | "taser"
+            | "zeusx27" => Self::Utility,
+            _ if key.contains("knife") || key.contains("bayonet") || key.contains("karambit") => {
+                Self::Knife
+            }
+            _ => Self::Other,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pistol => "pistol",
+            Self::Smg => "smg",
+            Self::Rifle => "rifle",
+            Self::Sniper => "sniper",
+            Self::Shotgun => "shotgun",
+            Self::MachineGun => "machine_gun",
+            Self::Knife => "knife",
+            Self::Utility => "utility",
+            Self::Other => "other",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_weapon_class_from_item_and_event_names() {
+        assert_eq!(WeaponClass::from_weapon_name("AK-47"), WeaponClass::Rifle);
+        assert_eq!(
+            WeaponClass::from_weapon_name("weapon_ak47"),
+            WeaponClass::Rifle
+        );
+        assert_eq!(WeaponClass::from_weapon_name("USP-S"), WeaponClass::Pistol);
+        assert_eq!(WeaponClass::from_weapon_name("awp"), WeaponClass::Sniper);
+        assert_eq!(
+            WeaponClass::from_weapon_name("M9 Bayonet"),
+            WeaponClass::Knife
+        );
+        assert_eq!(WeaponClass::from_weapon_name("knife_t"), WeaponClass::Knife);
+        assert_eq!(WeaponClass::from_weapon_name(""), WeaponClass::Other);
+    }
+}
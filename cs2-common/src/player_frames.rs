@@ -0,0 +1,274 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Eye height above the origin of a standing player (CS2 units)
+pub const STANDING_EYE_HEIGHT: f32 = 64.0;
+/// Eye height above the origin of a crouching player (CS2 units)
+pub const CROUCHING_EYE_HEIGHT: f32 = 46.0;
+
+/// `m_iTeamNum` of the terrorist side
+pub const TEAM_T: u8 = 2;
+/// `m_iTeamNum` of the counter-terrorist side
+pub const TEAM_CT: u8 = 3;
+
+/// Per-tick state of one player as decoded from the demo
+///
+/// Unlike [`crate::BehavioralVector`] this keeps team, visibility and place
+/// information, which the event-driven extractors need to relate players to
+/// each other.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PlayerFrame {
+    pub tick: u32,
+    pub steamid: u64,
+    pub team_num: u8,
+    pub is_alive: bool,
+    pub health: u32,
+    pub armor: u32,
+    pub equipment_value: u32, // m_unCurrentEquipmentValue
+    pub has_defuser: bool,
+    pub pos_x: f32,
+    pub pos_y: f32,
+    pub pos_z: f32,
+    pub pitch: f32,
+    pub yaw: f32,
+    pub is_ducking: bool,
+    pub flash_duration: f32, // m_flFlashDuration, seconds of the last flash
+    pub weapon: String,
+    pub place: String,        // m_szLastPlaceName callout
+    pub spotted_by: Vec<u64>, // Steam IDs from m_bSpottedByMask
+}
+
+impl PlayerFrame {
+    /// World position of the eyes, which is also where enemies aim for the head
+    pub fn eye_position(&self) -> [f32; 3] {
+        let height = if self.is_ducking {
+            CROUCHING_EYE_HEIGHT
+        } else {
+            STANDING_EYE_HEIGHT
+        };
+        [self.pos_x, self.pos_y, self.pos_z + height]
+    }
+
+    pub fn is_enemy_of(&self, other: &PlayerFrame) -> bool {
+        is_playing_team(self.team_num)
+            && is_playing_team(other.team_num)
+            && self.team_num != other.team_num
+    }
+
+    /// Crosshair error from this player's view to `target`'s head
+    pub fn aim_error_to(&self, target: &PlayerFrame) -> AimError {
+        let (pitch, yaw) = angles_between(self.eye_position(), target.eye_position());
+        AimError::between((self.pitch, self.yaw), (pitch, yaw))
+    }
+
+    pub fn distance_to(&self, other: &PlayerFrame) -> f32 {
+        ((self.pos_x - other.pos_x).powi(2)
+            + (self.pos_y - other.pos_y).powi(2)
+            + (self.pos_z - other.pos_z).powi(2))
+        .sqrt()
+    }
+}
+
+/// Whether `team_num` is the T or CT side rather than unassigned or spectating
+pub fn is_playing_team(team_num: u8) -> bool {
+    team_num == TEAM_T || team_num == TEAM_CT
+}
+
+/// Angular difference between a view direction and a target direction
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AimError {
+    pub pitch_deg: f32, // Positive when the target is below the crosshair
+    pub yaw_deg: f32,   // Positive when the target is to the left of the crosshair
+    pub total_deg: f32, // Great-circle angle between the two directions
+}
+
+impl AimError {
+    /// Error from `view` to `target`, both given as (pitch, yaw) in degrees
+    pub fn between(view: (f32, f32), target: (f32, f32)) -> Self {
+        let a = direction(view.0, view.1);
+        let b = direction(target.0, target.1);
+        let dot = (a[0] * b[0] + a[1] * b[1] + a[2] * b[2]).clamp(-1.0, 1.0);
+
+        Self {
+            pitch_deg: target.0 - view.0,
+            yaw_deg: wrap_degrees(target.1 - view.1),
+            total_deg: dot.acos().to_degrees(),
+        }
+    }
+}
+
+/// Wrap an angle difference into [-180, 180) degrees
+pub fn wrap_degrees(angle: f32) -> f32 {
+    (angle + 180.0).rem_euclid(360.0) - 180.0
+}
+
+/// Unit view vector for Source engine angles (pitch positive looks down)
+pub fn direction(pitch_deg: f32, yaw_deg: f32) -> [f32; 3] {
+    let (pitch, yaw) = (pitch_deg.to_radians(), yaw_deg.to_radians());
+    [
+        pitch.cos() * yaw.cos(),
+        pitch.cos() * yaw.sin(),
+        -pitch.sin(),
+    ]
+}
+
+/// (pitch, yaw) in degrees of the direction from `from` to `to`
+pub fn angles_between(from: [f32; 3], to: [f32; 3]) -> (f32, f32) {
+    let (dx, dy, dz) = (to[0] - from[0], to[1] - from[1], to[2] - from[2]);
+    let yaw = dy.atan2(dx).to_degrees();
+    let pitch = -dz.atan2((dx * dx + dy * dy).sqrt()).to_degrees();
+    (pitch, yaw)
+}
+
+/// Player frames grouped into per-player timelines sorted by tick
+#[derive(Debug, Clone, Default)]
+pub struct FrameIndex {
+    timelines: HashMap<u64, Vec<PlayerFrame>>,
+}
+
+impl FrameIndex {
+    pub fn new(frames: impl IntoIterator<Item = PlayerFrame>) -> Self {
+        let mut timelines: HashMap<u64, Vec<PlayerFrame>> = HashMap::new();
+        for frame in frames {
+            timelines.entry(frame.steamid).or_default().push(frame);
+        }
+        for timeline in timelines.values_mut() {
+            timeline.sort_by_key(|f| f.tick);
+        }
+        Self { timelines }
+    }
+
+    pub fn players(&self) -> impl Iterator<Item = u64> + '_ {
+        self.timelines.keys().copied()
+    }
+
+    pub fn timeline(&self, steamid: u64) -> &[PlayerFrame] {
+        self.timelines
+            .get(&steamid)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Latest frame of a player at or before `tick`
+    pub fn frame_at(&self, steamid: u64, tick: u32) -> Option<&PlayerFrame> {
+        let timeline = self.timeline(steamid);
+        let idx = timeline.partition_point(|f| f.tick <= tick);
+        idx.checked_sub(1).map(|i| &timeline[i])
+    }
+
+    /// Frames of a player with `from <= tick <= to`
+    pub fn frames_between(&self, steamid: u64, from: u32, to: u32) -> &[PlayerFrame] {
+        let timeline = self.timeline(steamid);
+        let start = timeline.partition_point(|f| f.tick < from);
+        let end = timeline.partition_point(|f| f.tick <= to);
+        &timeline[start..end.max(start)]
+    }
+
+    /// Latest frame at or before `tick` of every player
+    pub fn frames_at(&self, tick: u32) -> Vec<&PlayerFrame> {
+        self.players()
+            .filter_map(|steamid| self.frame_at(steamid, tick))
+            .collect()
+    }
+}
+
+/// The first tick an opponent shows up in a player's spotted mask
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpottedEvent {
+    pub tick: u32,
+    pub observer: u64,
+    pub target: u64,
+}
+
+/// Find every tick where an alive enemy starts spotting an alive player
+///
+/// An event fires when the observer appears in the target's spotted mask
+/// after not being in it on the previous frame.
+pub fn first_spotted_events(frames: &FrameIndex) -> Vec<SpottedEvent> {
+    let mut events = Vec::new();
+
+    for target in frames.players() {
+        let mut previous: HashSet<u64> = HashSet::new();
+        for frame in frames.timeline(target) {
+            let current: HashSet<u64> = if frame.is_alive {
+                frame.spotted_by.iter().copied().collect()
+            } else {
+                HashSet::new()
+            };
+            for &observer in current.difference(&previous) {
+                let is_live_enemy = frames
+                    .frame_at(observer, frame.tick)
+                    .is_some_and(|o| o.is_alive && o.is_enemy_of(frame));
+                if is_live_enemy {
+                    events.push(SpottedEvent {
+                        tick: frame.tick,
+                        observer,
+                        target,
+                    });
+                }
+            }
+            previous = current;
+        }
+    }
+
+    events.sort_by_key(|e| (e.tick, e.observer, e.target));
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::frame;
+
+    #[test]
+    fn test_aim_error_wraps_yaw() {
+        let error = AimError::between((0.0, 179.0), (0.0, -179.0));
+
+        assert!((error.yaw_deg - 2.0).abs() < 1e-4);
+        assert!((error.total_deg - 2.0).abs() < 1e-2);
+        assert_eq!(wrap_degrees(-190.0), 170.0);
+    }
+
+    #[test]
+    fn test_angles_between_follow_source_conventions() {
+        // Target straight along +Y and slightly below the eyes
+        let (pitch, yaw) = angles_between([0.0, 0.0, 64.0], [0.0, 100.0, 0.0]);
+
+        assert!((yaw - 90.0).abs() < 1e-4);
+        assert!(pitch > 0.0);
+    }
+
+    #[test]
+    fn test_first_spotted_events() {
+        let frames = FrameIndex::new(vec![
+            frame(1, 10, TEAM_T, vec![]),
+            frame(2, 10, TEAM_T, vec![20, 30]),
+            frame(3, 10, TEAM_T, vec![20, 30]),
+            frame(4, 10, TEAM_T, vec![]),
+            frame(5, 10, TEAM_T, vec![20]),
+            frame(1, 20, TEAM_CT, vec![]),
+            frame(1, 30, TEAM_T, vec![]),
+        ]);
+
+        let events = first_spotted_events(&frames);
+
+        // Teammate 30 is ignored and re-spotting after losing sight counts again
+        assert_eq!(
+            events,
+            vec![
+                SpottedEvent {
+                    tick: 2,
+                    observer: 20,
+                    target: 10
+                },
+                SpottedEvent {
+                    tick: 5,
+                    observer: 20,
+                    target: 10
+                },
+            ]
+        );
+        assert_eq!(frames.frame_at(10, 100).map(|f| f.tick), Some(5));
+        assert_eq!(frames.frames_between(10, 2, 4).len(), 3);
+    }
+}
@@ -0,0 +1,326 @@
+use crate::{CS2Error, InputVector, MovementVector, OutputVector};
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+
+/// Bytes a framed client sends before its first frame
+///
+/// Read as the leading `health` float of a legacy raw `InputVector` this is
+/// about 1.2e10, so the server can tell both modes apart from the first four
+/// bytes of a connection.
+pub const PROTOCOL_MAGIC: [u8; 4] = *b"CS2P";
+/// Version written into every frame header
+pub const PROTOCOL_VERSION: u8 = 1;
+/// Version, type, flags, model ID and request ID following the length prefix
+pub const HEADER_LEN: usize = 12;
+/// Largest accepted frame, header included
+pub const MAX_FRAME_LEN: usize = 1 << 20;
+
+/// Names of the `InputVector` fields in wire order
+pub const INPUT_VECTOR_FIELDS: [&str; 14] = [
+    "health",
+    "armor",
+    "pos_x",
+    "pos_y",
+    "pos_z",
+    "vel_x",
+    "vel_y",
+    "vel_z",
+    "yaw",
+    "pitch",
+    "weapon_id_f32",
+    "ammo",
+    "is_airborne",
+    "padding",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MessageType {
+    Hello,           // 1: client opens the session
+    Handshake,       // 2: server describes the model, payload is a JSON `Handshake`
+    Predict,         // 3: one or more `InputVector`s
+    Prediction,      // 4: one `OutputVector` per input of the request
+    Error,           // 5: `ErrorReply` for the request with the same ID
+    Heartbeat,       // 6: echoed back unchanged
+    PredictMovement, // 7: a player's recent `InputVector`s, oldest first
+    Movement,        // 8: one `MovementVector` for the latest input
+    Unknown(u8),     // A type from a newer peer, answered with an error
+}
+
+impl MessageType {
+    /// Type byte in the frame header
+    pub fn code(self) -> u8 {
+        match self {
+            Self::Hello => 1,
+            Self::Handshake => 2,
+            Self::Predict => 3,
+            Self::Prediction => 4,
+            Self::Error => 5,
+            Self::Heartbeat => 6,
+            Self::PredictMovement => 7,
+            Self::Movement => 8,
+            Self::Unknown(code) => code,
+        }
+    }
+}
+
+impl From<u8> for MessageType {
+    fn from(code: u8) -> Self {
+        match code {
+            1 => Self::Hello,
+            2 => Self::Handshake,
+            3 => Self::Predict,
+            4 => Self::Prediction,
+            5 => Self::Error,
+            6 => Self::Heartbeat,
+            7 => Self::PredictMovement,
+            8 => Self::Movement,
+            other => Self::Unknown(other),
+        }
+    }
+}
+
+/// One length-prefixed message
+///
+/// Layout, little-endian: `u32` length of everything after it, `u8` version,
+/// `u8` message type, `u16` flags (zero), `u32` model ID, `u32` request ID,
+/// then the payload.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub version: u8,
+    pub kind: MessageType,
+    pub model_id: u32,   // Model that produced a reply, zero in requests
+    pub request_id: u32, // Chosen by the client, echoed in the reply
+    pub payload: Vec<u8>,
+}
+
+impl Frame {
+    pub fn new(kind: MessageType, model_id: u32, request_id: u32, payload: Vec<u8>) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            kind,
+            model_id,
+            request_id,
+            payload,
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(4 + HEADER_LEN + self.payload.len());
+        bytes.extend_from_slice(&((HEADER_LEN + self.payload.len()) as u32).to_le_bytes());
+        bytes.push(self.version);
+        bytes.push(self.kind.code());
+        bytes.extend_from_slice(&0u16.to_le_bytes());
+        bytes.extend_from_slice(&self.model_id.to_le_bytes());
+        bytes.extend_from_slice(&self.request_id.to_le_bytes());
+        bytes.extend_from_slice(&self.payload);
+        bytes
+    }
+
+    pub fn write_to(&self, writer: &mut impl Write) -> Result<(), CS2Error> {
+        writer.write_all(&self.encode())?;
+        Ok(())
+    }
+
+    /// Read the next frame, failing on oversized or truncated frames
+    ///
+    /// Frames of another protocol version or of an unknown type are returned
+    /// as read, so the caller can answer with an error and keep the session.
+    pub fn read_from(reader: &mut impl Read) -> Result<Self, CS2Error> {
+        let mut len = [0u8; 4];
+        reader.read_exact(&mut len)?;
+        let len = u32::from_le_bytes(len) as usize;
+        if !(HEADER_LEN..=MAX_FRAME_LEN).contains(&len) {
+            return Err(CS2Error::ProtocolError(format!(
+                "frame length {len} outside {HEADER_LEN}..={MAX_FRAME_LEN}"
+            )));
+        }
+        let mut body = vec![0u8; len];
+        reader.read_exact(&mut body)?;
+        let word = |at: usize| u32::from_le_bytes(body[at..at + 4].try_into().unwrap());
+        Ok(Self {
+            version: body[0],
+            kind: MessageType::from(body[1]),
+            model_id: word(4),
+            request_id: word(8),
+            payload: body[HEADER_LEN..].to_vec(),
+        })
+    }
+}
+
+/// What the server reports about its model when a session opens
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Handshake {
+    pub model_id: u32,
+    pub model_name: String,
+    pub input_schema: Vec<String>, // Fields of each `InputVector`, in wire order
+    pub input_dim: usize,          // Features the model computes from them
+    pub output_dim: usize,
+    pub max_batch: usize, // Most inputs accepted in one `Predict`
+    #[serde(default)]
+    pub sequence_length: Option<usize>, // History used by the movement model, if the server has one
+}
+
+impl Handshake {
+    pub fn encode(&self) -> Vec<u8> {
+        serde_json::to_vec(self).expect("handshake serializes")
+    }
+
+    pub fn decode(payload: &[u8]) -> Result<Self, CS2Error> {
+        serde_json::from_slice(payload)
+            .map_err(|e| CS2Error::ProtocolError(format!("bad handshake: {e}")))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[repr(u16)]
+pub enum ErrorCode {
+    UnsupportedVersion = 1,
+    MalformedRequest = 2,
+    UnexpectedMessage = 3,
+    BatchTooLarge = 4,
+    ModelError = 5,
+    Unsupported = 6,
+}
+
+/// Error payload: `u16` code followed by a UTF-8 message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorReply {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl ErrorReply {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = (self.code as u16).to_le_bytes().to_vec();
+        bytes.extend_from_slice(self.message.as_bytes());
+        bytes
+    }
+
+    pub fn decode(payload: &[u8]) -> Result<Self, CS2Error> {
+        if payload.len() < 2 {
+            return Err(CS2Error::ProtocolError("truncated error reply".into()));
+        }
+        let code = match u16::from_le_bytes([payload[0], payload[1]]) {
+            1 => ErrorCode::UnsupportedVersion,
+            2 => ErrorCode::MalformedRequest,
+            3 => ErrorCode::UnexpectedMessage,
+            4 => ErrorCode::BatchTooLarge,
+            5 => ErrorCode::ModelError,
+            6 => ErrorCode::Unsupported,
+            other => {
+                return Err(CS2Error::ProtocolError(format!(
+                    "unknown error code {other}"
+                )))
+            }
+        };
+        Ok(Self {
+            code,
+            message: String::from_utf8_lossy(&payload[2..]).into_owned(),
+        })
+    }
+}
+
+impl std::fmt::Display for ErrorReply {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}: {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for ErrorReply {}
+
+pub fn encode_inputs(inputs: &[InputVector]) -> Vec<u8> {
+    bytemuck::cast_slice(inputs).to_vec()
+}
+
+pub fn decode_inputs(payload: &[u8]) -> Result<Vec<InputVector>, CS2Error> {
+    decode_pods(payload, "input")
+}
+
+pub fn encode_outputs(outputs: &[OutputVector]) -> Vec<u8> {
+    bytemuck::cast_slice(outputs).to_vec()
+}
+
+pub fn decode_outputs(payload: &[u8]) -> Result<Vec<OutputVector>, CS2Error> {
+    decode_pods(payload, "output")
+}
+
+pub fn encode_movement(movement: &MovementVector) -> Vec<u8> {
+    bytemuck::bytes_of(movement).to_vec()
+}
+
+pub fn decode_movement(payload: &[u8]) -> Result<MovementVector, CS2Error> {
+    match decode_pods::<MovementVector>(payload, "movement")?.as_slice() {
+        [movement] => Ok(*movement),
+        many => Err(CS2Error::ProtocolError(format!(
+            "{} movement vectors in one reply",
+            many.len()
+        ))),
+    }
+}
+
+fn decode_pods<T: bytemuck::Pod>(payload: &[u8], what: &str) -> Result<Vec<T>, CS2Error> {
+    let size = std::mem::size_of::<T>();
+    if payload.is_empty() || !payload.len().is_multiple_of(size) {
+        return Err(CS2Error::ProtocolError(format!(
+            "{} bytes is not a whole number of {size}-byte {what} vectors",
+            payload.len()
+        )));
+    }
+    Ok(payload
+        .chunks_exact(size)
+        .map(bytemuck::pod_read_unaligned)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytemuck::Zeroable;
+
+    #[test]
+    fn test_frame_roundtrip() {
+        let inputs = vec![InputVector::zeroed(), InputVector::zeroed()];
+        let frame = Frame::new(MessageType::Predict, 0, 7, encode_inputs(&inputs));
+        let bytes = frame.encode();
+        assert_eq!(bytes.len(), 4 + HEADER_LEN + 2 * 56);
+
+        let read = Frame::read_from(&mut bytes.as_slice()).unwrap();
+        assert_eq!(read, frame);
+        assert_eq!(decode_inputs(&read.payload).unwrap().len(), 2);
+        assert!(decode_inputs(&read.payload[1..]).is_err());
+    }
+
+    #[test]
+    fn test_rejects_bad_frames() {
+        let mut oversized = ((MAX_FRAME_LEN + 1) as u32).to_le_bytes().to_vec();
+        oversized.extend_from_slice(&[0; HEADER_LEN]);
+        assert!(Frame::read_from(&mut oversized.as_slice()).is_err());
+
+        // Unknown types are read whole, leaving the stream on a frame boundary
+        let mut unknown = Frame::new(MessageType::Heartbeat, 0, 3, b"new".to_vec()).encode();
+        unknown[5] = 99;
+        unknown.extend(Frame::new(MessageType::Heartbeat, 0, 4, vec![]).encode());
+        let mut stream = unknown.as_slice();
+        let read = Frame::read_from(&mut stream).unwrap();
+        assert_eq!((read.kind, read.request_id), (MessageType::Unknown(99), 3));
+        assert_eq!(read.encode()[5], 99);
+        let next = Frame::read_from(&mut stream).unwrap();
+        assert_eq!((next.kind, next.request_id), (MessageType::Heartbeat, 4));
+
+        let reply = ErrorReply::new(ErrorCode::BatchTooLarge, "too many");
+        assert_eq!(ErrorReply::decode(&reply.encode()).unwrap(), reply);
+    }
+
+    #[test]
+    fn test_magic_is_not_a_plausible_health() {
+        let health = f32::from_le_bytes(PROTOCOL_MAGIC);
+        assert!(health > 1e9);
+    }
+}
@@ -0,0 +1,276 @@
+use crate::feature_extraction::DecisionMetricsFeatures;
+use crate::feature_schema::FeatureStats;
+use crate::match_clock::MatchClock;
+use crate::match_events::ShotEvent;
+use crate::player_frames::{first_spotted_events, FrameIndex, SpottedEvent};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Visual Reaction Extractor - Measures how fast players respond to enemies becoming visible
+pub struct VisualReactionExtractor {
+    pub clock: MatchClock,
+    pub on_target_threshold_deg: f32, // Crosshair counts as on target below this error
+    pub pre_aim_threshold_deg: f32,   // Observer counts as pre-aimed below this error
+    pub max_reaction_secs: f32,       // Give up on a reaction after this long
+}
+
+impl Default for VisualReactionExtractor {
+    fn default() -> Self {
+        Self {
+            clock: MatchClock::default(),
+            on_target_threshold_deg: 2.5,
+            pre_aim_threshold_deg: 5.0,
+            max_reaction_secs: 1.5,
+        }
+    }
+}
+
+/// Reaction of one observer to one first-spotted event
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReactionSample {
+    pub tick: u32,
+    pub observer: u64,
+    pub target: u64,
+    pub weapon: String,
+    pub pre_aimed: bool,
+    pub initial_error_deg: f32,
+    pub time_to_crosshair: Option<f32>, // Seconds until the crosshair reached the head
+    pub time_to_first_shot: Option<f32>, // Seconds until the observer fired
+}
+
+/// Reaction time distribution over a set of encounters
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReactionDistribution {
+    pub encounters: usize,
+    pub time_to_crosshair: Option<FeatureStats>,
+    pub time_to_first_shot: Option<FeatureStats>,
+}
+
+impl ReactionDistribution {
+    pub fn from_samples<'a>(samples: impl IntoIterator<Item = &'a ReactionSample>) -> Self {
+        let samples: Vec<&ReactionSample> = samples.into_iter().collect();
+        let crosshair: Vec<f32> = samples.iter().filter_map(|s| s.time_to_crosshair).collect();
+        let shots: Vec<f32> = samples
+            .iter()
+            .filter_map(|s| s.time_to_first_shot)
+            .collect();
+
+        Self {
+            encounters: samples.len(),
+            time_to_crosshair: FeatureStats::from_values(&crosshair),
+            time_to_first_shot: FeatureStats::from_values(&shots),
+        }
+    }
+}
+
+/// Per-player reaction distributions split by weapon and pre-aim
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReactionProfile {
+    pub steamid: u64,
+    pub overall: ReactionDistribution,
+    pub by_weapon: HashMap<String, ReactionDistribution>,
+    pub pre_aimed: ReactionDistribution,
+    pub not_pre_aimed: ReactionDistribution,
+}
+
+impl ReactionProfile {
+    /// Replace the yaw-change estimate with the measured median reaction time
+    pub fn apply_to(&self, features: &mut DecisionMetricsFeatures) {
+        if let Some(stats) = &self.overall.time_to_crosshair {
+            features.reaction_time_visual_stimuli = stats.p50;
+        }
+    }
+}
+
+impl VisualReactionExtractor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an extractor that converts ticks with the demo's clock
+    pub fn with_clock(clock: &MatchClock) -> Self {
+        Self {
+            clock: *clock,
+            ..Self::default()
+        }
+    }
+
+    /// Measure the reaction to every first-spotted event in the demo
+    pub fn extract_samples(&self, frames: &FrameIndex, shots: &[ShotEvent]) -> Vec<ReactionSample> {
+        let mut shots_by_player: HashMap<u64, Vec<u32>> = HashMap::new();
+        for shot in shots {
+            shots_by_player
+                .entry(shot.steamid)
+                .or_default()
+                .push(shot.tick);
+        }
+        for ticks in shots_by_player.values_mut() {
+            ticks.sort_unstable();
+        }
+
+        first_spotted_events(frames)
+            .into_iter()
+            .filter_map(|event| {
+                let shot_ticks = shots_by_player
+                    .get(&event.observer)
+                    .map(Vec::as_slice)
+                    .unwrap_or_default();
+                self.measure_reaction(frames, shot_ticks, &event)
+            })
+            .collect()
+    }
+
+    /// Aggregate reaction samples into per-player profiles
+    pub fn extract_profiles(
+        &self,
+        frames: &FrameIndex,
+        shots: &[ShotEvent],
+    ) -> HashMap<u64, ReactionProfile> {
+        let mut by_player: HashMap<u64, Vec<ReactionSample>> = HashMap::new();
+        for sample in self.extract_samples(frames, shots) {
+            by_player.entry(sample.observer).or_default().push(sample);
+        }
+
+        by_player
+            .into_iter()
+            .map(|(steamid, samples)| {
+                let mut by_weapon: HashMap<String, Vec<&ReactionSample>> = HashMap::new();
+                for sample in &samples {
+                    by_weapon
+                        .entry(sample.weapon.clone())
+                        .or_default()
+                        .push(sample);
+                }
+
+                let profile = ReactionProfile {
+                    steamid,
+                    overall: ReactionDistribution::from_samples(&samples),
+                    by_weapon: by_weapon
+                        .into_iter()
+                        .map(|(weapon, s)| (weapon, ReactionDistribution::from_samples(s)))
+                        .collect(),
+                    pre_aimed: ReactionDistribution::from_samples(
+                        samples.iter().filter(|s| s.pre_aimed),
+                    ),
+                    not_pre_aimed: ReactionDistribution::from_samples(
+                        samples.iter().filter(|s| !s.pre_aimed),
+                    ),
+                };
+                (steamid, profile)
+            })
+            .collect()
+    }
+
+    fn measure_reaction(
+        &self,
+        frames: &FrameIndex,
+        shot_ticks: &[u32],
+        event: &SpottedEvent,
+    ) -> Option<ReactionSample> {
+        let observer = frames.frame_at(event.observer, event.tick)?;
+        let target = frames.frame_at(event.target, event.tick)?;
+        if !observer.is_alive || !target.is_alive {
+            return None;
+        }
+
+        let initial_error_deg = observer.aim_error_to(target).total_deg;
+        let deadline = event.tick + self.clock.seconds_to_ticks(self.max_reaction_secs);
+
+        // Follow the observer's crosshair until it reaches the target's head
+        let mut time_to_crosshair = None;
+        for frame in frames.frames_between(event.observer, event.tick, deadline) {
+            let Some(target) = frames.frame_at(event.target, frame.tick) else {
+                break;
+            };
+            if !frame.is_alive || !target.is_alive {
+                break;
+            }
+            if frame.aim_error_to(target).total_deg <= self.on_target_threshold_deg {
+                time_to_crosshair = Some(self.clock.elapsed_seconds(event.tick, frame.tick));
+                break;
+            }
+        }
+
+        let first_shot = shot_ticks.partition_point(|&t| t < event.tick);
+        let time_to_first_shot = shot_ticks
+            .get(first_shot)
+            .filter(|&&t| t <= deadline)
+            .map(|&t| self.clock.elapsed_seconds(event.tick, t));
+
+        Some(ReactionSample {
+            tick: event.tick,
+            observer: event.observer,
+            target: event.target,
+            weapon: observer.weapon.clone(),
+            pre_aimed: initial_error_deg <= self.pre_aim_threshold_deg,
+            initial_error_deg,
+            time_to_crosshair,
+            time_to_first_shot,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player_frames::{PlayerFrame, TEAM_CT, TEAM_T};
+
+    fn player(tick: u32, steamid: u64, team_num: u8, pos_x: f32, yaw: f32) -> PlayerFrame {
+        PlayerFrame {
+            tick,
+            steamid,
+            team_num,
+            is_alive: true,
+            pos_x,
+            yaw,
+            weapon: "ak47".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_reaction_to_spotted_enemy() {
+        // Observer at the origin turns from 40 degrees off to facing the enemy on +X
+        let mut frames = Vec::new();
+        for (tick, yaw) in [
+            (100, 40.0),
+            (101, 40.0),
+            (104, 20.0),
+            (110, 1.0),
+            (120, 0.0),
+        ] {
+            frames.push(player(tick, 1, TEAM_CT, 0.0, yaw));
+            let spotted_by = if tick >= 101 { vec![1] } else { vec![] };
+            frames.push(PlayerFrame {
+                spotted_by,
+                ..player(tick, 2, TEAM_T, 1000.0, 180.0)
+            });
+        }
+        let shots = vec![ShotEvent {
+            tick: 117,
+            steamid: 1,
+            weapon: "ak47".to_string(),
+        }];
+
+        let extractor = VisualReactionExtractor::new();
+        let samples = extractor.extract_samples(&FrameIndex::new(frames.clone()), &shots);
+
+        assert_eq!(samples.len(), 1);
+        let sample = &samples[0];
+        assert_eq!((sample.observer, sample.target, sample.tick), (1, 2, 101));
+        assert!(!sample.pre_aimed);
+        assert_eq!(sample.time_to_crosshair, Some(9.0 / 64.0));
+        assert_eq!(sample.time_to_first_shot, Some(16.0 / 64.0));
+
+        let profiles = extractor.extract_profiles(&FrameIndex::new(frames), &shots);
+        let profile = &profiles[&1];
+        assert_eq!(profile.overall.encounters, 1);
+        assert_eq!(profile.not_pre_aimed.encounters, 1);
+        assert_eq!(profile.pre_aimed.encounters, 0);
+        assert!(profile.by_weapon.contains_key("ak47"));
+
+        let mut features = DecisionMetricsFeatures::default();
+        profile.apply_to(&mut features);
+        assert_eq!(features.reaction_time_visual_stimuli, 9.0 / 64.0);
+    }
+}
@@ -0,0 +1,482 @@
+use crate::clutch_analysis::clutch_situations;
+use crate::match_clock::MatchClock;
+use crate::match_events::{DeathEvent, HurtEvent, RoundInfo};
+use crate::player_frames::{FrameIndex, TEAM_CT, TEAM_T};
+use crate::trade_analysis::TradeAnalyzer;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Starting health used to cap damage, so overkill does not inflate ADR
+const MAX_HEALTH: u32 = 100;
+
+/// Scoreboard Builder - Computes per-round and per-match player stats from typed events
+#[derive(Default)]
+pub struct ScoreboardBuilder {
+    pub clock: MatchClock,
+}
+
+/// One player's contribution to a single round
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RoundPlayerStats {
+    pub round: u32,
+    pub steamid: u64,
+    pub team_num: u8,
+    pub kills: u32,
+    pub died: bool,
+    pub assists: u32,
+    pub flash_assists: u32,
+    pub damage: u32, // Health damage to enemies, capped at their remaining health
+    pub utility_damage: u32,
+    pub survived: bool,
+    pub traded: bool,
+    pub opening_kill: bool,
+    pub opening_death: bool,
+    pub clutch_opponents: Option<u32>, // Set when the player was left alone against N enemies
+    pub clutch_won: bool,
+    pub round_won: bool,
+}
+
+impl RoundPlayerStats {
+    /// Kill, assist, survived or traded
+    pub fn kast(&self) -> bool {
+        self.kills > 0 || self.assists > 0 || self.survived || self.traded
+    }
+}
+
+/// Match totals for one player
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PlayerMatchStats {
+    pub steamid: u64,
+    pub starting_team: u8,
+    pub rounds_played: u32,
+    pub rounds_won: u32,
+    pub kills: u32,
+    pub deaths: u32,
+    pub assists: u32,
+    pub flash_assists: u32,
+    pub damage: u32,
+    pub utility_damage: u32,
+    pub kast_rounds: u32,
+    pub multi_kills: [u32; 6], // Rounds by kill count, index 0 unused
+    pub opening_kills: u32,
+    pub opening_deaths: u32,
+    pub clutches_attempted: u32,
+    pub clutches_won: u32,
+}
+
+impl PlayerMatchStats {
+    fn per_round(&self, value: u32) -> f32 {
+        if self.rounds_played > 0 {
+            value as f32 / self.rounds_played as f32
+        } else {
+            0.0
+        }
+    }
+
+    pub fn kpr(&self) -> f32 {
+        self.per_round(self.kills)
+    }
+
+    pub fn dpr(&self) -> f32 {
+        self.per_round(self.deaths)
+    }
+
+    pub fn apr(&self) -> f32 {
+        self.per_round(self.assists)
+    }
+
+    /// Average damage per round
+    pub fn adr(&self) -> f32 {
+        self.per_round(self.damage)
+    }
+
+    /// Share of rounds with a kill, assist, survival or trade, as a percentage
+    pub fn kast(&self) -> f32 {
+        self.per_round(self.kast_rounds) * 100.0
+    }
+
+    /// Rounds with two or more kills
+    pub fn multi_kill_rounds(&self) -> u32 {
+        self.multi_kills[2..].iter().sum()
+    }
+
+    /// Impact rating: `2.13 * KPR + 0.42 * APR - 0.41`
+    ///
+    /// The community approximation of HLTV's impact, which rewards kills
+    /// more than assists and centres an average player around 1.0.
+    pub fn impact(&self) -> f32 {
+        2.13 * self.kpr() + 0.42 * self.apr() - 0.41
+    }
+
+    /// HLTV 2.0 style rating
+    ///
+    /// `0.0073 * KAST + 0.3591 * KPR - 0.5329 * DPR + 0.2372 * Impact + 0.0032 * ADR + 0.1587`
+    ///
+    /// HLTV does not publish its exact formula; these are the widely used
+    /// regression coefficients. KAST is a percentage and an average player
+    /// lands around 1.0.
+    pub fn rating(&self) -> f32 {
+        if self.rounds_played == 0 {
+            return 0.0;
+        }
+        0.0073 * self.kast() + 0.3591 * self.kpr() - 0.5329 * self.dpr()
+            + 0.2372 * self.impact()
+            + 0.0032 * self.adr()
+            + 0.1587
+    }
+
+    fn add_round(&mut self, round: &RoundPlayerStats) {
+        self.rounds_played += 1;
+        self.rounds_won += round.round_won as u32;
+        self.kills += round.kills;
+        self.deaths += round.died as u32;
+        self.assists += round.assists;
+        self.flash_assists += round.flash_assists;
+        self.damage += round.damage;
+        self.utility_damage += round.utility_damage;
+        self.kast_rounds += round.kast() as u32;
+        self.multi_kills[round.kills.min(5) as usize] += 1;
+        self.opening_kills += round.opening_kill as u32;
+        self.opening_deaths += round.opening_death as u32;
+        self.clutches_attempted += round.clutch_opponents.is_some() as u32;
+        self.clutches_won += round.clutch_won as u32;
+    }
+}
+
+/// Per-round and per-match stats of every player
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Scoreboard {
+    pub rounds: Vec<RoundPlayerStats>,
+    pub players: HashMap<u64, PlayerMatchStats>,
+}
+
+impl ScoreboardBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a builder that converts ticks with the demo's clock
+    pub fn with_clock(clock: &MatchClock) -> Self {
+        Self { clock: *clock }
+    }
+
+    /// Build the scoreboard
+    ///
+    /// Round rosters and missing event teams come from `frames`; players
+    /// without any known team are left out.
+    pub fn build(
+        &self,
+        rounds: &[RoundInfo],
+        deaths: &[DeathEvent],
+        hurts: &[HurtEvent],
+        frames: &FrameIndex,
+    ) -> Scoreboard {
+        let trades = TradeAnalyzer::with_clock(&self.clock).analyze(deaths, hurts, frames);
+        let traded: HashSet<(u32, u64)> = trades
+            .deaths
+            .iter()
+            .filter(|d| d.is_traded())
+            .map(|d| (d.tick, d.victim))
+            .collect();
+
+        let mut scoreboard = Scoreboard::default();
+        for round in rounds {
+            let round_deaths: Vec<&DeathEvent> =
+                deaths.iter().filter(|d| round.contains(d.tick)).collect();
+            let round_hurts: Vec<&HurtEvent> =
+                hurts.iter().filter(|h| round.contains(h.tick)).collect();
+            let teams = round_teams(round, &round_deaths, frames);
+
+            let mut stats: HashMap<u64, RoundPlayerStats> = teams
+                .iter()
+                .map(|(&steamid, &team_num)| {
+                    let player = RoundPlayerStats {
+                        round: round.number,
+                        steamid,
+                        team_num,
+                        survived: true,
+                        round_won: round.winner_team != 0 && round.winner_team == team_num,
+                        ..Default::default()
+                    };
+                    (steamid, player)
+                })
+                .collect();
+            let is_enemy = |a: u64, b: u64| match (teams.get(&a), teams.get(&b)) {
+                (Some(ta), Some(tb)) => ta != tb,
+                _ => false,
+            };
+
+            // Damage, capped by each victim's remaining health
+            let mut health: HashMap<u64, u32> = HashMap::new();
+            for hurt in &round_hurts {
+                let remaining = health.entry(hurt.victim).or_insert(MAX_HEALTH);
+                let dealt = hurt.health_damage.min(*remaining);
+                *remaining -= dealt;
+                let Some(attacker) = hurt.attacker.filter(|&a| is_enemy(a, hurt.victim)) else {
+                    continue;
+                };
+                if let Some(player) = stats.get_mut(&attacker) {
+                    player.damage += dealt;
+                    if is_utility_damage(&hurt.weapon) {
+                        player.utility_damage += dealt;
+                    }
+                }
+            }
+
+            // The opening duel is the first kill by an enemy, not a suicide or world death
+            let opening = round_deaths
+                .iter()
+                .position(|d| d.killer().is_some_and(|k| is_enemy(k, d.victim)));
+            for (i, death) in round_deaths.iter().enumerate() {
+                if let Some(victim) = stats.get_mut(&death.victim) {
+                    victim.died = true;
+                    victim.survived = false;
+                    victim.traded = traded.contains(&(death.tick, death.victim));
+                    victim.opening_death = opening == Some(i);
+                }
+                if let Some(killer) = death.killer().filter(|&k| is_enemy(k, death.victim)) {
+                    if let Some(player) = stats.get_mut(&killer) {
+                        player.kills += 1;
+                        player.opening_kill |= opening == Some(i);
+                    }
+                }
+                if let Some(assister) = death.assister.filter(|&a| is_enemy(a, death.victim)) {
+                    if let Some(player) = stats.get_mut(&assister) {
+                        player.assists += 1;
+                        player.flash_assists += death.flash_assist as u32;
+                    }
+                }
+            }
+
+            for situation in clutch_situations(&teams, &round_deaths) {
+                if let Some(player) = stats.get_mut(&situation.steamid) {
+                    player.clutch_opponents = Some(situation.opponents);
+                    player.clutch_won = player.round_won;
+                }
+            }
+
+            let mut round_stats: Vec<RoundPlayerStats> = stats.into_values().collect();
+            round_stats.sort_by_key(|s| (s.team_num, s.steamid));
+            for player in &round_stats {
+                scoreboard
+                    .players
+                    .entry(player.steamid)
+                    .or_insert_with(|| PlayerMatchStats {
+                        steamid: player.steamid,
+                        starting_team: player.team_num,
+                        ..Default::default()
+                    })
+                    .add_round(player);
+            }
+            scoreboard.rounds.extend(round_stats);
+        }
+
+        scoreboard
+    }
+}
+
+/// Team of every player taking part in a round
+pub(crate) fn round_teams(
+    round: &RoundInfo,
+    deaths: &[&DeathEvent],
+    frames: &FrameIndex,
+) -> HashMap<u64, u8> {
+    let mut teams: HashMap<u64, u8> = HashMap::new();
+    let is_playing = |team: u8| team == TEAM_T || team == TEAM_CT;
+
+    for frame in frames.frames_at(round.end_tick) {
+        if frame.tick >= round.start_tick && is_playing(frame.team_num) {
+            teams.insert(frame.steamid, frame.team_num);
+        }
+    }
+    for death in deaths {
+        if is_playing(death.victim_team) {
+            teams.entry(death.victim).or_insert(death.victim_team);
+        }
+        if let Some(killer) = death.killer().filter(|_| is_playing(death.attacker_team)) {
+            teams.entry(killer).or_insert(death.attacker_team);
+        }
+    }
+    teams
+}
+
+fn is_utility_damage(weapon: &str) -> bool {
+    let weapon = weapon.to_ascii_lowercase();
+    [
+        "hegrenade",
+        "inferno",
+        "molotov",
+        "incgrenade",
+        "flashbang",
+        "decoy",
+    ]
+    .iter()
+    .any(|grenade| weapon.contains(grenade))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player_frames::PlayerFrame;
+    use crate::test_fixtures::{death, player};
+
+    fn hurt(tick: u32, victim: u64, attacker: u64, damage: u32, weapon: &str) -> HurtEvent {
+        HurtEvent {
+            tick,
+            victim,
+            attacker: Some(attacker),
+            health_damage: damage,
+            weapon: weapon.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_round_stats_and_clutch() {
+        // 2v2: T 1 opens on CT 10 with a flash assist from T 2, CT 11 trades, then T 2 wins the 1v1
+        let frames = FrameIndex::new(vec![
+            player(1, TEAM_T),
+            player(2, TEAM_T),
+            player(10, TEAM_CT),
+            player(11, TEAM_CT),
+        ]);
+        let rounds = vec![RoundInfo {
+            number: 1,
+            start_tick: 0,
+            end_tick: 1000,
+            winner_team: TEAM_T,
+            reason: "ct_killed".to_string(),
+        }];
+        let hurts = vec![
+            hurt(90, 10, 2, 50, "hegrenade"),
+            hurt(100, 10, 1, 120, "awp"), // Capped at the remaining 50
+            hurt(200, 1, 11, 100, "ak47"),
+            hurt(500, 11, 2, 100, "ak47"),
+        ];
+        let deaths = vec![
+            DeathEvent {
+                assister: Some(2),
+                flash_assist: true,
+                ..death(100, 10, 1)
+            },
+            death(200, 1, 11),
+            death(500, 11, 2),
+        ];
+
+        let board = ScoreboardBuilder::new().build(&rounds, &deaths, &hurts, &frames);
+
+        let t1 = &board.players[&1];
+        assert_eq!((t1.kills, t1.deaths, t1.opening_kills), (1, 1, 1));
+        assert_eq!(t1.damage, 50);
+        assert_eq!(t1.kast(), 100.0);
+
+        // CT 10 died without a kill but was traded by CT 11
+        let ct10 = &board.players[&10];
+        assert_eq!(ct10.opening_deaths, 1);
+        assert_eq!(ct10.kast(), 100.0);
+        assert!(board.rounds.iter().any(|r| r.steamid == 10 && r.traded));
+
+        let t2 = &board.players[&2];
+        assert_eq!(t2.utility_damage, 50);
+        assert_eq!(t2.damage, 150);
+        // CT 11 was alone first, so the 1v1 is their clutch only
+        assert_eq!((t2.clutches_attempted, t2.clutches_won), (0, 0));
+        assert_eq!(t2.multi_kills[1], 1);
+        assert_eq!(t2.adr(), 150.0);
+        assert_eq!((t2.assists, t2.flash_assists), (1, 1));
+
+        let ct11 = &board.players[&11];
+        assert_eq!((ct11.clutches_attempted, ct11.clutches_won), (1, 0));
+        assert!(t2.rating() > ct10.rating());
+    }
+
+    #[test]
+    fn test_passive_survivor_from_event_snapshots() {
+        // Only event snapshots: spawns at each round start plus the fight in round 1.
+        // CT 12 spawns twice and never fights.
+        let spawn = |tick: u32, steamid: u64, team_num: u8| PlayerFrame {
+            tick,
+            health: 100,
+            ..player(steamid, team_num)
+        };
+        let mut frames = Vec::new();
+        for start in [0, 2000] {
+            frames.extend([
+                spawn(start, 1, TEAM_T),
+                spawn(start, 10, TEAM_CT),
+                spawn(start, 12, TEAM_CT),
+            ]);
+        }
+        frames.push(PlayerFrame {
+            tick: 100,
+            is_alive: false,
+            ..player(10, TEAM_CT)
+        });
+        let frames = FrameIndex::new(frames);
+        let round = |number: u32, start_tick: u32, winner_team: u8| RoundInfo {
+            number,
+            start_tick,
+            end_tick: start_tick + 1000,
+            winner_team,
+            reason: String::new(),
+        };
+        let rounds = vec![round(1, 0, TEAM_T), round(2, 2000, TEAM_CT)];
+
+        let board = ScoreboardBuilder::new().build(&rounds, &[death(100, 10, 1)], &[], &frames);
+
+        let ct12 = &board.players[&12];
+        assert_eq!(ct12.rounds_played, 2);
+        assert_eq!((ct12.kills, ct12.deaths), (0, 0));
+        assert_eq!(ct12.kast_rounds, 2);
+        assert_eq!(ct12.rounds_won, 1);
+        assert_eq!(board.players[&10].kast_rounds, 1);
+    }
+
+    #[test]
+    fn test_opening_duel_skips_suicides_and_world_deaths() {
+        let frames = FrameIndex::new(vec![
+            player(1, TEAM_T),
+            player(2, TEAM_T),
+            player(10, TEAM_CT),
+            player(11, TEAM_CT),
+        ]);
+        let rounds = vec![RoundInfo {
+            number: 1,
+            start_tick: 0,
+            end_tick: 1000,
+            winner_team: TEAM_CT,
+            reason: String::new(),
+        }];
+        let deaths = vec![
+            death(50, 2, 2), // Suicide
+            DeathEvent {
+                attacker: None, // Fall damage
+                ..death(80, 11, 0)
+            },
+            death(100, 1, 10),
+        ];
+
+        let board = ScoreboardBuilder::new().build(&rounds, &deaths, &[], &frames);
+
+        assert_eq!(board.players[&2].opening_deaths, 0);
+        assert_eq!(board.players[&11].opening_deaths, 0);
+        assert_eq!(board.players[&1].opening_deaths, 1);
+        assert_eq!(board.players[&10].opening_kills, 1);
+    }
+
+    #[test]
+    fn test_rating_of_average_player() {
+        let stats = PlayerMatchStats {
+            rounds_played: 100,
+            kills: 65,
+            deaths: 67,
+            assists: 14,
+            damage: 7500,
+            kast_rounds: 71,
+            ..Default::default()
+        };
+
+        assert!((stats.impact() - 1.0).abs() < 0.05);
+        assert!((stats.rating() - 1.0).abs() < 0.05);
+    }
+}
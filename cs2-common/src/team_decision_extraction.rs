@@ -1,4 +1,8 @@
 use crate::feature_extraction::{DecisionMetricsFeatures, TeamDynamicsFeatures};
+use crate::flash_extraction::FlashStats;
+use crate::match_clock::MatchClock;
+use crate::reaction_extraction::ReactionProfile;
+use crate::trade_analysis::TradeStats;
 use crate::BehavioralVector;
 use std::collections::HashMap;
 
@@ -7,6 +11,8 @@ pub struct TeamDynamicsExtractor {
     pub max_team_distance: f32, // Maximum distance for team spread analysis
     pub utility_impact_radius: f32, // Radius for utility effectiveness analysis
     pub execute_time_window: u32, // Ticks for tactical execute timing analysis
+    pub flash_stats: HashMap<u64, FlashStats>, // Per-thrower stats from FlashEffectivenessExtractor
+    pub trade_stats: HashMap<u64, TradeStats>, // Per-player stats from TradeAnalyzer
 }
 
 impl Default for TeamDynamicsExtractor {
@@ -15,6 +21,8 @@ impl Default for TeamDynamicsExtractor {
             max_team_distance: 2000.0,    // CS2 units
             utility_impact_radius: 500.0, // CS2 units
             execute_time_window: 64,      // ~1 second at 64 tick
+            flash_stats: HashMap::new(),
+            trade_stats: HashMap::new(),
         }
     }
 }
@@ -24,6 +32,26 @@ impl TeamDynamicsExtractor {
         Self::default()
     }
 
+    /// Create an extractor whose tick windows match the demo's clock
+    pub fn with_clock(clock: &MatchClock) -> Self {
+        Self {
+            execute_time_window: clock.seconds_to_ticks(1.0),
+            ..Self::default()
+        }
+    }
+
+    /// Score flashes from the throwers' measured flash statistics
+    pub fn with_flash_stats(mut self, flash_stats: HashMap<u64, FlashStats>) -> Self {
+        self.flash_stats = flash_stats;
+        self
+    }
+
+    /// Score trades from the players' measured refrag statistics
+    pub fn with_trade_stats(mut self, trade_stats: HashMap<u64, TradeStats>) -> Self {
+        self.trade_stats = trade_stats;
+        self
+    }
+
     /// Extract team dynamics features from multiple players' behavioral vectors
     pub fn extract_features(
         &self,
@@ -65,6 +93,29 @@ impl TeamDynamicsExtractor {
         // Extract tactical execution metrics
         self.extract_tactical_execution(&mut features, team_vectors);
 
+        // Flash scores come from every flash thrown by the team's players
+        let mut team_flashes = FlashStats::default();
+        for stats in team_vectors
+            .keys()
+            .filter_map(|id| self.flash_stats.get(id))
+        {
+            team_flashes.flashes_thrown += stats.flashes_thrown;
+            team_flashes.flashes_blinding_enemies += stats.flashes_blinding_enemies;
+            team_flashes.flashes_blinding_teammates += stats.flashes_blinding_teammates;
+        }
+        team_flashes.apply_to(&mut features);
+
+        // Trade efficiency comes from every refrag chance the team's players had
+        let mut team_trades = TradeStats::default();
+        for stats in team_vectors
+            .keys()
+            .filter_map(|id| self.trade_stats.get(id))
+        {
+            team_trades.refrag_opportunities += stats.refrag_opportunities;
+            team_trades.refrags += stats.refrags;
+        }
+        team_trades.apply_to(&mut features);
+
         features
     }
 
@@ -206,9 +257,6 @@ impl TeamDynamicsExtractor {
         // Analyze role adherence through positioning patterns
         features.role_adherence = self.analyze_role_adherence(team_vectors);
 
-        // Analyze trade potential and effectiveness
-        features.trade_efficiency = self.analyze_trade_efficiency(team_vectors);
-
         // Detect mid-round adaptations through sudden position changes
         features.mid_round_adaptation_frequency = self.detect_mid_round_adaptations(team_vectors);
 
@@ -478,47 +526,6 @@ impl TeamDynamicsExtractor {
         0.8 // Placeholder value
     }
 
-    fn analyze_trade_efficiency(&self, team_vectors: &HashMap<u64, Vec<BehavioralVector>>) -> f32 {
-        // Analyze potential for trading kills based on player proximity
-        let common_ticks = self.find_common_ticks(team_vectors);
-        let mut trade_opportunities = 0;
-        let mut good_trade_positions = 0;
-
-        for tick in common_ticks.iter().step_by(10) {
-            // Sample every 10 ticks
-            let positions: Vec<(u64, f32, f32, f32)> = team_vectors
-                .iter()
-                .filter_map(|(&player_id, vectors)| {
-                    vectors
-                        .iter()
-                        .find(|v| v.tick == *tick)
-                        .map(|v| (player_id, v.pos_x, v.pos_y, v.pos_z))
-                })
-                .collect();
-
-            for i in 0..positions.len() {
-                for j in (i + 1)..positions.len() {
-                    trade_opportunities += 1;
-
-                    let dx = positions[i].1 - positions[j].1;
-                    let dy = positions[i].2 - positions[j].2;
-                    let distance = (dx * dx + dy * dy).sqrt();
-
-                    // Good trade distance: close enough to help, far enough to avoid double peek
-                    if distance > 200.0 && distance < 800.0 {
-                        good_trade_positions += 1;
-                    }
-                }
-            }
-        }
-
-        if trade_opportunities > 0 {
-            good_trade_positions as f32 / trade_opportunities as f32
-        } else {
-            0.0
-        }
-    }
-
     fn detect_mid_round_adaptations(
         &self,
         team_vectors: &HashMap<u64, Vec<BehavioralVector>>,
@@ -618,6 +625,8 @@ pub struct DecisionMetricsExtractor {
     pub economy_analysis_window: u32, // Ticks to analyze for economy decisions
     pub reaction_time_threshold: f32, // Threshold for fast reactions (degrees/tick)
     pub decision_confidence_threshold: f32, // Threshold for confident decisions
+    pub clock: MatchClock,            // Tick/second conversion for timing features
+    pub reaction_profiles: HashMap<u64, ReactionProfile>, // Measured reactions from VisualReactionExtractor
 }
 
 impl Default for DecisionMetricsExtractor {
@@ -626,6 +635,8 @@ impl Default for DecisionMetricsExtractor {
             economy_analysis_window: 320, // ~5 seconds at 64 tick
             reaction_time_threshold: 2.0, // degrees per tick
             decision_confidence_threshold: 0.7,
+            clock: MatchClock::default(),
+            reaction_profiles: HashMap::new(),
         }
     }
 }
@@ -635,6 +646,24 @@ impl DecisionMetricsExtractor {
         Self::default()
     }
 
+    /// Create an extractor whose timings follow the demo's clock
+    pub fn with_clock(clock: &MatchClock) -> Self {
+        Self {
+            economy_analysis_window: clock.seconds_to_ticks(5.0),
+            clock: *clock,
+            ..Self::default()
+        }
+    }
+
+    /// Time visual reactions with the players' measured reaction profiles
+    pub fn with_reaction_profiles(
+        mut self,
+        reaction_profiles: HashMap<u64, ReactionProfile>,
+    ) -> Self {
+        self.reaction_profiles = reaction_profiles;
+        self
+    }
+
     /// Extract decision-making features from behavioral vectors
     pub fn extract_features(
         &self,
@@ -672,6 +701,11 @@ impl DecisionMetricsExtractor {
         // Extract reaction metrics
         self.extract_reaction_metrics(&mut features, vectors);
 
+        // Visual reaction time comes from the player's measured reactions
+        if let Some(profile) = self.reaction_profiles.get(&vectors[0].steamid) {
+            profile.apply_to(&mut features);
+        }
+
         features
     }
 
@@ -774,7 +808,8 @@ impl DecisionMetricsExtractor {
         if !rotation_timings.is_empty() {
             let avg_rotation_time =
                 rotation_timings.iter().sum::<u32>() as f32 / rotation_timings.len() as f32;
-            features.information_based_rotation_timing = 1.0 - (avg_rotation_time / 64.0).min(1.0);
+            features.information_based_rotation_timing =
+                1.0 - (avg_rotation_time / self.clock.tick_rate).min(1.0);
             // Normalize by 1 second
         }
 
@@ -806,7 +841,7 @@ impl DecisionMetricsExtractor {
                 .sqrt();
 
                 if reaction_magnitude > self.reaction_time_threshold {
-                    let reaction_time = (current.tick - baseline.tick) as f32 / 64.0; // Convert to seconds
+                    let reaction_time = self.clock.elapsed_seconds(baseline.tick, current.tick);
                     reaction_times.push(reaction_time);
 
                     // Calculate reaction quality (smooth vs jerky)
@@ -824,7 +859,7 @@ impl DecisionMetricsExtractor {
                 .sqrt();
 
                 if adjustment_needed > self.reaction_time_threshold * 2.0 {
-                    let adjustment_time = (window[4].tick - mid.tick) as f32 / 64.0;
+                    let adjustment_time = self.clock.elapsed_seconds(mid.tick, window[4].tick);
                     adjustment_times.push(adjustment_time);
                 }
             }
@@ -1049,6 +1084,41 @@ mod tests {
         assert!(features.map_control_percentage >= 0.0);
         assert!(features.crossfire_setup_effectiveness >= 0.0);
         assert!(!features.site_approach_patterns.is_empty());
+
+        // Flash stats of both players are pooled; other throwers are ignored
+        let thrower = |steamid, thrown, enemies, teammates| FlashStats {
+            steamid,
+            flashes_thrown: thrown,
+            flashes_blinding_enemies: enemies,
+            flashes_blinding_teammates: teammates,
+            ..Default::default()
+        };
+        let features = TeamDynamicsExtractor::new()
+            .with_flash_stats(HashMap::from([
+                (76561198123456789, thrower(76561198123456789, 3, 2, 1)),
+                (76561198123456790, thrower(76561198123456790, 1, 1, 0)),
+                (1, thrower(1, 4, 0, 4)),
+            ]))
+            .extract_features(&team_vectors);
+        assert_eq!(features.flash_effectiveness_enemies, 0.75);
+        assert_eq!(features.flash_effectiveness_teammates, 0.75);
+
+        // Refrag chances are pooled the same way; without any the efficiency stays unset
+        assert_eq!(features.trade_efficiency, 0.0);
+        let refragger = |steamid, opportunities, refrags| TradeStats {
+            steamid,
+            refrag_opportunities: opportunities,
+            refrags,
+            ..Default::default()
+        };
+        let features = TeamDynamicsExtractor::new()
+            .with_trade_stats(HashMap::from([
+                (76561198123456789, refragger(76561198123456789, 3, 2)),
+                (76561198123456790, refragger(76561198123456790, 1, 1)),
+                (1, refragger(1, 4, 0)),
+            ]))
+            .extract_features(&team_vectors);
+        assert_eq!(features.trade_efficiency, 0.75);
     }
 
     #[test]
@@ -1105,6 +1175,70 @@ mod tests {
         assert!(features.reaction_time_visual_stimuli >= 0.0);
     }
 
+    #[test]
+    fn test_reaction_time_follows_tick_rate() {
+        let base = BehavioralVector {
+            tick: 0,
+            steamid: 76561198123456789,
+            health: 100.0,
+            armor: 100.0,
+            pos_x: 0.0,
+            pos_y: 0.0,
+            pos_z: 64.0,
+            vel_x: 0.0,
+            vel_y: 0.0,
+            vel_z: 0.0,
+            yaw: 0.0,
+            pitch: 0.0,
+            weapon_id: 7,
+            ammo: 30.0,
+            is_airborne: 0.0,
+            delta_yaw: 0.0,
+            delta_pitch: 0.0,
+        };
+        // Aim snaps 10 degrees two ticks after the window starts
+        let vectors: Vec<BehavioralVector> = (0..5)
+            .map(|i| BehavioralVector {
+                tick: i,
+                yaw: if i >= 2 { 10.0 } else { 0.0 },
+                ..base.clone()
+            })
+            .collect();
+        let team_vectors = HashMap::new();
+
+        let at_64 = DecisionMetricsExtractor::new().extract_features(&vectors, &team_vectors);
+        let at_128 = DecisionMetricsExtractor::with_clock(&MatchClock::new(128.0))
+            .extract_features(&vectors, &team_vectors);
+
+        assert!((at_64.reaction_time_visual_stimuli - 2.0 / 64.0).abs() < 1e-6);
+        assert!((at_128.reaction_time_visual_stimuli - 2.0 / 128.0).abs() < 1e-6);
+
+        // Measured reactions replace the yaw-change estimate for that player only
+        let measured = ReactionProfile {
+            steamid: base.steamid,
+            overall: crate::reaction_extraction::ReactionDistribution {
+                encounters: 3,
+                time_to_crosshair: crate::FeatureStats::from_values(&[0.2, 0.25, 0.3]),
+                time_to_first_shot: None,
+            },
+            ..Default::default()
+        };
+        let extractor = DecisionMetricsExtractor::new()
+            .with_reaction_profiles(HashMap::from([(base.steamid, measured)]));
+        let features = extractor.extract_features(&vectors, &team_vectors);
+        assert_eq!(features.reaction_time_visual_stimuli, 0.25);
+
+        let other: Vec<BehavioralVector> = vectors
+            .iter()
+            .map(|v| BehavioralVector {
+                steamid: 1,
+                ..v.clone()
+            })
+            .collect();
+        let features = extractor.extract_features(&other, &team_vectors);
+        assert!((features.reaction_time_visual_stimuli - 2.0 / 64.0).abs() < 1e-6);
+    }
+
     #[test]
     fn test_team_spread_calculation() {
         let extractor = TeamDynamicsExtractor::new();
@@ -1,13 +1,15 @@
+use crate::clutch_analysis::ClutchStats;
 use crate::feature_extraction::TemporalContextFeatures;
+use crate::match_clock::MatchClock;
 use crate::BehavioralVector;
 use std::collections::HashMap;
 
 /// Temporal Context Extractor - Analyzes round phases, map context, and opponent adaptation
 pub struct TemporalContextExtractor {
-    pub round_length_ticks: u32,         // Typical round length in ticks
-    pub early_round_threshold: u32,      // Ticks defining early round
-    pub late_round_threshold: u32,       // Ticks defining late round
-    pub clutch_players_threshold: usize, // Max players alive to be considered clutch
+    pub round_length_ticks: u32,    // Typical round length in ticks
+    pub early_round_threshold: u32, // Ticks defining early round
+    pub late_round_threshold: u32,  // Ticks defining late round
+    pub clutch_stats: HashMap<u64, ClutchStats>, // Measured clutches from ClutchAnalyzer
 }
 
 impl Default for TemporalContextExtractor {
@@ -16,7 +18,7 @@ impl Default for TemporalContextExtractor {
             round_length_ticks: 7000,    // ~110 seconds at 64 tick
             early_round_threshold: 1000, // ~15 seconds
             late_round_threshold: 5000,  // ~78 seconds
-            clutch_players_threshold: 2,
+            clutch_stats: HashMap::new(),
         }
     }
 }
@@ -26,6 +28,22 @@ impl TemporalContextExtractor {
         Self::default()
     }
 
+    /// Create an extractor whose round phases follow the demo's clock
+    pub fn with_clock(clock: &MatchClock) -> Self {
+        Self {
+            round_length_ticks: clock.seconds_to_ticks(clock.round_time_secs),
+            early_round_threshold: clock.seconds_to_ticks(15.0),
+            late_round_threshold: clock.seconds_to_ticks(clock.round_time_secs * 0.7),
+            ..Self::default()
+        }
+    }
+
+    /// Score clutch performance from the players' measured 1vN results
+    pub fn with_clutch_stats(mut self, clutch_stats: HashMap<u64, ClutchStats>) -> Self {
+        self.clutch_stats = clutch_stats;
+        self
+    }
+
     /// Extract temporal and contextual features from behavioral vectors
     pub fn extract_features(
         &self,
@@ -81,8 +99,10 @@ impl TemporalContextExtractor {
         // Analyze late round decision patterns
         features.late_round_decision_patterns = self.analyze_phase_decisions(&late_vectors, "late");
 
-        // Analyze clutch performance
-        features.clutch_performance_metrics = self.analyze_clutch_performance(vectors);
+        // Clutch performance comes from the player's measured clutches
+        if let Some(stats) = self.clutch_stats.get(&vectors[0].steamid) {
+            stats.apply_to(features);
+        }
     }
 
     fn extract_map_context(
@@ -258,36 +278,6 @@ impl TemporalContextExtractor {
         decisions
     }
 
-    fn analyze_clutch_performance(&self, vectors: &[BehavioralVector]) -> f32 {
-        // Simplified clutch detection and performance analysis
-        // In real implementation, would need team state and elimination events
-
-        let mut clutch_situations = 0;
-        let mut successful_clutch_behaviors = 0;
-
-        for window in vectors.windows(10) {
-            // Detect potential clutch situations through isolated positioning
-            let isolation_score = self.calculate_isolation_score(window);
-
-            if isolation_score > 0.7 {
-                // High isolation suggests potential clutch
-                clutch_situations += 1;
-
-                // Analyze clutch behavior quality
-                let behavior_quality = self.analyze_clutch_behavior_quality(window);
-                if behavior_quality > 0.6 {
-                    successful_clutch_behaviors += 1;
-                }
-            }
-        }
-
-        if clutch_situations > 0 {
-            successful_clutch_behaviors as f32 / clutch_situations as f32
-        } else {
-            0.0
-        }
-    }
-
     fn analyze_map_positioning(
         &self,
         vectors: &[BehavioralVector],
@@ -736,33 +726,6 @@ impl TemporalContextExtractor {
         (speed_factor + variance_factor) / 2.0
     }
 
-    fn calculate_isolation_score(&self, vectors: &[BehavioralVector]) -> f32 {
-        // Simplified isolation calculation
-        // In real implementation, would need teammate positions
-        let position_variance = vectors
-            .iter()
-            .map(|v| (v.pos_x.powi(2) + v.pos_y.powi(2)).sqrt())
-            .collect::<Vec<f32>>();
-
-        if position_variance.is_empty() {
-            return 0.0;
-        }
-
-        let mean_distance = position_variance.iter().sum::<f32>() / position_variance.len() as f32;
-
-        // Higher distance from center suggests isolation
-        (mean_distance / 1500.0).min(1.0)
-    }
-
-    fn analyze_clutch_behavior_quality(&self, vectors: &[BehavioralVector]) -> f32 {
-        // Analyze quality of behavior in clutch situations
-        let movement_efficiency = self.calculate_movement_efficiency(vectors);
-        let aim_stability = self.calculate_aim_stability(vectors);
-        let positioning_quality = self.calculate_positioning_quality(vectors);
-
-        (movement_efficiency + aim_stability + positioning_quality) / 3.0
-    }
-
     fn classify_map_areas(
         &self,
         vectors: &[BehavioralVector],
@@ -1016,62 +979,6 @@ impl TemporalContextExtractor {
         variance.sqrt()
     }
 
-    fn calculate_movement_efficiency(&self, vectors: &[BehavioralVector]) -> f32 {
-        if vectors.len() < 2 {
-            return 1.0;
-        }
-
-        let mut total_distance = 0.0;
-        for window in vectors.windows(2) {
-            let actual_dist = ((window[1].pos_x - window[0].pos_x).powi(2)
-                + (window[1].pos_y - window[0].pos_y).powi(2))
-            .sqrt();
-            total_distance += actual_dist;
-        }
-
-        // Calculate direct distance from start to end
-        let start = &vectors[0];
-        let end = &vectors[vectors.len() - 1];
-
-        let optimal_distance =
-            ((end.pos_x - start.pos_x).powi(2) + (end.pos_y - start.pos_y).powi(2)).sqrt();
-
-        if total_distance > 0.0 {
-            optimal_distance / total_distance
-        } else {
-            1.0
-        }
-    }
-
-    fn calculate_aim_stability(&self, vectors: &[BehavioralVector]) -> f32 {
-        if vectors.len() < 2 {
-            return 1.0;
-        }
-
-        let mut total_aim_change = 0.0;
-
-        for window in vectors.windows(2) {
-            let yaw_change = (window[1].yaw - window[0].yaw).abs();
-            let pitch_change = (window[1].pitch - window[0].pitch).abs();
-            total_aim_change += (yaw_change.powi(2) + pitch_change.powi(2)).sqrt();
-        }
-
-        let avg_aim_change = total_aim_change / (vectors.len() - 1) as f32;
-
-        // Stability is inverse of change (normalized)
-        1.0 - (avg_aim_change / 90.0).min(1.0)
-    }
-
-    fn calculate_positioning_quality(&self, vectors: &[BehavioralVector]) -> f32 {
-        // Simplified positioning quality based on health maintenance
-        if vectors.is_empty() {
-            return 1.0;
-        }
-
-        let avg_health = vectors.iter().map(|v| v.health).sum::<f32>() / vectors.len() as f32;
-        avg_health / 100.0
-    }
-
     fn position_to_area(&self, vector: &BehavioralVector, _map_name: &str) -> String {
         // Simplified area classification
         match (vector.pos_x > 0.0, vector.pos_y > 0.0) {
@@ -1370,4 +1277,29 @@ mod tests {
 
         assert!(unpredictable_score > predictable_score);
     }
+
+    #[test]
+    fn test_clutch_performance_from_clutch_stats() {
+        let steamid = 76561198123456789;
+        let vectors: Vec<BehavioralVector> = (0..20)
+            .map(|i| BehavioralVector::new(i * 100, steamid))
+            .collect();
+        let stats = ClutchStats {
+            steamid,
+            attempts: 1,
+            wins: 1,
+            attempts_by_opponents: [0, 0, 1, 0, 0, 0],
+            wins_by_opponents: [0, 0, 1, 0, 0, 0],
+            ..Default::default()
+        };
+
+        let without =
+            TemporalContextExtractor::new().extract_features(&vectors, &HashMap::new(), None);
+        assert_eq!(without.clutch_performance_metrics, 0.0);
+
+        let extractor =
+            TemporalContextExtractor::new().with_clutch_stats(HashMap::from([(steamid, stats)]));
+        let features = extractor.extract_features(&vectors, &HashMap::new(), None);
+        assert_eq!(features.clutch_performance_metrics, 1.0);
+    }
 }
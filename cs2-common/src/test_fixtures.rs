@@ -0,0 +1,31 @@
+//! Frame and event builders shared by the unit tests
+
+use crate::match_events::DeathEvent;
+use crate::player_frames::PlayerFrame;
+
+/// A live player at `tick`, spotted by `spotted_by`
+pub fn frame(tick: u32, steamid: u64, team_num: u8, spotted_by: Vec<u64>) -> PlayerFrame {
+    PlayerFrame {
+        tick,
+        steamid,
+        team_num,
+        is_alive: true,
+        spotted_by,
+        ..Default::default()
+    }
+}
+
+/// A live player at the start of the demo
+pub fn player(steamid: u64, team_num: u8) -> PlayerFrame {
+    frame(0, steamid, team_num, Vec::new())
+}
+
+/// `attacker` kills `victim` at `tick`
+pub fn death(tick: u32, victim: u64, attacker: u64) -> DeathEvent {
+    DeathEvent {
+        tick,
+        victim,
+        attacker: Some(attacker),
+        ..Default::default()
+    }
+}
@@ -0,0 +1,427 @@
+use crate::feature_extraction::TeamDynamicsFeatures;
+use crate::match_clock::MatchClock;
+use crate::match_events::{DeathEvent, HurtEvent};
+use crate::player_frames::{is_playing_team, FrameIndex};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Trade Analyzer - Detects traded deaths and missed refrags from kill events
+pub struct TradeAnalyzer {
+    pub clock: MatchClock,
+    pub trade_window_secs: f32, // Killer must die within this time for a trade
+    pub tradeable_distance: f32, // Teammates within this range could have refragged
+    pub isolated_distance: f32, // Beyond this range nobody could have helped
+}
+
+impl Default for TradeAnalyzer {
+    fn default() -> Self {
+        Self {
+            clock: MatchClock::default(),
+            trade_window_secs: 5.0,
+            tradeable_distance: 800.0, // CS2 units
+            isolated_distance: 1500.0, // CS2 units
+        }
+    }
+}
+
+/// How reachable the victim was for a refrag, from the nearest alive teammate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Tradeability {
+    Tradeable, // Teammate within `tradeable_distance`
+    Distant,   // Teammate within `isolated_distance`
+    Isolated,  // All teammates further away
+    LastAlive, // No teammate alive
+    Unknown,   // No position data for the victim
+}
+
+/// Trade outcome of a single death
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeathTrade {
+    pub tick: u32,
+    pub victim: u64,
+    pub killer: Option<u64>,
+    pub traded_by: Option<u64>,
+    pub trade_tick: Option<u32>,
+    pub trade_time_secs: Option<f32>,
+    pub nearest_teammate: Option<u64>,
+    pub nearest_teammate_distance: Option<f32>,
+    pub tradeability: Tradeability,
+    pub missed_refrags: Vec<u64>, // Teammates in range who did not trade an untraded death
+}
+
+impl DeathTrade {
+    pub fn is_traded(&self) -> bool {
+        self.traded_by.is_some()
+    }
+
+    /// Untraded death that no teammate was in position to trade
+    pub fn is_untradeable(&self) -> bool {
+        !self.is_traded() && self.tradeability != Tradeability::Tradeable
+    }
+}
+
+/// Per-player trade statistics
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TradeStats {
+    pub steamid: u64,
+    pub deaths: u32,
+    pub traded_deaths: u32,
+    pub untradeable_deaths: u32,
+    pub trade_kills: u32,
+    pub avg_trade_time_secs: Option<f32>,
+    pub refrag_opportunities: u32, // Teammate deaths within tradeable range not traded by someone else
+    pub refrags: u32,              // Opportunities converted by killing the killer in time
+    pub refrag_opportunities_missed: u32,
+    pub refrag_damage: u32, // Damage dealt to killers during missed refrags
+}
+
+impl TradeStats {
+    pub fn traded_death_ratio(&self) -> f32 {
+        ratio(self.traded_deaths, self.deaths)
+    }
+
+    /// Share of refrag opportunities that were converted
+    pub fn refrag_conversion(&self) -> f32 {
+        ratio(self.refrags, self.refrag_opportunities)
+    }
+
+    /// Trade efficiency from the measured refrag conversion
+    pub fn apply_to(&self, features: &mut TeamDynamicsFeatures) {
+        if self.refrag_opportunities > 0 {
+            features.trade_efficiency = self.refrag_conversion();
+        }
+    }
+}
+
+fn ratio(numerator: u32, denominator: u32) -> f32 {
+    if denominator > 0 {
+        numerator as f32 / denominator as f32
+    } else {
+        0.0
+    }
+}
+
+/// Trade outcome of every death plus per-player aggregates
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TradeReport {
+    pub deaths: Vec<DeathTrade>,
+    pub players: HashMap<u64, TradeStats>,
+}
+
+impl TradeAnalyzer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an analyzer that converts ticks with the demo's clock
+    pub fn with_clock(clock: &MatchClock) -> Self {
+        Self {
+            clock: *clock,
+            ..Self::default()
+        }
+    }
+
+    /// Resolve trades for all deaths
+    ///
+    /// Teams missing from the events are taken from `frames`, which also
+    /// provide the positions for the nearest-teammate classification.
+    pub fn analyze(
+        &self,
+        deaths: &[DeathEvent],
+        hurts: &[HurtEvent],
+        frames: &FrameIndex,
+    ) -> TradeReport {
+        let mut deaths: Vec<DeathEvent> = deaths.to_vec();
+        deaths.sort_by_key(|d| d.tick);
+        for death in &mut deaths {
+            if death.victim_team == 0 {
+                death.victim_team = team_at(frames, death.victim, death.tick);
+            }
+            if let (0, Some(killer)) = (death.attacker_team, death.killer()) {
+                death.attacker_team = team_at(frames, killer, death.tick);
+            }
+        }
+
+        let window = self.clock.seconds_to_ticks(self.trade_window_secs);
+        let mut report = TradeReport::default();
+
+        for (i, death) in deaths.iter().enumerate() {
+            let killer = death.killer();
+            let trade = killer
+                .filter(|_| is_playing_team(death.victim_team))
+                .and_then(|killer| {
+                    deaths[i + 1..]
+                        .iter()
+                        .take_while(|later| later.tick <= death.tick + window)
+                        .find(|later| {
+                            later.victim == killer
+                                && later.attacker_team == death.victim_team
+                                && later.victim_team != death.victim_team
+                        })
+                });
+
+            let (nearest, tradeability, mut in_range) = self.nearest_teammates(frames, death);
+            if killer.is_none() {
+                // World and suicide deaths leave nobody to refrag
+                in_range.clear();
+            }
+            let traded_by = trade.and_then(DeathEvent::killer);
+            let missed_refrags: Vec<u64> = if traded_by.is_some() {
+                Vec::new()
+            } else {
+                in_range.clone()
+            };
+
+            let victim_stats = player_stats(&mut report.players, death.victim);
+            victim_stats.deaths += 1;
+            if traded_by.is_some() {
+                victim_stats.traded_deaths += 1;
+            } else if tradeability != Tradeability::Tradeable {
+                victim_stats.untradeable_deaths += 1;
+            }
+
+            // Only the trader converts a traded death; nobody else had it to miss
+            for &teammate in &in_range {
+                if traded_by.is_none() || traded_by == Some(teammate) {
+                    let stats = player_stats(&mut report.players, teammate);
+                    stats.refrag_opportunities += 1;
+                    stats.refrags += u32::from(traded_by.is_some());
+                }
+            }
+            for &teammate in &missed_refrags {
+                let damage: u32 = hurts
+                    .iter()
+                    .filter(|h| {
+                        h.attacker == Some(teammate)
+                            && Some(h.victim) == killer
+                            && h.tick >= death.tick
+                            && h.tick <= death.tick + window
+                    })
+                    .map(|h| h.health_damage)
+                    .sum();
+                let stats = player_stats(&mut report.players, teammate);
+                stats.refrag_opportunities_missed += 1;
+                stats.refrag_damage += damage;
+            }
+
+            let trade_time_secs = trade.map(|t| self.clock.elapsed_seconds(death.tick, t.tick));
+            if let (Some(trader), Some(secs)) = (traded_by, trade_time_secs) {
+                let stats = player_stats(&mut report.players, trader);
+                let total = stats.avg_trade_time_secs.unwrap_or(0.0) * stats.trade_kills as f32;
+                stats.trade_kills += 1;
+                stats.avg_trade_time_secs = Some((total + secs) / stats.trade_kills as f32);
+            }
+
+            report.deaths.push(DeathTrade {
+                tick: death.tick,
+                victim: death.victim,
+                killer,
+                traded_by,
+                trade_tick: trade.map(|t| t.tick),
+                trade_time_secs,
+                nearest_teammate: nearest.map(|(id, _)| id),
+                nearest_teammate_distance: nearest.map(|(_, d)| d),
+                tradeability,
+                missed_refrags,
+            });
+        }
+
+        report
+    }
+
+    /// Nearest alive teammate, the resulting class and all teammates in tradeable range
+    fn nearest_teammates(
+        &self,
+        frames: &FrameIndex,
+        death: &DeathEvent,
+    ) -> (Option<(u64, f32)>, Tradeability, Vec<u64>) {
+        let victim = frames
+            .frame_at(death.victim, death.tick)
+            .filter(|_| is_playing_team(death.victim_team));
+        let Some(victim) = victim else {
+            return (None, Tradeability::Unknown, Vec::new());
+        };
+
+        let mut teammates: Vec<(u64, f32)> = frames
+            .frames_at(death.tick)
+            .into_iter()
+            .filter(|f| f.steamid != death.victim && f.is_alive && f.team_num == death.victim_team)
+            .map(|f| (f.steamid, f.distance_to(victim)))
+            .collect();
+        teammates.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+
+        let nearest = teammates.first().copied();
+        let tradeability = match nearest {
+            None => Tradeability::LastAlive,
+            Some((_, d)) if d <= self.tradeable_distance => Tradeability::Tradeable,
+            Some((_, d)) if d <= self.isolated_distance => Tradeability::Distant,
+            Some(_) => Tradeability::Isolated,
+        };
+        let in_range = teammates
+            .iter()
+            .filter(|(_, d)| *d <= self.tradeable_distance)
+            .map(|(id, _)| *id)
+            .collect();
+
+        (nearest, tradeability, in_range)
+    }
+}
+
+fn team_at(frames: &FrameIndex, steamid: u64, tick: u32) -> u8 {
+    frames
+        .frame_at(steamid, tick)
+        .map(|f| f.team_num)
+        .unwrap_or(0)
+}
+
+fn player_stats(players: &mut HashMap<u64, TradeStats>, steamid: u64) -> &mut TradeStats {
+    players.entry(steamid).or_insert_with(|| TradeStats {
+        steamid,
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player_frames::{PlayerFrame, TEAM_CT, TEAM_T};
+    use crate::test_fixtures::{death, player};
+
+    fn frame(steamid: u64, team_num: u8, pos_x: f32) -> PlayerFrame {
+        PlayerFrame {
+            pos_x,
+            ..player(steamid, team_num)
+        }
+    }
+
+    fn dead(tick: u32, frame: PlayerFrame) -> PlayerFrame {
+        PlayerFrame {
+            tick,
+            is_alive: false,
+            ..frame
+        }
+    }
+
+    #[test]
+    fn test_trades_and_missed_refrags() {
+        // CTs 1 and 2 stand together, CT 3 is alone across the map
+        let frames = FrameIndex::new(vec![
+            frame(1, TEAM_CT, 0.0),
+            frame(2, TEAM_CT, 300.0),
+            frame(3, TEAM_CT, 5000.0),
+            frame(10, TEAM_T, 1000.0),
+            frame(11, TEAM_T, 1200.0),
+            frame(12, TEAM_T, 6000.0),
+            dead(100, frame(1, TEAM_CT, 0.0)),
+            dead(200, frame(10, TEAM_T, 1000.0)),
+            dead(1000, frame(2, TEAM_CT, 300.0)),
+        ]);
+        let deaths = vec![
+            death(100, 1, 10), // Traded by 2 after 100 ticks
+            death(200, 10, 2),
+            death(1000, 2, 11), // Nobody in range: teammate 3 is far away
+            death(2000, 3, 12), // Last CT alive
+        ];
+        let hurts = vec![HurtEvent {
+            tick: 150,
+            victim: 10,
+            attacker: Some(2),
+            health_damage: 40,
+            ..Default::default()
+        }];
+
+        let report = TradeAnalyzer::new().analyze(&deaths, &hurts, &frames);
+
+        let first = &report.deaths[0];
+        assert_eq!(first.traded_by, Some(2));
+        assert_eq!(first.trade_time_secs, Some(100.0 / 64.0));
+        assert_eq!(first.tradeability, Tradeability::Tradeable);
+        assert_eq!(report.deaths[2].tradeability, Tradeability::Isolated);
+        assert!(report.deaths[2].is_untradeable());
+        assert_eq!(report.deaths[3].tradeability, Tradeability::LastAlive);
+
+        let ct2 = &report.players[&2];
+        assert_eq!(ct2.trade_kills, 1);
+        assert_eq!(ct2.refrag_opportunities, 1);
+        assert_eq!(ct2.refrag_opportunities_missed, 0);
+        assert_eq!(report.players[&1].traded_death_ratio(), 1.0);
+        assert_eq!(report.players[&3].untradeable_deaths, 1);
+    }
+
+    #[test]
+    fn test_refrags_credit_only_the_trader() {
+        // CTs 1, 2 and 4 stand together; 5 has no team yet and stands on the victim
+        let frames = FrameIndex::new(vec![
+            frame(1, TEAM_CT, 0.0),
+            frame(2, TEAM_CT, 300.0),
+            frame(4, TEAM_CT, 500.0),
+            frame(5, 0, 10.0),
+            frame(10, TEAM_T, 1000.0),
+            frame(11, TEAM_T, 1100.0),
+            dead(100, frame(1, TEAM_CT, 0.0)),
+        ]);
+        let deaths = vec![
+            death(100, 1, 10),
+            death(150, 11, 2), // Another T, not the killer
+            death(200, 10, 4), // The trade
+        ];
+
+        let report = TradeAnalyzer::new().analyze(&deaths, &[], &frames);
+
+        let first = &report.deaths[0];
+        assert_eq!(first.traded_by, Some(4));
+        assert_eq!(first.nearest_teammate, Some(2));
+        assert!(!report.players.contains_key(&5));
+
+        let ct4 = &report.players[&4];
+        assert_eq!((ct4.refrag_opportunities, ct4.refrags), (1, 1));
+        assert_eq!(ct4.refrag_conversion(), 1.0);
+        // 2 was nearer but neither had nor missed the refrag
+        assert!(!report.players.contains_key(&2));
+
+        // Without a known team the victim has no teammates and no trade
+        let mut unknown = death(100, 1, 10);
+        unknown.victim_team = 0;
+        let frames = FrameIndex::new(vec![frame(1, 0, 0.0), frame(5, 0, 10.0)]);
+        let report = TradeAnalyzer::new().analyze(&[unknown, death(200, 10, 5)], &[], &frames);
+        assert_eq!(report.deaths[0].tradeability, Tradeability::Unknown);
+        assert_eq!(report.deaths[0].traded_by, None);
+    }
+
+    #[test]
+    fn test_missed_refrag_tracks_damage() {
+        let frames = FrameIndex::new(vec![
+            frame(1, TEAM_CT, 0.0),
+            frame(2, TEAM_CT, 400.0),
+            frame(10, TEAM_T, 1000.0),
+        ]);
+        let hurts = vec![HurtEvent {
+            tick: 110,
+            victim: 10,
+            attacker: Some(2),
+            health_damage: 40,
+            ..Default::default()
+        }];
+
+        let report = TradeAnalyzer::new().analyze(&[death(100, 1, 10)], &hurts, &frames);
+
+        assert_eq!(report.deaths[0].missed_refrags, vec![2]);
+        let ct2 = &report.players[&2];
+        assert_eq!(ct2.refrag_opportunities_missed, 1);
+        assert_eq!(ct2.refrag_damage, 40);
+        assert_eq!(ct2.refrag_conversion(), 0.0);
+
+        let mut features = TeamDynamicsFeatures::default();
+        ct2.apply_to(&mut features);
+        assert_eq!(features.trade_efficiency, 0.0);
+
+        // Falling to death or a suicide is no refrag chance
+        let world = DeathEvent {
+            attacker: None,
+            ..death(100, 1, 0)
+        };
+        let report = TradeAnalyzer::new().analyze(&[world, death(300, 1, 1)], &hurts, &frames);
+        assert!(report.deaths.iter().all(|d| d.missed_refrags.is_empty()));
+        assert!(!report.players.contains_key(&2));
+    }
+}
@@ -0,0 +1,614 @@
+use crate::feature_schema::{read_json, write_json};
+use crate::match_clock::{MatchClock, DEFAULT_BOMB_TIMER_SECS, DEFAULT_ROUND_TIME_SECS};
+use crate::match_events::{BombAction, BombEvent, BombSite, DeathEvent, HurtEvent, RoundInfo};
+use crate::player_frames::{FrameIndex, TEAM_CT, TEAM_T};
+use crate::scoreboard::round_teams;
+use crate::CS2Error;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+/// Players per side in a competitive match, used to fill partly known rosters
+pub const PLAYERS_PER_SIDE: u32 = 5;
+
+const MAX_HEALTH: u32 = 100;
+
+/// Names of the model inputs, in the order of [`RoundState::features`]
+pub const WIN_PROBABILITY_FEATURES: [&str; 11] = [
+    "alive_diff",
+    "ct_alive",
+    "t_alive",
+    "health_diff",
+    "armor_diff",
+    "equipment_diff",
+    "bomb_planted",
+    "bomb_time_left",
+    "round_time_left",
+    "defuse_kits",
+    "site_b",
+];
+
+/// Aggregated state of the alive players of one side
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SideState {
+    pub alive: u32,
+    pub health: u32,
+    pub armor: u32,
+    pub equipment_value: u32,
+    pub defuse_kits: u32, // Always zero for the T side
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BombState {
+    #[default]
+    NotPlanted,
+    Planted,
+    Defused,
+    Exploded,
+}
+
+/// Everything the win probability model looks at, at one tick
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RoundState {
+    pub tick: u32,
+    pub round: u32,
+    pub ct: SideState,
+    pub t: SideState,
+    pub bomb: BombState,
+    pub site: BombSite,
+    pub time_remaining_secs: f32, // Round clock before the plant, bomb timer after it
+}
+
+impl RoundState {
+    /// Winner of a round that is already decided, `Some(true)` for CT
+    pub fn decided(&self) -> Option<bool> {
+        match self.bomb {
+            BombState::Defused => Some(true),
+            BombState::Exploded => Some(false),
+            _ if self.ct.alive == 0 => Some(false),
+            BombState::NotPlanted if self.t.alive == 0 || self.time_remaining_secs <= 0.0 => {
+                Some(true)
+            }
+            _ => None,
+        }
+    }
+
+    /// Model inputs, named by [`WIN_PROBABILITY_FEATURES`]
+    pub fn features(&self) -> [f32; 11] {
+        let planted = self.bomb == BombState::Planted;
+        let when_planted = |value: f32| if planted { value } else { 0.0 };
+        [
+            self.ct.alive as f32 - self.t.alive as f32,
+            self.ct.alive as f32 / PLAYERS_PER_SIDE as f32,
+            self.t.alive as f32 / PLAYERS_PER_SIDE as f32,
+            (self.ct.health as f32 - self.t.health as f32) / MAX_HEALTH as f32,
+            (self.ct.armor as f32 - self.t.armor as f32) / 100.0,
+            (self.ct.equipment_value as f32 - self.t.equipment_value as f32) / 1000.0,
+            when_planted(1.0),
+            when_planted(self.time_remaining_secs / DEFAULT_BOMB_TIMER_SECS),
+            if planted {
+                0.0
+            } else {
+                self.time_remaining_secs / DEFAULT_ROUND_TIME_SECS
+            },
+            when_planted((self.ct.defuse_kits > 0) as u8 as f32),
+            when_planted((self.site == BombSite::B) as u8 as f32),
+        ]
+    }
+}
+
+/// A round state labelled with the round's winner
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WinProbabilitySample {
+    pub state: RoundState,
+    pub ct_won: bool,
+}
+
+/// Gradient descent settings for [`WinProbabilityModel::fit`]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FitConfig {
+    pub epochs: usize,
+    pub learning_rate: f32,
+    pub l2: f32, // Weight decay towards zero, keeps sparse features stable
+}
+
+impl Default for FitConfig {
+    fn default() -> Self {
+        Self {
+            epochs: 500,
+            learning_rate: 0.1,
+            l2: 1e-3,
+        }
+    }
+}
+
+/// Logistic regression over [`RoundState::features`] giving P(CT wins the round)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WinProbabilityModel {
+    pub features: Vec<String>,
+    pub weights: Vec<f32>,
+    pub bias: f32,
+    pub training_samples: usize, // Zero for the prior
+}
+
+impl Default for WinProbabilityModel {
+    /// Hand-set prior used until a model has been fit on demos
+    fn default() -> Self {
+        Self {
+            features: WIN_PROBABILITY_FEATURES
+                .iter()
+                .map(|f| f.to_string())
+                .collect(),
+            weights: vec![0.9, 0.2, -0.2, 0.5, 0.1, 0.15, -1.0, 1.2, 0.5, 0.6, 0.0],
+            bias: -0.2,
+            training_samples: 0,
+        }
+    }
+}
+
+impl WinProbabilityModel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// P(CT wins the round) in the given state
+    pub fn predict(&self, state: &RoundState) -> f32 {
+        match state.decided() {
+            Some(ct_won) => ct_won as u8 as f32,
+            None => sigmoid(self.logit(&state.features())),
+        }
+    }
+
+    /// Fit the weights by full-batch gradient descent, starting from the current ones
+    ///
+    /// Decided states carry no information and are skipped. Returns the final
+    /// mean log loss over the remaining samples.
+    pub fn fit(&mut self, samples: &[WinProbabilitySample], config: &FitConfig) -> f32 {
+        let data: Vec<([f32; 11], f32)> = samples
+            .iter()
+            .filter(|s| s.state.decided().is_none())
+            .map(|s| (s.state.features(), s.ct_won as u8 as f32))
+            .collect();
+        if data.is_empty() {
+            return 0.0;
+        }
+        let n = data.len() as f32;
+
+        for _ in 0..config.epochs {
+            let mut grad = [0.0f32; 11];
+            let mut grad_bias = 0.0;
+            for (x, y) in &data {
+                let error = sigmoid(self.logit(x)) - y;
+                for (g, xi) in grad.iter_mut().zip(x) {
+                    *g += error * xi;
+                }
+                grad_bias += error;
+            }
+            for (w, g) in self.weights.iter_mut().zip(grad) {
+                *w -= config.learning_rate * (g / n + config.l2 * *w);
+            }
+            self.bias -= config.learning_rate * grad_bias / n;
+        }
+        self.training_samples = data.len();
+
+        data.iter()
+            .map(|(x, y)| {
+                let p = sigmoid(self.logit(x)).clamp(1e-6, 1.0 - 1e-6);
+                -(y * p.ln() + (1.0 - y) * (1.0 - p).ln())
+            })
+            .sum::<f32>()
+            / n
+    }
+
+    /// Win probability of every state, as a per-tick column
+    pub fn timeline(&self, states: &[RoundState]) -> WinProbabilityTimeline {
+        WinProbabilityTimeline {
+            ticks: states.iter().map(|s| s.tick).collect(),
+            rounds: states.iter().map(|s| s.round).collect(),
+            ct_win_probability: states.iter().map(|s| self.predict(s)).collect(),
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), CS2Error> {
+        write_json(self, path)
+    }
+
+    /// Load a fitted model, rejecting one trained on a different feature layout
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, CS2Error> {
+        let model: Self = read_json(path)?;
+        if model.features != WIN_PROBABILITY_FEATURES
+            || model.weights.len() != WIN_PROBABILITY_FEATURES.len()
+        {
+            return Err(CS2Error::ModelError(format!(
+                "win probability model expects features {:?}, got {:?}",
+                WIN_PROBABILITY_FEATURES, model.features
+            )));
+        }
+        Ok(model)
+    }
+
+    fn logit(&self, x: &[f32; 11]) -> f32 {
+        self.bias + self.weights.iter().zip(x).map(|(w, x)| w * x).sum::<f32>()
+    }
+}
+
+fn sigmoid(x: f32) -> f32 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+/// P(CT wins) over a match, stepped at every sampled state
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct WinProbabilityTimeline {
+    pub ticks: Vec<u32>,
+    pub rounds: Vec<u32>,
+    pub ct_win_probability: Vec<f32>,
+}
+
+impl WinProbabilityTimeline {
+    /// Probability after everything that happened up to and including `tick`
+    pub fn at(&self, tick: u32) -> Option<f32> {
+        let idx = self.ticks.partition_point(|&t| t <= tick);
+        idx.checked_sub(1).map(|i| self.ct_win_probability[i])
+    }
+
+    /// Absolute change in win probability from `before` to `after`
+    pub fn swing(&self, before: u32, after: u32) -> f32 {
+        match (self.at(before), self.at(after)) {
+            (Some(a), Some(b)) => (b - a).abs(),
+            _ => 0.0,
+        }
+    }
+}
+
+/// Round State Builder - Replays rounds from events into model inputs
+#[derive(Debug, Clone, Copy)]
+pub struct RoundStateBuilder {
+    pub clock: MatchClock,
+    pub sample_interval_secs: f32, // Spacing of states between events
+}
+
+impl Default for RoundStateBuilder {
+    fn default() -> Self {
+        Self {
+            clock: MatchClock::default(),
+            sample_interval_secs: 1.0,
+        }
+    }
+}
+
+impl RoundStateBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a builder that converts ticks with the demo's clock
+    pub fn with_clock(clock: &MatchClock) -> Self {
+        Self {
+            clock: *clock,
+            ..Self::default()
+        }
+    }
+
+    /// States at every event tick and every sample interval of every round
+    ///
+    /// Rosters come from death teams and `frames`; sides with fewer known
+    /// players are filled up to [`PLAYERS_PER_SIDE`] at full health. Armor,
+    /// equipment and defuse kits come from the latest frame of each player in
+    /// the round and count as zero without one. A round stops being sampled
+    /// once it is decided.
+    pub fn build(
+        &self,
+        rounds: &[RoundInfo],
+        deaths: &[DeathEvent],
+        hurts: &[HurtEvent],
+        bomb_events: &[BombEvent],
+        frames: &FrameIndex,
+    ) -> Vec<RoundState> {
+        let mut states = Vec::new();
+        for round in rounds {
+            let round_deaths: Vec<&DeathEvent> =
+                deaths.iter().filter(|d| round.contains(d.tick)).collect();
+            let round_hurts: Vec<&HurtEvent> =
+                hurts.iter().filter(|h| round.contains(h.tick)).collect();
+            let round_bombs: Vec<&BombEvent> = bomb_events
+                .iter()
+                .filter(|b| round.contains(b.tick))
+                .collect();
+            let teams = round_teams(round, &round_deaths, frames);
+
+            let step = self
+                .clock
+                .seconds_to_ticks(self.sample_interval_secs)
+                .max(1);
+            let mut checkpoints: Vec<u32> = (round.start_tick..=round.end_tick)
+                .step_by(step as usize)
+                .chain(round_deaths.iter().map(|d| d.tick))
+                .chain(round_hurts.iter().map(|h| h.tick))
+                .chain(round_bombs.iter().map(|b| b.tick))
+                .collect();
+            checkpoints.sort_unstable();
+            checkpoints.dedup();
+
+            for tick in checkpoints {
+                let state = self.state_at(
+                    round,
+                    tick,
+                    &teams,
+                    &round_deaths,
+                    &round_hurts,
+                    &round_bombs,
+                    frames,
+                );
+                let decided = state.decided().is_some();
+                states.push(state);
+                if decided {
+                    break;
+                }
+            }
+        }
+        states
+    }
+
+    /// Label every undecided state with its round's winner
+    pub fn samples(
+        &self,
+        rounds: &[RoundInfo],
+        states: &[RoundState],
+    ) -> Vec<WinProbabilitySample> {
+        let winners: HashMap<u32, u8> = rounds.iter().map(|r| (r.number, r.winner_team)).collect();
+        states
+            .iter()
+            .filter(|s| s.decided().is_none())
+            .filter_map(|state| {
+                let winner = *winners.get(&state.round)?;
+                (winner == TEAM_T || winner == TEAM_CT).then(|| WinProbabilitySample {
+                    state: state.clone(),
+                    ct_won: winner == TEAM_CT,
+                })
+            })
+            .collect()
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn state_at(
+        &self,
+        round: &RoundInfo,
+        tick: u32,
+        teams: &HashMap<u64, u8>,
+        deaths: &[&DeathEvent],
+        hurts: &[&HurtEvent],
+        bombs: &[&BombEvent],
+        frames: &FrameIndex,
+    ) -> RoundState {
+        let dead: HashSet<u64> = deaths
+            .iter()
+            .filter(|d| d.tick <= tick)
+            .map(|d| d.victim)
+            .collect();
+        let mut last_hurt: HashMap<u64, &HurtEvent> = HashMap::new();
+        for hurt in hurts.iter().filter(|h| h.tick <= tick) {
+            last_hurt.insert(hurt.victim, hurt);
+        }
+
+        let mut state = RoundState {
+            tick,
+            round: round.number,
+            ..Default::default()
+        };
+        let mut known = [0u32; 2];
+        for (&steamid, &team) in teams {
+            let side = (team == TEAM_CT) as usize;
+            known[side] += 1;
+            if dead.contains(&steamid) {
+                continue;
+            }
+
+            let frame = frames
+                .frame_at(steamid, tick)
+                .filter(|f| f.tick >= round.start_tick);
+            let hurt = last_hurt.get(&steamid);
+            let health = match (frame, hurt) {
+                (Some(f), Some(h)) if f.tick > h.tick && f.health > 0 => f.health,
+                (_, Some(h)) => h.remaining_health,
+                (Some(f), None) if f.health > 0 => f.health,
+                _ => MAX_HEALTH,
+            };
+
+            let totals = if side == 1 {
+                &mut state.ct
+            } else {
+                &mut state.t
+            };
+            totals.alive += 1;
+            totals.health += health.min(MAX_HEALTH);
+            if let Some(f) = frame {
+                totals.armor += f.armor;
+                totals.equipment_value += f.equipment_value;
+                totals.defuse_kits += (side == 1 && f.has_defuser) as u32;
+            }
+        }
+        for (totals, known) in [(&mut state.t, known[0]), (&mut state.ct, known[1])] {
+            let unknown = PLAYERS_PER_SIDE.saturating_sub(known);
+            totals.alive += unknown;
+            totals.health += unknown * MAX_HEALTH;
+        }
+
+        let freeze_end = self.clock.freeze_end_tick(round.start_tick);
+        state.time_remaining_secs = self.clock.round_time_remaining(freeze_end, tick);
+        for bomb in bombs.iter().filter(|b| b.tick <= tick) {
+            match bomb.action {
+                BombAction::Planted => {
+                    state.bomb = BombState::Planted;
+                    state.site = bomb.site;
+                    state.time_remaining_secs = self.clock.bomb_time_remaining(bomb.tick, tick);
+                }
+                BombAction::Defused => state.bomb = BombState::Defused,
+                BombAction::Exploded => state.bomb = BombState::Exploded,
+            }
+        }
+        state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player_frames::PlayerFrame;
+
+    fn death(tick: u32, victim: u64, victim_team: u8) -> DeathEvent {
+        DeathEvent {
+            tick,
+            victim,
+            victim_team,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_round_states_follow_deaths_and_bomb() {
+        let rounds = vec![RoundInfo {
+            number: 1,
+            start_tick: 0,
+            end_tick: 64 * 80,
+            winner_team: TEAM_T,
+            reason: "bomb_exploded".to_string(),
+        }];
+        let frames = FrameIndex::new(vec![PlayerFrame {
+            tick: 10,
+            steamid: 6,
+            team_num: TEAM_CT,
+            is_alive: true,
+            health: 100,
+            armor: 100,
+            equipment_value: 5000,
+            has_defuser: true,
+            ..Default::default()
+        }]);
+        let deaths = vec![death(640, 1, TEAM_T), death(700, 6, TEAM_CT)];
+        let hurts = vec![HurtEvent {
+            tick: 600,
+            victim: 2,
+            health_damage: 60,
+            remaining_health: 40,
+            ..Default::default()
+        }];
+        let bombs = vec![
+            BombEvent {
+                tick: 2000,
+                action: BombAction::Planted,
+                steamid: Some(2),
+                site: BombSite::B,
+            },
+            BombEvent {
+                tick: 2000 + 64 * 40,
+                action: BombAction::Exploded,
+                steamid: None,
+                site: BombSite::B,
+            },
+        ];
+
+        let builder = RoundStateBuilder::new();
+        let states = builder.build(&rounds, &deaths, &hurts, &bombs, &FrameIndex::default());
+        let at = |tick: u32| states.iter().rev().find(|s| s.tick <= tick).unwrap();
+
+        assert_eq!(at(0).ct.alive, 5);
+        assert_eq!(at(0).time_remaining_secs, 115.0);
+        assert_eq!(at(640).t.alive, 4);
+        assert_eq!(at(700).ct.alive, 4);
+        assert_eq!(at(2000).bomb, BombState::Planted);
+        assert_eq!(at(2000).site, BombSite::B);
+        assert_eq!(at(2000).time_remaining_secs, 40.0);
+        let last = states.last().unwrap();
+        assert_eq!(last.bomb, BombState::Exploded);
+        assert_eq!(last.decided(), Some(false));
+
+        // Economy comes from the frames of the round
+        let with_frames = builder.build(&rounds, &[], &[], &[], &frames);
+        assert_eq!(with_frames[1].ct.equipment_value, 5000);
+        assert_eq!(with_frames[1].ct.defuse_kits, 1);
+
+        let samples = builder.samples(&rounds, &states);
+        assert!(!samples.is_empty());
+        assert!(samples
+            .iter()
+            .all(|s| !s.ct_won && s.state.decided().is_none()));
+    }
+
+    #[test]
+    fn test_model_prior_and_fit() {
+        let model = WinProbabilityModel::new();
+        let even = RoundState {
+            ct: SideState {
+                alive: 5,
+                health: 500,
+                ..Default::default()
+            },
+            t: SideState {
+                alive: 5,
+                health: 500,
+                ..Default::default()
+            },
+            time_remaining_secs: 60.0,
+            ..Default::default()
+        };
+        let man_down = RoundState {
+            ct: SideState {
+                alive: 4,
+                health: 400,
+                ..Default::default()
+            },
+            ..even.clone()
+        };
+        let p_even = model.predict(&even);
+        assert!(p_even > 0.3 && p_even < 0.7);
+        assert!(model.predict(&man_down) < p_even);
+        assert_eq!(
+            model.predict(&RoundState {
+                bomb: BombState::Defused,
+                ..even.clone()
+            }),
+            1.0
+        );
+
+        // CTs always lose 4v5 and always win 5v5 in this toy data set
+        let samples: Vec<WinProbabilitySample> = (0..20)
+            .flat_map(|_| {
+                [
+                    WinProbabilitySample {
+                        state: even.clone(),
+                        ct_won: true,
+                    },
+                    WinProbabilitySample {
+                        state: man_down.clone(),
+                        ct_won: false,
+                    },
+                ]
+            })
+            .collect();
+        let mut fitted = WinProbabilityModel::new();
+        let loss = fitted.fit(&samples, &FitConfig::default());
+        assert!(loss < 0.4);
+        assert!(fitted.predict(&even) > 0.7);
+        assert!(fitted.predict(&man_down) < 0.3);
+        assert_eq!(fitted.training_samples, 40);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("win_probability.json");
+        fitted.save(&path).unwrap();
+        assert_eq!(WinProbabilityModel::load(&path).unwrap(), fitted);
+
+        let timeline = fitted.timeline(&[
+            RoundState {
+                tick: 10,
+                ..even.clone()
+            },
+            RoundState {
+                tick: 20,
+                ..man_down.clone()
+            },
+        ]);
+        assert_eq!(timeline.at(5), None);
+        assert_eq!(timeline.at(15), Some(fitted.predict(&even)));
+        assert!(timeline.swing(19, 20) > 0.4);
+    }
+}
@@ -3,8 +3,8 @@ use sqlx::{PgPool, Row};
 use uuid::Uuid;
 
 use crate::models::{
-    BehavioralEmbedding, KeyMoment, KeyMomentType, Match, MomentBehavior, PlayerSnapshot,
-    ProcessingStatus,
+    BehavioralEmbedding, KeyMoment, KeyMomentType, Match, MatchParticipation, MomentBehavior,
+    PlayerSnapshot, ProcessingStatus,
 };
 
 /// Multi-tier DB manager for the CS2 analysis system
@@ -140,6 +140,63 @@ impl PostgresManager {
         Ok(out)
     }
 
+    /// Insert a player or refresh its name, returning the stored id
+    pub async fn upsert_player(&self, steamid: i64, name: &str) -> Result<Uuid> {
+        let row = sqlx::query(
+            r#"
+            INSERT INTO players (id, steamid, name)
+            VALUES ($1,$2,$3)
+            ON CONFLICT (steamid) DO UPDATE SET name = EXCLUDED.name
+            RETURNING id
+            "#,
+        )
+        .bind(Uuid::now_v7())
+        .bind(steamid)
+        .bind(name)
+        .fetch_one(&self.pool)
+        .await?;
+        let id: Uuid = row.try_get("id")?;
+        Ok(id)
+    }
+
+    // Reprocessing a match overwrites the previous scoreboard
+    pub async fn upsert_match_participations_batch(
+        &self,
+        participations: &[MatchParticipation],
+    ) -> Result<()> {
+        for p in participations {
+            sqlx::query(
+                r#"
+                INSERT INTO match_participations
+                  (id, match_id, player_id, team_side, final_score, kills, deaths, assists,
+                   adr, rating)
+                VALUES ($1,$2,$3,$4,$5,$6,$7,$8,$9,$10)
+                ON CONFLICT (match_id, player_id) DO UPDATE SET
+                  team_side = EXCLUDED.team_side,
+                  final_score = EXCLUDED.final_score,
+                  kills = EXCLUDED.kills,
+                  deaths = EXCLUDED.deaths,
+                  assists = EXCLUDED.assists,
+                  adr = EXCLUDED.adr,
+                  rating = EXCLUDED.rating
+                "#,
+            )
+            .bind(p.id)
+            .bind(p.match_id)
+            .bind(p.player_id)
+            .bind(&p.team_side)
+            .bind(p.final_score)
+            .bind(p.kills)
+            .bind(p.deaths)
+            .bind(p.assists)
+            .bind(p.adr)
+            .bind(p.rating)
+            .execute(&self.pool)
+            .await?;
+        }
+        Ok(())
+    }
+
     pub async fn insert_key_moments_batch(&self, moments: &[KeyMoment]) -> Result<()> {
         for m in moments {
             sqlx::query(
@@ -4,6 +4,8 @@ use sqlx::Row; // Add this import to fix the Row trait issues
 use std::path::PathBuf;
 use tracing::{info, Level};
 
+use cs2_common::win_probability::{FitConfig, WinProbabilityModel};
+use cs2_data_pipeline::pipeline::discover_demo_files;
 use cs2_data_pipeline::{DatabaseManager, DemoProcessor, PipelineConfig};
 
 #[derive(Parser)]
@@ -29,6 +31,10 @@ struct Cli {
     #[arg(long, env = "DEMO_DIR", default_value = "./demos")]
     demo_dir: PathBuf,
 
+    /// Fitted win probability model used to score key moments
+    #[arg(long, env = "WIN_PROBABILITY_MODEL")]
+    win_probability_model: Option<PathBuf>,
+
     /// Maximum concurrent processing jobs
     #[arg(long, default_value = "4")]
     max_jobs: usize,
@@ -73,6 +79,17 @@ enum Commands {
         #[arg(long, default_value = "3")]
         max_retries: usize,
     },
+
+    /// Fit the round win probability model on the demos in the demo directory
+    FitWinProbability {
+        /// Where to write the fitted model JSON
+        #[arg(short, long, default_value = "win_probability.json")]
+        output: PathBuf,
+
+        /// Gradient descent epochs
+        #[arg(long, default_value = "500")]
+        epochs: usize,
+    },
 }
 
 #[tokio::main]
@@ -87,6 +104,32 @@ async fn main() -> Result<()> {
     };
     tracing_subscriber::fmt().with_max_level(log_level).init();
 
+    // Fitting only reads demo files, so it runs without the databases
+    if let Commands::FitWinProbability { output, epochs } = &cli.command {
+        let mut samples = Vec::new();
+        for demo_path in discover_demo_files(&cli.demo_dir)? {
+            match DemoProcessor::win_probability_samples(&demo_path).await {
+                Ok(demo_samples) => samples.extend(demo_samples),
+                Err(e) => info!("Skipped: {} ({})", demo_path.display(), e),
+            }
+        }
+
+        let mut model = WinProbabilityModel::default();
+        let config = FitConfig {
+            epochs: *epochs,
+            ..Default::default()
+        };
+        let loss = model.fit(&samples, &config);
+        model.save(output)?;
+        println!(
+            "Fitted win probability model on {} states (log loss {:.4}) -> {}",
+            model.training_samples,
+            loss,
+            output.display()
+        );
+        return Ok(());
+    }
+
     // Set up database connections
     let timescale_url = cli.timescale_url.as_ref().unwrap_or(&cli.postgres_url);
 
@@ -98,6 +141,7 @@ async fn main() -> Result<()> {
     let config = PipelineConfig {
         max_concurrent_jobs: cli.max_jobs,
         demo_directory: cli.demo_dir,
+        win_probability_model: cli.win_probability_model,
         enable_ai_analysis: true,
         ..Default::default()
     };
@@ -199,6 +243,8 @@ async fn main() -> Result<()> {
             // TODO: Implement retry logic for failed matches
             todo!("Retry functionality not yet implemented");
         }
+
+        Commands::FitWinProbability { .. } => unreachable!("handled before connecting"),
     }
 
     Ok(())
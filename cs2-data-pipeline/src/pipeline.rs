@@ -2,7 +2,7 @@ use anyhow::{anyhow, Result};
 use chrono::Utc;
 use futures::stream::{self, StreamExt};
 use serde_json::Value as JsonValue;
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, OnceLock};
 use tokio::sync::Semaphore;
@@ -11,9 +11,20 @@ use uuid::Uuid;
 
 use crate::database::DatabaseManager;
 use crate::models::{
-    KeyMoment, KeyMomentType, Match, MomentBehavior, PlayerSnapshot, ProcessingStatus,
+    KeyMoment, KeyMomentType, Match, MatchParticipation, MomentBehavior, PlayerSnapshot,
+    ProcessingStatus,
 };
 
+use cs2_common::clutch_analysis::ClutchAnalyzer;
+use cs2_common::match_clock::DEFAULT_TICK_RATE;
+use cs2_common::match_events::RoundInfo;
+use cs2_common::player_frames::{FrameIndex, TEAM_T};
+use cs2_common::scoreboard::ScoreboardBuilder;
+use cs2_common::trade_analysis::TradeAnalyzer;
+use cs2_common::win_probability::{
+    RoundState, RoundStateBuilder, WinProbabilityModel, WinProbabilitySample,
+    WinProbabilityTimeline,
+};
 use cs2_demo_parser::first_pass::parser_settings::ParserInputs;
 use cs2_demo_parser::parse_demo::{DemoOutput, Parser, ParsingMode};
 
@@ -25,6 +36,7 @@ pub struct PipelineConfig {
     pub temp_directory: PathBuf,
     pub enable_ai_analysis: bool,
     pub chunk_size_ticks: u32,
+    pub win_probability_model: Option<PathBuf>, // Fitted model JSON, the prior when unset
 }
 
 impl Default for PipelineConfig {
@@ -36,6 +48,7 @@ impl Default for PipelineConfig {
             temp_directory: PathBuf::from("./temp"),
             enable_ai_analysis: true,
             chunk_size_ticks: 64 * 60,
+            win_probability_model: None,
         }
     }
 }
@@ -81,19 +94,7 @@ impl DemoProcessor {
     }
 
     pub async fn discover_demos(&self) -> Result<Vec<PathBuf>> {
-        use walkdir::WalkDir;
-        let mut out = Vec::new();
-        for entry in WalkDir::new(&self.config.demo_directory) {
-            let e = entry?;
-            if e.path()
-                .extension()
-                .and_then(|s| s.to_str())
-                .map(|ext| ext.eq_ignore_ascii_case("dem"))
-                .unwrap_or(false)
-            {
-                out.push(e.path().to_path_buf());
-            }
-        }
+        let out = discover_demo_files(&self.config.demo_directory)?;
         info!("Discovered {} demos", out.len());
         Ok(out)
     }
@@ -126,7 +127,7 @@ impl DemoProcessor {
             score_team2: 0,
             demo_file_path: path.to_string_lossy().to_string(),
             demo_file_size: size,
-            tick_rate: 64,
+            tick_rate: DEFAULT_TICK_RATE as i32,
             duration_seconds: 0,
             created_at: Utc::now(),
             processed_at: None,
@@ -211,6 +212,10 @@ impl DemoProcessor {
                 "m_bIsWalking",
                 "m_flFlashDuration",
                 "m_iAccount",
+                "team_num",
+                "current_equip_value",
+                "has_defuser",
+                "last_place_name",
             ]
             .into_iter()
             .map(|s| s.to_string())
@@ -221,6 +226,8 @@ impl DemoProcessor {
             wanted_events: vec![
                 "round_start",
                 "round_end",
+                // Spawns put every player on the round roster, even if they never fight
+                "player_spawn",
                 "player_death",
                 "weapon_fire",
                 "player_hurt",
@@ -258,14 +265,22 @@ impl DemoProcessor {
             .parse_demo(&bytes)
             .map_err(|e| anyhow!("parse failure: {e:?}"))?;
 
-        if out.header.is_some() {
-            m.tick_rate = 64;
-            m.duration_seconds = (out.game_events.len() as f32 / 64.0) as i32;
-        }
+        let clock = out.match_clock();
+        let last_tick = out.game_events.iter().map(|e| e.tick.max(0) as u32).max();
+        m.tick_rate = clock.tick_rate as i32;
+        m.duration_seconds = clock.ticks_to_seconds(last_tick.unwrap_or(0)) as i32;
 
         Self::ingest_player_snapshots(db, config, m, &out).await?;
+        Self::persist_scoreboard(db, m, &out).await?;
 
-        let moments = Self::detect_key_moments(m, &out).await?;
+        let model = match &config.win_probability_model {
+            Some(path) => WinProbabilityModel::load(path)
+                .map_err(|e| anyhow!("load win probability model {}: {e}", path.display()))?,
+            None => WinProbabilityModel::default(),
+        };
+        let win_probability = model.timeline(&Self::round_states(&out).1);
+
+        let moments = Self::detect_key_moments(m, &out, &win_probability).await?;
         if !moments.is_empty() {
             Self::persist_key_moments_and_behaviors(db, m, &out, &moments).await?;
             info!("Persisted {} key moments for {}", moments.len(), m.match_id);
@@ -276,6 +291,94 @@ impl DemoProcessor {
         Ok(())
     }
 
+    async fn persist_scoreboard(
+        db: &Arc<DatabaseManager>,
+        match_data: &Match,
+        out: &DemoOutput,
+    ) -> Result<()> {
+        let scoreboard = ScoreboardBuilder::with_clock(&out.match_clock()).build(
+            &out.rounds(),
+            &out.death_events(),
+            &out.hurt_events(),
+            &Self::merged_frames(out),
+        );
+        let names = out.player_names();
+
+        let mut participations = Vec::with_capacity(scoreboard.players.len());
+        for stats in scoreboard.players.values() {
+            let steamid = stats.steamid as i64;
+            let name = names
+                .get(&stats.steamid)
+                .cloned()
+                .unwrap_or_else(|| steamid.to_string());
+            let player_id = db.postgres.upsert_player(steamid, &name).await?;
+            participations.push(MatchParticipation {
+                id: Uuid::now_v7(),
+                match_id: match_data.id,
+                player_id,
+                team_side: if stats.starting_team == TEAM_T {
+                    "T"
+                } else {
+                    "CT"
+                }
+                .to_string(),
+                // Rounds won by the player's team while they played
+                final_score: stats.rounds_won as i32,
+                kills: stats.kills as i32,
+                deaths: stats.deaths as i32,
+                assists: stats.assists as i32,
+                adr: stats.adr(),
+                rating: stats.rating(),
+            });
+        }
+        db.postgres
+            .upsert_match_participations_batch(&participations)
+            .await?;
+        info!(
+            "Persisted scoreboard for {} players in {}",
+            participations.len(),
+            match_data.match_id
+        );
+        Ok(())
+    }
+
+    /// Rounds of a parsed demo and the win probability model inputs over them
+    fn round_states(out: &DemoOutput) -> (Vec<RoundInfo>, Vec<RoundState>) {
+        let rounds = out.rounds();
+        let states = RoundStateBuilder::with_clock(&out.match_clock()).build(
+            &rounds,
+            &out.death_events(),
+            &out.hurt_events(),
+            &out.bomb_events(),
+            &Self::merged_frames(out),
+        );
+        (rounds, states)
+    }
+
+    /// Player frames including the snapshots attached to events
+    fn merged_frames(out: &DemoOutput) -> FrameIndex {
+        // Per-tick rows are not collected alongside projectiles, so teams,
+        // positions and the economy come from the player props attached to events
+        let mut frames = out.player_frames();
+        frames.extend(out.event_player_frames());
+        FrameIndex::new(frames)
+    }
+
+    /// Labelled win probability training samples from one demo file
+    pub async fn win_probability_samples(path: &Path) -> Result<Vec<WinProbabilitySample>> {
+        let bytes = tokio::fs::read(path)
+            .await
+            .map_err(|e| anyhow!("read demo {}: {e}", path.display()))?;
+        let inputs = Self::build_parser_inputs(&bytes);
+        let mut parser = Parser::new(inputs, ParsingMode::ForceMultiThreaded);
+        let out = parser
+            .parse_demo(&bytes)
+            .map_err(|e| anyhow!("parse failure: {e:?}"))?;
+
+        let (rounds, states) = Self::round_states(&out);
+        Ok(RoundStateBuilder::with_clock(&out.match_clock()).samples(&rounds, &states))
+    }
+
     async fn ingest_player_snapshots(
         db: &Arc<DatabaseManager>,
         config: &PipelineConfig,
@@ -320,15 +423,79 @@ impl DemoProcessor {
     }
 
     // v4 moment detection (uses serde_json view of GameEvent)
-    async fn detect_key_moments(match_data: &Match, out: &DemoOutput) -> Result<Vec<KeyMoment>> {
-        const TICK_RATE: u32 = 64;
-        const TRADE_WINDOW_TICKS: u32 = 5 * TICK_RATE;
-        const EXECUTE_CLUSTER_WINDOW: u32 = 10 * TICK_RATE;
-        const MOMENT_PAD_BEFORE: u32 = 2 * TICK_RATE;
-        const MOMENT_PAD_AFTER: u32 = 5 * TICK_RATE;
+    //
+    // Importance is the swing in CT win probability over the moment
+    async fn detect_key_moments(
+        match_data: &Match,
+        out: &DemoOutput,
+        win_probability: &WinProbabilityTimeline,
+    ) -> Result<Vec<KeyMoment>> {
+        let clock = out.match_clock();
+        let execute_cluster_window = clock.seconds_to_ticks(10.0);
+        let moment_pad_before = clock.seconds_to_ticks(2.0);
+        let moment_pad_after = clock.seconds_to_ticks(5.0);
 
         let mut moments: Vec<KeyMoment> = Vec::new();
 
+        // Trades come from the event-driven trade engine
+        let trades = TradeAnalyzer::with_clock(&clock).analyze(
+            &out.death_events(),
+            &out.hurt_events(),
+            &Self::merged_frames(out),
+        );
+        for death in trades.deaths.iter().filter(|d| d.is_traded()) {
+            let (Some(killer), Some(trader), Some(trade_tick), Some(secs)) = (
+                death.killer,
+                death.traded_by,
+                death.trade_tick,
+                death.trade_time_secs,
+            ) else {
+                continue;
+            };
+            moments.push(KeyMoment {
+                id: Uuid::now_v7(),
+                match_id: match_data.id,
+                moment_type: KeyMomentType::ImportantDuel,
+                start_tick: death.tick.saturating_sub(moment_pad_before),
+                end_tick: trade_tick + moment_pad_after,
+                players_involved: vec![trader as i64, killer as i64, death.victim as i64],
+                outcome: format!(
+                    "Trade: {} refragged {} {:.1}s after {} died",
+                    trader, killer, secs, death.victim
+                ),
+                importance_score: win_probability.swing(death.tick.saturating_sub(1), trade_tick),
+                created_at: Utc::now(),
+            });
+        }
+
+        // Clutches come from the round rosters and kill events
+        let clutches = ClutchAnalyzer::with_clock(&clock).analyze(
+            &out.rounds(),
+            &out.death_events(),
+            &out.bomb_events(),
+            &Self::merged_frames(out),
+        );
+        for clutch in &clutches.clutches {
+            moments.push(KeyMoment {
+                id: Uuid::now_v7(),
+                match_id: match_data.id,
+                moment_type: KeyMomentType::Clutch,
+                start_tick: clutch.start_tick.saturating_sub(moment_pad_before),
+                end_tick: clutch.end_tick,
+                players_involved: vec![clutch.steamid as i64],
+                outcome: format!(
+                    "1v{} clutch {} by {} ({} kills)",
+                    clutch.opponents,
+                    if clutch.won { "won" } else { "lost" },
+                    clutch.steamid,
+                    clutch.kills
+                ),
+                importance_score: win_probability
+                    .swing(clutch.start_tick.saturating_sub(1), clutch.end_tick),
+                created_at: Utc::now(),
+            });
+        }
+
         // Round trackers
         #[allow(unused_variables)]
         let mut round_number: i32 = 0;
@@ -336,18 +503,10 @@ impl DemoProcessor {
         let mut round_first_blood_done = false;
         let mut kills_this_round: HashMap<i64, u32> = HashMap::new();
 
-        let mut alive_t: i32 = 5;
-        let mut alive_ct: i32 = 5;
-
-        let mut last_deaths_window: VecDeque<(u32, i64, i64)> = VecDeque::new();
-
         let mut current_plant_tick: Option<u32> = None;
         let mut postplant_ct_kills: Vec<(u32, i64, i64)> = Vec::new();
         let mut preplant_t_kills: Vec<(u32, i64, i64)> = Vec::new();
 
-        let mut min_alive_t = 5;
-        let mut min_alive_ct = 5;
-
         for ev in &out.game_events {
             let tick = ev.tick as u32;
             match ev.name.as_str() {
@@ -356,14 +515,9 @@ impl DemoProcessor {
                     round_start_tick = tick;
                     round_first_blood_done = false;
                     kills_this_round.clear();
-                    last_deaths_window.clear();
                     current_plant_tick = None;
                     postplant_ct_kills.clear();
                     preplant_t_kills.clear();
-                    alive_t = 5;
-                    alive_ct = 5;
-                    min_alive_t = 5;
-                    min_alive_ct = 5;
                 }
                 "player_death" => {
                     let obj = serde_json::to_value(ev).unwrap_or(JsonValue::Null);
@@ -374,24 +528,14 @@ impl DemoProcessor {
                         get_i64(&obj, &["attacker", "killer", "attacker_steamid"]).unwrap_or(-1);
                     let headshot = get_bool(&obj, &["headshot"]).unwrap_or(false);
                     let weapon = get_str(&obj, &["weapon"]);
-                    let victim_team = get_str(&obj, &["userteam", "victimteam", "victim_team"]);
                     let killer_team =
                         get_str(&obj, &["attackerteam", "killerteam", "attacker_team"]);
 
-                    match victim_team.as_deref() {
-                        Some("T") | Some("t") => alive_t = (alive_t - 1).max(0),
-                        Some("CT") | Some("ct") => alive_ct = (alive_ct - 1).max(0),
-                        _ => {}
-                    }
-                    min_alive_t = min_alive_t.min(alive_t);
-                    min_alive_ct = min_alive_ct.min(alive_ct);
-
                     // Opening duel
                     if !round_first_blood_done && killer != -1 && victim != -1 {
                         round_first_blood_done = true;
-                        let start_tick = tick.saturating_sub(MOMENT_PAD_BEFORE);
-                        let end_tick = tick + MOMENT_PAD_AFTER;
-                        let diff_before = (alive_t - alive_ct).abs();
+                        let start_tick = tick.saturating_sub(moment_pad_before);
+                        let end_tick = tick + moment_pad_after;
                         moments.push(KeyMoment {
                             id: Uuid::now_v7(),
                             match_id: match_data.id,
@@ -409,7 +553,7 @@ impl DemoProcessor {
                                     .map(|w| format!(" with {}", w))
                                     .unwrap_or_default()
                             ),
-                            importance_score: 0.6 + (diff_before as f32 * 0.05),
+                            importance_score: win_probability.swing(tick.saturating_sub(1), tick),
                             created_at: Utc::now(),
                         });
                     }
@@ -445,10 +589,10 @@ impl DemoProcessor {
                                 match_id: match_data.id,
                                 moment_type: KeyMomentType::Ace,
                                 start_tick: round_start_tick,
-                                end_tick: tick + MOMENT_PAD_AFTER,
+                                end_tick: tick + moment_pad_after,
                                 players_involved: vec![killer],
                                 outcome: format!("Ace by {}", killer),
-                                importance_score: 0.9,
+                                importance_score: win_probability.swing(round_start_tick, tick),
                                 created_at: Utc::now(),
                             });
                         } else if *cnt >= 2 {
@@ -456,58 +600,19 @@ impl DemoProcessor {
                                 id: Uuid::now_v7(),
                                 match_id: match_data.id,
                                 moment_type: KeyMomentType::ImportantDuel,
-                                start_tick: tick.saturating_sub(MOMENT_PAD_BEFORE),
-                                end_tick: tick + MOMENT_PAD_AFTER,
+                                start_tick: tick.saturating_sub(moment_pad_before),
+                                end_tick: tick + moment_pad_after,
                                 players_involved: vec![killer, victim],
                                 outcome: format!(
                                     "{}-kill streak updated ({} -> {})",
                                     *cnt, killer, victim
                                 ),
-                                importance_score: 0.55 + (*cnt as f32 * 0.07),
+                                importance_score: win_probability
+                                    .swing(tick.saturating_sub(1), tick),
                                 created_at: Utc::now(),
                             });
                         }
                     }
-
-                    // Trade detection
-                    last_deaths_window.push_back((tick, victim, killer));
-                    while let Some((t0, _, _)) = last_deaths_window.front().copied() {
-                        if tick.saturating_sub(t0) > TRADE_WINDOW_TICKS {
-                            last_deaths_window.pop_front();
-                        } else {
-                            break;
-                        }
-                    }
-                    if killer != -1 && victim != -1 {
-                        for (t0, prev_victim, prev_killer) in last_deaths_window.iter().copied() {
-                            if prev_killer == victim
-                                && tick.saturating_sub(t0) <= TRADE_WINDOW_TICKS
-                            {
-                                let start_tick = tick.saturating_sub(MOMENT_PAD_BEFORE);
-                                let end_tick = tick + MOMENT_PAD_AFTER;
-                                moments.push(KeyMoment {
-                                    id: Uuid::now_v7(),
-                                    match_id: match_data.id,
-                                    moment_type: KeyMomentType::ImportantDuel,
-                                    start_tick,
-                                    end_tick,
-                                    players_involved: vec![
-                                        killer,
-                                        victim,
-                                        prev_killer,
-                                        prev_victim,
-                                    ],
-                                    outcome: format!(
-                                        "Trade: {} traded {} (prev killer {})",
-                                        killer, victim, prev_killer
-                                    ),
-                                    importance_score: 0.65,
-                                    created_at: Utc::now(),
-                                });
-                                break;
-                            }
-                        }
-                    }
                 }
                 "bomb_planted" => {
                     current_plant_tick = Some(tick);
@@ -515,8 +620,8 @@ impl DemoProcessor {
                 "bomb_defused" => {
                     if let Some(plant_tick) = current_plant_tick {
                         if postplant_ct_kills.len() >= 2 {
-                            let start_tick = plant_tick.saturating_sub(MOMENT_PAD_BEFORE);
-                            let end_tick = tick + MOMENT_PAD_AFTER;
+                            let start_tick = plant_tick.saturating_sub(moment_pad_before);
+                            let end_tick = tick + moment_pad_after;
                             let mut players: HashSet<i64> = HashSet::new();
                             for (_, k, v) in &postplant_ct_kills {
                                 if *k != -1 {
@@ -534,7 +639,8 @@ impl DemoProcessor {
                                 end_tick,
                                 players_involved: players.into_iter().collect(),
                                 outcome: "CT retake with defuse".to_string(),
-                                importance_score: 0.8,
+                                importance_score: win_probability
+                                    .swing(plant_tick.saturating_sub(1), tick),
                                 created_at: Utc::now(),
                             });
                         }
@@ -545,15 +651,16 @@ impl DemoProcessor {
                         let cluster: Vec<_> = preplant_t_kills
                             .iter()
                             .filter(|(t, _, _)| {
-                                *t + EXECUTE_CLUSTER_WINDOW >= plant_tick && *t <= plant_tick
+                                *t + execute_cluster_window >= plant_tick && *t <= plant_tick
                             })
                             .cloned()
                             .collect();
                         if cluster.len() >= 2 {
+                            let cluster_start = cluster[0].0;
                             let start_tick = plant_tick
-                                .saturating_sub(EXECUTE_CLUSTER_WINDOW)
-                                .saturating_sub(MOMENT_PAD_BEFORE);
-                            let end_tick = tick + MOMENT_PAD_AFTER;
+                                .saturating_sub(execute_cluster_window)
+                                .saturating_sub(moment_pad_before);
+                            let end_tick = tick + moment_pad_after;
                             let mut players: HashSet<i64> = HashSet::new();
                             for (_, k, v) in &cluster {
                                 if *k != -1 {
@@ -571,37 +678,13 @@ impl DemoProcessor {
                                 end_tick,
                                 players_involved: players.into_iter().collect(),
                                 outcome: "T execute leading to bomb explosion".to_string(),
-                                importance_score: 0.75,
+                                importance_score: win_probability
+                                    .swing(cluster_start.saturating_sub(1), tick),
                                 created_at: Utc::now(),
                             });
                         }
                     }
                 }
-                "round_end" => {
-                    // Clutch heuristic
-                    if (min_alive_t == 1 && alive_ct >= 2) || (min_alive_ct == 1 && alive_t >= 2) {
-                        if let Some((&player, &count)) =
-                            kills_this_round.iter().max_by_key(|(_, v)| *v)
-                        {
-                            if count >= 3 {
-                                moments.push(KeyMoment {
-                                    id: Uuid::now_v7(),
-                                    match_id: match_data.id,
-                                    moment_type: KeyMomentType::Clutch,
-                                    start_tick: round_start_tick,
-                                    end_tick: tick,
-                                    players_involved: vec![player],
-                                    outcome: format!(
-                                        "Potential clutch by {} ({} kills)",
-                                        player, count
-                                    ),
-                                    importance_score: 0.85_f32.min(0.55 + count as f32 * 0.1),
-                                    created_at: Utc::now(),
-                                });
-                            }
-                        }
-                    }
-                }
                 _ => {}
             }
         }
@@ -680,7 +763,7 @@ impl DemoProcessor {
         }
 
         // Behavior extraction per protagonist per moment
-        const TICK_RATE: f32 = 64.0;
+        let clock = out.match_clock();
         let grenade_names: [&str; 6] = [
             "hegrenade",
             "flashbang",
@@ -765,8 +848,7 @@ impl DemoProcessor {
                 }
                 let avg_speed = speed_sum / (window.len() as f32);
 
-                let duration_ticks = (m.end_tick - m.start_tick).max(1) as f32;
-                let duration_seconds = duration_ticks / TICK_RATE;
+                let duration_seconds = clock.ticks_to_seconds((m.end_tick - m.start_tick).max(1));
 
                 // Event-derived stats in the window
                 let shots: Vec<_> = fires
@@ -856,9 +938,9 @@ impl DemoProcessor {
                     "path_length": path_len,
                     "avg_speed": avg_speed,
                     "max_speed": max_speed,
-                    "time_scoped_seconds": time_scoped_ticks as f32 / TICK_RATE,
-                    "time_walking_seconds": time_walking_ticks as f32 / TICK_RATE,
-                    "time_airborne_seconds": time_air_ticks as f32 / TICK_RATE,
+                    "time_scoped_seconds": clock.ticks_to_seconds(time_scoped_ticks),
+                    "time_walking_seconds": clock.ticks_to_seconds(time_walking_ticks),
+                    "time_airborne_seconds": clock.ticks_to_seconds(time_air_ticks),
                     "flash_exposure_total": flash_sum,
                     "yaw_mean_abs_delta": yaw_delta_sum / (window.len().max(1) as f32),
                     "pitch_mean_abs_delta": pitch_delta_sum / (window.len().max(1) as f32),
@@ -961,3 +1043,21 @@ fn get_bool(obj: &JsonValue, keys: &[&str]) -> Option<bool> {
     }
     None
 }
+
+/// All `.dem` files below `dir`
+pub fn discover_demo_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    use walkdir::WalkDir;
+    let mut out = Vec::new();
+    for entry in WalkDir::new(dir) {
+        let e = entry?;
+        if e.path()
+            .extension()
+            .and_then(|s| s.to_str())
+            .map(|ext| ext.eq_ignore_ascii_case("dem"))
+            .unwrap_or(false)
+        {
+            out.push(e.path().to_path_buf());
+        }
+    }
+    Ok(out)
+}
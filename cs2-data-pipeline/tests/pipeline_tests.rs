@@ -26,6 +26,7 @@ mod pipeline_tests {
             temp_directory: temp_demo_dir,
             enable_ai_analysis: false, // Disable for testing
             chunk_size_ticks: 64 * 10, // 10 seconds
+            win_probability_model: None,
         };
 
         // Use test database URLs
@@ -1,9 +1,13 @@
 use anyhow::Result;
 use clap::{Parser, Subcommand};
 use cs2_common::{
-    BehavioralVector, DecisionMetricsExtractor, ExtractedFeatures, PlayerMechanicsExtractor,
-    TeamDynamicsExtractor, TemporalContextExtractor,
+    BehavioralVector, ClutchAnalyzer, ClutchReport, CrosshairPlacementExtractor,
+    DecisionMetricsExtractor, ExtractedFeatures, FlashEffectivenessExtractor, FlashReport,
+    FrameIndex, MatchClock, PlacementProfile, PlayerMechanicsExtractor, ReactionProfile,
+    TeamDynamicsExtractor, TemporalContextExtractor, TradeAnalyzer, TradeReport,
+    VisualReactionExtractor,
 };
+use cs2_demo_parser::parse_demo::DemoOutput;
 use cs2_ml::{DecisionQualityRNN, PlayerStyleClassifier, TeamDynamicsTransformer};
 use plotters::prelude::*;
 use polars::prelude::*;
@@ -101,8 +105,27 @@ fn main() -> Result<()> {
             );
 
             // Parse the demo and extract behavioral vectors
-            let vectors = cs2_ml::data::vectors_from_demo(&demo)?;
-            info!("Extracted {} behavioral vectors", vectors.len());
+            let parsed = cs2_ml::data::parse_demo(&demo)?;
+            let projectiles = cs2_ml::data::parse_projectiles(&demo)?;
+            let vectors = cs2_ml::data::vectors_from_output(&parsed)?;
+            let clock = parsed.match_clock();
+            info!(
+                "Extracted {} behavioral vectors ({} tick)",
+                vectors.len(),
+                clock.tick_rate
+            );
+
+            let Measurements {
+                clutches,
+                reactions,
+                placements,
+                flashes,
+                trades,
+            } = Measurements::from_demo(&parsed, &projectiles, &clock);
+            info!("Found {} clutch situations", clutches.clutches.len());
+            info!("Measured reactions for {} players", reactions.len());
+            info!("Scored {} flashbangs", flashes.flashes.len());
+            info!("Resolved trades for {} deaths", trades.deaths.len());
 
             // Group vectors by player
             let mut player_vectors: HashMap<u64, Vec<BehavioralVector>> = HashMap::new();
@@ -116,10 +139,15 @@ fn main() -> Result<()> {
             info!("Found {} unique players", player_vectors.len());
 
             // Initialize comprehensive feature extractors
-            let mechanics_extractor = PlayerMechanicsExtractor::new();
-            let team_extractor = TeamDynamicsExtractor::new();
-            let decision_extractor = DecisionMetricsExtractor::new();
-            let temporal_extractor = TemporalContextExtractor::new();
+            let mechanics_extractor =
+                PlayerMechanicsExtractor::new().with_placement_profiles(placements);
+            let team_extractor = TeamDynamicsExtractor::with_clock(&clock)
+                .with_flash_stats(flashes.players)
+                .with_trade_stats(trades.players);
+            let decision_extractor =
+                DecisionMetricsExtractor::with_clock(&clock).with_reaction_profiles(reactions);
+            let temporal_extractor =
+                TemporalContextExtractor::with_clock(&clock).with_clutch_stats(clutches.players);
 
             // Extract comprehensive features for each player
             let mut all_extracted_features: HashMap<u64, ExtractedFeatures> = HashMap::new();
@@ -164,7 +192,8 @@ fn main() -> Result<()> {
             let all_vectors: Vec<BehavioralVector> =
                 player_vectors.values().flatten().cloned().collect();
             let parquet_path = output_dir.join("vectors.parquet");
-            cs2_ml::data::write_parquet(&all_vectors, &parquet_path)?;
+            let map = parsed.map_name().unwrap_or("unknown");
+            cs2_ml::data::write_match_parquet(&all_vectors, map, &parquet_path)?;
             info!("Wrote behavioral vectors to {}", parquet_path.display());
 
             // Generate comprehensive analysis using ML models
@@ -341,6 +370,47 @@ fn main() -> Result<()> {
     Ok(())
 }
 
+/// Stats measured from a demo's events and frames, in place of the extractors' estimates
+struct Measurements {
+    clutches: ClutchReport,                     // Clutch performance metric
+    reactions: HashMap<u64, ReactionProfile>,   // Visual reaction time
+    placements: HashMap<u64, PlacementProfile>, // Crosshair placement metrics
+    flashes: FlashReport,                       // Team flash effectiveness
+    trades: TradeReport,                        // Team trade efficiency
+}
+
+impl Measurements {
+    /// `projectiles` is the projectile pass over the same demo, see `data::parse_projectiles`
+    fn from_demo(parsed: &DemoOutput, projectiles: &DemoOutput, clock: &MatchClock) -> Self {
+        let mut frames = parsed.player_frames();
+        frames.extend(parsed.event_player_frames());
+        let frames = FrameIndex::new(frames);
+
+        Self {
+            clutches: ClutchAnalyzer::with_clock(clock).analyze(
+                &parsed.rounds(),
+                &parsed.death_events(),
+                &parsed.bomb_events(),
+                &frames,
+            ),
+            reactions: VisualReactionExtractor::with_clock(clock)
+                .extract_profiles(&frames, &parsed.shot_events()),
+            placements: CrosshairPlacementExtractor::new().extract_profiles(&frames),
+            flashes: FlashEffectivenessExtractor::with_clock(clock).extract(
+                &projectiles.grenade_detonations(),
+                &parsed.blind_events(),
+                &parsed.death_events(),
+                &frames,
+            ),
+            trades: TradeAnalyzer::with_clock(clock).analyze(
+                &parsed.death_events(),
+                &parsed.hurt_events(),
+                &frames,
+            ),
+        }
+    }
+}
+
 /// Generate basic statistics about the behavioral vectors
 fn generate_statistics(vectors: &[BehavioralVector], output_dir: &Path) -> Result<()> {
     // Extract player statistics
@@ -1109,3 +1179,283 @@ fn save_movement_predictions_csv(
     std::fs::write(csv_path, csv_content.join("\n"))?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ahash::AHashMap;
+    use cs2_common::player_frames::{TEAM_CT, TEAM_T};
+    use cs2_demo_parser::first_pass::prop_controller::{
+        PropController, PropInfo, ENTITY_ID_ID, GRENADE_TYPE_ID, PLAYER_X_ID, STEAMID_ID, TICK_ID,
+        WEAPON_NAME_ID, YAW_ID,
+    };
+    use cs2_demo_parser::second_pass::collect_data::PropType;
+    use cs2_demo_parser::second_pass::game_events::{EventField, GameEvent};
+    use cs2_demo_parser::second_pass::variants::{PropColumn, VarVec, Variant};
+    use cs2_ml::data;
+
+    /// One parsed player row: tick, steamid, team, X, yaw and who spotted the player
+    type Row = (u32, u64, u8, f32, f32, Vec<u64>);
+
+    fn column(data: VarVec) -> PropColumn {
+        PropColumn {
+            data: Some(data),
+            num_nones: 0,
+        }
+    }
+
+    /// Parser output holding only the player props `data::parse_demo` requests
+    fn demo(rows: &[Row], game_events: Vec<GameEvent>) -> DemoOutput {
+        let requested = data::player_props();
+        let mut prop_controller = PropController::new(
+            requested.clone(),
+            vec![],
+            AHashMap::default(),
+            AHashMap::default(),
+            false,
+            &[],
+            false,
+        );
+        let mut df = AHashMap::default();
+        df.insert(
+            TICK_ID,
+            column(VarVec::I32(rows.iter().map(|r| Some(r.0 as i32)).collect())),
+        );
+        df.insert(
+            STEAMID_ID,
+            column(VarVec::U64(rows.iter().map(|r| Some(r.1)).collect())),
+        );
+
+        // Custom props get ids of their own, like the parser's prop controller hands out
+        let f32s = |value: fn(&Row) -> f32| {
+            column(VarVec::F32(rows.iter().map(|r| Some(value(r))).collect()))
+        };
+        let props = [
+            ("X", PLAYER_X_ID, f32s(|r| r.3)),
+            ("yaw", YAW_ID, f32s(|r| r.4)),
+            (
+                "weapon_name",
+                WEAPON_NAME_ID,
+                column(VarVec::String(
+                    rows.iter().map(|_| Some("ak47".to_string())).collect(),
+                )),
+            ),
+            (
+                "CCSPlayerPawn.m_iTeamNum",
+                1,
+                column(VarVec::U32(rows.iter().map(|r| Some(r.2 as u32)).collect())),
+            ),
+            (
+                "CCSPlayerPawn.m_bSpottedByMask",
+                2,
+                column(VarVec::U64Vec(rows.iter().map(|r| r.5.clone()).collect())),
+            ),
+        ];
+        for (name, id, values) in props {
+            if !requested.iter().any(|p| p == name) {
+                continue;
+            }
+            prop_controller.prop_infos.push(PropInfo {
+                id,
+                prop_type: PropType::Player,
+                prop_name: name.to_string(),
+                prop_friendly_name: name.to_string(),
+                is_player_prop: true,
+            });
+            df.insert(id, values);
+        }
+
+        output(df, prop_controller, game_events)
+    }
+
+    /// Projectile pass with one record per (tick, entity id, thrower) of a flashbang in flight
+    fn flashbangs(records: &[(i32, i32, u64)]) -> DemoOutput {
+        let mut df = AHashMap::default();
+        df.insert(
+            TICK_ID,
+            column(VarVec::I32(records.iter().map(|r| Some(r.0)).collect())),
+        );
+        df.insert(
+            ENTITY_ID_ID,
+            column(VarVec::I32(records.iter().map(|r| Some(r.1)).collect())),
+        );
+        df.insert(
+            STEAMID_ID,
+            column(VarVec::U64(records.iter().map(|r| Some(r.2)).collect())),
+        );
+        df.insert(
+            GRENADE_TYPE_ID,
+            column(VarVec::String(
+                records
+                    .iter()
+                    .map(|_| Some("CFlashbangProjectile".to_string()))
+                    .collect(),
+            )),
+        );
+        let prop_controller = PropController::new(
+            vec![],
+            vec![],
+            AHashMap::default(),
+            AHashMap::default(),
+            false,
+            &[],
+            true,
+        );
+        output(df, prop_controller, vec![])
+    }
+
+    fn output(
+        df: AHashMap<u32, PropColumn>,
+        prop_controller: PropController,
+        game_events: Vec<GameEvent>,
+    ) -> DemoOutput {
+        DemoOutput {
+            df,
+            game_events,
+            skins: vec![],
+            item_drops: vec![],
+            chat_messages: vec![],
+            convars: AHashMap::default(),
+            header: None,
+            player_md: vec![],
+            game_events_counter: Default::default(),
+            uniq_prop_names: vec![],
+            projectiles: vec![],
+            voice_data: vec![],
+            prop_controller,
+            df_per_player: AHashMap::default(),
+        }
+    }
+
+    fn event(name: &str, tick: i32, fields: Vec<(&str, Variant)>) -> GameEvent {
+        GameEvent {
+            name: name.to_string(),
+            tick,
+            fields: fields
+                .into_iter()
+                .map(|(name, data)| EventField {
+                    name: name.to_string(),
+                    data: Some(data),
+                })
+                .collect(),
+        }
+    }
+
+    fn shot(tick: i32, steamid: u64) -> GameEvent {
+        event(
+            "weapon_fire",
+            tick,
+            vec![
+                ("user_steamid", Variant::U64(steamid)),
+                ("weapon", Variant::String("weapon_ak47".to_string())),
+            ],
+        )
+    }
+
+    /// CT 1 at the origin turns onto T 2 on +X after spotting them at tick 101
+    fn encounter() -> Vec<Row> {
+        let mut rows = Vec::new();
+        for (tick, yaw) in [
+            (100, 40.0),
+            (101, 40.0),
+            (104, 20.0),
+            (110, 1.0),
+            (120, 0.0),
+        ] {
+            let spotted_by = if tick >= 101 { vec![1] } else { vec![] };
+            rows.push((tick, 1, TEAM_CT, 0.0, yaw, vec![]));
+            rows.push((tick, 2, TEAM_T, 1000.0, 180.0, spotted_by));
+        }
+        rows
+    }
+
+    #[test]
+    fn test_reactions_measured_from_parsed_teams() {
+        // Fails when the parse leaves frames on team 0, where nobody is an enemy
+        let measured = Measurements::from_demo(
+            &demo(&encounter(), vec![shot(117, 1)]),
+            &flashbangs(&[]),
+            &MatchClock::default(),
+        );
+        assert_eq!(measured.reactions[&1].overall.encounters, 1);
+    }
+
+    #[test]
+    fn test_placements_measured_from_parsed_teams() {
+        // CT 1 was looking 40 degrees away from T 2 when spotting them
+        let measured = Measurements::from_demo(
+            &demo(&encounter(), vec![]),
+            &flashbangs(&[]),
+            &MatchClock::default(),
+        );
+        let placement = &measured.placements[&1].overall;
+        assert_eq!(placement.encounters, 1);
+        assert_eq!(placement.mean_yaw_error, 40.0);
+    }
+
+    #[test]
+    fn test_flashes_measured_from_projectile_pass() {
+        // CT 1's flashbang pops at tick 102 and blinds T 2 for two seconds
+        let blind = event(
+            "player_blind",
+            103,
+            vec![
+                ("user_steamid", Variant::U64(2)),
+                ("attacker_steamid", Variant::U64(1)),
+                ("blind_duration", Variant::F32(2.0)),
+                ("entityid", Variant::I32(50)),
+            ],
+        );
+        let parsed = demo(&encounter(), vec![blind]);
+        let projectiles = flashbangs(&[(100, 50, 1), (101, 50, 1), (102, 50, 1)]);
+
+        let flashes =
+            Measurements::from_demo(&parsed, &projectiles, &MatchClock::default()).flashes;
+        let stats = &flashes.players[&1];
+        assert_eq!(stats.flashes_thrown, 1);
+        assert_eq!(stats.enemies_blinded, 1);
+        assert_eq!(stats.enemy_blind_secs, 2.0);
+    }
+
+    #[test]
+    fn test_trades_measured_from_parsed_teams() {
+        // T 2 kills CT 1 and is refragged by CT 3, who stood 400 units away
+        let rows = vec![
+            (90, 1, TEAM_CT, 0.0, 0.0, vec![]),
+            (90, 3, TEAM_CT, 400.0, 0.0, vec![]),
+            (90, 2, TEAM_T, 1000.0, 180.0, vec![]),
+        ];
+        // Events carry the requested player props of their user and attacker
+        let kill =
+            |tick, (victim, victim_team): (u64, u8), (attacker, attacker_team): (u64, u8)| {
+                event(
+                    "player_death",
+                    tick,
+                    vec![
+                        ("user_steamid", Variant::U64(victim)),
+                        ("user_team_num", Variant::U32(victim_team as u32)),
+                        ("attacker_steamid", Variant::U64(attacker)),
+                        ("attacker_team_num", Variant::U32(attacker_team as u32)),
+                    ],
+                )
+            };
+        let parsed = demo(
+            &rows,
+            vec![
+                kill(100, (1, TEAM_CT), (2, TEAM_T)),
+                kill(150, (2, TEAM_T), (3, TEAM_CT)),
+            ],
+        );
+
+        let trades =
+            Measurements::from_demo(&parsed, &flashbangs(&[]), &MatchClock::default()).trades;
+        assert!(trades.deaths[0].is_traded());
+        assert_eq!(trades.players[&3].refrags, 1);
+
+        let team = HashMap::from([(1, vec![]), (3, vec![])]);
+        let features = TeamDynamicsExtractor::new()
+            .with_trade_stats(trades.players)
+            .extract_features(&team);
+        assert_eq!(features.trade_efficiency, 1.0);
+    }
+}
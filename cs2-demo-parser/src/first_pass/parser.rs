@@ -22,6 +22,7 @@ use csgoproto::CDemoFullPacket;
 use csgoproto::CDemoPacket;
 use csgoproto::CDemoSendTables;
 use csgoproto::CsvcMsgGameEventList;
+use csgoproto::CsvcMsgServerInfo;
 use csgoproto::EDemoCommands;
 use prost::Message;
 use snap::raw::decompress_len;
@@ -293,12 +294,24 @@ impl<'a> FirstPassParser<'a> {
                 svc_CreateStringTable => self.parse_create_stringtable(&msg_bytes),
                 svc_UpdateStringTable => self.update_string_table(&msg_bytes),
                 svc_ClearAllStringTables => self.clear_stringtables(),
+                svc_ServerInfo => self.parse_server_info(&msg_bytes),
                 _ => Ok(()),
             };
             ok?
         }
         Ok(())
     }
+    fn parse_server_info(&mut self, bytes: &[u8]) -> Result<(), DemoParserError> {
+        let server_info = match CsvcMsgServerInfo::decode(bytes) {
+            Ok(info) => info,
+            Err(_) => return Err(DemoParserError::MalformedMessage),
+        };
+        // Lets consumers derive the tick rate instead of assuming 64
+        if let Some(tick_interval) = server_info.tick_interval {
+            self.header.insert("tick_interval".to_string(), tick_interval.to_string());
+        }
+        Ok(())
+    }
     fn clear_stringtables(&mut self) -> Result<(), DemoParserError> {
         self.string_tables = vec![];
         Ok(())
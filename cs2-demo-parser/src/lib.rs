@@ -4,3 +4,4 @@ pub mod first_pass;
 pub mod maps;
 pub mod parse_demo;
 pub mod second_pass;
+pub mod typed;
@@ -12,6 +12,7 @@ use crate::second_pass::variants::VarVec;
 use crate::second_pass::variants::{PropColumn, Variant};
 use ahash::AHashMap;
 use ahash::AHashSet;
+use cs2_common::MatchClock;
 use csgoproto::CsvcMsgVoiceData;
 use itertools::Itertools;
 use rayon::iter::IntoParallelRefIterator;
@@ -40,6 +41,20 @@ pub struct DemoOutput {
     pub df_per_player: AHashMap<u64, AHashMap<u32, PropColumn>>,
 }
 
+impl DemoOutput {
+    /// Match timing from the header tick interval and the server convars
+    ///
+    /// Convars set mid-demo are looked up by name; the last value sent wins.
+    pub fn match_clock(&self) -> MatchClock {
+        MatchClock::from_demo_metadata(self.header.as_deref(), &self.convars)
+    }
+
+    /// Map the demo was recorded on, from the header
+    pub fn map_name(&self) -> Option<&str> {
+        self.header.as_ref()?.get("map_name").map(String::as_str)
+    }
+}
+
 pub struct Parser<'a> {
     input: ParserInputs<'a>,
     pub parsing_mode: ParsingMode,
@@ -418,13 +418,19 @@ impl<'a> SecondPassParser<'a> {
 
     pub fn create_custom_event_parse_convars(&mut self, bytes: &[u8]) -> Result<(), DemoParserError> {
         self.game_events_counter.insert("server_cvar".to_string());
-        if !self.wanted_events.contains(&"server_cvar".to_string()) && self.wanted_events.first() != Some(&"all".to_string()) {
-            return Ok(());
-        }
         let convar = match CnetMsgSetConVar::decode(bytes) {
             Ok(m) => m,
             Err(_e) => return Err(DemoParserError::MalformedMessage),
         };
+        // Keep the latest value of every convar by name, whether or not the event is wanted
+        if let Some(convars) = &convar.convars {
+            for var in &convars.cvars {
+                self.convars.insert(var.name().to_string(), var.value().to_string());
+            }
+        }
+        if !self.wanted_events.contains(&"server_cvar".to_string()) && self.wanted_events.first() != Some(&"all".to_string()) {
+            return Ok(());
+        }
         if let Some(convars) = &convar.convars {
             let mut fields = vec![];
             for var in &convars.cvars {
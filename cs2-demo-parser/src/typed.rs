@@ -0,0 +1,521 @@
+//! Typed views of a parsed demo for the cs2-common extractors.
+//!
+//! The parser output is column oriented (`df`) and events carry loosely typed fields. The
+//! helpers here turn them into the plain structs the feature extractors work on.
+use crate::first_pass::prop_controller::{
+    ENTITY_ID_ID, GRENADE_TYPE_ID, GRENADE_X, GRENADE_Y, GRENADE_Z, IS_ALIVE_ID, PITCH_ID, PLAYER_X_ID, PLAYER_Y_ID, PLAYER_Z_ID, STEAMID_ID, TICK_ID,
+    WEAPON_NAME_ID, YAW_ID,
+};
+use crate::parse_demo::DemoOutput;
+use crate::second_pass::game_events::GameEvent;
+use crate::second_pass::variants::{PropColumn, VarVec, Variant};
+use ahash::AHashMap;
+use cs2_common::match_events::{BlindEvent, BombAction, BombEvent, BombSite, DeathEvent, GrenadeDetonation, HurtEvent, RoundInfo, ShotEvent};
+use cs2_common::player_frames::{PlayerFrame, TEAM_CT, TEAM_T};
+
+const TEAM_NUM_PROP: &str = "CCSPlayerPawn.m_iTeamNum";
+const DUCKING_PROP: &str = "CCSPlayerPawn.CCSPlayer_MovementServices.m_bDucking";
+const PLACE_PROP: &str = "CCSPlayerPawn.m_szLastPlaceName";
+const SPOTTED_BY_PROP: &str = "CCSPlayerPawn.m_bSpottedByMask";
+const FLASH_DURATION_PROP: &str = "CCSPlayerPawn.m_flFlashDuration";
+const HEALTH_PROP: &str = "CCSPlayerPawn.m_iHealth";
+const ARMOR_PROP: &str = "CCSPlayerPawn.m_ArmorValue";
+const EQUIPMENT_VALUE_PROP: &str = "CCSPlayerPawn.m_unCurrentEquipmentValue";
+const DEFUSER_PROP: &str = "CCSPlayerPawn.CCSPlayer_ItemServices.m_bHasDefuser";
+
+/// Every requested prop of one player on one parsed tick
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayerPropRow {
+    pub tick: u32,
+    pub steamid: u64,
+    pub props: AHashMap<String, Variant>, // Keyed by the name the prop was requested with
+}
+
+impl DemoOutput {
+    /// One frame per player and parsed tick, sorted by tick
+    ///
+    /// Props that were not requested from the parser are left at their defaults.
+    pub fn player_frames(&self) -> Vec<PlayerFrame> {
+        let mut frames = Vec::new();
+        if !self.df.is_empty() {
+            self.collect_frames(&self.df, &mut frames);
+        }
+        for df in self.df_per_player.values() {
+            self.collect_frames(df, &mut frames);
+        }
+        frames.sort_by_key(|f| (f.tick, f.steamid));
+        frames
+    }
+
+    /// Raw prop values of every player and parsed tick, sorted by tick
+    pub fn player_prop_rows(&self) -> Vec<PlayerPropRow> {
+        let mut rows = Vec::new();
+        if !self.df.is_empty() {
+            self.collect_prop_rows(&self.df, &mut rows);
+        }
+        for df in self.df_per_player.values() {
+            self.collect_prop_rows(df, &mut rows);
+        }
+        rows.sort_by_key(|r| (r.tick, r.steamid));
+        rows
+    }
+
+    /// All `weapon_fire` events
+    pub fn shot_events(&self) -> Vec<ShotEvent> {
+        self.events_named("weapon_fire")
+            .filter_map(|event| {
+                Some(ShotEvent {
+                    tick: event.tick.max(0) as u32,
+                    steamid: event_steamid(event, "user_steamid")?,
+                    weapon: event_string(event, "weapon").unwrap_or_default(),
+                })
+            })
+            .collect()
+    }
+
+    /// Rounds delimited by `round_start` and `round_end`
+    ///
+    /// A round without a `round_start` (e.g. the first one of a demo that
+    /// starts mid-round) begins right after the previous round ended.
+    pub fn rounds(&self) -> Vec<RoundInfo> {
+        let mut rounds = Vec::new();
+        let mut start_tick = 0;
+        for event in &self.game_events {
+            let tick = event.tick.max(0) as u32;
+            match event.name.as_str() {
+                "round_start" => start_tick = tick,
+                "round_end" => {
+                    let winner_team = match event_string(event, "winner").as_deref() {
+                        Some("T") => TEAM_T,
+                        Some("CT") => TEAM_CT,
+                        _ => 0,
+                    };
+                    rounds.push(RoundInfo {
+                        number: event_field(event, "round").and_then(as_u64).map_or(rounds.len() as u32 + 1, |r| r as u32),
+                        start_tick,
+                        end_tick: tick,
+                        winner_team,
+                        reason: event_string(event, "reason").unwrap_or_default(),
+                    });
+                    start_tick = tick + 1;
+                }
+                _ => {}
+            }
+        }
+        rounds
+    }
+
+    /// Latest known name of every player appearing in an event
+    pub fn player_names(&self) -> AHashMap<u64, String> {
+        let mut names = AHashMap::default();
+        for event in &self.game_events {
+            for prefix in ["user", "attacker", "assister"] {
+                let (Some(steamid), Some(name)) = (
+                    event_steamid(event, &format!("{prefix}_steamid")),
+                    event_string(event, &format!("{prefix}_name")),
+                ) else {
+                    continue;
+                };
+                names.insert(steamid, name);
+            }
+        }
+        names
+    }
+
+    /// All `player_blind` events
+    pub fn blind_events(&self) -> Vec<BlindEvent> {
+        self.events_named("player_blind")
+            .filter_map(|event| {
+                Some(BlindEvent {
+                    tick: event.tick.max(0) as u32,
+                    victim: event_steamid(event, "user_steamid")?,
+                    attacker: event_steamid(event, "attacker_steamid"),
+                    victim_team: event_team(event, "user"),
+                    blind_duration: event_field(event, "blind_duration").and_then(as_f32).unwrap_or(0.0),
+                    entity_id: event_field(event, "entityid").and_then(as_f32).map(|id| id as i32),
+                })
+            })
+            .collect()
+    }
+
+    /// Detonation of every grenade projectile, taken from its last projectile record
+    ///
+    /// Needs a parse with `parse_projectiles` enabled.
+    pub fn grenade_detonations(&self) -> Vec<GrenadeDetonation> {
+        let mut records = Vec::new();
+        for df in std::iter::once(&self.df).chain(self.df_per_player.values()) {
+            let column = |id: u32| df.get(&id).and_then(|c| c.data.as_ref());
+            let (Some(types), Some(ticks), Some(entities), Some(throwers)) =
+                (column(GRENADE_TYPE_ID), column(TICK_ID), column(ENTITY_ID_ID), column(STEAMID_ID))
+            else {
+                continue;
+            };
+            let f32_at = |id: u32, row: usize| column(id).and_then(|c| value_at(c, row)).and_then(|v| as_f32(&v));
+
+            for row in 0..var_vec_len(types) {
+                let (Some(grenade_type), Some(tick), Some(entity_id), Some(thrower)) = (
+                    value_at(types, row).and_then(as_string),
+                    value_at(ticks, row).and_then(|v| as_f32(&v)),
+                    value_at(entities, row).and_then(|v| as_f32(&v)),
+                    value_at(throwers, row).and_then(|v| as_u64(&v)),
+                ) else {
+                    continue;
+                };
+                records.push(GrenadeDetonation {
+                    tick: tick.max(0.0) as u32,
+                    entity_id: entity_id as i32,
+                    thrower,
+                    grenade_type,
+                    pos_x: f32_at(GRENADE_X, row).unwrap_or(0.0),
+                    pos_y: f32_at(GRENADE_Y, row).unwrap_or(0.0),
+                    pos_z: f32_at(GRENADE_Z, row).unwrap_or(0.0),
+                });
+            }
+        }
+
+        // Entity ids are reused, so a grenade is a run of records without gaps in ticks
+        records.sort_by_key(|r| (r.entity_id, r.tick));
+        let mut detonations: Vec<GrenadeDetonation> = Vec::new();
+        for (i, record) in records.iter().enumerate() {
+            let ends_here = records
+                .get(i + 1)
+                .is_none_or(|next| next.entity_id != record.entity_id || next.grenade_type != record.grenade_type || next.tick > record.tick + 1);
+            if ends_here {
+                detonations.push(record.clone());
+            }
+        }
+        detonations.sort_by_key(|d| (d.tick, d.entity_id));
+        detonations
+    }
+
+    /// All `player_death` events
+    ///
+    /// Teams are only filled in when `team_num` was requested as a player prop.
+    pub fn death_events(&self) -> Vec<DeathEvent> {
+        self.events_named("player_death")
+            .filter_map(|event| {
+                Some(DeathEvent {
+                    tick: event.tick.max(0) as u32,
+                    victim: event_steamid(event, "user_steamid")?,
+                    attacker: event_steamid(event, "attacker_steamid"),
+                    assister: event_steamid(event, "assister_steamid"),
+                    victim_team: event_team(event, "user"),
+                    attacker_team: event_team(event, "attacker"),
+                    weapon: event_string(event, "weapon").unwrap_or_default(),
+                    headshot: event_bool(event, "headshot"),
+                    flash_assist: event_bool(event, "assistedflash"),
+                })
+            })
+            .collect()
+    }
+
+    /// All `player_hurt` events
+    pub fn hurt_events(&self) -> Vec<HurtEvent> {
+        self.events_named("player_hurt")
+            .filter_map(|event| {
+                let number = |name: &str| event_field(event, name).and_then(as_f32).unwrap_or(0.0).max(0.0) as u32;
+                Some(HurtEvent {
+                    tick: event.tick.max(0) as u32,
+                    victim: event_steamid(event, "user_steamid")?,
+                    attacker: event_steamid(event, "attacker_steamid"),
+                    health_damage: number("dmg_health"),
+                    armor_damage: number("dmg_armor"),
+                    remaining_health: number("health"),
+                    weapon: event_string(event, "weapon").unwrap_or_default(),
+                })
+            })
+            .collect()
+    }
+
+    /// All `bomb_planted`, `bomb_defused` and `bomb_exploded` events
+    ///
+    /// The site is read from the planter's `last_place_name` prop when it was
+    /// requested; defuses and detonations inherit the site of the last plant.
+    pub fn bomb_events(&self) -> Vec<BombEvent> {
+        let mut bomb_events = Vec::new();
+        let mut site = BombSite::Unknown;
+        for event in &self.game_events {
+            let action = match event.name.as_str() {
+                "bomb_planted" => BombAction::Planted,
+                "bomb_defused" => BombAction::Defused,
+                "bomb_exploded" => BombAction::Exploded,
+                _ => continue,
+            };
+            if action == BombAction::Planted {
+                site = event_string(event, "user_last_place_name").map_or(BombSite::Unknown, |place| BombSite::from_place(&place));
+            }
+            bomb_events.push(BombEvent {
+                tick: event.tick.max(0) as u32,
+                action,
+                steamid: event_steamid(event, "user_steamid"),
+                site,
+            });
+        }
+        bomb_events
+    }
+
+    /// Partial frames of the players attached to events, sorted by tick
+    ///
+    /// Events carry the requested player props of their `user` and `attacker`,
+    /// so this gives state snapshots even when the parse did not collect
+    /// per-tick player rows. Props missing from an event are left at their defaults.
+    pub fn event_player_frames(&self) -> Vec<PlayerFrame> {
+        let mut frames = Vec::new();
+        for event in &self.game_events {
+            for prefix in ["user", "attacker"] {
+                let Some(steamid) = event_steamid(event, &format!("{prefix}_steamid")) else {
+                    continue;
+                };
+                let field = |name: &str| event_field(event, &format!("{prefix}_{name}"));
+                let number = |name: &str| field(name).and_then(as_f32);
+                let health = number("health").unwrap_or(0.0).max(0.0) as u32;
+                frames.push(PlayerFrame {
+                    tick: event.tick.max(0) as u32,
+                    steamid,
+                    team_num: event_team(event, prefix),
+                    is_alive: field("is_alive").and_then(as_bool).unwrap_or(health > 0),
+                    health,
+                    armor: number("armor_value").unwrap_or(0.0).max(0.0) as u32,
+                    equipment_value: number("current_equip_value").unwrap_or(0.0).max(0.0) as u32,
+                    has_defuser: field("has_defuser").and_then(as_bool).unwrap_or(false),
+                    pos_x: number("X").unwrap_or(0.0),
+                    pos_y: number("Y").unwrap_or(0.0),
+                    pos_z: number("Z").unwrap_or(0.0),
+                    place: field("last_place_name").cloned().and_then(as_string).unwrap_or_default(),
+                    ..Default::default()
+                });
+            }
+        }
+        frames.sort_by_key(|f| (f.tick, f.steamid));
+        frames
+    }
+
+    pub(crate) fn events_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a GameEvent> + 'a {
+        self.game_events.iter().filter(move |e| e.name == name)
+    }
+
+    fn prop_id(&self, prop_name: &str) -> Option<u32> {
+        self.prop_controller
+            .prop_infos
+            .iter()
+            .find(|info| info.prop_name == prop_name || info.prop_friendly_name == prop_name)
+            .map(|info| info.id)
+    }
+
+    fn collect_prop_rows(&self, df: &AHashMap<u32, PropColumn>, rows: &mut Vec<PlayerPropRow>) {
+        let column = |id: u32| df.get(&id).and_then(|c| c.data.as_ref());
+        let (Some(ticks), Some(steamids)) = (column(TICK_ID), column(STEAMID_ID)) else {
+            return;
+        };
+        let grenade_type = column(GRENADE_TYPE_ID);
+        let named: Vec<(&str, &VarVec)> = self
+            .prop_controller
+            .prop_infos
+            .iter()
+            .filter(|info| info.is_player_prop && info.id != TICK_ID && info.id != STEAMID_ID)
+            .filter_map(|info| Some((info.prop_friendly_name.as_str(), column(info.id)?)))
+            .collect();
+
+        for row in 0..var_vec_len(ticks) {
+            if grenade_type.and_then(|c| value_at(c, row)).is_some() {
+                continue;
+            }
+            let (Some(tick), Some(steamid)) = (value_at(ticks, row), value_at(steamids, row)) else {
+                continue;
+            };
+            let (Some(tick), Some(steamid)) = (as_f32(&tick), as_u64(&steamid)) else {
+                continue;
+            };
+            rows.push(PlayerPropRow {
+                tick: tick.max(0.0) as u32,
+                steamid,
+                props: named.iter().filter_map(|(name, col)| Some((name.to_string(), value_at(col, row)?))).collect(),
+            });
+        }
+    }
+
+    fn collect_frames(&self, df: &AHashMap<u32, PropColumn>, frames: &mut Vec<PlayerFrame>) {
+        let column = |id: Option<u32>| id.and_then(|id| df.get(&id)).and_then(|c| c.data.as_ref());
+        let (Some(ticks), Some(steamids)) = (column(Some(TICK_ID)), column(Some(STEAMID_ID))) else {
+            return;
+        };
+        let x = column(Some(PLAYER_X_ID));
+        let y = column(Some(PLAYER_Y_ID));
+        let z = column(Some(PLAYER_Z_ID));
+        let pitch = column(Some(PITCH_ID));
+        let yaw = column(Some(YAW_ID));
+        let is_alive = column(Some(IS_ALIVE_ID));
+        let weapon = column(Some(WEAPON_NAME_ID));
+        let team_num = column(self.prop_id(TEAM_NUM_PROP));
+        let ducking = column(self.prop_id(DUCKING_PROP));
+        let place = column(self.prop_id(PLACE_PROP));
+        let spotted_by = column(self.prop_id(SPOTTED_BY_PROP));
+        let flash_duration = column(self.prop_id(FLASH_DURATION_PROP));
+        let health = column(self.prop_id(HEALTH_PROP));
+        let armor = column(self.prop_id(ARMOR_PROP));
+        let equipment_value = column(self.prop_id(EQUIPMENT_VALUE_PROP));
+        let has_defuser = column(self.prop_id(DEFUSER_PROP));
+        // Projectile records share the dataframe with the thrower's steamid
+        let grenade_type = column(Some(GRENADE_TYPE_ID));
+
+        for row in 0..var_vec_len(ticks) {
+            if grenade_type.and_then(|c| value_at(c, row)).is_some() {
+                continue;
+            }
+            let (Some(tick), Some(steamid)) = (value_at(ticks, row), value_at(steamids, row)) else {
+                continue;
+            };
+            let (Some(tick), Some(steamid)) = (as_f32(&tick), as_u64(&steamid)) else {
+                continue;
+            };
+            let f32_at = |col: Option<&VarVec>| col.and_then(|c| value_at(c, row)).and_then(|v| as_f32(&v));
+
+            frames.push(PlayerFrame {
+                tick: tick.max(0.0) as u32,
+                steamid,
+                team_num: f32_at(team_num).unwrap_or(0.0) as u8,
+                is_alive: is_alive.and_then(|c| value_at(c, row)).and_then(|v| as_bool(&v)).unwrap_or(true),
+                health: f32_at(health).unwrap_or(0.0).max(0.0) as u32,
+                armor: f32_at(armor).unwrap_or(0.0).max(0.0) as u32,
+                equipment_value: f32_at(equipment_value).unwrap_or(0.0).max(0.0) as u32,
+                has_defuser: has_defuser.and_then(|c| value_at(c, row)).and_then(|v| as_bool(&v)).unwrap_or(false),
+                pos_x: f32_at(x).unwrap_or(0.0),
+                pos_y: f32_at(y).unwrap_or(0.0),
+                pos_z: f32_at(z).unwrap_or(0.0),
+                pitch: f32_at(pitch).unwrap_or(0.0),
+                yaw: f32_at(yaw).unwrap_or(0.0),
+                flash_duration: f32_at(flash_duration).unwrap_or(0.0),
+                is_ducking: ducking.and_then(|c| value_at(c, row)).and_then(|v| as_bool(&v)).unwrap_or(false),
+                weapon: weapon.and_then(|c| value_at(c, row)).and_then(as_string).unwrap_or_default(),
+                place: place.and_then(|c| value_at(c, row)).and_then(as_string).unwrap_or_default(),
+                spotted_by: match spotted_by.and_then(|c| value_at(c, row)) {
+                    Some(Variant::U64Vec(ids)) => ids,
+                    _ => Vec::new(),
+                },
+            });
+        }
+    }
+}
+
+fn var_vec_len(col: &VarVec) -> usize {
+    match col {
+        VarVec::U32(v) => v.len(),
+        VarVec::Bool(v) => v.len(),
+        VarVec::U64(v) => v.len(),
+        VarVec::F32(v) => v.len(),
+        VarVec::I32(v) => v.len(),
+        VarVec::String(v) => v.len(),
+        VarVec::StringVec(v) => v.len(),
+        VarVec::U64Vec(v) => v.len(),
+        VarVec::U32Vec(v) => v.len(),
+        VarVec::XYVec(v) => v.len(),
+        VarVec::XYZVec(v) => v.len(),
+        VarVec::Stickers(v) => v.len(),
+        VarVec::InputHistory(v) => v.len(),
+    }
+}
+
+fn value_at(col: &VarVec, row: usize) -> Option<Variant> {
+    match col {
+        VarVec::U32(v) => v.get(row).copied().flatten().map(Variant::U32),
+        VarVec::Bool(v) => v.get(row).copied().flatten().map(Variant::Bool),
+        VarVec::U64(v) => v.get(row).copied().flatten().map(Variant::U64),
+        VarVec::F32(v) => v.get(row).copied().flatten().map(Variant::F32),
+        VarVec::I32(v) => v.get(row).copied().flatten().map(Variant::I32),
+        VarVec::String(v) => v.get(row).cloned().flatten().map(Variant::String),
+        VarVec::U64Vec(v) => v.get(row).cloned().map(Variant::U64Vec),
+        VarVec::U32Vec(v) => v.get(row).cloned().map(Variant::U32Vec),
+        VarVec::StringVec(v) => v.get(row).cloned().map(Variant::StringVec),
+        VarVec::XYVec(v) => v.get(row).copied().flatten().map(Variant::VecXY),
+        VarVec::XYZVec(v) => v.get(row).copied().flatten().map(Variant::VecXYZ),
+        VarVec::Stickers(_) | VarVec::InputHistory(_) => None,
+    }
+}
+
+pub(crate) fn as_f32(v: &Variant) -> Option<f32> {
+    match v {
+        Variant::F32(f) => Some(*f),
+        Variant::I32(i) => Some(*i as f32),
+        Variant::U32(u) => Some(*u as f32),
+        Variant::U64(u) => Some(*u as f32),
+        Variant::Bool(b) => Some(*b as u8 as f32),
+        Variant::String(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+pub(crate) fn as_u64(v: &Variant) -> Option<u64> {
+    match v {
+        Variant::U64(u) => Some(*u),
+        Variant::U32(u) => Some(*u as u64),
+        Variant::I32(i) if *i >= 0 => Some(*i as u64),
+        Variant::String(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+pub(crate) fn as_bool(v: &Variant) -> Option<bool> {
+    match v {
+        Variant::Bool(b) => Some(*b),
+        Variant::I32(i) => Some(*i != 0),
+        Variant::U32(u) => Some(*u != 0),
+        Variant::String(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+pub(crate) fn as_string(v: Variant) -> Option<String> {
+    match v {
+        Variant::String(s) => Some(s),
+        _ => None,
+    }
+}
+
+pub(crate) fn event_field<'a>(event: &'a GameEvent, name: &str) -> Option<&'a Variant> {
+    event.fields.iter().find(|f| f.name == name).and_then(|f| f.data.as_ref())
+}
+
+/// Steam ID of a player field, skipping bots and disconnected players (0)
+pub(crate) fn event_steamid(event: &GameEvent, name: &str) -> Option<u64> {
+    event_field(event, name).and_then(as_u64).filter(|id| *id != 0)
+}
+
+pub(crate) fn event_bool(event: &GameEvent, name: &str) -> bool {
+    event_field(event, name).and_then(as_bool).unwrap_or(false)
+}
+
+fn event_team(event: &GameEvent, prefix: &str) -> u8 {
+    event_field(event, &format!("{prefix}_team_num")).and_then(as_f32).unwrap_or(0.0) as u8
+}
+
+pub(crate) fn event_string(event: &GameEvent, name: &str) -> Option<String> {
+    event_field(event, name).cloned().and_then(as_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::second_pass::game_events::EventField;
+
+    #[test]
+    fn test_event_field_helpers() {
+        let event = GameEvent {
+            name: "weapon_fire".to_string(),
+            tick: 42,
+            fields: vec![
+                EventField {
+                    name: "user_steamid".to_string(),
+                    data: Some(Variant::String("76561198000000001".to_string())),
+                },
+                EventField {
+                    name: "weapon".to_string(),
+                    data: Some(Variant::String("weapon_ak47".to_string())),
+                },
+            ],
+        };
+
+        assert_eq!(event_steamid(&event, "user_steamid"), Some(76561198000000001));
+        assert_eq!(event_string(&event, "weapon").as_deref(), Some("weapon_ak47"));
+        assert_eq!(event_steamid(&event, "attacker_steamid"), None);
+    }
+}
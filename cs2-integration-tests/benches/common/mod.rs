@@ -0,0 +1,23 @@
+//! Helpers shared by the benchmarks
+
+use cs2_common::InputVector;
+
+/// A varied but deterministic policy input
+pub fn input(i: usize) -> InputVector {
+    InputVector {
+        health: 100.0,
+        armor: 50.0,
+        pos_x: i as f32,
+        pos_y: 2.0,
+        pos_z: 3.0,
+        vel_x: 0.1,
+        vel_y: 0.2,
+        vel_z: 0.0,
+        yaw: (i * 7 % 360) as f32,
+        pitch: 10.0,
+        weapon_id_f32: 7.0,
+        ammo: 30.0,
+        is_airborne: 0.0,
+        padding: 0.0,
+    }
+}
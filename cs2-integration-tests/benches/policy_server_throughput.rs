@@ -0,0 +1,176 @@
+mod common;
+
+use candle_core::Device;
+use common::input;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use cs2_common::policy_protocol::{decode_outputs, encode_inputs};
+use cs2_common::{Frame, InputVector, MessageType, PROTOCOL_MAGIC};
+use cs2_ml::model::{BEHAVIOR_INPUT_DIM, BEHAVIOR_OUTPUT_DIM};
+use cs2_ml::{BatchConfig, BehaviorNet, PolicyServer};
+use std::hint::black_box;
+use std::net::{SocketAddr, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// Requests each client sends per iteration
+const REQUESTS_PER_CLIENT: usize = 20;
+
+fn net() -> BehaviorNet {
+    BehaviorNet::new(BEHAVIOR_INPUT_DIM, BEHAVIOR_OUTPUT_DIM, Device::Cpu).unwrap()
+}
+
+/// A policy server on a background thread, stopped on drop
+struct RunningServer {
+    addr: SocketAddr,
+    shutdown: Arc<AtomicBool>,
+    handle: Option<thread::JoinHandle<()>>,
+}
+
+impl RunningServer {
+    fn start(batching: Option<BatchConfig>) -> Self {
+        let mut server = PolicyServer::bind(net(), "127.0.0.1:0").unwrap();
+        if let Some(config) = batching {
+            server = server.with_batching(config);
+        }
+        let addr = server.local_addr().unwrap();
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let flag = shutdown.clone();
+        let handle = thread::spawn(move || server.run(flag).unwrap());
+        Self {
+            addr,
+            shutdown,
+            handle: Some(handle),
+        }
+    }
+}
+
+impl Drop for RunningServer {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Open a framed session and consume the handshake
+fn connect(addr: SocketAddr) -> TcpStream {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream.set_nodelay(true).unwrap();
+    std::io::Write::write_all(&mut stream, &PROTOCOL_MAGIC).unwrap();
+    Frame::new(MessageType::Hello, 0, 0, Vec::new())
+        .write_to(&mut stream)
+        .unwrap();
+    Frame::read_from(&mut stream).unwrap();
+    stream
+}
+
+/// Every client sends single-input requests one after another, all at once
+fn run_clients(streams: &mut [TcpStream]) {
+    thread::scope(|s| {
+        for (client, stream) in streams.iter_mut().enumerate() {
+            s.spawn(move || {
+                for step in 0..REQUESTS_PER_CLIENT {
+                    let payload = encode_inputs(&[input(client * REQUESTS_PER_CLIENT + step)]);
+                    Frame::new(MessageType::Predict, 0, step as u32, payload)
+                        .write_to(stream)
+                        .unwrap();
+                    let reply = Frame::read_from(stream).unwrap();
+                    black_box(decode_outputs(&reply.payload).unwrap());
+                }
+            });
+        }
+    });
+}
+
+/// One client with `depth` single-input requests in flight, sent before any reply is read
+fn run_pipelined(stream: &mut TcpStream, depth: usize) {
+    let mut payload = Vec::new();
+    for step in 0..depth {
+        Frame::new(
+            MessageType::Predict,
+            0,
+            step as u32,
+            encode_inputs(&[input(step)]),
+        )
+        .write_to(&mut payload)
+        .unwrap();
+    }
+    std::io::Write::write_all(stream, &payload).unwrap();
+    for _ in 0..depth {
+        let reply = Frame::read_from(stream).unwrap();
+        black_box(decode_outputs(&reply.payload).unwrap());
+    }
+}
+
+/// Concurrent single-input clients against a server with and without micro-batching
+fn benchmark_server_throughput(c: &mut Criterion) {
+    let mut group = c.benchmark_group("policy_server_throughput");
+    group.measurement_time(Duration::from_secs(5));
+    group.sample_size(10);
+
+    let modes = [
+        ("unbatched", None),
+        (
+            "micro_batched",
+            Some(BatchConfig {
+                max_wait: Duration::from_micros(500),
+                max_batch: 64,
+            }),
+        ),
+    ];
+    for (mode, batching) in modes {
+        let server = RunningServer::start(batching);
+        for clients in [1, 8, 32] {
+            let mut streams: Vec<TcpStream> = (0..clients).map(|_| connect(server.addr)).collect();
+            group.throughput(Throughput::Elements((clients * REQUESTS_PER_CLIENT) as u64));
+            group.bench_with_input(BenchmarkId::new(mode, clients), &clients, |b, _| {
+                b.iter(|| run_clients(&mut streams))
+            });
+        }
+
+        // A single session whose requests overlap
+        let mut stream = connect(server.addr);
+        for depth in [8, 32] {
+            group.throughput(Throughput::Elements(depth as u64));
+            let id = BenchmarkId::new(format!("{mode}_pipelined"), depth);
+            group.bench_with_input(id, &depth, |b, &depth| {
+                b.iter(|| run_pipelined(&mut stream, depth))
+            });
+        }
+    }
+
+    group.finish();
+}
+
+/// One forward pass per input against one forward pass per batch
+fn benchmark_forward_batching(c: &mut Criterion) {
+    let mut group = c.benchmark_group("behavior_net_forward");
+    group.measurement_time(Duration::from_secs(3));
+
+    let net = net();
+    for size in [1, 8, 64] {
+        let inputs: Vec<InputVector> = (0..size).map(input).collect();
+        group.throughput(Throughput::Elements(size as u64));
+        group.bench_with_input(BenchmarkId::new("per_input", size), &inputs, |b, inputs| {
+            b.iter(|| {
+                let outputs: Vec<_> = inputs.iter().map(|i| net.predict(i).unwrap()).collect();
+                black_box(outputs)
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("batched", size), &inputs, |b, inputs| {
+            b.iter(|| black_box(net.predict_batch(inputs).unwrap()))
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    benchmark_server_throughput,
+    benchmark_forward_batching
+);
+criterion_main!(benches);
@@ -0,0 +1,105 @@
+mod common;
+
+use candle_core::quantized::GgmlDType;
+use candle_core::Device;
+use common::input;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use cs2_common::{BehavioralVector, InputVector};
+use cs2_ml::model::{BEHAVIOR_INPUT_DIM, BEHAVIOR_OUTPUT_DIM};
+use cs2_ml::quantization::format_name;
+use cs2_ml::{BehaviorNet, MLMOVETransformer};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+/// Per-tick latency budget of the movement model
+const TICK_BUDGET: Duration = Duration::from_micros(500);
+
+/// Formats compared against f32
+const FORMATS: [GgmlDType; 2] = [GgmlDType::Q8_0, GgmlDType::Q4_0];
+
+fn history(len: usize) -> Vec<BehavioralVector> {
+    (0..len)
+        .map(|tick| BehavioralVector {
+            pos_x: tick as f32 * 4.0,
+            pos_y: 100.0,
+            vel_x: 250.0,
+            yaw: 90.0,
+            health: 100.0,
+            armor: 100.0,
+            ..BehavioralVector::new(tick as u32, 1)
+        })
+        .collect()
+}
+
+/// The paper-sized MLMOVE model (4 layers, 256-d, 32 ticks) in every format
+fn mlmove_models() -> Vec<(&'static str, MLMOVETransformer)> {
+    let mut models = vec![("f32", MLMOVETransformer::new(Device::Cpu).unwrap())];
+    for format in FORMATS {
+        let mut model = MLMOVETransformer::new(Device::Cpu).unwrap();
+        model.quantize(format).unwrap();
+        models.push((format_name(format), model));
+    }
+    models
+}
+
+/// One movement prediction per tick, against the 0.5 ms budget
+fn benchmark_mlmove_tick(c: &mut Criterion) {
+    let mut group = c.benchmark_group("mlmove_tick");
+    group.measurement_time(Duration::from_secs(5));
+
+    let sequence = history(32);
+    for (name, model) in mlmove_models() {
+        // Criterion reports the distribution; say up front whether the mean fits
+        let ticks = 200;
+        let start = Instant::now();
+        for _ in 0..ticks {
+            black_box(model.predict_movement(&sequence).unwrap());
+        }
+        let per_tick = start.elapsed() / ticks;
+        println!(
+            "mlmove {name}: {:.3} ms per tick ({} the {:.1} ms budget)",
+            per_tick.as_secs_f64() * 1e3,
+            if per_tick <= TICK_BUDGET {
+                "within"
+            } else {
+                "over"
+            },
+            TICK_BUDGET.as_secs_f64() * 1e3
+        );
+        group.bench_with_input(BenchmarkId::new(name, 32), &sequence, |b, sequence| {
+            b.iter(|| black_box(model.predict_movement(sequence).unwrap()))
+        });
+    }
+
+    group.finish();
+}
+
+/// Single-input and batched aim predictions
+fn benchmark_behavior_net(c: &mut Criterion) {
+    let mut group = c.benchmark_group("behavior_net_quantized");
+    group.measurement_time(Duration::from_secs(3));
+
+    let mut nets = vec![(
+        "f32",
+        BehaviorNet::new(BEHAVIOR_INPUT_DIM, BEHAVIOR_OUTPUT_DIM, Device::Cpu).unwrap(),
+    )];
+    for format in FORMATS {
+        let mut net =
+            BehaviorNet::new(BEHAVIOR_INPUT_DIM, BEHAVIOR_OUTPUT_DIM, Device::Cpu).unwrap();
+        net.quantize(format).unwrap();
+        nets.push((format_name(format), net));
+    }
+    for size in [1, 64] {
+        let inputs: Vec<InputVector> = (0..size).map(input).collect();
+        for (name, net) in &nets {
+            group.bench_with_input(BenchmarkId::new(*name, size), &inputs, |b, inputs| {
+                b.iter(|| black_box(net.predict_batch(inputs).unwrap()))
+            });
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, benchmark_mlmove_tick, benchmark_behavior_net);
+criterion_main!(benches);
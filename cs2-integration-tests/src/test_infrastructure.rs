@@ -39,6 +39,7 @@ impl TestInfrastructure {
             temp_directory: std::path::PathBuf::from("./temp_test"),
             enable_ai_analysis: false,
             chunk_size_ticks: 64 * 10, // 10 seconds for testing
+            win_probability_model: None,
         };
 
         Ok(DemoProcessor::new((*self.db_manager).clone(), config))
@@ -0,0 +1,336 @@
+use anyhow::{anyhow, Result};
+use cs2_common::{InputVector, OutputVector};
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::server::ModelSlot;
+
+/// How long requests may wait for company and how large a batch may grow
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BatchConfig {
+    pub max_wait: Duration, // Latency budget, counted from the first request of a batch
+    pub max_batch: usize,   // Inputs at which a batch runs without waiting further
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        Self {
+            max_wait: Duration::from_micros(500),
+            max_batch: 64,
+        }
+    }
+}
+
+/// One request waiting for its share of a batch
+struct Job {
+    client: u64, // ID of the registration that sent it
+    inputs: Vec<InputVector>,
+    reply: Sender<Result<(u32, Vec<OutputVector>), String>>,
+}
+
+/// Gathers concurrent prediction requests into one forward pass
+///
+/// Client threads hand their inputs to a single worker thread. The worker
+/// takes the first waiting request and keeps collecting others until every
+/// registered client has a request in the batch, `max_batch` inputs are
+/// queued or `max_wait` has passed. It then runs one
+/// [`crate::BehaviorNet::predict_batch`] over all of them and scatters the outputs
+/// back. A lone client therefore never waits for company that cannot come.
+/// Requests are never split, so one request larger than `max_batch` runs as
+/// a batch of its own.
+pub struct MicroBatcher {
+    jobs: Option<Sender<Job>>, // Taken on drop to stop the worker
+    counters: Arc<BatchCounters>,
+    worker: Option<thread::JoinHandle<()>>,
+}
+
+#[derive(Debug, Default)]
+struct BatchCounters {
+    batches: AtomicU64,
+    inputs: AtomicU64,
+    clients: AtomicUsize, // Registered clients that may send requests
+    next_client: AtomicU64,
+}
+
+/// A client of a [`MicroBatcher`], counted until dropped
+///
+/// Requests go through the registration so a batch can tell several requests
+/// of one client from requests of several clients.
+pub struct ClientRegistration<'a> {
+    batcher: &'a MicroBatcher,
+    id: u64,
+}
+
+impl ClientRegistration<'_> {
+    /// Queue `inputs` and wait until their batch has run
+    ///
+    /// Returns the ID of the model that ran the batch with the outputs.
+    pub fn predict(&self, inputs: Vec<InputVector>) -> Result<(u32, Vec<OutputVector>)> {
+        let (reply, outputs) = mpsc::channel();
+        let job = Job {
+            client: self.id,
+            inputs,
+            reply,
+        };
+        self.batcher
+            .jobs
+            .as_ref()
+            .and_then(|jobs| jobs.send(job).ok())
+            .ok_or_else(|| anyhow!("micro-batcher stopped"))?;
+        outputs
+            .recv()
+            .map_err(|_| anyhow!("micro-batcher stopped"))?
+            .map_err(|e| anyhow!(e))
+    }
+}
+
+impl Drop for ClientRegistration<'_> {
+    fn drop(&mut self) {
+        self.batcher.counters.clients.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl MicroBatcher {
+    /// Run batches on whichever model `model` holds when each batch starts
+    pub fn spawn(model: ModelSlot, config: BatchConfig) -> Self {
+        let (jobs, queue) = mpsc::channel();
+        let counters = Arc::new(BatchCounters::default());
+        let worker_counters = counters.clone();
+        let worker = thread::Builder::new()
+            .name("micro-batcher".to_string())
+            .spawn(move || run_batches(&model, queue, config, &worker_counters))
+            .expect("spawn micro-batcher");
+        Self {
+            jobs: Some(jobs),
+            counters,
+            worker: Some(worker),
+        }
+    }
+
+    /// Count a client whose requests a batch may wait for
+    pub fn register_client(&self) -> ClientRegistration<'_> {
+        self.counters.clients.fetch_add(1, Ordering::SeqCst);
+        ClientRegistration {
+            batcher: self,
+            id: self.counters.next_client.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+
+    /// Forward passes run so far
+    pub fn batches(&self) -> u64 {
+        self.counters.batches.load(Ordering::Relaxed)
+    }
+
+    /// Inputs per forward pass so far
+    pub fn mean_batch_size(&self) -> f64 {
+        let batches = self.batches();
+        if batches == 0 {
+            return 0.0;
+        }
+        self.counters.inputs.load(Ordering::Relaxed) as f64 / batches as f64
+    }
+}
+
+impl Drop for MicroBatcher {
+    fn drop(&mut self) {
+        // Closing the queue ends the worker once it has answered every job
+        self.jobs.take();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn run_batches(
+    slot: &ModelSlot,
+    queue: Receiver<Job>,
+    config: BatchConfig,
+    counters: &BatchCounters,
+) {
+    while let Ok(first) = queue.recv() {
+        let deadline = Instant::now() + config.max_wait;
+        let mut queued = first.inputs.len();
+        // A client with several requests in flight still counts once
+        let mut waiting = HashSet::from([first.client]);
+        let mut batch = vec![first];
+        while queued < config.max_batch && waiting.len() < counters.clients.load(Ordering::SeqCst) {
+            let wait = deadline.saturating_duration_since(Instant::now());
+            match queue.recv_timeout(wait) {
+                Ok(job) => {
+                    queued += job.inputs.len();
+                    waiting.insert(job.client);
+                    batch.push(job);
+                }
+                Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => break,
+            }
+        }
+
+        let inputs: Vec<InputVector> = batch
+            .iter()
+            .flat_map(|job| job.inputs.iter().copied())
+            .collect();
+        counters.batches.fetch_add(1, Ordering::Relaxed);
+        counters
+            .inputs
+            .fetch_add(inputs.len() as u64, Ordering::Relaxed);
+        let model = slot.current();
+        match model.net.predict_batch(&inputs) {
+            Ok(outputs) => {
+                let mut outputs = outputs.into_iter();
+                for job in batch {
+                    let share: Vec<OutputVector> =
+                        outputs.by_ref().take(job.inputs.len()).collect();
+                    // A client that hung up no longer waits for its reply
+                    let _ = job.reply.send(Ok((model.id, share)));
+                }
+            }
+            Err(e) => {
+                for job in batch {
+                    let _ = job.reply.send(Err(e.to_string()));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::BehaviorNet;
+    use bytemuck::Zeroable;
+    use candle_core::Device;
+
+    #[test]
+    fn test_concurrent_requests_share_batches() {
+        let model = ModelSlot::new(
+            BehaviorNet::new(
+                crate::model::BEHAVIOR_INPUT_DIM,
+                crate::model::BEHAVIOR_OUTPUT_DIM,
+                Device::Cpu,
+            )
+            .unwrap(),
+        );
+        let batcher = MicroBatcher::spawn(
+            model.clone(),
+            BatchConfig {
+                max_wait: Duration::from_millis(50),
+                max_batch: 64,
+            },
+        );
+
+        let inputs: Vec<InputVector> = (0..16)
+            .map(|i| InputVector {
+                yaw: i as f32 * 20.0,
+                health: 100.0,
+                ..InputVector::zeroed()
+            })
+            .collect();
+        let clients: Vec<ClientRegistration> =
+            inputs.iter().map(|_| batcher.register_client()).collect();
+        let outputs: Vec<Vec<OutputVector>> = thread::scope(|s| {
+            let handles: Vec<_> = inputs
+                .iter()
+                .zip(&clients)
+                .map(|(input, client)| s.spawn(|| client.predict(vec![*input]).unwrap().1))
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+
+        // Every client gets the answer to its own input, up to the summation
+        // order of larger batched matmuls
+        let close = |a: f32, b: f32| (a - b).abs() <= 1e-4 * a.abs().max(1.0);
+        for (input, output) in inputs.iter().zip(&outputs) {
+            let expected = model.current().net.predict(input).unwrap();
+            assert_eq!(output.len(), 1);
+            assert!(close(output[0].delta_yaw, expected.delta_yaw));
+            assert!(close(output[0].delta_pitch, expected.delta_pitch));
+        }
+        assert!(batcher.batches() < 16);
+        assert!(batcher.mean_batch_size() > 1.0);
+    }
+
+    #[test]
+    fn test_full_batches_do_not_wait() {
+        let model = ModelSlot::new(
+            BehaviorNet::new(
+                crate::model::BEHAVIOR_INPUT_DIM,
+                crate::model::BEHAVIOR_OUTPUT_DIM,
+                Device::Cpu,
+            )
+            .unwrap(),
+        );
+        let batcher = MicroBatcher::spawn(
+            model,
+            BatchConfig {
+                max_wait: Duration::from_secs(10),
+                max_batch: 4,
+            },
+        );
+        let clients: Vec<ClientRegistration> = (0..8).map(|_| batcher.register_client()).collect();
+        let start = Instant::now();
+        let (_, outputs) = clients[0].predict(vec![InputVector::zeroed(); 6]).unwrap();
+        assert_eq!(outputs.len(), 6);
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_lone_client_does_not_wait() {
+        let model = ModelSlot::new(
+            BehaviorNet::new(
+                crate::model::BEHAVIOR_INPUT_DIM,
+                crate::model::BEHAVIOR_OUTPUT_DIM,
+                Device::Cpu,
+            )
+            .unwrap(),
+        );
+        let batcher = MicroBatcher::spawn(
+            model,
+            BatchConfig {
+                max_wait: Duration::from_secs(10),
+                max_batch: 64,
+            },
+        );
+        let client = batcher.register_client();
+        let start = Instant::now();
+        for _ in 0..3 {
+            client.predict(vec![InputVector::zeroed()]).unwrap();
+        }
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(batcher.batches(), 3);
+        drop(client);
+    }
+
+    #[test]
+    fn test_batches_wait_for_other_clients() {
+        let model = ModelSlot::new(
+            BehaviorNet::new(
+                crate::model::BEHAVIOR_INPUT_DIM,
+                crate::model::BEHAVIOR_OUTPUT_DIM,
+                Device::Cpu,
+            )
+            .unwrap(),
+        );
+        let batcher = MicroBatcher::spawn(
+            model,
+            BatchConfig {
+                max_wait: Duration::from_secs(10),
+                max_batch: 64,
+            },
+        );
+        let (busy, late) = (batcher.register_client(), batcher.register_client());
+        thread::scope(|s| {
+            // Two requests of one client do not stand in for the other client
+            for _ in 0..2 {
+                s.spawn(|| busy.predict(vec![InputVector::zeroed()]).unwrap());
+            }
+            thread::sleep(Duration::from_millis(100));
+            late.predict(vec![InputVector::zeroed()]).unwrap();
+        });
+        assert_eq!(batcher.batches(), 1);
+        assert_eq!(batcher.mean_batch_size(), 3.0);
+    }
+}
@@ -1,12 +1,22 @@
-use crate::mlmove_transformer::{MLMOVEConfig, MLMOVETransformer};
+use crate::dataset::{
+    action_counts, balanced_indices, DatasetConfig, SequenceDataset, SequenceWindow, Split,
+};
+use crate::mlmove_transformer::{DiscreteAction, MLMOVEConfig, MLMOVETransformer};
 /// PyTorch to Candle Conversion Utilities
 ///
 /// Implements conversion pipeline for PyTorch→Candle model weights and fine-tuning
 /// infrastructure for CS2 demo adaptation as outlined in the MLMOVE research integration.
-use anyhow::Result;
-use candle_core::Device;
+use anyhow::{bail, Context, Result};
+use candle_core::{pickle, Device, Tensor, D};
+use candle_nn::{AdamW, Optimizer, ParamsAdamW};
 use cs2_common::BehavioralVector;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufReader, Read};
 use std::path::Path;
 
 /// Configuration for PyTorch to Candle conversion
@@ -18,8 +28,21 @@ pub struct ConversionConfig {
     pub candle_output_path: String,
     /// Model architecture configuration
     pub model_config: MLMOVEConfig,
-    /// Whether to validate conversion by comparing outputs
-    pub validate_conversion: bool,
+    /// JSON with an `input` sequence and the `logits` the source model computed
+    /// for it, which the converted model must reproduce
+    pub reference_path: Option<String>,
+    /// Whether the source encoder layers normalize first, for checkpoints
+    /// that do not record it (see [`checkpoint_norm_first`])
+    pub norm_first: Option<bool>,
+}
+
+/// Logits the source model produced for one input sequence
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversionReference {
+    /// One row of input features per tick
+    pub input: Vec<Vec<f32>>,
+    /// One logit per movement action
+    pub logits: Vec<f32>,
 }
 
 /// Fine-tuning configuration for CS2 demo adaptation
@@ -39,8 +62,16 @@ pub struct FineTuningConfig {
     pub batch_size: usize,
     /// Sequence length for training
     pub sequence_length: usize,
-    /// Validation split ratio
+    /// Ticks between the starts of consecutive training windows
+    pub window_stride: usize,
+    /// Validation split ratio, in whole matches
     pub validation_split: f32,
+    /// Test split ratio, in whole matches, never trained on or selected with
+    pub test_split: f32,
+    /// Draw each epoch so every movement action is equally frequent
+    pub class_balanced: bool,
+    /// Drives the split assignment and the training order
+    pub seed: u64,
 }
 
 /// PyTorch to Candle converter
@@ -65,6 +96,16 @@ pub struct TrainingSample {
     pub weight: f32,
 }
 
+impl From<SequenceWindow> for TrainingSample {
+    fn from(window: SequenceWindow) -> Self {
+        Self {
+            target_action: window.action_index() as u32,
+            input_sequence: window.vectors,
+            weight: 1.0,
+        }
+    }
+}
+
 /// Training dataset for CS2 adaptation
 pub struct CS2TrainingDataset {
     /// Training samples
@@ -91,7 +132,7 @@ pub struct DatasetMetadata {
 /// Training metrics and progress tracking
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TrainingMetrics {
-    /// Current epoch
+    /// Epoch the best checkpoint was saved at (1-based)
     pub epoch: usize,
     /// Training loss
     pub train_loss: f32,
@@ -113,21 +154,16 @@ impl TorchToCandleConverter {
         Self { config }
     }
 
-    /// Convert PyTorch model to Candle safetensors format
+    /// Convert a PyTorch MLMOVE checkpoint to Candle safetensors format
     ///
-    /// This is a placeholder implementation. In production, would use:
-    /// 1. Load PyTorch model using tch-rs or Python interop
-    /// 2. Extract state_dict weights and biases
-    /// 3. Convert to Candle tensor format
-    /// 4. Save as safetensors file
+    /// Reads a `.pt`/`.pth` state dict, an ONNX export or a safetensors file,
+    /// maps the PyTorch parameter names onto our var paths and writes the
+    /// weights with a `.json` config sidecar next to them.
     pub fn convert(&self) -> Result<()> {
         println!("Converting PyTorch model to Candle format...");
         println!("Input: {}", self.config.pytorch_model_path);
         println!("Output: {}", self.config.candle_output_path);
 
-        // TODO: Implement actual PyTorch→Candle conversion
-        // For now, create a placeholder safetensors file structure
-
         if !Path::new(&self.config.pytorch_model_path).exists() {
             return Err(anyhow::anyhow!(
                 "PyTorch model file not found: {}",
@@ -135,175 +171,339 @@ impl TorchToCandleConverter {
             ));
         }
 
-        // Create Candle model with the specified configuration
-        let device = Device::Cpu;
-        let _transformer =
-            MLMOVETransformer::with_config(self.config.model_config.clone(), device)?;
-
-        // In production, would:
-        // 1. Load PyTorch weights using Python script or tch-rs
-        // 2. Map PyTorch parameter names to Candle parameter names
-        // 3. Convert tensor formats and save as safetensors
-
-        self.create_conversion_script()?;
+        let transformer = self.load_source()?;
+        transformer.save(&self.config.candle_output_path)?;
 
         println!("Conversion completed successfully!");
         Ok(())
     }
 
-    /// Create Python script for PyTorch→safetensors conversion
-    fn create_conversion_script(&self) -> Result<()> {
-        let script_content = r#"
-#!/usr/bin/env python3
-"""
-PyTorch to Safetensors Conversion Script
-Converts MLMOVE PyTorch weights to Candle-compatible safetensors format
-"""
-
-import torch
-import numpy as np
-from safetensors.torch import save_file
-import json
-
-def convert_pytorch_to_safetensors(pytorch_path, output_path, config_path=None):
-    """
-    Convert PyTorch model to safetensors format
-    """
-    print(f"Loading PyTorch model from {{pytorch_path}}")
-    
-    # Load PyTorch model
-    checkpoint = torch.load(pytorch_path, map_location='cpu')
-    
-    if isinstance(checkpoint, dict) and 'state_dict' in checkpoint:
-        state_dict = checkpoint['state_dict']
-    elif isinstance(checkpoint, dict) and 'model' in checkpoint:
-        state_dict = checkpoint['model']
-    else:
-        state_dict = checkpoint
-    
-    # Convert parameter names from PyTorch to Candle format
-    candle_state_dict = {{}}
-    
-    for name, tensor in state_dict.items():
-        # Map PyTorch naming to Candle naming convention
-        candle_name = map_parameter_name(name)
-        candle_state_dict[candle_name] = tensor
-        print(f"Mapped {{name}} -> {{candle_name}}, shape: {{tensor.shape}}")
-    
-    # Save as safetensors
-    save_file(candle_state_dict, output_path)
-    print(f"Saved converted model to {{output_path}}")
-    
-    # Save config if provided
-    if config_path:
-        config = {{
-            "model_type": "mlmove_transformer",
-            "num_layers": 4,
-            "num_heads": 1,
-            "model_dim": 256,
-            "ff_dim": 1024,
-            "sequence_length": 32,
-            "action_space_size": 97,
-            "input_dim": 10
-        }}
-        
-        with open(config_path, 'w') as f:
-            json.dump(config, f, indent=2)
-        print(f"Saved model config to {{config_path}}")
-
-def map_parameter_name(pytorch_name):
-    """
-    Map PyTorch parameter names to Candle naming convention
-    """
-    # Example mappings for MLMOVE transformer
-    name_mappings = {{
-        'input_embed.weight': 'input_embedding.weight',
-        'input_embed.bias': 'input_embedding.bias',
-        'pos_embed.weight': 'pos_embedding.weight',
-        'output_proj.weight': 'output.weight',
-        'output_proj.bias': 'output.bias',
-    }}
-    
-    # Handle transformer layers
-    for i in range(4):  # 4 layers in MLMOVE
-        layer_mappings = {{
-            f'layers.{{i}}.self_attn.q_proj.weight': f'layer_{{i}}.attention.query.weight',
-            f'layers.{{i}}.self_attn.q_proj.bias': f'layer_{{i}}.attention.query.bias',
-            f'layers.{{i}}.self_attn.k_proj.weight': f'layer_{{i}}.attention.key.weight',
-            f'layers.{{i}}.self_attn.k_proj.bias': f'layer_{{i}}.attention.key.bias',
-            f'layers.{{i}}.self_attn.v_proj.weight': f'layer_{{i}}.attention.value.weight',
-            f'layers.{{i}}.self_attn.v_proj.bias': f'layer_{{i}}.attention.value.bias',
-            f'layers.{{i}}.self_attn.out_proj.weight': f'layer_{{i}}.attention.output.weight',
-            f'layers.{{i}}.self_attn.out_proj.bias': f'layer_{{i}}.attention.output.bias',
-            f'layers.{{i}}.linear1.weight': f'layer_{{i}}.ff.linear1.weight',
-            f'layers.{{i}}.linear1.bias': f'layer_{{i}}.ff.linear1.bias',
-            f'layers.{{i}}.linear2.weight': f'layer_{{i}}.ff.linear2.weight',
-            f'layers.{{i}}.linear2.bias': f'layer_{{i}}.ff.linear2.bias',
-            f'layers.{{i}}.norm1.weight': f'layer_{{i}}.ln1.weight.weight',
-            f'layers.{{i}}.norm1.bias': f'layer_{{i}}.ln1.bias.weight',
-            f'layers.{{i}}.norm2.weight': f'layer_{{i}}.ln2.weight.weight',
-            f'layers.{{i}}.norm2.bias': f'layer_{{i}}.ln2.bias.weight',
-        }}
-        name_mappings.update(layer_mappings)
-    
-    return name_mappings.get(pytorch_name, pytorch_name)
-
-if __name__ == "__main__":
-    import sys
-    
-    if len(sys.argv) < 3:
-        print("Usage: python torch2safetensors.py <pytorch_model_path> <output_path> [config_path]")
-        sys.exit(1)
-    
-    pytorch_path = sys.argv[1]
-    output_path = sys.argv[2]
-    config_path = sys.argv[3] if len(sys.argv) > 3 else None
-    
-    convert_pytorch_to_safetensors(pytorch_path, output_path, config_path)
-"#;
-
-        std::fs::write("scripts/torch2safetensors.py", script_content)?;
-        println!("Created conversion script at scripts/torch2safetensors.py");
+    /// Check that the converted model reproduces the reference logits
+    pub fn validate_conversion(&self) -> Result<bool> {
+        let Some(reference_path) = &self.config.reference_path else {
+            bail!(
+                "no reference logits to validate {} against",
+                self.config.candle_output_path
+            );
+        };
 
-        Ok(())
+        println!("Validating conversion against {reference_path}...");
+        let reference: ConversionReference = serde_json::from_str(
+            &std::fs::read_to_string(reference_path)
+                .with_context(|| format!("read {reference_path}"))?,
+        )
+        .with_context(|| format!("parse {reference_path}"))?;
+        let converted =
+            MLMOVETransformer::load_pretrained(&self.config.candle_output_path, Device::Cpu)?;
+
+        let input = Tensor::new(reference.input, &Device::Cpu)?.unsqueeze(0)?;
+        let logits = converted.forward(&input)?.squeeze(0)?.to_vec1::<f32>()?;
+        if logits.len() != reference.logits.len() {
+            bail!(
+                "converted model has {} actions, the reference {}",
+                logits.len(),
+                reference.logits.len()
+            );
+        }
+        let difference = logits
+            .iter()
+            .zip(&reference.logits)
+            .map(|(a, b)| (a - b).abs())
+            .fold(0.0f32, f32::max);
+
+        let valid = difference <= PARITY_TOLERANCE;
+        println!("Largest output difference: {difference:.2e} (valid: {valid})");
+        Ok(valid)
     }
 
-    /// Validate conversion by comparing PyTorch and Candle outputs
-    pub fn validate_conversion(&self) -> Result<bool> {
-        if !self.config.validate_conversion {
-            return Ok(true);
+    fn load_source(&self) -> Result<MLMOVETransformer> {
+        let path = &self.config.pytorch_model_path;
+        let tensors = read_checkpoint(path, &Device::Cpu)?;
+        let norm_first = match (checkpoint_norm_first(path)?, self.config.norm_first) {
+            (Some(recorded), Some(given)) if recorded != given => bail!(
+                "{path} records norm_first={recorded}, the conversion was configured with norm_first={given}"
+            ),
+            (recorded, given) => recorded.or(given),
+        };
+        MLMOVETransformer::from_tensors(
+            &map_pytorch_names(tensors, norm_first)?,
+            Some(self.config.model_config.clone()),
+            Device::Cpu,
+        )
+    }
+}
+
+/// Largest absolute logit difference accepted between a conversion and its reference
+pub const PARITY_TOLERANCE: f32 = 1e-4;
+
+/// Read every tensor of a checkpoint, chosen by extension
+///
+/// `.onnx` files contribute their graph initializers, `.pt`/`.pth`/`.bin`
+/// are PyTorch pickled state dicts, possibly under a `state_dict` key, and
+/// anything else is read as safetensors.
+pub fn read_checkpoint(path: impl AsRef<Path>, device: &Device) -> Result<HashMap<String, Tensor>> {
+    let path = path.as_ref();
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    let tensors = match extension {
+        "onnx" => crate::onnx::read_initializers(path, device)?,
+        "pt" | "pth" | "bin" => {
+            let context = || format!("read PyTorch state dict {}", path.display());
+            let mut tensors = candle_core::pickle::read_all(path).with_context(context)?;
+            if tensors.is_empty() {
+                tensors = candle_core::pickle::read_all_with_key(path, Some("state_dict"))
+                    .with_context(context)?;
+            }
+            tensors
+                .into_iter()
+                .map(|(name, tensor)| Ok((name, tensor.to_device(device)?)))
+                .collect::<Result<_>>()?
         }
+        _ => candle_core::safetensors::load(path, device)
+            .with_context(|| format!("read safetensors {}", path.display()))?,
+    };
+    Ok(tensors)
+}
 
-        // TODO: Implement validation by running identical inputs through both models
-        println!("Validating conversion...");
-        println!("Validation completed successfully!");
-        Ok(true)
+/// The `norm_first` flag a checkpoint records for its encoder layers
+///
+/// Safetensors files carry it in their header metadata and PyTorch pickles as
+/// a `norm_first` entry anywhere in the pickled object, e.g. next to a
+/// `state_dict`. Bare state dicts do not record it, so the caller has to give
+/// it for them (see [`map_pytorch_names`]). ONNX graphs reveal it
+/// through their wiring, see [`crate::onnx::read_norm_first`], and are
+/// refused when it cannot be told.
+pub fn checkpoint_norm_first(path: impl AsRef<Path>) -> Result<Option<bool>> {
+    let path = path.as_ref();
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    match extension {
+        "onnx" => match crate::onnx::read_norm_first(path)? {
+            Some(norm_first) => Ok(Some(norm_first)),
+            None => bail!(
+                "cannot tell the normalization order of {}, it has no LayerNormalization nodes",
+                path.display()
+            ),
+        },
+        "pt" | "pth" | "bin" => {
+            let file = File::open(path).with_context(|| format!("open {}", path.display()))?;
+            let mut archive = zip::ZipArchive::new(BufReader::new(file))?;
+            for i in 0..archive.len() {
+                let entry = archive.by_index(i)?;
+                if !entry.name().ends_with("data.pkl") {
+                    continue;
+                }
+                let mut stack = pickle::Stack::empty();
+                stack.read_loop(&mut BufReader::new(entry))?;
+                if let Some(norm_first) = pickled_norm_first(&stack.finalize()?) {
+                    return Ok(Some(norm_first));
+                }
+            }
+            Ok(None)
+        }
+        _ => {
+            let mut file = File::open(path).with_context(|| format!("open {}", path.display()))?;
+            let mut len = [0u8; 8];
+            file.read_exact(&mut len)?;
+            let mut header = vec![0u8; u64::from_le_bytes(len) as usize];
+            file.read_exact(&mut header)
+                .with_context(|| format!("read safetensors header of {}", path.display()))?;
+            let header: serde_json::Value = serde_json::from_slice(&header)?;
+            Ok(header["__metadata__"]["norm_first"]
+                .as_str()
+                .map(|flag| flag.eq_ignore_ascii_case("true")))
+        }
+    }
+}
+
+fn pickled_norm_first(object: &pickle::Object) -> Option<bool> {
+    use pickle::Object;
+    match object {
+        Object::Dict(entries) => entries.iter().find_map(|(key, value)| match (key, value) {
+            (Object::Unicode(key), Object::Bool(flag)) if key == "norm_first" => Some(*flag),
+            _ => pickled_norm_first(value),
+        }),
+        Object::Tuple(items) | Object::List(items) => items.iter().find_map(pickled_norm_first),
+        Object::Reduce { args, .. } | Object::Build { args, .. } => pickled_norm_first(args),
+        _ => None,
     }
 }
 
+/// Rename PyTorch MLMOVE parameters to our var paths
+///
+/// Encoder layers are recognised by the names `nn.TransformerEncoder` gives
+/// them, `layers.N.` followed by the `nn.TransformerEncoderLayer` parameters
+/// (packed `self_attn.in_proj_*`, `self_attn.out_proj`, `linear1`/`linear2`,
+/// `norm1`/`norm2`), under whatever attribute holds the encoder. The input
+/// embedding and output head are taken from the published model's modules
+/// ([`PUBLISHED_MODULES`]) or our var paths, the names of checkpoints written
+/// by [`MLMOVETransformer::save`]. A `module.` prefix left by `DataParallel`
+/// is dropped. Any other name is an error listing every parameter that
+/// matched nothing, rather than a guess.
+///
+/// `norm_first` is the flag from [`checkpoint_norm_first`] or given by the
+/// caller. Our encoder layers normalize before attention and the feed-forward
+/// block, so post-norm checkpoints are rejected instead of being evaluated
+/// with the wrong layout, and so are PyTorch encoder layers of unknown order.
+/// Layers already under our var paths are ours and need no flag.
+pub fn map_pytorch_names(
+    tensors: HashMap<String, Tensor>,
+    norm_first: Option<bool>,
+) -> Result<HashMap<String, Tensor>> {
+    match norm_first {
+        Some(true) => {}
+        Some(false) => bail!(
+            "checkpoint uses post-norm encoder layers (norm_first=False), MLMOVE layers are pre-norm"
+        ),
+        None if tensors.keys().any(|name| {
+            let key = name.strip_prefix("module.").unwrap_or(name);
+            !is_var_path(key) && encoder_layer_param(key).is_some()
+        }) =>
+        {
+            bail!("checkpoint does not record the normalization order of its encoder layers (norm_first), pass it explicitly")
+        }
+        None => {}
+    }
+    let mut mapped = HashMap::new();
+    let mut unmapped = Vec::new();
+    for (name, tensor) in tensors {
+        let key = name.strip_prefix("module.").unwrap_or(&name);
+        if is_var_path(key) {
+            mapped.insert(key.to_string(), tensor);
+            continue;
+        }
+        if let Some(var_path) = PUBLISHED_MODULES.iter().find_map(|(module, var_prefix)| {
+            let param = key.strip_prefix(module)?.strip_prefix('.')?;
+            Some(format!("{var_prefix}.{param}"))
+        }) {
+            mapped.insert(var_path, tensor);
+            continue;
+        }
+        let Some((layer, param)) = encoder_layer_param(key) else {
+            unmapped.push(name);
+            continue;
+        };
+        let prefix = format!("layer_{layer}");
+        if let Some(kind) = param.strip_prefix("self_attn.in_proj_") {
+            // Packed query, key and value rows in that order
+            let rows = tensor.dim(0)? / 3;
+            for (i, part) in ["query", "key", "value"].iter().enumerate() {
+                mapped.insert(
+                    format!("{prefix}.attention.{part}.{kind}"),
+                    tensor.narrow(0, i * rows, rows)?,
+                );
+            }
+            continue;
+        }
+        match map_layer_param(param) {
+            Some(param) => {
+                mapped.insert(format!("{prefix}.{param}"), tensor);
+            }
+            None => unmapped.push(name),
+        }
+    }
+    if !unmapped.is_empty() {
+        unmapped.sort();
+        bail!("no MLMOVE parameter matches {}", unmapped.join(", "));
+    }
+    Ok(mapped)
+}
+
+/// Linear layers of the published MLMOVE model and the var paths they load into
+///
+/// The embedding and decoder are `nn.Sequential` stacks; only a single
+/// `nn.Linear` at index 0 fits our layout, anything after it stays unmapped.
+pub const PUBLISHED_MODULES: [(&str, &str); 2] = [
+    ("embedding_model.0", "input_embedding"),
+    ("decoder.0", "output"),
+];
+
+/// Layer index and parameter name of a `nn.TransformerEncoder` layer parameter
+fn encoder_layer_param(key: &str) -> Option<(&str, &str)> {
+    let rest = key
+        .strip_prefix("layers.")
+        .or_else(|| key.split_once(".layers.").map(|(_, rest)| rest))?;
+    let (layer, param) = rest.split_once('.')?;
+    layer
+        .bytes()
+        .all(|b| b.is_ascii_digit())
+        .then_some((layer, param))
+}
+
+fn map_layer_param(param: &str) -> Option<String> {
+    let (module, kind) = param.rsplit_once('.')?;
+    let target = match module {
+        "self_attn.out_proj" => "attention.output",
+        "linear1" => "ff.linear1",
+        "linear2" => "ff.linear2",
+        "norm1" => "ln1",
+        "norm2" => "ln2",
+        _ => return None,
+    };
+    Some(format!("{target}.{kind}"))
+}
+
+fn is_var_path(name: &str) -> bool {
+    ["input_embedding.", "pos_embedding.", "output.", "layer_"]
+        .iter()
+        .any(|prefix| name.starts_with(prefix))
+}
+
 impl CS2FineTuner {
     /// Create new fine-tuner with configuration
     pub fn new(config: FineTuningConfig, device: Device) -> Self {
         Self { config, device }
     }
 
+    /// Fingerprint of the data `fine_tune` trains and validates on
+    pub fn dataset_fingerprint(&self) -> Result<String> {
+        SequenceDataset::open(&self.config.cs2_dataset_path, self.dataset_config())?.fingerprint()
+    }
+
     /// Load CS2 training dataset from parquet files
+    ///
+    /// `cs2_dataset_path` is a file written by `data::write_to_parquet` or a
+    /// directory of them. Each sample is a window of one player's consecutive
+    /// vectors, labelled with the discrete action taken on the following tick.
     pub fn load_dataset(&self) -> Result<CS2TrainingDataset> {
         println!(
             "Loading CS2 training dataset from: {}",
             self.config.cs2_dataset_path
         );
 
-        // TODO: Implement actual dataset loading from parquet
-        // For now, create a demo dataset
-
-        let samples = self.create_demo_samples(1000)?;
+        // Every match goes to the training split
+        let dataset = SequenceDataset::open(
+            &self.config.cs2_dataset_path,
+            DatasetConfig {
+                validation_fraction: 0.0,
+                test_fraction: 0.0,
+                ..self.dataset_config()
+            },
+        )?;
+        let samples: Vec<TrainingSample> = dataset
+            .windows(Split::Train)?
+            .into_iter()
+            .map(TrainingSample::from)
+            .collect();
+        if samples.is_empty() {
+            bail!(
+                "no training windows of {} ticks in {}",
+                self.config.sequence_length,
+                self.config.cs2_dataset_path
+            );
+        }
 
+        let players: HashSet<u64> = samples
+            .iter()
+            .map(|s| s.input_sequence[0].steamid)
+            .collect();
         let metadata = DatasetMetadata {
             sample_count: samples.len(),
-            unique_players: 50, // Estimated from demo data
-            maps: vec!["de_dust2".to_string(), "de_mirage".to_string()],
-            avg_sequence_length: 32.0,
+            unique_players: players.len(),
+            maps: Vec::new(), // Behavioral Parquet files carry no map name
+            avg_sequence_length: samples
+                .iter()
+                .map(|s| s.input_sequence.len() as f32)
+                .sum::<f32>()
+                / samples.len() as f32,
             version: "1.0.0".to_string(),
         };
 
@@ -311,34 +511,128 @@ impl CS2FineTuner {
     }
 
     /// Fine-tune MLMOVE model on CS2 data
+    ///
+    /// Starts from `base_model_path` when it exists, otherwise from freshly
+    /// initialized weights. A base model must take sequences of
+    /// `sequence_length` ticks.
     pub fn fine_tune(&self) -> Result<TrainingMetrics> {
         println!("Starting fine-tuning on CS2 data...");
 
         // Load base model
-        let model =
-            MLMOVETransformer::load_pretrained(&self.config.base_model_path, self.device.clone())?;
+        let model = if Path::new(&self.config.base_model_path).exists() {
+            MLMOVETransformer::load_pretrained(&self.config.base_model_path, self.device.clone())?
+        } else {
+            println!(
+                "Base model {} not found, training from scratch",
+                self.config.base_model_path
+            );
+            MLMOVETransformer::with_config(
+                MLMOVEConfig {
+                    sequence_length: self.config.sequence_length,
+                    ..Default::default()
+                },
+                self.device.clone(),
+            )?
+        };
+
+        // Split by match, the checkpoint is chosen on the validation matches
+        let dataset = SequenceDataset::open(&self.config.cs2_dataset_path, self.dataset_config())?;
+        let validation_matches = dataset.files(Split::Validation).len();
+        if self.config.validation_split > 0.0 && validation_matches == 0 {
+            bail!(
+                "no match left for validation in {}: {} match files are too few for a validation split",
+                self.config.cs2_dataset_path,
+                dataset.files(Split::Train).len() + dataset.files(Split::Test).len()
+            );
+        }
+        println!(
+            "Training on {} matches, validating on {validation_matches}",
+            dataset.files(Split::Train).len()
+        );
+
+        self.fine_tune_model(&model, &dataset)
+    }
 
-        // Load training dataset
-        let dataset = self.load_dataset()?;
-        println!("Loaded {} training samples", dataset.metadata.sample_count);
+    /// Loss and accuracy of the saved best checkpoint on the test split
+    ///
+    /// `None` when the split holds no windows.
+    pub fn test(&self) -> Result<Option<(f32, f32)>> {
+        let dataset = SequenceDataset::open(&self.config.cs2_dataset_path, self.dataset_config())?;
+        if dataset.files(Split::Test).is_empty() {
+            return Ok(None);
+        }
+        let model = MLMOVETransformer::load_pretrained(
+            &self.config.output_model_path,
+            self.device.clone(),
+        )?;
+        self.validate_epoch(&model, &dataset, Split::Test)
+    }
+
+    /// Train `model` in place with cross-entropy on the dataset's action labels
+    ///
+    /// Matches are read one at a time, so only one match's windows are held in
+    /// memory. The weights of the epoch with the lowest validation loss, or
+    /// training loss without validation matches, are written to
+    /// `output_model_path`, and the returned metrics are that epoch's.
+    pub fn fine_tune_model(
+        &self,
+        model: &MLMOVETransformer,
+        dataset: &SequenceDataset,
+    ) -> Result<TrainingMetrics> {
+        if dataset.files(Split::Train).is_empty() {
+            bail!("no training matches");
+        }
+        // The positional embedding only covers the model's own sequence length
+        if model.config().sequence_length != dataset.config.sequence_length {
+            bail!(
+                "the model takes sequences of {} ticks, the dataset is windowed into {} (sequence_length)",
+                model.config().sequence_length,
+                dataset.config.sequence_length
+            );
+        }
+        if let Some(format) = model.quantization() {
+            bail!(
+                "cannot fine-tune a model quantized to {}",
+                crate::quantization::format_name(format)
+            );
+        }
 
-        // Split into train/validation
-        let val_split_idx =
-            (dataset.samples.len() as f32 * (1.0 - self.config.validation_split)) as usize;
-        let (train_samples, val_samples) = dataset.samples.split_at(val_split_idx);
+        let mut optimizer = AdamW::new(
+            model.trainable_vars(),
+            ParamsAdamW {
+                lr: self.config.learning_rate,
+                ..Default::default()
+            },
+        )?;
+
+        // Labels of the whole training split, to balance one match at a time
+        let frequency = if self.config.class_balanced {
+            let mut frequency = vec![0; DiscreteAction::NO_OP_INDEX + 1];
+            for windows in dataset.matches(Split::Train) {
+                for (total, count) in frequency.iter_mut().zip(action_counts(&windows?)) {
+                    *total += count;
+                }
+            }
+            Some(frequency)
+        } else {
+            None
+        };
 
         let start_time = std::time::Instant::now();
-        let mut best_val_loss = f32::INFINITY;
+        let mut best: Option<TrainingMetrics> = None;
 
         // Training loop
         for epoch in 0..self.config.epochs {
             let epoch_start = std::time::Instant::now();
 
             // Training phase
-            let train_metrics = self.train_epoch(&model, train_samples, epoch)?;
+            let train_metrics =
+                self.train_epoch(model, dataset, frequency.as_deref(), &mut optimizer, epoch)?;
 
             // Validation phase
-            let val_metrics = self.validate_epoch(&model, val_samples)?;
+            let val_metrics = self
+                .validate_epoch(model, dataset, Split::Validation)?
+                .unwrap_or(train_metrics);
 
             let epoch_time = epoch_start.elapsed().as_secs_f32();
 
@@ -350,110 +644,169 @@ impl CS2FineTuner {
             );
 
             // Save best model
-            if val_metrics.0 < best_val_loss {
-                best_val_loss = val_metrics.0;
-                self.save_model(&model, epoch)?;
-                println!("Saved new best model with validation loss: {best_val_loss:.4}");
+            if best.as_ref().is_none_or(|b| val_metrics.0 < b.val_loss) {
+                self.save_model(model, epoch)?;
+                println!(
+                    "Saved new best model with validation loss: {:.4}",
+                    val_metrics.0
+                );
+                best = Some(TrainingMetrics {
+                    epoch: epoch + 1,
+                    train_loss: train_metrics.0,
+                    val_loss: val_metrics.0,
+                    train_accuracy: train_metrics.1,
+                    val_accuracy: val_metrics.1,
+                    learning_rate: self.config.learning_rate,
+                    training_time_sec: 0.0,
+                });
             }
         }
 
-        let total_time = start_time.elapsed().as_secs_f32();
-
-        Ok(TrainingMetrics {
-            epoch: self.config.epochs,
-            train_loss: 0.0, // Would track actual losses
-            val_loss: best_val_loss,
-            train_accuracy: 0.85, // Placeholder
-            val_accuracy: 0.82,   // Placeholder
-            learning_rate: self.config.learning_rate,
-            training_time_sec: total_time,
-        })
+        let Some(mut metrics) = best else {
+            bail!("fine-tuning ran no epochs");
+        };
+        metrics.training_time_sec = start_time.elapsed().as_secs_f32();
+        Ok(metrics)
     }
 
-    /// Train for one epoch
+    /// Train for one epoch, returning the mean loss and accuracy
+    ///
+    /// Matches are visited in a seeded random order and each match's windows
+    /// are shuffled, or drawn by `frequency` when classes are balanced.
     fn train_epoch(
         &self,
-        _model: &MLMOVETransformer,
-        _samples: &[TrainingSample],
+        model: &MLMOVETransformer,
+        dataset: &SequenceDataset,
+        frequency: Option<&[usize]>,
+        optimizer: &mut AdamW,
         epoch: usize,
     ) -> Result<(f32, f32)> {
-        // TODO: Implement actual training with gradient computation
-        // For now, return mock metrics
-
-        let train_loss = 0.5 - (epoch as f32 * 0.05); // Simulated decreasing loss
-        let train_accuracy = 0.6 + (epoch as f32 * 0.03); // Simulated increasing accuracy
+        let seed = self.config.seed.wrapping_add(epoch as u64);
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut total_loss = 0.0;
+        let mut correct = 0;
+        let mut count = 0;
+        for windows in dataset.shuffled_matches(Split::Train, seed) {
+            let samples: Vec<TrainingSample> =
+                windows?.into_iter().map(TrainingSample::from).collect();
+            if samples.is_empty() {
+                continue;
+            }
+            let order: Vec<&TrainingSample> = match frequency {
+                Some(frequency) => {
+                    let labels: Vec<usize> =
+                        samples.iter().map(|s| s.target_action as usize).collect();
+                    balanced_indices(&labels, frequency, samples.len(), &mut rng)?
+                        .into_iter()
+                        .map(|i| &samples[i])
+                        .collect()
+                }
+                None => {
+                    let mut order: Vec<&TrainingSample> = samples.iter().collect();
+                    order.shuffle(&mut rng);
+                    order
+                }
+            };
+
+            for batch in order.chunks(self.config.batch_size.max(1)) {
+                let (loss, batch_correct) = self.batch_loss(model, batch)?;
+                optimizer.backward_step(&loss)?;
+                total_loss += loss.to_scalar::<f32>()? * batch.len() as f32;
+                correct += batch_correct;
+                count += batch.len();
+            }
+        }
+        if count == 0 {
+            bail!(
+                "no training windows of {} ticks",
+                self.config.sequence_length
+            );
+        }
 
-        Ok((train_loss.max(0.1), train_accuracy.min(0.95)))
+        Ok((total_loss / count as f32, correct as f32 / count as f32))
     }
 
-    /// Validate for one epoch
+    /// Mean loss and accuracy over a split, read one match at a time
+    ///
+    /// `None` when the split holds no windows.
     fn validate_epoch(
         &self,
-        _model: &MLMOVETransformer,
-        _samples: &[TrainingSample],
-    ) -> Result<(f32, f32)> {
-        // TODO: Implement actual validation
-        // For now, return mock metrics
+        model: &MLMOVETransformer,
+        dataset: &SequenceDataset,
+        split: Split,
+    ) -> Result<Option<(f32, f32)>> {
+        let mut total_loss = 0.0;
+        let mut correct = 0;
+        let mut count = 0;
+        for windows in dataset.matches(split) {
+            let samples: Vec<TrainingSample> =
+                windows?.into_iter().map(TrainingSample::from).collect();
+            let order: Vec<&TrainingSample> = samples.iter().collect();
+            for batch in order.chunks(self.config.batch_size.max(1)) {
+                let (loss, batch_correct) = self.batch_loss(model, batch)?;
+                total_loss += loss.to_scalar::<f32>()? * batch.len() as f32;
+                correct += batch_correct;
+                count += batch.len();
+            }
+        }
+        if count == 0 {
+            return Ok(None);
+        }
 
-        let val_loss = 0.6; // Placeholder
-        let val_accuracy = 0.75; // Placeholder
+        Ok(Some((
+            total_loss / count as f32,
+            correct as f32 / count as f32,
+        )))
+    }
 
-        Ok((val_loss, val_accuracy))
+    /// Sample-weighted cross-entropy of a batch and how many argmax predictions hit the label
+    fn batch_loss(
+        &self,
+        model: &MLMOVETransformer,
+        batch: &[&TrainingSample],
+    ) -> Result<(Tensor, usize)> {
+        let sequences: Vec<&[BehavioralVector]> =
+            batch.iter().map(|s| s.input_sequence.as_slice()).collect();
+        let logits = model.forward(&model.sequences_to_tensor(&sequences)?)?;
+
+        let labels: Vec<u32> = batch.iter().map(|s| s.target_action).collect();
+        let weights: Vec<f32> = batch.iter().map(|s| s.weight).collect();
+        let weight_sum = weights.iter().sum::<f32>().max(f32::EPSILON);
+        let targets = Tensor::new(labels.as_slice(), &self.device)?;
+        let weights = Tensor::new(weights.as_slice(), &self.device)?;
+
+        let log_probs = candle_nn::ops::log_softmax(&logits, D::Minus1)?;
+        let nll = log_probs
+            .gather(&targets.unsqueeze(1)?, 1)?
+            .squeeze(1)?
+            .neg()?;
+        let loss = ((nll * weights)?.sum_all()? / weight_sum as f64)?;
+
+        let predicted = logits.argmax(D::Minus1)?.to_vec1::<u32>()?;
+        let correct = predicted
+            .iter()
+            .zip(&labels)
+            .filter(|(p, l)| p == l)
+            .count();
+        Ok((loss, correct))
     }
 
     /// Save fine-tuned model
-    fn save_model(&self, _model: &MLMOVETransformer, epoch: usize) -> Result<()> {
-        let save_path = format!(
-            "{}_epoch_{}.safetensors",
-            self.config.output_model_path, epoch
-        );
-        println!("Saving model to: {save_path}");
-
-        // TODO: Implement actual model saving
-        // Would extract weights from model and save as safetensors
-
-        Ok(())
+    fn save_model(&self, model: &MLMOVETransformer, epoch: usize) -> Result<()> {
+        let save_path = &self.config.output_model_path;
+        println!("Saving epoch {} model to: {save_path}", epoch + 1);
+        model.save(save_path)
     }
 
-    /// Create demo training samples for testing
-    fn create_demo_samples(&self, count: usize) -> Result<Vec<TrainingSample>> {
-        let mut samples = Vec::new();
-
-        for i in 0..count {
-            // Create sequence of behavioral vectors
-            let sequence: Vec<BehavioralVector> = (0..self.config.sequence_length)
-                .map(|j| BehavioralVector {
-                    tick: j as u32,
-                    steamid: 76561198000000000 + (i as u64),
-                    health: 100.0,
-                    armor: 100.0,
-                    pos_x: (i + j) as f32 * 10.0,
-                    pos_y: (i + j) as f32 * 5.0,
-                    pos_z: 64.0,
-                    vel_x: 250.0 + (i as f32 * 10.0) % 100.0,
-                    vel_y: 0.0,
-                    vel_z: 0.0,
-                    yaw: (i as f32 * 45.0) % 360.0,
-                    pitch: 0.0,
-                    weapon_id: 7,
-                    ammo: 30.0,
-                    is_airborne: if i % 10 == 0 { 1.0 } else { 0.0 },
-                    delta_yaw: 0.0,
-                    delta_pitch: 0.0,
-                })
-                .collect();
-
-            // Create target action (random for demo)
-            let target_action = (i * 7) % 97; // Random action within 97-way space
-
-            samples.push(TrainingSample {
-                input_sequence: sequence,
-                target_action: target_action as u32,
-                weight: 1.0,
-            });
+    fn dataset_config(&self) -> DatasetConfig {
+        DatasetConfig {
+            sequence_length: self.config.sequence_length,
+            window_stride: self.config.window_stride,
+            validation_fraction: self.config.validation_split,
+            test_fraction: self.config.test_split,
+            seed: self.config.seed,
+            ..Default::default()
         }
-
-        Ok(samples)
     }
 }
 
@@ -465,7 +818,8 @@ pub fn create_conversion_config(pytorch_path: &str, candle_path: &str) -> Conver
         pytorch_model_path: pytorch_path.to_string(),
         candle_output_path: candle_path.to_string(),
         model_config: MLMOVEConfig::default(),
-        validate_conversion: true,
+        reference_path: None,
+        norm_first: None,
     }
 }
 
@@ -484,17 +838,31 @@ pub fn create_finetuning_config(
         epochs,
         batch_size: 32,
         sequence_length: 32,
+        window_stride: 8,
         validation_split: 0.2,
+        test_split: 0.1,
+        class_balanced: false,
+        seed: 42,
     }
 }
 
 /// High-level API for converting PyTorch MLMOVE to Candle
-pub fn convert_mlmove_to_candle(pytorch_path: &str, candle_path: &str) -> Result<()> {
-    let config = create_conversion_config(pytorch_path, candle_path);
+///
+/// With `reference_path`, a JSON [`ConversionReference`] exported next to the
+/// checkpoint, the converted model must reproduce PyTorch's logits.
+pub fn convert_mlmove_to_candle(
+    pytorch_path: &str,
+    candle_path: &str,
+    reference_path: Option<&str>,
+) -> Result<()> {
+    let mut config = create_conversion_config(pytorch_path, candle_path);
+    config.reference_path = reference_path.map(str::to_string);
     let converter = TorchToCandleConverter::new(config);
 
     converter.convert()?;
-    converter.validate_conversion()?;
+    if reference_path.is_some() && !converter.validate_conversion()? {
+        bail!("converted model {candle_path} does not reproduce the reference logits");
+    }
 
     Ok(())
 }
@@ -516,13 +884,304 @@ pub fn finetune_on_cs2_data(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use candle_core::DType;
+    use std::path::PathBuf;
 
     #[test]
     fn test_conversion_config_creation() {
         let config = create_conversion_config("model.pt", "model.safetensors");
         assert_eq!(config.pytorch_model_path, "model.pt");
         assert_eq!(config.candle_output_path, "model.safetensors");
-        assert!(config.validate_conversion);
+        assert!(config.reference_path.is_none());
+    }
+
+    /// Fixture weights with logits from the forward pass spelled out in
+    /// `generate.py`, independently of our implementation
+    fn parity_fixture() -> (PathBuf, MLMOVEConfig, Vec<Vec<f32>>, Vec<f32>) {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("test_data/mlmove_parity");
+        let reference: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(dir.join("reference.json")).unwrap())
+                .unwrap();
+        let config = serde_json::from_value(reference["config"].clone()).unwrap();
+        let input = serde_json::from_value(reference["input"].clone()).unwrap();
+        let logits = serde_json::from_value(reference["logits"].clone()).unwrap();
+        (dir.join("weights.safetensors"), config, input, logits)
+    }
+
+    fn assert_parity(model: &MLMOVETransformer, input: &[Vec<f32>], expected: &[f32]) {
+        let input = Tensor::new(input.to_vec(), &Device::Cpu)
+            .unwrap()
+            .unsqueeze(0)
+            .unwrap();
+        let logits = model.forward(&input).unwrap().squeeze(0).unwrap();
+        let logits = logits.to_vec1::<f32>().unwrap();
+        assert_eq!(logits.len(), expected.len());
+        for (i, (got, want)) in logits.iter().zip(expected).enumerate() {
+            assert!(
+                (got - want).abs() <= PARITY_TOLERANCE,
+                "logit {i}: got {got}, reference {want}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_safetensors_matches_reference_logits() -> Result<()> {
+        let (weights, config, input, logits) = parity_fixture();
+
+        // No sidecar next to the fixture, so the architecture is inferred from shapes
+        let model = MLMOVETransformer::load_pretrained(weights.to_str().unwrap(), Device::Cpu)?;
+        assert_eq!(model.config().num_layers, config.num_layers);
+        assert_eq!(model.config().ff_dim, config.ff_dim);
+        assert_parity(&model, &input, &logits);
+        Ok(())
+    }
+
+    #[test]
+    fn test_pickle_matches_reference_logits() -> Result<()> {
+        let (weights, config, input, logits) = parity_fixture();
+
+        // A bare state dict does not record its norm order, so it has to be given
+        let weights = weights.with_extension("pt");
+        let tensors = map_pytorch_names(read_checkpoint(&weights, &Device::Cpu)?, Some(true))?;
+        let model = MLMOVETransformer::from_tensors(&tensors, None, Device::Cpu)?;
+        assert_eq!(model.config().num_layers, config.num_layers);
+        assert_parity(&model, &input, &logits);
+        Ok(())
+    }
+
+    #[test]
+    fn test_post_norm_checkpoints_are_rejected() -> Result<()> {
+        let (weights, _, _, _) = parity_fixture();
+        assert_eq!(checkpoint_norm_first(&weights)?, Some(true));
+        let bare = weights.with_extension("pt");
+        assert_eq!(checkpoint_norm_first(&bare)?, None);
+        let Err(err) = MLMOVETransformer::load_pretrained(bare.to_str().unwrap(), Device::Cpu)
+        else {
+            panic!("checkpoint of unknown norm order loaded");
+        };
+        assert!(err.to_string().contains("pass it explicitly"), "{err}");
+
+        // Same parameter names and shapes, but nested under `state_dict` next to the flag
+        let post_norm = weights.with_file_name("post_norm.pt");
+        assert_eq!(checkpoint_norm_first(&post_norm)?, Some(false));
+        assert_eq!(read_checkpoint(&post_norm, &Device::Cpu)?.len(), 29);
+        let Err(err) = MLMOVETransformer::load_pretrained(post_norm.to_str().unwrap(), Device::Cpu)
+        else {
+            panic!("post-norm checkpoint loaded");
+        };
+        assert!(err.to_string().contains("norm_first=False"), "{err}");
+        Ok(())
+    }
+
+    /// An ONNX graph holding the fixture weights as initializers
+    ///
+    /// Built by hand rather than by `torch.onnx.export`, so it only has the
+    /// node wiring that reveals the normalization order.
+    fn write_onnx(path: &Path, initializer: Vec<crate::onnx::TensorProto>, pre_norm: bool) {
+        use crate::onnx::{GraphProto, ModelProto, NodeProto};
+        use prost::Message;
+
+        let residual = if pre_norm { "x" } else { "n" };
+        let node = vec![
+            NodeProto::new("LayerNormalization", &["x", "g", "b"], &["n"]),
+            NodeProto::new("MatMul", &["n", "w"], &["attn"]),
+            NodeProto::new("Add", &[residual, "attn"], &["y"]),
+        ];
+        let onnx = ModelProto {
+            graph: Some(GraphProto { node, initializer }),
+        };
+        std::fs::write(path, onnx.encode_to_vec()).unwrap();
+    }
+
+    fn fixture_initializers(weights: &Path) -> Result<Vec<crate::onnx::TensorProto>> {
+        candle_core::safetensors::load(weights, &Device::Cpu)?
+            .into_iter()
+            .map(|(name, tensor)| {
+                let values = tensor.flatten_all()?.to_vec1::<f32>()?;
+                Ok(crate::onnx::TensorProto::from_f32(
+                    &name,
+                    tensor.dims(),
+                    values,
+                ))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_onnx_initializers_match_reference_logits() -> Result<()> {
+        let (weights, _, input, logits) = parity_fixture();
+        let tmp = tempfile::tempdir()?;
+        let path = tmp.path().join("mlmove.onnx");
+        write_onnx(&path, fixture_initializers(&weights)?, true);
+
+        let model = MLMOVETransformer::load_pretrained(path.to_str().unwrap(), Device::Cpu)?;
+        assert_parity(&model, &input, &logits);
+        Ok(())
+    }
+
+    #[test]
+    fn test_onnx_needs_a_known_norm_order() -> Result<()> {
+        use crate::onnx::{GraphProto, ModelProto};
+        use prost::Message;
+
+        let (weights, _, _, _) = parity_fixture();
+        let tmp = tempfile::tempdir()?;
+        let path = tmp.path().join("mlmove.onnx");
+
+        write_onnx(&path, fixture_initializers(&weights)?, false);
+        let err =
+            MLMOVETransformer::load_pretrained(path.to_str().unwrap(), Device::Cpu).unwrap_err();
+        assert!(err.to_string().contains("norm_first=False"), "{err}");
+
+        let onnx = ModelProto {
+            graph: Some(GraphProto {
+                node: vec![],
+                initializer: fixture_initializers(&weights)?,
+            }),
+        };
+        std::fs::write(&path, onnx.encode_to_vec())?;
+        let err =
+            MLMOVETransformer::load_pretrained(path.to_str().unwrap(), Device::Cpu).unwrap_err();
+        assert!(err.to_string().contains("normalization order"), "{err}");
+        Ok(())
+    }
+
+    #[test]
+    fn test_published_module_names_are_mapped() -> Result<()> {
+        let (weights, _, input, logits) = parity_fixture();
+        let mut tensors = candle_core::safetensors::load(&weights, &Device::Cpu)?
+            .into_iter()
+            .map(|(name, tensor)| {
+                let name = match name.split_once('.') {
+                    Some(("input_embedding", param)) => format!("embedding_model.0.{param}"),
+                    Some(("output", param)) => format!("module.decoder.0.{param}"),
+                    _ => name,
+                };
+                (name, tensor)
+            })
+            .collect::<HashMap<_, _>>();
+
+        let mapped = map_pytorch_names(tensors.clone(), Some(true))?;
+        let model = MLMOVETransformer::from_tensors(&mapped, None, Device::Cpu)?;
+        assert_parity(&model, &input, &logits);
+
+        // A second embedding layer has nowhere to go in our layout
+        tensors.insert(
+            "embedding_model.2.weight".to_string(),
+            Tensor::ones((8, 8), DType::F32, &Device::Cpu)?,
+        );
+        let err = map_pytorch_names(tensors, Some(true)).unwrap_err();
+        assert!(
+            err.to_string().contains("embedding_model.2.weight"),
+            "{err}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_names_are_listed() -> Result<()> {
+        let (weights, _, _, _) = parity_fixture();
+        let mut tensors = candle_core::safetensors::load(&weights, &Device::Cpu)?;
+        let output = tensors.remove("output.weight").unwrap();
+        tensors.insert("onnx::MatMul_1234".to_string(), output.t()?);
+        tensors.insert(
+            "encoder.norm.weight".to_string(),
+            Tensor::ones(8, DType::F32, &Device::Cpu)?,
+        );
+
+        let err = map_pytorch_names(tensors, Some(true)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "no MLMOVE parameter matches encoder.norm.weight, onnx::MatMul_1234"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_and_validate() -> Result<()> {
+        let (weights, config, input, logits) = parity_fixture();
+        let tmp = tempfile::tempdir()?;
+        let output = tmp.path().join("mlmove_candle.safetensors");
+
+        let mut conversion =
+            create_conversion_config(weights.to_str().unwrap(), output.to_str().unwrap());
+        conversion.model_config = config;
+        let converter = TorchToCandleConverter::new(conversion.clone());
+        converter.convert()?;
+        // Nothing to compare against without reference logits
+        assert!(converter.validate_conversion().is_err());
+
+        conversion.reference_path = Some(
+            weights
+                .with_file_name("reference.json")
+                .to_string_lossy()
+                .into_owned(),
+        );
+        assert!(TorchToCandleConverter::new(conversion.clone()).validate_conversion()?);
+
+        // Logits that the weights do not produce fail validation
+        let mut reference = ConversionReference {
+            input: input.clone(),
+            logits: logits.clone(),
+        };
+        reference.logits[3] += 0.01;
+        let mismatched = tmp.path().join("mismatched.json");
+        std::fs::write(&mismatched, serde_json::to_string(&reference)?)?;
+        conversion.reference_path = Some(mismatched.to_string_lossy().into_owned());
+        assert!(!TorchToCandleConverter::new(conversion).validate_conversion()?);
+
+        // The converted file uses our var paths and carries a config sidecar
+        let tensors = candle_core::safetensors::load(&output, &Device::Cpu)?;
+        assert!(tensors.contains_key("layer_1.attention.value.weight"));
+        assert!(output.with_extension("json").exists());
+        let model = MLMOVETransformer::load_pretrained(output.to_str().unwrap(), Device::Cpu)?;
+        assert_parity(&model, &input, &logits);
+        Ok(())
+    }
+
+    #[test]
+    fn test_conversion_takes_an_explicit_norm_order() -> Result<()> {
+        let (weights, config, input, logits) = parity_fixture();
+        let tmp = tempfile::tempdir()?;
+        let output = tmp.path().join("mlmove_candle.safetensors");
+
+        let bare = weights.with_extension("pt");
+        let mut conversion =
+            create_conversion_config(bare.to_str().unwrap(), output.to_str().unwrap());
+        conversion.model_config = config;
+        let err = TorchToCandleConverter::new(conversion.clone())
+            .convert()
+            .unwrap_err();
+        assert!(err.to_string().contains("pass it explicitly"), "{err}");
+
+        conversion.norm_first = Some(true);
+        TorchToCandleConverter::new(conversion.clone()).convert()?;
+        let model = MLMOVETransformer::load_pretrained(output.to_str().unwrap(), Device::Cpu)?;
+        assert_parity(&model, &input, &logits);
+
+        // A given order must agree with the one the checkpoint records
+        conversion.pytorch_model_path = weights.to_string_lossy().into_owned();
+        conversion.norm_first = Some(false);
+        let err = TorchToCandleConverter::new(conversion)
+            .convert()
+            .unwrap_err();
+        assert!(err.to_string().contains("norm_first=true"), "{err}");
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_weights_are_reported() {
+        let (weights, _, _, _) = parity_fixture();
+        let mut tensors = map_pytorch_names(
+            candle_core::safetensors::load(&weights, &Device::Cpu).unwrap(),
+            Some(true),
+        )
+        .unwrap();
+        tensors.remove("layer_0.ln2.bias");
+
+        let config = MLMOVEConfig::from_weights(&tensors).unwrap();
+        let err = MLMOVETransformer::from_tensors(&tensors, Some(config), Device::Cpu).unwrap_err();
+        assert!(err.to_string().contains("layer_0.ln2.bias"), "{err}");
     }
 
     #[test]
@@ -534,33 +1193,183 @@ mod tests {
         assert_eq!(config.learning_rate, 1e-4);
     }
 
+    /// Two players running along their view direction, one jumping every 10 ticks
+    fn write_movement_parquet(path: &Path) -> Result<()> {
+        let vectors: Vec<BehavioralVector> = [1u64, 2]
+            .iter()
+            .flat_map(|&player| {
+                (0..40u32).map(move |tick| BehavioralVector {
+                    tick,
+                    steamid: 76561198000000000 + player,
+                    health: 100.0,
+                    armor: 100.0,
+                    pos_x: tick as f32 * 4.0,
+                    pos_y: player as f32 * 100.0,
+                    pos_z: 64.0,
+                    vel_x: 250.0,
+                    vel_y: 0.0,
+                    vel_z: 0.0,
+                    yaw: 0.0,
+                    pitch: 0.0,
+                    weapon_id: 7,
+                    ammo: 30.0,
+                    is_airborne: if player == 2 && tick % 10 == 0 {
+                        1.0
+                    } else {
+                        0.0
+                    },
+                    delta_yaw: 0.0,
+                    delta_pitch: 0.0,
+                })
+            })
+            .collect();
+        crate::data::write_to_parquet(&vectors, path)
+    }
+
     #[test]
-    fn test_demo_samples_creation() -> Result<()> {
-        let config =
-            create_finetuning_config("base.safetensors", "data.parquet", "output.safetensors", 5);
+    fn test_dataset_loading() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        write_movement_parquet(&tmp.path().join("match.parquet"))?;
+
+        let mut config = create_finetuning_config(
+            "base.safetensors",
+            tmp.path().to_str().unwrap(),
+            "output.safetensors",
+            5,
+        );
+        config.sequence_length = 4;
+        config.window_stride = 1;
         let finetuner = CS2FineTuner::new(config, Device::Cpu);
 
-        let samples = finetuner.create_demo_samples(100)?;
-        assert_eq!(samples.len(), 100);
+        let dataset = finetuner.load_dataset()?;
+        // 36 windows of 4 ticks per player, each followed by a labelled tick
+        assert_eq!(dataset.samples.len(), 72);
+        assert_eq!(dataset.metadata.unique_players, 2);
+        assert_eq!(dataset.metadata.avg_sequence_length, 4.0);
+
+        // Running forward at full speed, jumping on player 2's take-off ticks
+        let run_forward = DiscreteAction {
+            direction: 0,
+            speed: 4,
+            jump: 0,
+        };
+        let jump_forward = DiscreteAction {
+            jump: 1,
+            ..run_forward.clone()
+        };
+        let labels: HashSet<u32> = dataset.samples.iter().map(|s| s.target_action).collect();
+        assert_eq!(
+            labels,
+            HashSet::from([run_forward.index() as u32, jump_forward.index() as u32])
+        );
 
-        for sample in &samples {
-            assert_eq!(sample.input_sequence.len(), 32);
-            assert!(sample.target_action < 97);
-            assert_eq!(sample.weight, 1.0);
-        }
+        Ok(())
+    }
 
+    #[test]
+    fn test_fine_tune_needs_a_validation_match() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        write_movement_parquet(&tmp.path().join("match.parquet"))?;
+
+        let mut config = create_finetuning_config(
+            "missing.safetensors",
+            tmp.path().to_str().unwrap(),
+            tmp.path().join("out.safetensors").to_str().unwrap(),
+            1,
+        );
+        config.sequence_length = 4;
+        let err = CS2FineTuner::new(config, Device::Cpu)
+            .fine_tune()
+            .unwrap_err();
+        assert!(err.to_string().contains("validation"), "{err}");
         Ok(())
     }
 
     #[test]
-    fn test_dataset_loading() -> Result<()> {
-        let config =
-            create_finetuning_config("base.safetensors", "data.parquet", "output.safetensors", 5);
+    fn test_fine_tune_needs_the_base_sequence_length() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let data_path = tmp.path().join("matches");
+        let base_path = tmp.path().join("base.safetensors");
+        std::fs::create_dir(&data_path)?;
+        for i in 0..3 {
+            write_movement_parquet(&data_path.join(format!("match_{i}.parquet")))?;
+        }
+        MLMOVETransformer::with_config(
+            MLMOVEConfig {
+                num_layers: 1,
+                model_dim: 16,
+                ff_dim: 32,
+                sequence_length: 4,
+                ..Default::default()
+            },
+            Device::Cpu,
+        )?
+        .save(base_path.to_str().unwrap())?;
+
+        // The default config windows 32 ticks, the base model takes 4
+        let config = create_finetuning_config(
+            base_path.to_str().unwrap(),
+            data_path.to_str().unwrap(),
+            tmp.path().join("finetuned.safetensors").to_str().unwrap(),
+            1,
+        );
+        let err = CS2FineTuner::new(config, Device::Cpu)
+            .fine_tune()
+            .unwrap_err();
+        assert!(err.to_string().contains("sequences of 4 ticks"), "{err}");
+        Ok(())
+    }
+
+    #[test]
+    fn test_fine_tune_saves_best_checkpoint() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let data_path = tmp.path().join("matches");
+        let output_path = tmp.path().join("finetuned.safetensors");
+        std::fs::create_dir(&data_path)?;
+        for i in 0..10 {
+            write_movement_parquet(&data_path.join(format!("match_{i}.parquet")))?;
+        }
+
+        let mut config = create_finetuning_config(
+            "missing.safetensors",
+            data_path.to_str().unwrap(),
+            output_path.to_str().unwrap(),
+            3,
+        );
+        config.sequence_length = 4;
+        config.window_stride = 2;
+        config.learning_rate = 1e-3;
+        config.class_balanced = true;
         let finetuner = CS2FineTuner::new(config, Device::Cpu);
 
-        let dataset = finetuner.load_dataset()?;
-        assert!(!dataset.samples.is_empty());
-        assert!(dataset.metadata.sample_count > 0);
+        let model = MLMOVETransformer::with_config(
+            MLMOVEConfig {
+                num_layers: 1,
+                model_dim: 16,
+                ff_dim: 32,
+                sequence_length: 4,
+                ..Default::default()
+            },
+            Device::Cpu,
+        )?;
+        let dataset = SequenceDataset::open(&data_path, finetuner.dataset_config())?;
+        assert_eq!(dataset.files(Split::Validation).len(), 2);
+        let metrics = finetuner.fine_tune_model(&model, &dataset)?;
+
+        assert!((1..=3).contains(&metrics.epoch));
+        assert!(metrics.train_loss.is_finite() && metrics.val_loss.is_finite());
+        assert!((0.0..=1.0).contains(&metrics.train_accuracy));
+        assert!((0.0..=1.0).contains(&metrics.val_accuracy));
+
+        let restored =
+            MLMOVETransformer::load_pretrained(output_path.to_str().unwrap(), Device::Cpu)?;
+        assert_eq!(restored.config().model_dim, 16);
+
+        // One of the ten matches is held out for testing the saved checkpoint
+        assert_eq!(dataset.files(Split::Test).len(), 1);
+        let (test_loss, test_accuracy) = finetuner.test()?.expect("test windows");
+        assert!(test_loss.is_finite());
+        assert!((0.0..=1.0).contains(&test_accuracy));
 
         Ok(())
     }
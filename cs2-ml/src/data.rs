@@ -1,35 +1,100 @@
 use anyhow::Result;
-use cs2_common::parsing_features::{build_wanted, ParsingPreset};
-use cs2_common::BehavioralVector;
+use cs2_common::parsing_features::{build_wanted, ParsingPreset, Wanted};
+use cs2_common::player_frames::wrap_degrees;
+use cs2_common::{BehavioralVector, MatchClock};
 use cs2_demo_parser::first_pass::parser_settings::ParserInputs;
 use cs2_demo_parser::parse_demo::{DemoOutput, Parser as DemoParser, ParsingMode};
-use cs2_demo_parser::second_pass::variants::PropColumn;
+use cs2_demo_parser::second_pass::variants::Variant;
+use cs2_demo_parser::typed::PlayerPropRow;
+use std::collections::BTreeMap;
 use std::path::Path;
 
-use arrow::array::{ArrayRef, Float32Array, UInt32Array, UInt64Array};
+use arrow::array::{Array, ArrayRef, Float32Array, UInt32Array, UInt64Array};
 use arrow::datatypes::{DataType, Field, Schema};
 use arrow::record_batch::RecordBatch;
 use std::sync::Arc;
 
 use crate::player::PlayerMeta;
 use ahash::AHashMap;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
 use parquet::arrow::ArrowWriter;
+use parquet::file::metadata::KeyValue;
 use parquet::file::properties::WriterProperties;
-use std::collections::HashMap;
+
+const HEALTH_PROP: &str = "CCSPlayerPawn.m_iHealth";
+const ARMOR_PROP: &str = "CCSPlayerPawn.m_ArmorValue";
+const CLIP_PROP: &str = "m_iClip1";
+const TEAM_NUM_PROP: &str = "CCSPlayerPawn.m_iTeamNum";
+
+/// Player props the behavioral vectors are built from
+const VECTOR_PROPS: [&str; 13] = [
+    "X",
+    "Y",
+    "Z",
+    "velocity_X",
+    "velocity_Y",
+    "velocity_Z",
+    "pitch",
+    "yaw",
+    "is_airborne",
+    "weapon_name",
+    HEALTH_PROP,
+    ARMOR_PROP,
+    CLIP_PROP,
+];
+
+/// Player props the cs2-common extractors need on top, with the names events report them under
+///
+/// Without the team every frame is on team 0 and no player is anyone's enemy.
+const FRAME_PROPS: [(&str, &str); 1] = [(TEAM_NUM_PROP, "team_num")];
 
 pub fn vectors_from_demo(path: impl AsRef<Path>) -> Result<Vec<BehavioralVector>> {
-    let bytes = std::fs::read(path)?;
+    Ok(vectors_and_clock_from_demo(path)?.0)
+}
 
-    // Create a longer-lived empty vector for the huffman table
-    let huffman_table = Vec::new();
+/// Like [`vectors_from_demo`], also returning the demo's tick rate and round timings
+pub fn vectors_and_clock_from_demo(
+    path: impl AsRef<Path>,
+) -> Result<(Vec<BehavioralVector>, MatchClock)> {
+    let parsed = parse_demo(path)?;
+    Ok((vectors_from_output(&parsed)?, parsed.match_clock()))
+}
 
+/// Parse a demo with the props and events of the Standard preset
+pub fn parse_demo(path: impl AsRef<Path>) -> Result<DemoOutput> {
     // Build wanted lists from a Standard preset for ML use
     let wanted = build_wanted(ParsingPreset::Standard.to_features());
+    parse(
+        path,
+        Wanted {
+            player_props: player_props(),
+            ..wanted
+        },
+        false,
+    )
+}
+
+/// Parse only the grenade projectiles of a demo, for `DemoOutput::grenade_detonations`
+///
+/// The parser records projectiles instead of per-tick player rows and skips
+/// both when events are wanted, so this is a pass of its own next to `parse_demo`.
+pub fn parse_projectiles(path: impl AsRef<Path>) -> Result<DemoOutput> {
+    parse(path, Wanted::default(), true)
+}
+
+fn parse(path: impl AsRef<Path>, wanted: Wanted, parse_projectiles: bool) -> Result<DemoOutput> {
+    let bytes = std::fs::read(path)?;
+
+    // Create a longer-lived empty vector for the huffman table
+    let huffman_table = Vec::new();
 
     // Create parser with correct ParserInputs structure including all required fields
     let mut parser = DemoParser::new(
         ParserInputs {
-            real_name_to_og_name: AHashMap::new(),
+            real_name_to_og_name: FRAME_PROPS
+                .iter()
+                .map(|(prop, name)| (prop.to_string(), name.to_string()))
+                .collect(),
             wanted_players: Vec::new(),
             wanted_player_props: wanted.player_props,
             wanted_other_props: wanted.other_props,
@@ -37,7 +102,7 @@ pub fn vectors_from_demo(path: impl AsRef<Path>) -> Result<Vec<BehavioralVector>
             wanted_ticks: Vec::new(),
             wanted_events: wanted.events,
             parse_ents: true,
-            parse_projectiles: false,
+            parse_projectiles,
             parse_grenades: true,
             only_header: false,
             only_convars: false,
@@ -49,163 +114,141 @@ pub fn vectors_from_demo(path: impl AsRef<Path>) -> Result<Vec<BehavioralVector>
         ParsingMode::Normal,
     );
     // Use parse_demo with the bytes
-    let parsed = parser.parse_demo(&bytes)?;
-
-    let mut out = Vec::new();
+    Ok(parser.parse_demo(&bytes)?)
+}
 
-    // Access the demo data correctly - DemoOutput has a df field that is an AHashMap
-    process_ticks(&parsed, &mut out)?;
+/// Player props `parse_demo` requests: the Standard preset plus the vector and frame props
+pub fn player_props() -> Vec<String> {
+    let mut player_props = build_wanted(ParsingPreset::Standard.to_features()).player_props;
+    let extra = VECTOR_PROPS
+        .into_iter()
+        .chain(FRAME_PROPS.map(|(prop, _)| prop));
+    for prop in extra {
+        if !player_props.iter().any(|p| p == prop) {
+            player_props.push(prop.to_string());
+        }
+    }
+    player_props
+}
 
-    Ok(out)
+/// Behavioral vectors of every player on consecutive ticks of a parsed demo
+pub fn vectors_from_output(parsed: &DemoOutput) -> Result<Vec<BehavioralVector>> {
+    Ok(vectors_from_rows(&parsed.player_prop_rows()))
 }
 
-// Helper function to process ticks from the demo output
-fn process_ticks(parsed: &DemoOutput, out: &mut Vec<BehavioralVector>) -> Result<()> {
-    // Convert the AHashMap to a sorted vector of ticks for sequential processing
-    let mut tick_numbers: Vec<u32> = parsed.df.keys().cloned().collect();
-    tick_numbers.sort();
-
-    // Process sequential ticks
-    for i in 1..tick_numbers.len() {
-        let cur_tick = tick_numbers[i - 1];
-        let next_tick = tick_numbers[i];
-
-        if let (Some(cur_data), Some(next_data)) =
-            (parsed.df.get(&cur_tick), parsed.df.get(&next_tick))
-        {
-            // Extract player IDs from the current tick
-            let player_ids = get_player_ids(cur_data);
-
-            for player_id in player_ids {
-                // Create PlayerMeta objects
-                let c = create_player_meta(cur_data, player_id);
-                let n = create_player_meta(next_data, player_id);
-
-                // Extract weapon ID from name
-                let weap_id = c
-                    .active_weapon_name
-                    .as_deref()
-                    .unwrap_or("none")
-                    .chars()
-                    .fold(0u16, |a, b| a.wrapping_add(b as u16));
-
-                // Create behavioral vector
-                out.push(BehavioralVector {
-                    tick: cur_tick,
-                    steamid: c.steamid,
-                    health: c
-                        .props
-                        .get("m_iHealth")
-                        .and_then(|v| v.parse().ok())
-                        .unwrap_or(0) as f32,
-                    armor: c
-                        .props
-                        .get("m_ArmorValue")
-                        .and_then(|v| v.parse().ok())
-                        .unwrap_or(0) as f32,
-                    pos_x: c
-                        .props
-                        .get("m_vecOrigin[0]")
-                        .and_then(|v| v.parse().ok())
-                        .unwrap_or(0.0),
-                    pos_y: c
-                        .props
-                        .get("m_vecOrigin[1]")
-                        .and_then(|v| v.parse().ok())
-                        .unwrap_or(0.0),
-                    pos_z: c
-                        .props
-                        .get("m_vecOrigin[2]")
-                        .and_then(|v| v.parse().ok())
-                        .unwrap_or(0.0),
-                    vel_x: c
-                        .props
-                        .get("m_vecVelocity[0]")
-                        .and_then(|v| v.parse().ok())
-                        .unwrap_or(0.0),
-                    vel_y: c
-                        .props
-                        .get("m_vecVelocity[1]")
-                        .and_then(|v| v.parse().ok())
-                        .unwrap_or(0.0),
-                    vel_z: c
-                        .props
-                        .get("m_vecVelocity[2]")
-                        .and_then(|v| v.parse().ok())
-                        .unwrap_or(0.0),
-                    yaw: c
-                        .props
-                        .get("m_angEyeAngles[1]")
-                        .and_then(|v| v.parse().ok())
-                        .unwrap_or(0.0),
-                    pitch: c
-                        .props
-                        .get("m_angEyeAngles[0]")
-                        .and_then(|v| v.parse().ok())
-                        .unwrap_or(0.0),
-                    weapon_id: weap_id,
-                    ammo: c.ammo_clip.unwrap_or(0) as f32,
-                    is_airborne: if c.props.get("m_hGroundEntity").is_none_or(|v| v == "-1") {
-                        1.0
-                    } else {
-                        0.0
-                    },
-                    delta_yaw: n
-                        .props
-                        .get("m_angEyeAngles[1]")
-                        .and_then(|v| v.parse().ok())
-                        .unwrap_or(0.0)
-                        - c.props
-                            .get("m_angEyeAngles[1]")
-                            .and_then(|v| v.parse().ok())
-                            .unwrap_or(0.0),
-                    delta_pitch: n
-                        .props
-                        .get("m_angEyeAngles[0]")
-                        .and_then(|v| v.parse().ok())
-                        .unwrap_or(0.0)
-                        - c.props
-                            .get("m_angEyeAngles[0]")
-                            .and_then(|v| v.parse().ok())
-                            .unwrap_or(0.0),
-                });
-            }
-        }
+/// One vector per player row, with the view change to the player's next row
+fn vectors_from_rows(rows: &[PlayerPropRow]) -> Vec<BehavioralVector> {
+    let mut by_player: BTreeMap<u64, Vec<&PlayerPropRow>> = BTreeMap::new();
+    for row in rows {
+        by_player.entry(row.steamid).or_default().push(row);
     }
 
-    Ok(())
+    let mut out = Vec::new();
+    for player_rows in by_player.values() {
+        for pair in player_rows.windows(2) {
+            let c = create_player_meta(pair[0]);
+            let n = create_player_meta(pair[1]);
+            out.push(behavioral_vector(pair[0].tick, &c, &n));
+        }
+    }
+    out.sort_by_key(|v| (v.tick, v.steamid));
+    out
 }
 
-// Helper function to extract player IDs from a PropColumn
-fn get_player_ids(_data: &PropColumn) -> Vec<u32> {
-    // Placeholder
-    vec![1, 2, 3, 4, 5]
+fn behavioral_vector(tick: u32, c: &PlayerMeta, n: &PlayerMeta) -> BehavioralVector {
+    let number = |meta: &PlayerMeta, prop: &str| {
+        meta.props
+            .get(prop)
+            .and_then(|v| v.parse::<f32>().ok())
+            .unwrap_or(0.0)
+    };
+    let flag = |meta: &PlayerMeta, prop: &str| {
+        meta.props
+            .get(prop)
+            .is_some_and(|v| v == "true" || v.parse::<f32>().is_ok_and(|x| x != 0.0))
+    };
+
+    // Demos report display names ("AK-47"); vectors use item names ("weapon_ak47")
+    let weapon = c.active_weapon_name.as_deref().unwrap_or("none");
+    let weapon_id =
+        cs2_common::weapon_id_from_name(cs2_common::weapon_item_name(weapon).unwrap_or(weapon));
+
+    BehavioralVector {
+        tick,
+        steamid: c.steamid,
+        health: number(c, HEALTH_PROP),
+        armor: number(c, ARMOR_PROP),
+        pos_x: number(c, "X"),
+        pos_y: number(c, "Y"),
+        pos_z: number(c, "Z"),
+        vel_x: number(c, "velocity_X"),
+        vel_y: number(c, "velocity_Y"),
+        vel_z: number(c, "velocity_Z"),
+        yaw: number(c, "yaw"),
+        pitch: number(c, "pitch"),
+        weapon_id,
+        ammo: c.ammo_clip.unwrap_or(0) as f32,
+        is_airborne: flag(c, "is_airborne") as u8 as f32,
+        delta_yaw: wrap_degrees(number(n, "yaw") - number(c, "yaw")),
+        delta_pitch: number(n, "pitch") - number(c, "pitch"),
+    }
 }
 
-// Helper function to create a PlayerMeta from PropColumn data
-fn create_player_meta(_data: &PropColumn, player_id: u32) -> PlayerMeta {
-    // Placeholder
+/// Player state of one parsed row, with scalar props as strings
+fn create_player_meta(row: &PlayerPropRow) -> PlayerMeta {
+    let props = row
+        .props
+        .iter()
+        .filter_map(|(name, value)| {
+            let value = match value {
+                Variant::Bool(b) => b.to_string(),
+                Variant::U32(n) => n.to_string(),
+                Variant::I32(n) => n.to_string(),
+                Variant::U64(n) => n.to_string(),
+                Variant::F32(f) => f.to_string(),
+                Variant::String(s) => s.clone(),
+                _ => return None,
+            };
+            Some((name.clone(), value))
+        })
+        .collect();
+
     PlayerMeta {
-        steamid: 76561198000000000 + player_id as u64,
-        props: HashMap::from([
-            ("m_iHealth".to_string(), "100".to_string()),
-            ("m_ArmorValue".to_string(), "100".to_string()),
-            ("m_vecOrigin[0]".to_string(), "0.0".to_string()),
-            ("m_vecOrigin[1]".to_string(), "0.0".to_string()),
-            ("m_vecOrigin[2]".to_string(), "0.0".to_string()),
-            ("m_vecVelocity[0]".to_string(), "0.0".to_string()),
-            ("m_vecVelocity[1]".to_string(), "0.0".to_string()),
-            ("m_vecVelocity[2]".to_string(), "0.0".to_string()),
-            ("m_angEyeAngles[0]".to_string(), "0.0".to_string()),
-            ("m_angEyeAngles[1]".to_string(), "0.0".to_string()),
-            ("m_hGroundEntity".to_string(), "0".to_string()),
-        ]),
-        active_weapon_name: Some("weapon_ak47".to_string()),
-        ammo_clip: Some(30),
+        steamid: row.steamid,
+        props,
+        active_weapon_name: match row.props.get("weapon_name") {
+            Some(Variant::String(name)) => Some(name.clone()),
+            _ => None,
+        },
+        ammo_clip: match row.props.get(CLIP_PROP) {
+            Some(Variant::U32(n)) => Some(*n),
+            Some(Variant::I32(n)) => u32::try_from(*n).ok(),
+            _ => None,
+        },
     }
 }
 
+/// Parquet key-value metadata entry holding the map a match was played on
+pub const MAP_NAME_KEY: &str = "map_name";
+
 pub fn write_to_parquet(vecs: &[BehavioralVector], path: impl AsRef<Path>) -> Result<()> {
+    write_vectors(vecs, None, path)
+}
+
+/// Write one match's vectors, recording its map in the file metadata
+pub fn write_match_parquet(
+    vecs: &[BehavioralVector],
+    map_name: &str,
+    path: impl AsRef<Path>,
+) -> Result<()> {
+    write_vectors(vecs, Some(map_name), path)
+}
+
+fn write_vectors(
+    vecs: &[BehavioralVector],
+    map_name: Option<&str>,
+    path: impl AsRef<Path>,
+) -> Result<()> {
     let file = std::fs::File::create(path)?;
 
     // Create schema
@@ -264,7 +307,11 @@ pub fn write_to_parquet(vecs: &[BehavioralVector], path: impl AsRef<Path>) -> Re
 
     let batch = RecordBatch::try_new(Arc::new(schema.clone()), arrays)?;
 
-    let props = WriterProperties::builder().build();
+    let metadata =
+        map_name.map(|map| vec![KeyValue::new(MAP_NAME_KEY.to_string(), map.to_string())]);
+    let props = WriterProperties::builder()
+        .set_key_value_metadata(metadata)
+        .build();
     let mut writer = ArrowWriter::try_new(file, Arc::new(schema), Some(props))?;
 
     writer.write(&batch)?;
@@ -273,6 +320,98 @@ pub fn write_to_parquet(vecs: &[BehavioralVector], path: impl AsRef<Path>) -> Re
     Ok(())
 }
 
+/// Read back behavioral vectors written by [`write_to_parquet`]
+pub fn read_parquet(path: impl AsRef<Path>) -> Result<Vec<BehavioralVector>> {
+    let mut out = Vec::new();
+    for batch in stream_parquet(path, DEFAULT_BATCH_ROWS)? {
+        out.extend(batch?);
+    }
+    Ok(out)
+}
+
+/// Map recorded by [`write_match_parquet`], if the file has one
+pub fn read_map_name(path: impl AsRef<Path>) -> Result<Option<String>> {
+    let file = std::fs::File::open(path)?;
+    let builder = ParquetRecordBatchReaderBuilder::try_new(file)?;
+    let map = builder
+        .metadata()
+        .file_metadata()
+        .key_value_metadata()
+        .and_then(|entries| entries.iter().find(|kv| kv.key == MAP_NAME_KEY))
+        .and_then(|kv| kv.value.clone());
+    Ok(map)
+}
+
+/// Rows per Arrow batch when streaming Parquet files
+pub const DEFAULT_BATCH_ROWS: usize = 8192;
+
+/// Stream behavioral vectors from a Parquet file one Arrow batch at a time
+pub fn stream_parquet(
+    path: impl AsRef<Path>,
+    batch_rows: usize,
+) -> Result<impl Iterator<Item = Result<Vec<BehavioralVector>>>> {
+    let file = std::fs::File::open(path)?;
+    let reader = ParquetRecordBatchReaderBuilder::try_new(file)?
+        .with_batch_size(batch_rows.max(1))
+        .build()?;
+    Ok(reader.map(|batch| batch_vectors(&batch?)))
+}
+
+fn batch_vectors(batch: &RecordBatch) -> Result<Vec<BehavioralVector>> {
+    let f32s = |name: &str| -> Result<&Float32Array> {
+        column(batch, name)?
+            .as_any()
+            .downcast_ref::<Float32Array>()
+            .ok_or_else(|| anyhow::anyhow!("column {name} is not Float32"))
+    };
+    let u32s = |name: &str| -> Result<&UInt32Array> {
+        column(batch, name)?
+            .as_any()
+            .downcast_ref::<UInt32Array>()
+            .ok_or_else(|| anyhow::anyhow!("column {name} is not UInt32"))
+    };
+    let tick = u32s("tick")?;
+    let steamid = column(batch, "steamid")?
+        .as_any()
+        .downcast_ref::<UInt64Array>()
+        .ok_or_else(|| anyhow::anyhow!("column steamid is not UInt64"))?;
+    let weapon_id = u32s("weapon_id")?;
+    let (health, armor) = (f32s("health")?, f32s("armor")?);
+    let (pos_x, pos_y, pos_z) = (f32s("pos_x")?, f32s("pos_y")?, f32s("pos_z")?);
+    let (vel_x, vel_y, vel_z) = (f32s("vel_x")?, f32s("vel_y")?, f32s("vel_z")?);
+    let (yaw, pitch) = (f32s("yaw")?, f32s("pitch")?);
+    let (ammo, is_airborne) = (f32s("ammo")?, f32s("is_airborne")?);
+    let (delta_yaw, delta_pitch) = (f32s("delta_yaw")?, f32s("delta_pitch")?);
+
+    Ok((0..batch.num_rows())
+        .map(|i| BehavioralVector {
+            tick: tick.value(i),
+            steamid: steamid.value(i),
+            health: health.value(i),
+            armor: armor.value(i),
+            pos_x: pos_x.value(i),
+            pos_y: pos_y.value(i),
+            pos_z: pos_z.value(i),
+            vel_x: vel_x.value(i),
+            vel_y: vel_y.value(i),
+            vel_z: vel_z.value(i),
+            yaw: yaw.value(i),
+            pitch: pitch.value(i),
+            weapon_id: weapon_id.value(i) as u16,
+            ammo: ammo.value(i),
+            is_airborne: is_airborne.value(i),
+            delta_yaw: delta_yaw.value(i),
+            delta_pitch: delta_pitch.value(i),
+        })
+        .collect())
+}
+
+fn column<'a>(batch: &'a RecordBatch, name: &str) -> Result<&'a ArrayRef> {
+    batch
+        .column_by_name(name)
+        .ok_or_else(|| anyhow::anyhow!("missing column {name}"))
+}
+
 // Add an alias function to match what the main files are calling
 pub fn write_parquet(vecs: &[BehavioralVector], path: impl AsRef<Path>) -> Result<()> {
     // Just call the original function
@@ -287,6 +426,15 @@ mod tests {
     use std::fs::File;
     use tempfile::tempdir;
 
+    #[test]
+    fn test_wanted_props_include_teams() {
+        let props = player_props();
+        assert!(props.iter().any(|p| p == TEAM_NUM_PROP));
+        assert!(props.iter().any(|p| p == HEALTH_PROP));
+        let unique: std::collections::HashSet<&String> = props.iter().collect();
+        assert_eq!(unique.len(), props.len());
+    }
+
     #[test]
     fn test_vectors_from_demo() {
         // Use the actual test demo file from the test_data directory
@@ -307,6 +455,57 @@ mod tests {
         assert!(first_vector.steamid > 0);
     }
 
+    #[test]
+    fn test_vectors_from_prop_rows() {
+        let row = |tick: u32, steamid: u64, yaw: f32, weapon: &str| PlayerPropRow {
+            tick,
+            steamid,
+            props: AHashMap::from_iter([
+                ("X".to_string(), Variant::F32(100.0)),
+                ("velocity_X".to_string(), Variant::F32(250.0)),
+                ("yaw".to_string(), Variant::F32(yaw)),
+                ("pitch".to_string(), Variant::F32(-2.0)),
+                ("is_airborne".to_string(), Variant::Bool(true)),
+                (
+                    "weapon_name".to_string(),
+                    Variant::String(weapon.to_string()),
+                ),
+                (HEALTH_PROP.to_string(), Variant::I32(87)),
+                (ARMOR_PROP.to_string(), Variant::I32(50)),
+                (CLIP_PROP.to_string(), Variant::U32(21)),
+            ]),
+        };
+        let rows = vec![
+            row(1, 7, 179.0, "AK-47"),
+            row(1, 8, 0.0, "AWP"),
+            row(2, 7, -179.0, "AK-47"),
+            row(2, 8, 10.0, "AWP"),
+            row(3, 7, -170.0, "AK-47"), // Last row of the player has no next view
+        ];
+
+        let vectors = vectors_from_rows(&rows);
+
+        assert_eq!(vectors.len(), 3);
+        let first = &vectors[0];
+        assert_eq!((first.tick, first.steamid), (1, 7));
+        assert_eq!((first.health, first.armor, first.ammo), (87.0, 50.0, 21.0));
+        assert_eq!(
+            (first.pos_x, first.vel_x, first.is_airborne),
+            (100.0, 250.0, 1.0)
+        );
+        assert_eq!(
+            first.weapon_id,
+            cs2_common::weapon_id_from_name("weapon_ak47")
+        );
+        assert!((first.delta_yaw - 2.0).abs() < 1e-4);
+        assert_eq!(
+            vectors[1].weapon_id,
+            cs2_common::weapon_id_from_name("weapon_awp")
+        );
+        assert_eq!(vectors[1].delta_yaw, 10.0);
+        assert_eq!((vectors[2].tick, vectors[2].steamid), (2, 7));
+    }
+
     #[test]
     fn test_parquet_roundtrip() {
         let vectors = vec![
@@ -379,5 +578,20 @@ mod tests {
             assert_eq!(row.get_float(15).unwrap(), vectors[i].delta_yaw);
             assert_eq!(row.get_float(16).unwrap(), vectors[i].delta_pitch);
         }
+
+        let read_back = read_parquet(&test_file).unwrap();
+        assert_eq!(read_back.len(), vectors.len());
+        assert_eq!(read_back[1].tick, 2);
+        assert_eq!(read_back[1].delta_pitch, 1.0);
+        assert_eq!(read_back[1].weapon_id, 7);
+        assert_eq!(read_map_name(&test_file).unwrap(), None);
+
+        let match_file = tmp.path().join("match.parquet");
+        write_match_parquet(&vectors, "de_inferno", &match_file).unwrap();
+        assert_eq!(
+            read_map_name(&match_file).unwrap().as_deref(),
+            Some("de_inferno")
+        );
+        assert_eq!(read_parquet(&match_file).unwrap().len(), vectors.len());
     }
 }
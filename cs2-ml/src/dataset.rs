@@ -0,0 +1,424 @@
+/// Sequence datasets streamed from behavioral Parquet files
+///
+/// Every Parquet file written by `cs2-ml prepare` holds one match. Matches are
+/// assigned to the train, validation and test splits as a whole so windows of
+/// the same match never land on both sides, and a split's files are only read,
+/// batch by batch, when its windows are requested.
+use crate::data::stream_parquet;
+use crate::mlmove_transformer::{DiscreteAction, MLMOVEConfig};
+use anyhow::{bail, Result};
+use cs2_common::BehavioralVector;
+use rand::distr::weighted::WeightedIndex;
+use rand::distr::Distribution;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Largest tick gap allowed inside one window
+pub const MAX_WINDOW_TICK_GAP: u32 = 16;
+
+/// Settings for cutting matches into windows and splitting them
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DatasetConfig {
+    pub sequence_length: usize,   // Vectors per window
+    pub window_stride: usize,     // Ticks between the starts of consecutive windows
+    pub validation_fraction: f32, // Share of matches held out for validation
+    pub test_fraction: f32,       // Share of matches held out for testing
+    pub seed: u64,                // Drives the split assignment and window order
+    pub batch_rows: usize,        // Rows per streamed Arrow batch
+}
+
+impl Default for DatasetConfig {
+    fn default() -> Self {
+        Self {
+            sequence_length: MLMOVEConfig::default().sequence_length,
+            window_stride: 8,
+            validation_fraction: 0.1,
+            test_fraction: 0.1,
+            seed: 42,
+            batch_rows: crate::data::DEFAULT_BATCH_ROWS,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Split {
+    Train,
+    Validation,
+    Test,
+}
+
+/// A run of one player's consecutive vectors and the vector that follows it
+#[derive(Debug, Clone)]
+pub struct SequenceWindow {
+    pub match_index: usize, // Position of the match file in the dataset
+    pub steamid: u64,
+    pub vectors: Vec<BehavioralVector>,
+    pub next: BehavioralVector, // The tick the label describes
+}
+
+impl SequenceWindow {
+    /// Movement taken from the last vector of the window to the next one
+    pub fn action(&self) -> DiscreteAction {
+        DiscreteAction::from_movement(self.vectors.last().unwrap_or(&self.next), &self.next)
+    }
+
+    pub fn action_index(&self) -> usize {
+        self.action().index()
+    }
+}
+
+/// Match files with their split assignment
+#[derive(Debug, Clone)]
+pub struct SequenceDataset {
+    pub config: DatasetConfig,
+    files: Vec<PathBuf>,
+    splits: Vec<Split>,
+}
+
+impl SequenceDataset {
+    /// A Parquet file, or every `.parquet` file of a directory
+    pub fn open(path: impl AsRef<Path>, config: DatasetConfig) -> Result<Self> {
+        let path = path.as_ref();
+        let files = if path.is_dir() {
+            let mut files: Vec<PathBuf> = std::fs::read_dir(path)?
+                .map(|entry| entry.map(|e| e.path()))
+                .collect::<std::io::Result<_>>()?;
+            files.retain(|f| f.extension().is_some_and(|ext| ext == "parquet"));
+            files
+        } else {
+            vec![path.to_path_buf()]
+        };
+        Self::from_files(files, config)
+    }
+
+    /// Assign whole matches to splits with a seeded shuffle
+    ///
+    /// Held-out splits are rounded to whole matches, but a nonzero fraction
+    /// holds out at least one. The training split always keeps one match and
+    /// validation is filled before test, so only datasets of one or two
+    /// matches leave a requested split empty.
+    pub fn from_files(mut files: Vec<PathBuf>, config: DatasetConfig) -> Result<Self> {
+        if files.is_empty() {
+            bail!("no Parquet files to build a dataset from");
+        }
+        files.sort();
+
+        let mut order: Vec<usize> = (0..files.len()).collect();
+        order.shuffle(&mut StdRng::seed_from_u64(config.seed));
+        let held_out = |fraction: f32| {
+            let count = (files.len() as f32 * fraction).round() as usize;
+            if fraction > 0.0 {
+                count.max(1)
+            } else {
+                count
+            }
+        };
+        let validation = held_out(config.validation_fraction).min(files.len() - 1);
+        let test = held_out(config.test_fraction).min(files.len() - 1 - validation);
+
+        let mut splits = vec![Split::Train; files.len()];
+        for (rank, &file) in order.iter().enumerate() {
+            if rank < test {
+                splits[file] = Split::Test;
+            } else if rank < test + validation {
+                splits[file] = Split::Validation;
+            }
+        }
+        Ok(Self {
+            config,
+            files,
+            splits,
+        })
+    }
+
+    /// Match files of a split
+    pub fn files(&self, split: Split) -> Vec<&Path> {
+        self.match_indices(split)
+            .map(|i| self.files[i].as_path())
+            .collect()
+    }
+
+    /// File of the match a window was cut from
+    pub fn match_file(&self, match_index: usize) -> Option<&Path> {
+        self.files.get(match_index).map(PathBuf::as_path)
+    }
+
+    /// Windows of a split, read one match at a time
+    pub fn matches(&self, split: Split) -> impl Iterator<Item = Result<Vec<SequenceWindow>>> + '_ {
+        self.match_indices(split)
+            .map(move |i| match_windows(&self.files[i], i, &self.config))
+    }
+
+    /// Windows of a split one match at a time, with the matches in a seeded random order
+    pub fn shuffled_matches(
+        &self,
+        split: Split,
+        seed: u64,
+    ) -> impl Iterator<Item = Result<Vec<SequenceWindow>>> + '_ {
+        let mut order: Vec<usize> = self.match_indices(split).collect();
+        order.shuffle(&mut StdRng::seed_from_u64(seed));
+        order
+            .into_iter()
+            .map(move |i| match_windows(&self.files[i], i, &self.config))
+    }
+
+    /// Every window of a split in a seeded random order
+    pub fn windows(&self, split: Split) -> Result<Vec<SequenceWindow>> {
+        let mut windows = Vec::new();
+        for batch in self.matches(split) {
+            windows.extend(batch?);
+        }
+        let salt = match split {
+            Split::Train => 0,
+            Split::Validation => 1,
+            Split::Test => 2,
+        };
+        windows.shuffle(&mut StdRng::seed_from_u64(
+            self.config.seed.wrapping_add(salt),
+        ));
+        Ok(windows)
+    }
+
+    /// Hash of the match files and the split settings, as 16 hex digits
+    ///
+    /// Two datasets with the same fingerprint yield the same windows in every
+    /// split, so a model can be traced back to the data it was trained on.
+    pub fn fingerprint(&self) -> Result<String> {
+        let mut hash = FNV64_OFFSET;
+        let mut buf = vec![0u8; 1 << 16];
+        for file in &self.files {
+            let name = file.file_name().unwrap_or_default().to_string_lossy();
+            hash = fnv1a64(hash, name.as_bytes());
+            let mut reader = std::fs::File::open(file)?;
+            loop {
+                let read = std::io::Read::read(&mut reader, &mut buf)?;
+                if read == 0 {
+                    break;
+                }
+                hash = fnv1a64(hash, &buf[..read]);
+            }
+        }
+        hash = fnv1a64(hash, &serde_json::to_vec(&self.config)?);
+        Ok(format!("{hash:016x}"))
+    }
+
+    fn match_indices(&self, split: Split) -> impl Iterator<Item = usize> + '_ {
+        (0..self.files.len()).filter(move |&i| self.splits[i] == split)
+    }
+}
+
+const FNV64_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// 64-bit FNV-1a, continued from `hash`
+fn fnv1a64(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// Cut each player's track of one match into windows
+///
+/// Windows never span deaths or gaps in the recording.
+pub fn match_windows(
+    path: &Path,
+    match_index: usize,
+    config: &DatasetConfig,
+) -> Result<Vec<SequenceWindow>> {
+    let mut tracks: BTreeMap<u64, Vec<BehavioralVector>> = BTreeMap::new();
+    for batch in stream_parquet(path, config.batch_rows)? {
+        for vector in batch? {
+            tracks.entry(vector.steamid).or_default().push(vector);
+        }
+    }
+
+    let sequence_length = config.sequence_length.max(1);
+    let stride = config.window_stride.max(1);
+    let mut windows = Vec::new();
+    for (steamid, mut track) in tracks {
+        track.sort_by_key(|v| v.tick);
+        let segments = track.chunk_by(|a, b| {
+            b.tick.saturating_sub(a.tick) <= MAX_WINDOW_TICK_GAP
+                && (a.health > 0.0) == (b.health > 0.0)
+        });
+        for segment in segments.filter(|s| s[0].health > 0.0) {
+            let mut start = 0;
+            while start + sequence_length < segment.len() {
+                windows.push(SequenceWindow {
+                    match_index,
+                    steamid,
+                    vectors: segment[start..start + sequence_length].to_vec(),
+                    next: segment[start + sequence_length].clone(),
+                });
+                start += stride;
+            }
+        }
+    }
+    Ok(windows)
+}
+
+/// How often each of the 97 actions labels a window
+pub fn action_counts(windows: &[SequenceWindow]) -> Vec<usize> {
+    let mut counts = vec![0; DiscreteAction::NO_OP_INDEX + 1];
+    for window in windows {
+        counts[window.action_index()] += 1;
+    }
+    counts
+}
+
+/// Draw `count` positions with replacement, weighting each by its label's rarity
+///
+/// `frequency` counts every label, as from [`action_counts`], over `labels` or
+/// over the whole split they were taken from, so one match at a time can be
+/// drawn from as if the split were balanced. Rare actions are oversampled and
+/// common ones undersampled, which keeps a classifier from settling on running
+/// forward and standing still.
+pub fn balanced_indices(
+    labels: &[usize],
+    frequency: &[usize],
+    count: usize,
+    rng: &mut StdRng,
+) -> Result<Vec<usize>> {
+    let weights: Vec<f64> = labels
+        .iter()
+        .map(|&label| 1.0 / frequency[label].max(1) as f64)
+        .collect();
+    let distribution = WeightedIndex::new(&weights)?;
+    Ok((0..count).map(|_| distribution.sample(rng)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::write_to_parquet;
+
+    /// One player per match, running along +x and jumping every `jump_every` ticks
+    fn write_match(path: &Path, steamid: u64, ticks: u32, jump_every: u32) {
+        let vectors: Vec<BehavioralVector> = (0..ticks)
+            .map(|tick| BehavioralVector {
+                vel_x: 250.0,
+                is_airborne: if tick % jump_every == 0 { 1.0 } else { 0.0 },
+                ..BehavioralVector::new(tick, steamid)
+            })
+            .collect();
+        write_to_parquet(&vectors, path).unwrap();
+    }
+
+    #[test]
+    fn test_small_datasets_hold_out_whole_matches() -> Result<()> {
+        let files = |n: usize| {
+            (0..n)
+                .map(|m| PathBuf::from(format!("match_{m}.parquet")))
+                .collect()
+        };
+        let counts = |dataset: &SequenceDataset| {
+            [Split::Train, Split::Validation, Split::Test].map(|split| dataset.files(split).len())
+        };
+
+        // A tenth of three matches still holds one out for validation and one for testing
+        let dataset = SequenceDataset::from_files(files(3), DatasetConfig::default())?;
+        assert_eq!(counts(&dataset), [1, 1, 1]);
+
+        // Validation comes before testing when there are too few to hold out both
+        let dataset = SequenceDataset::from_files(files(2), DatasetConfig::default())?;
+        assert_eq!(counts(&dataset), [1, 1, 0]);
+        let dataset = SequenceDataset::from_files(files(1), DatasetConfig::default())?;
+        assert_eq!(counts(&dataset), [1, 0, 0]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_splits_keep_matches_whole() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        for m in 0..10u64 {
+            write_match(&tmp.path().join(format!("match_{m}.parquet")), m, 30, 7);
+        }
+        let config = DatasetConfig {
+            sequence_length: 4,
+            window_stride: 2,
+            batch_rows: 8, // Several batches per match
+            ..Default::default()
+        };
+        let dataset = SequenceDataset::open(tmp.path(), config.clone())?;
+        assert_eq!(dataset.files(Split::Train).len(), 8);
+        assert_eq!(dataset.files(Split::Validation).len(), 1);
+        assert_eq!(dataset.files(Split::Test).len(), 1);
+
+        // Each player is its own match, so no player may appear in two splits
+        let players = |split| -> Result<Vec<u64>> {
+            let mut ids: Vec<u64> = dataset.windows(split)?.iter().map(|w| w.steamid).collect();
+            ids.sort();
+            ids.dedup();
+            Ok(ids)
+        };
+        let (train, val, test) = (
+            players(Split::Train)?,
+            players(Split::Validation)?,
+            players(Split::Test)?,
+        );
+        assert_eq!(train.len() + val.len() + test.len(), 10);
+        assert!(val.iter().chain(&test).all(|p| !train.contains(p)));
+
+        // 13 windows of 4 ticks fit in a 30-tick track with stride 2
+        let windows = dataset.windows(Split::Validation)?;
+        assert_eq!(windows.len(), 13);
+        assert!(windows.iter().all(|w| w.vectors.len() == 4));
+        assert!(windows.iter().all(|w| w.next.tick == w.vectors[3].tick + 1));
+
+        // The same seed gives the same assignment and order
+        let again = SequenceDataset::open(tmp.path(), config)?;
+        assert_eq!(again.files(Split::Test), dataset.files(Split::Test));
+        let order = |d: &SequenceDataset| -> Result<Vec<(u64, u32)>> {
+            Ok(d.windows(Split::Train)?
+                .iter()
+                .map(|w| (w.steamid, w.next.tick))
+                .collect())
+        };
+        assert_eq!(order(&again)?, order(&dataset)?);
+
+        // Another seed or another match changes the fingerprint
+        let fingerprint = dataset.fingerprint()?;
+        assert_eq!(again.fingerprint()?, fingerprint);
+        let reseeded = SequenceDataset::open(
+            tmp.path(),
+            DatasetConfig {
+                seed: 7,
+                ..again.config.clone()
+            },
+        )?;
+        assert_ne!(reseeded.fingerprint()?, fingerprint);
+        write_match(&tmp.path().join("match_3.parquet"), 3, 31, 7);
+        assert_ne!(again.fingerprint()?, fingerprint);
+        Ok(())
+    }
+
+    #[test]
+    fn test_balanced_sampling_evens_out_actions() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let path = tmp.path().join("match.parquet");
+        write_match(&path, 1, 200, 20);
+        let config = DatasetConfig {
+            sequence_length: 2,
+            window_stride: 1,
+            ..Default::default()
+        };
+        let windows = match_windows(&path, 0, &config)?;
+        let counts = action_counts(&windows);
+        let jump = DiscreteAction {
+            direction: 0,
+            speed: 4,
+            jump: 1,
+        }
+        .index();
+        assert!(counts[jump] * 10 < windows.len());
+
+        let labels: Vec<usize> = windows.iter().map(|w| w.action_index()).collect();
+        let drawn = balanced_indices(&labels, &counts, 4000, &mut StdRng::seed_from_u64(7))?;
+        let jumps = drawn.iter().filter(|&&i| labels[i] == jump).count();
+        assert!((1700..2300).contains(&jumps), "{jumps} jumps of 4000");
+        Ok(())
+    }
+}
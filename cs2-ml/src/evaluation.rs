@@ -0,0 +1,726 @@
+/// Offline evaluation of movement and aim models on held-out matches
+///
+/// Movement models are scored on the 97-way [`DiscreteAction`]: top-1 and
+/// top-5 accuracy, direction and speed confusion matrices and calibration.
+/// Aim models are scored on the angle between their predicted view change
+/// and the player's. Every metric is also broken down per map and per weapon.
+use anyhow::{bail, Context, Result};
+use candle_core::{Device, D};
+use cs2_common::player_frames::wrap_degrees;
+use cs2_common::{BehavioralVector, InputVector};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::{self, Write};
+use std::path::Path;
+use std::rc::Rc;
+
+use crate::conversion_utils::create_finetuning_config;
+use crate::dataset::{DatasetConfig, SequenceDataset, Split};
+use crate::mlmove_transformer::{DiscreteAction, MLMOVEConfig, MLMOVETransformer};
+use crate::model::BehaviorNet;
+use crate::registry::ModelKind;
+
+/// Movement directions relative to the view, as in [`DiscreteAction::direction`]
+pub const DIRECTION_LABELS: [&str; 9] = ["F", "FR", "R", "BR", "B", "BL", "L", "FL", "none"];
+
+/// Speed buckets of [`DiscreteAction::speed`], as shares of the run speed
+pub const SPEED_LABELS: [&str; 6] = ["still", "<45%", "45-70%", "70-90%", "90-105%", ">105%"];
+
+/// Equal-width confidence bins of the calibration table
+pub const CALIBRATION_BINS: usize = 10;
+
+/// Upper edges of the angular error histogram, in degrees; the last bin is open
+pub const AIM_ERROR_EDGES: [f64; 7] = [0.5, 1.0, 2.0, 5.0, 10.0, 20.0, 45.0];
+
+/// Windows or frames per forward pass
+const EVALUATION_BATCH: usize = 256;
+
+/// Dataset settings and split holding the matches a model was not trained on
+///
+/// Both models are scored on their test split. The movement model's windows
+/// follow the sequence length of its sidecar and the fine-tuning split settings.
+pub fn held_out(kind: ModelKind, checkpoint: &Path, seed: u64) -> Result<(DatasetConfig, Split)> {
+    Ok(match kind {
+        ModelKind::Mlmove => {
+            let sequence_length = match std::fs::read_to_string(checkpoint.with_extension("json")) {
+                Ok(json) => serde_json::from_str::<MLMOVEConfig>(&json)?.sequence_length,
+                Err(_) => MLMOVEConfig::default().sequence_length,
+            };
+            let tuning = create_finetuning_config("", "", "", 0);
+            let config = DatasetConfig {
+                sequence_length,
+                window_stride: tuning.window_stride,
+                validation_fraction: tuning.validation_split,
+                test_fraction: tuning.test_split,
+                seed,
+                ..Default::default()
+            };
+            (config, Split::Test)
+        }
+        ModelKind::Behavior => {
+            let config = DatasetConfig {
+                sequence_length: 1,
+                window_stride: 1,
+                seed,
+                ..Default::default()
+            };
+            (config, Split::Test)
+        }
+    })
+}
+
+/// Weapon of a behavioral vector without the `weapon_` prefix, or its raw ID
+pub fn weapon_label(weapon_id: u16) -> String {
+    match cs2_common::weapon_name(weapon_id) {
+        Some(name) => name.trim_start_matches("weapon_").to_string(),
+        None => format!("#{weapon_id}"),
+    }
+}
+
+/// A sample with the map and weapon it is broken down by
+struct Tagged<T> {
+    map: Rc<str>,
+    weapon_id: u16,
+    sample: T,
+}
+
+/// Metrics over all samples and per map and weapon
+#[derive(Debug, Clone, Serialize)]
+pub struct Breakdown<M> {
+    pub overall: M,
+    pub by_map: BTreeMap<String, M>,
+    pub by_weapon: BTreeMap<String, M>,
+}
+
+impl<M> Breakdown<M> {
+    fn from_samples<T>(samples: &[Tagged<T>], metrics: impl Fn(&[&T]) -> M) -> Self {
+        let mut maps: BTreeMap<String, Vec<&T>> = BTreeMap::new();
+        let mut weapons: BTreeMap<String, Vec<&T>> = BTreeMap::new();
+        for tagged in samples {
+            maps.entry(tagged.map.to_string())
+                .or_default()
+                .push(&tagged.sample);
+            weapons
+                .entry(weapon_label(tagged.weapon_id))
+                .or_default()
+                .push(&tagged.sample);
+        }
+        let all: Vec<&T> = samples.iter().map(|t| &t.sample).collect();
+        Self {
+            overall: metrics(&all),
+            by_map: maps.into_iter().map(|(k, v)| (k, metrics(&v))).collect(),
+            by_weapon: weapons.into_iter().map(|(k, v)| (k, metrics(&v))).collect(),
+        }
+    }
+}
+
+/// One movement prediction against the action taken
+#[derive(Debug, Clone)]
+struct MovementSample {
+    label: usize,
+    top5: [usize; 5], // Most likely actions first
+    confidence: f32,  // Probability of the most likely action
+}
+
+impl MovementSample {
+    fn new(probabilities: &[f32], label: usize) -> Self {
+        let mut ranked: Vec<usize> = (0..probabilities.len()).collect();
+        ranked.sort_by(|&a, &b| probabilities[b].total_cmp(&probabilities[a]));
+        let mut top5 = [ranked[0]; 5];
+        for (slot, &action) in top5.iter_mut().zip(&ranked) {
+            *slot = action;
+        }
+        Self {
+            label,
+            top5,
+            confidence: probabilities[ranked[0]],
+        }
+    }
+}
+
+/// Predictions of one confidence bin
+#[derive(Debug, Clone, Serialize)]
+pub struct CalibrationBin {
+    pub lower: f64, // Confidence range
+    pub upper: f64,
+    pub samples: usize,
+    pub confidence: f64, // Mean confidence of the bin's predictions
+    pub accuracy: f64,   // Share of them that were right
+}
+
+/// Accuracy and calibration of a movement model
+#[derive(Debug, Clone, Serialize)]
+pub struct MovementMetrics {
+    pub samples: usize,
+    pub top1: f64,
+    pub top5: f64,
+    pub ece: f64, // Expected calibration error over the confidence bins
+    pub direction_confusion: Vec<Vec<usize>>, // [actual][predicted], indexed like DIRECTION_LABELS
+    pub speed_confusion: Vec<Vec<usize>>, // [actual][predicted], indexed like SPEED_LABELS
+    pub calibration: Vec<CalibrationBin>,
+}
+
+impl MovementMetrics {
+    fn from_samples(samples: &[&MovementSample]) -> Self {
+        let mut direction_confusion = vec![vec![0; DIRECTION_LABELS.len()]; DIRECTION_LABELS.len()];
+        let mut speed_confusion = vec![vec![0; SPEED_LABELS.len()]; SPEED_LABELS.len()];
+        let mut bins = vec![(0usize, 0.0f64, 0usize); CALIBRATION_BINS];
+        let (mut top1, mut top5) = (0, 0);
+        for sample in samples {
+            let predicted = sample.top5[0];
+            let correct = predicted == sample.label;
+            top1 += usize::from(correct);
+            top5 += usize::from(sample.top5.contains(&sample.label));
+
+            let (actual, predicted) = (
+                DiscreteAction::from_index(sample.label),
+                DiscreteAction::from_index(predicted),
+            );
+            direction_confusion[actual.direction as usize][predicted.direction as usize] += 1;
+            speed_confusion[actual.speed as usize][predicted.speed as usize] += 1;
+
+            let bin =
+                ((sample.confidence * CALIBRATION_BINS as f32) as usize).min(CALIBRATION_BINS - 1);
+            bins[bin].0 += 1;
+            bins[bin].1 += sample.confidence as f64;
+            bins[bin].2 += usize::from(correct);
+        }
+
+        let n = samples.len().max(1) as f64;
+        let mut ece = 0.0;
+        let calibration = bins
+            .into_iter()
+            .enumerate()
+            .map(|(i, (count, confidence, correct))| {
+                let per = |total: f64| {
+                    if count == 0 {
+                        0.0
+                    } else {
+                        total / count as f64
+                    }
+                };
+                let bin = CalibrationBin {
+                    lower: i as f64 / CALIBRATION_BINS as f64,
+                    upper: (i + 1) as f64 / CALIBRATION_BINS as f64,
+                    samples: count,
+                    confidence: per(confidence),
+                    accuracy: per(correct as f64),
+                };
+                ece += count as f64 / n * (bin.accuracy - bin.confidence).abs();
+                bin
+            })
+            .collect();
+        Self {
+            samples: samples.len(),
+            top1: top1 as f64 / n,
+            top5: top5 as f64 / n,
+            ece,
+            direction_confusion,
+            speed_confusion,
+            calibration,
+        }
+    }
+}
+
+/// Signed view-change errors of one aim prediction, in degrees
+#[derive(Debug, Clone, Copy)]
+struct AimSample {
+    yaw: f64,
+    pitch: f64,
+}
+
+impl AimSample {
+    fn angle(&self) -> f64 {
+        self.yaw.hypot(self.pitch)
+    }
+}
+
+/// Aim errors falling below `upper` degrees, `None` for the open last bin
+#[derive(Debug, Clone, Serialize)]
+pub struct HistogramBin {
+    pub upper: Option<f64>,
+    pub samples: usize,
+}
+
+/// Angular error distribution of an aim model, in degrees
+#[derive(Debug, Clone, Serialize)]
+pub struct AimMetrics {
+    pub samples: usize,
+    pub mean: f64, // Angle between predicted and actual view change
+    pub median: f64,
+    pub p90: f64,
+    pub p99: f64,
+    pub max: f64,
+    pub yaw_mae: f64,
+    pub pitch_mae: f64,
+    pub histogram: Vec<HistogramBin>,
+}
+
+impl AimMetrics {
+    fn from_samples(samples: &[&AimSample]) -> Self {
+        let mut angles: Vec<f64> = samples.iter().map(|s| s.angle()).collect();
+        angles.sort_by(f64::total_cmp);
+        let quantile = |q: f64| {
+            angles
+                .get(((angles.len().max(1) - 1) as f64 * q).round() as usize)
+                .copied()
+                .unwrap_or(0.0)
+        };
+        let mut histogram: Vec<HistogramBin> = AIM_ERROR_EDGES
+            .iter()
+            .map(|&edge| Some(edge))
+            .chain([None])
+            .map(|upper| HistogramBin { upper, samples: 0 })
+            .collect();
+        for angle in &angles {
+            let bin = AIM_ERROR_EDGES
+                .iter()
+                .position(|edge| angle < edge)
+                .unwrap_or(AIM_ERROR_EDGES.len());
+            histogram[bin].samples += 1;
+        }
+
+        let n = samples.len().max(1) as f64;
+        Self {
+            samples: samples.len(),
+            mean: angles.iter().sum::<f64>() / n,
+            median: quantile(0.5),
+            p90: quantile(0.9),
+            p99: quantile(0.99),
+            max: angles.last().copied().unwrap_or(0.0),
+            yaw_mae: samples.iter().map(|s| s.yaw.abs()).sum::<f64>() / n,
+            pitch_mae: samples.iter().map(|s| s.pitch.abs()).sum::<f64>() / n,
+            histogram,
+        }
+    }
+}
+
+/// Map of the match a split's windows were cut from, as stored in its file
+pub(crate) fn match_map(dataset: &SequenceDataset, match_index: usize) -> Result<Rc<str>> {
+    let map = match dataset.match_file(match_index) {
+        Some(file) => crate::data::read_map_name(file)?,
+        None => None,
+    };
+    Ok(map.as_deref().unwrap_or("unknown").into())
+}
+
+/// Run a movement model over every window of a split
+pub fn evaluate_movement(
+    model: &MLMOVETransformer,
+    dataset: &SequenceDataset,
+    split: Split,
+) -> Result<Breakdown<MovementMetrics>> {
+    let mut samples = Vec::new();
+    for windows in dataset.matches(split) {
+        let windows = windows?;
+        let Some(first) = windows.first() else {
+            continue;
+        };
+        let map = match_map(dataset, first.match_index)?;
+        for chunk in windows.chunks(EVALUATION_BATCH) {
+            let sequences: Vec<&[BehavioralVector]> =
+                chunk.iter().map(|w| w.vectors.as_slice()).collect();
+            let logits = model.forward(&model.sequences_to_tensor(&sequences)?)?;
+            let probabilities: Vec<Vec<f32>> =
+                candle_nn::ops::softmax(&logits, D::Minus1)?.to_vec2()?;
+            for (window, probabilities) in chunk.iter().zip(&probabilities) {
+                samples.push(Tagged {
+                    map: map.clone(),
+                    weapon_id: window.vectors.last().unwrap_or(&window.next).weapon_id,
+                    sample: MovementSample::new(probabilities, window.action_index()),
+                });
+            }
+        }
+    }
+    if samples.is_empty() {
+        bail!("the {split:?} split has no windows to evaluate on");
+    }
+    Ok(Breakdown::from_samples(
+        &samples,
+        MovementMetrics::from_samples,
+    ))
+}
+
+/// Run an aim policy over the first vector of every window of a split
+///
+/// Cut the dataset into single-vector windows, as the policy is trained on.
+pub fn evaluate_aim(
+    net: &BehaviorNet,
+    dataset: &SequenceDataset,
+    split: Split,
+) -> Result<Breakdown<AimMetrics>> {
+    let mut samples = Vec::new();
+    for windows in dataset.matches(split) {
+        let windows = windows?;
+        let Some(first) = windows.first() else {
+            continue;
+        };
+        let map = match_map(dataset, first.match_index)?;
+        for chunk in windows.chunks(EVALUATION_BATCH) {
+            let inputs: Vec<InputVector> = chunk
+                .iter()
+                .map(|w| InputVector::from_behavioral(&w.vectors[0]))
+                .collect();
+            for (window, predicted) in chunk.iter().zip(net.predict_batch(&inputs)?) {
+                let actual = &window.vectors[0];
+                samples.push(Tagged {
+                    map: map.clone(),
+                    weapon_id: actual.weapon_id,
+                    sample: AimSample {
+                        yaw: wrap_degrees(predicted.delta_yaw - actual.delta_yaw) as f64,
+                        pitch: wrap_degrees(predicted.delta_pitch - actual.delta_pitch) as f64,
+                    },
+                });
+            }
+        }
+    }
+    if samples.is_empty() {
+        bail!("the {split:?} split has no frames to evaluate on");
+    }
+    Ok(Breakdown::from_samples(&samples, AimMetrics::from_samples))
+}
+
+/// Everything `cs2-ml evaluate` reports about one model
+#[derive(Debug, Clone, Serialize)]
+pub struct EvaluationReport {
+    pub model: String, // Checkpoint path
+    pub kind: ModelKind,
+    pub dataset: String, // Fingerprint of the match files and split settings
+    pub split: Split,
+    pub movement: Option<Breakdown<MovementMetrics>>,
+    pub aim: Option<Breakdown<AimMetrics>>,
+}
+
+/// Evaluate a checkpoint on the matches of `data` it was not trained on
+///
+/// `seed` must be the one the model was trained with, or the held-out
+/// split will contain training matches.
+pub fn evaluate(
+    checkpoint: &Path,
+    kind: ModelKind,
+    data: &Path,
+    seed: u64,
+) -> Result<EvaluationReport> {
+    let (config, split) = held_out(kind, checkpoint, seed)?;
+    let dataset = SequenceDataset::open(data, config)?;
+    let checkpoint_str = checkpoint.to_str().context("path is not valid UTF-8")?;
+    let (movement, aim) = match kind {
+        ModelKind::Mlmove => {
+            let model = MLMOVETransformer::load_pretrained(checkpoint_str, Device::Cpu)?;
+            (Some(evaluate_movement(&model, &dataset, split)?), None)
+        }
+        ModelKind::Behavior => {
+            let net = BehaviorNet::load(checkpoint_str, Device::Cpu)?;
+            (None, Some(evaluate_aim(&net, &dataset, split)?))
+        }
+    };
+    Ok(EvaluationReport {
+        model: checkpoint.display().to_string(),
+        kind,
+        dataset: dataset.fingerprint()?,
+        split,
+        movement,
+        aim,
+    })
+}
+
+impl EvaluationReport {
+    /// Summary tables, per-map and per-weapon breakdowns and, for movement
+    /// models, the confusion matrices and calibration of all samples
+    pub fn to_markdown(&self) -> String {
+        let mut md = String::new();
+        // Writing to a String cannot fail
+        let _ = self.write_markdown(&mut md);
+        md
+    }
+
+    fn write_markdown(&self, md: &mut String) -> fmt::Result {
+        writeln!(md, "# Evaluation of `{}`\n", self.model)?;
+        writeln!(
+            md,
+            "{:?} model on the {:?} split of dataset `{}`.\n",
+            self.kind, self.split, self.dataset
+        )?;
+        if let Some(movement) = &self.movement {
+            let header = "| Samples | Top-1 | Top-5 | ECE |";
+            let row = |m: &MovementMetrics| {
+                format!(
+                    "| {} | {:.1}% | {:.1}% | {:.3} |",
+                    m.samples,
+                    m.top1 * 100.0,
+                    m.top5 * 100.0,
+                    m.ece
+                )
+            };
+            write_breakdown(md, movement, header, row)?;
+
+            let overall = &movement.overall;
+            writeln!(
+                md,
+                "## Direction confusion (rows actual, columns predicted)\n"
+            )?;
+            write_matrix(md, &DIRECTION_LABELS, &overall.direction_confusion)?;
+            writeln!(md, "## Speed confusion (rows actual, columns predicted)\n")?;
+            write_matrix(md, &SPEED_LABELS, &overall.speed_confusion)?;
+            writeln!(md, "## Calibration\n")?;
+            writeln!(md, "| Confidence | Samples | Mean confidence | Accuracy |")?;
+            writeln!(md, "|---|---|---|---|")?;
+            for bin in &overall.calibration {
+                writeln!(
+                    md,
+                    "| {:.1}-{:.1} | {} | {:.3} | {:.3} |",
+                    bin.lower, bin.upper, bin.samples, bin.confidence, bin.accuracy
+                )?;
+            }
+            writeln!(md)?;
+        }
+        if let Some(aim) = &self.aim {
+            let header =
+                "| Samples | Mean° | Median° | p90° | p99° | Max° | Yaw MAE° | Pitch MAE° |";
+            let row = |m: &AimMetrics| {
+                format!(
+                    "| {} | {:.2} | {:.2} | {:.2} | {:.2} | {:.2} | {:.2} | {:.2} |",
+                    m.samples, m.mean, m.median, m.p90, m.p99, m.max, m.yaw_mae, m.pitch_mae
+                )
+            };
+            write_breakdown(md, aim, header, row)?;
+
+            writeln!(md, "## Angular error histogram\n")?;
+            writeln!(md, "| Error | Samples | Share |")?;
+            writeln!(md, "|---|---|---|")?;
+            let mut lower = 0.0;
+            for bin in &aim.overall.histogram {
+                let range = match bin.upper {
+                    Some(upper) => format!("{lower}-{upper}°"),
+                    None => format!("≥{lower}°"),
+                };
+                writeln!(
+                    md,
+                    "| {range} | {} | {:.1}% |",
+                    bin.samples,
+                    bin.samples as f64 * 100.0 / aim.overall.samples.max(1) as f64
+                )?;
+                lower = bin.upper.unwrap_or(lower);
+            }
+            writeln!(md)?;
+        }
+        Ok(())
+    }
+}
+
+/// Overall, per-map and per-weapon rows of one metrics table each
+fn write_breakdown<M>(
+    md: &mut String,
+    breakdown: &Breakdown<M>,
+    header: &str,
+    row: impl Fn(&M) -> String,
+) -> fmt::Result {
+    let separator = "|---".repeat(header.matches('|').count() - 1) + "|";
+    writeln!(
+        md,
+        "## Overall\n\n{header}\n{separator}\n{}\n",
+        row(&breakdown.overall)
+    )?;
+    for (title, column, groups) in [
+        ("map", "Map", &breakdown.by_map),
+        ("weapon", "Weapon", &breakdown.by_weapon),
+    ] {
+        writeln!(md, "## By {title}\n\n| {column} {header}\n|---{separator}")?;
+        for (group, metrics) in groups {
+            writeln!(md, "| {group} {}", row(metrics))?;
+        }
+        writeln!(md)?;
+    }
+    Ok(())
+}
+
+fn write_matrix(md: &mut String, labels: &[&str], matrix: &[Vec<usize>]) -> fmt::Result {
+    writeln!(md, "| | {} |", labels.join(" | "))?;
+    writeln!(md, "|---{}|", "|---".repeat(labels.len()))?;
+    for (label, row) in labels.iter().zip(matrix) {
+        let cells: Vec<String> = row.iter().map(|c| c.to_string()).collect();
+        writeln!(md, "| **{label}** | {} |", cells.join(" | "))?;
+    }
+    writeln!(md)
+}
+
+impl fmt::Display for EvaluationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(m) = &self.movement {
+            write!(
+                f,
+                "{} windows: top-1 {:.1}%, top-5 {:.1}%, ECE {:.3}",
+                m.overall.samples,
+                m.overall.top1 * 100.0,
+                m.overall.top5 * 100.0,
+                m.overall.ece
+            )?;
+        }
+        if let Some(m) = &self.aim {
+            write!(
+                f,
+                "{} frames: angular error mean {:.2}°, median {:.2}°, p90 {:.2}°, p99 {:.2}°",
+                m.overall.samples, m.overall.mean, m.overall.median, m.overall.p90, m.overall.p99
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{BEHAVIOR_INPUT_DIM, BEHAVIOR_OUTPUT_DIM};
+    use cs2_common::weapon_id_from_name;
+
+    #[test]
+    fn test_weapon_labels() {
+        assert_eq!(weapon_label(weapon_id_from_name("weapon_ak47")), "ak47");
+        assert_eq!(weapon_label(0), "#0");
+    }
+
+    #[test]
+    fn test_movement_metrics() {
+        let mut certain = vec![0.0; DiscreteAction::NO_OP_INDEX + 1];
+        certain[0] = 1.0;
+        // Ranks the actions 10, 9, 8, ... with the taken one (3) in the top 5 only
+        let unsure: Vec<f32> = (0..=DiscreteAction::NO_OP_INDEX)
+            .map(|i| if i <= 10 { 0.05 + i as f32 * 0.01 } else { 0.0 })
+            .collect();
+        let samples = [
+            MovementSample::new(&certain, 0),
+            MovementSample::new(&certain, DiscreteAction::NO_OP_INDEX),
+            MovementSample::new(&unsure, 8),
+        ];
+        let metrics = MovementMetrics::from_samples(&samples.iter().collect::<Vec<_>>());
+        assert_eq!(metrics.samples, 3);
+        assert!((metrics.top1 - 1.0 / 3.0).abs() < 1e-9);
+        assert!((metrics.top5 - 2.0 / 3.0).abs() < 1e-9);
+
+        // Forward at speed 0 predicted for a no-op, and speed 1 forward for speed 1 right
+        assert_eq!(metrics.direction_confusion[0][0], 1);
+        assert_eq!(metrics.direction_confusion[8][0], 1);
+        assert_eq!(metrics.direction_confusion[0][2], 1);
+        assert_eq!(metrics.speed_confusion[1][1], 1);
+        assert_eq!(metrics.speed_confusion[0][0], 2);
+
+        // Two predictions at full confidence, half right, and one at 0.15 that is wrong
+        let top = &metrics.calibration[CALIBRATION_BINS - 1];
+        assert_eq!((top.samples, top.accuracy), (2, 0.5));
+        assert!((metrics.ece - (2.0 * 0.5 + 0.15) / 3.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_aim_metrics() {
+        let samples = [
+            AimSample {
+                yaw: 3.0,
+                pitch: -4.0,
+            },
+            AimSample {
+                yaw: 0.3,
+                pitch: 0.0,
+            },
+        ];
+        let metrics = AimMetrics::from_samples(&samples.iter().collect::<Vec<_>>());
+        assert!((metrics.mean - 2.65).abs() < 1e-9);
+        assert_eq!(metrics.max, 5.0);
+        assert!((metrics.yaw_mae - 1.65).abs() < 1e-9);
+        let counts: Vec<usize> = metrics.histogram.iter().map(|b| b.samples).collect();
+        assert_eq!(counts, [1, 0, 0, 0, 1, 0, 0, 0]);
+    }
+
+    fn write_match(path: &Path, map: &str, weapon: &str) -> Result<()> {
+        let vectors: Vec<BehavioralVector> = (0..24u32)
+            .map(|tick| BehavioralVector {
+                pos_x: tick as f32 * 4.0,
+                vel_x: 250.0,
+                weapon_id: weapon_id_from_name(weapon),
+                delta_yaw: (tick % 5) as f32,
+                delta_pitch: -1.0,
+                ..BehavioralVector::new(tick, 76561198000000001)
+            })
+            .collect();
+        crate::data::write_match_parquet(&vectors, map, path)
+    }
+
+    #[test]
+    fn test_evaluation_report() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        write_match(
+            &tmp.path().join("a_vs_b.parquet"),
+            "de_mirage",
+            "weapon_ak47",
+        )?;
+        write_match(&tmp.path().join("c_vs_d.parquet"), "de_nuke", "weapon_awp")?;
+        write_match(
+            &tmp.path().join("e_vs_f.parquet"),
+            "de_ancient",
+            "weapon_awp",
+        )?;
+        let config = DatasetConfig {
+            sequence_length: 8,
+            window_stride: 4,
+            validation_fraction: 0.0,
+            test_fraction: 1.0,
+            ..Default::default()
+        };
+        let dataset = SequenceDataset::open(tmp.path(), config)?;
+
+        let model = MLMOVETransformer::with_config(
+            MLMOVEConfig {
+                num_layers: 1,
+                model_dim: 32,
+                ff_dim: 64,
+                sequence_length: 8,
+                ..Default::default()
+            },
+            Device::Cpu,
+        )?;
+        let movement = evaluate_movement(&model, &dataset, Split::Test)?;
+        // Two of the three matches are held out, each cut into 4 windows
+        assert_eq!(movement.overall.samples, 8);
+        assert_eq!(movement.by_map.len(), 2);
+        assert!(movement.by_map.keys().all(|map| map.starts_with("de_")));
+        assert_eq!(
+            movement.by_map.values().map(|m| m.samples).sum::<usize>(),
+            8
+        );
+        assert!(movement.overall.top5 >= movement.overall.top1);
+
+        let frames = SequenceDataset::open(
+            tmp.path(),
+            DatasetConfig {
+                sequence_length: 1,
+                window_stride: 1,
+                validation_fraction: 0.0,
+                test_fraction: 1.0,
+                ..Default::default()
+            },
+        )?;
+        let net = BehaviorNet::new(BEHAVIOR_INPUT_DIM, BEHAVIOR_OUTPUT_DIM, Device::Cpu)?;
+        let aim = evaluate_aim(&net, &frames, Split::Test)?;
+        assert_eq!(aim.overall.samples, 46);
+        assert!(aim.overall.max >= aim.overall.p99 && aim.overall.p99 >= aim.overall.median);
+
+        let report = EvaluationReport {
+            model: "policy.safetensors".to_string(),
+            kind: ModelKind::Mlmove,
+            dataset: dataset.fingerprint()?,
+            split: Split::Test,
+            movement: Some(movement),
+            aim: Some(aim),
+        };
+        let json: serde_json::Value = serde_json::to_value(&report)?;
+        assert_eq!(json["movement"]["overall"]["samples"], 8);
+        assert_eq!(
+            json["aim"]["overall"]["histogram"][7]["upper"],
+            serde_json::Value::Null
+        );
+        let markdown = report.to_markdown();
+        for section in ["## By map", "## By weapon", "## Calibration", "| **FL** |"] {
+            assert!(markdown.contains(section), "missing {section}");
+        }
+        Ok(())
+    }
+}
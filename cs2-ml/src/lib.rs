@@ -1,16 +1,27 @@
 // Re-export modules for library usage
+pub mod batching;
 pub mod conversion_utils;
 pub mod data;
+pub mod dataset;
+pub mod evaluation;
 pub mod ml_architectures;
 pub mod mlmove_transformer;
 pub mod model;
+pub mod onnx;
 pub mod player;
+pub mod quantization;
+pub mod registry;
+pub mod reload;
+pub mod rollout;
 pub mod server;
 
 // Re-export main types for convenience
-pub use data::{vectors_from_demo, write_to_parquet};
+pub use batching::{BatchConfig, ClientRegistration, MicroBatcher};
+pub use data::{vectors_and_clock_from_demo, vectors_from_demo, write_to_parquet};
 pub use model::BehaviorNet;
-pub use server::{serve, serve_with_model};
+pub use registry::{ModelEntry, ModelKind, ModelRegistry};
+pub use reload::{ModelSource, ModelWatcher};
+pub use server::{serve, serve_with_model, LatencySummary, ModelSlot, PolicyServer, ServeOptions};
 
 // Re-export advanced ML architectures
 pub use ml_architectures::{
@@ -26,6 +37,7 @@ pub use mlmove_transformer::{
 
 // Re-export conversion utilities
 pub use conversion_utils::{
-    convert_mlmove_to_candle, finetune_on_cs2_data, CS2FineTuner, CS2TrainingDataset,
-    ConversionConfig, FineTuningConfig, TorchToCandleConverter, TrainingMetrics, TrainingSample,
+    convert_mlmove_to_candle, finetune_on_cs2_data, map_pytorch_names, read_checkpoint,
+    CS2FineTuner, CS2TrainingDataset, ConversionConfig, FineTuningConfig, TorchToCandleConverter,
+    TrainingMetrics, TrainingSample,
 };
@@ -1,11 +1,10 @@
+use candle_core::quantized::GgmlDType;
 use clap::{Parser, Subcommand};
+use cs2_ml::registry::{ModelKind, ModelRegistry, Provenance, PRODUCTION};
+use cs2_ml::{data, evaluation, model, quantization, rollout, server, ModelSource, ServeOptions};
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
-mod data;
-mod model;
-mod player;
-mod server;
-
 #[derive(Parser)]
 #[command(name = "cs2-ml")]
 #[command(about = "CS2 behavior-cloning ML pipeline")]
@@ -23,16 +22,144 @@ enum Commands {
     },
     /// Train the policy network
     Train {
-        parquet: PathBuf,
+        /// Parquet file or directory with one Parquet file per match
+        data: PathBuf,
         model_out: PathBuf,
-        #[arg(long, default_value = "1000")]
-        epochs: i64,
+        #[arg(long, default_value = "100")]
+        epochs: usize,
+        /// Train the MLMOVE transformer on movement windows instead of the aim policy
+        #[arg(long)]
+        mlmove: bool,
+        /// Oversample rare movement actions (with --mlmove)
+        #[arg(long, requires = "mlmove")]
+        balanced: bool,
+        /// Seed for the match split and shuffling
+        #[arg(long, default_value = "42")]
+        seed: u64,
+        /// Register the trained model under this name
+        #[arg(long)]
+        register: Option<String>,
+        #[arg(long, default_value = "models/registry")]
+        registry: PathBuf,
     },
     /// Serve the trained policy
     Serve {
-        model: PathBuf,
+        /// Checkpoint path, or a model name, `name@version` or alias with --registry
+        model: String,
+        /// MLMOVE checkpoint answering movement requests, resolved like `model`
+        #[arg(long)]
+        movement_model: Option<String>,
+        /// Resolve models in this registry instead of reading paths
+        #[arg(long)]
+        registry: Option<PathBuf>,
         #[arg(long, default_value = "8123")]
         port: u16,
+        /// Gather concurrent requests into micro-batches, waiting at most this long
+        #[arg(long)]
+        batch_wait_us: Option<u64>,
+        /// Inputs at which a micro-batch runs without waiting further
+        #[arg(long, default_value = "64", requires = "batch_wait_us")]
+        max_batch: usize,
+        /// Swap in new versions of `model` while serving, following the alias with --registry
+        #[arg(long)]
+        watch: bool,
+        /// Run f32 checkpoints on weights quantized to this format (q8_0, q4_0, ...)
+        #[arg(long, value_parser = quantization::parse_format)]
+        quantize: Option<GgmlDType>,
+    },
+    /// Quantize a checkpoint for CPU inference and report the accuracy drop
+    Quantize {
+        checkpoint: PathBuf,
+        /// Output `.gguf` file, written with a `.json` sidecar
+        output: PathBuf,
+        #[arg(long, default_value = "q8_0", value_parser = quantization::parse_format)]
+        format: GgmlDType,
+        /// The checkpoint is an MLMOVE movement model
+        #[arg(long)]
+        mlmove: bool,
+        /// Parquet file or directory whose test split the f32 and quantized models are compared on
+        #[arg(long)]
+        data: Option<PathBuf>,
+        /// Seed the model was trained with, so the test split holds unseen matches
+        #[arg(long, default_value = "42")]
+        seed: u64,
+        /// Also write the report as JSON
+        #[arg(long)]
+        report: Option<PathBuf>,
+    },
+    /// Score a model on held-out matches, with per-map and per-weapon breakdowns
+    Evaluate {
+        checkpoint: PathBuf,
+        /// Parquet file or directory with one Parquet file per match
+        data: PathBuf,
+        /// The checkpoint is an MLMOVE movement model
+        #[arg(long)]
+        mlmove: bool,
+        /// Seed the model was trained with, so the held-out split holds unseen matches
+        #[arg(long, default_value = "42")]
+        seed: u64,
+        /// Write the full report as JSON
+        #[arg(long)]
+        json: Option<PathBuf>,
+        /// Write the Markdown summary here instead of printing it
+        #[arg(long)]
+        markdown: Option<PathBuf>,
+    },
+    /// Roll an MLMOVE model forward from held-out demo states and compare paths
+    Rollout {
+        checkpoint: PathBuf,
+        /// Parquet file or directory with one Parquet file per match
+        data: PathBuf,
+        /// Recorded steps each rollout runs for
+        #[arg(long, default_value = "64")]
+        horizon: usize,
+        /// Steps between the starts of consecutive rollouts
+        #[arg(long, default_value = "64")]
+        stride: usize,
+        /// Sample actions from the policy instead of taking the most likely one
+        #[arg(long)]
+        sample: bool,
+        /// Seed the model was trained with, also seeding the sampling
+        #[arg(long, default_value = "42")]
+        seed: u64,
+        /// Side of the occupancy grid cells, in game units
+        #[arg(long, default_value = "128")]
+        cell_size: f32,
+        /// Also write the report as JSON
+        #[arg(long)]
+        report: Option<PathBuf>,
+    },
+    /// List, register, promote and roll back registered models
+    Registry {
+        #[arg(long, default_value = "models/registry")]
+        root: PathBuf,
+        #[command(subcommand)]
+        command: RegistryCommands,
+    },
+}
+
+#[derive(Subcommand)]
+enum RegistryCommands {
+    /// Every registered version and the aliases pointing at them
+    List,
+    /// Copy an existing checkpoint into the registry as a new version
+    Register {
+        name: String,
+        checkpoint: PathBuf,
+        /// The checkpoint is an MLMOVE movement model
+        #[arg(long)]
+        mlmove: bool,
+    },
+    /// Point an alias at a model, `name` meaning its latest version
+    Promote {
+        model: String,
+        #[arg(long, default_value = PRODUCTION)]
+        alias: String,
+    },
+    /// Point an alias back at the model it had before its last promotion
+    Rollback {
+        #[arg(long, default_value = PRODUCTION)]
+        alias: String,
     },
 }
 
@@ -47,44 +174,348 @@ fn main() -> anyhow::Result<()> {
             std::fs::create_dir_all(&output_dir)?;
             for entry in glob::glob(&demo_glob)? {
                 let demo = entry?;
-                let vecs = data::vectors_from_demo(&demo)?;
+                let parsed = data::parse_demo(&demo)?;
+                let vecs = data::vectors_from_output(&parsed)?;
                 let out = output_dir
                     .join(demo.file_stem().unwrap())
                     .with_extension("parquet");
-                data::write_parquet(&vecs, &out)?;
+                data::write_match_parquet(&vecs, parsed.map_name().unwrap_or("unknown"), &out)?;
                 println!("Wrote {}", out.display());
             }
         }
         Commands::Train {
-            parquet,
+            data,
             model_out,
-            epochs: _,
+            epochs,
+            mlmove,
+            balanced,
+            seed,
+            register,
+            registry,
         } => {
-            use parquet::file::reader::SerializedFileReader;
-            use parquet::record::reader::RowIter;
-            use parquet::record::RowAccessor;
-            let file = std::fs::File::open(parquet)?;
-            let reader = SerializedFileReader::new(file)?;
-            let row_iter = RowIter::from_file(None, &reader)?;
-            let mut dataset = Vec::new();
-            for row_result in row_iter {
-                let row = row_result?;
-                let vec: Vec<f32> = (0..14).map(|i| row.get_double(i).unwrap() as f32).collect();
-                let label = vec![
-                    row.get_double(14).unwrap() as f32,
-                    row.get_double(15).unwrap() as f32,
-                ];
-                dataset.push((vec, label));
+            use candle_core::Device;
+            use cs2_ml::dataset::{DatasetConfig, SequenceDataset, Split};
+
+            if mlmove {
+                let mut config = cs2_ml::conversion_utils::create_finetuning_config(
+                    "",
+                    data.to_str().unwrap(),
+                    model_out.to_str().unwrap(),
+                    epochs,
+                );
+                config.class_balanced = balanced;
+                config.seed = seed;
+                let tuner = cs2_ml::CS2FineTuner::new(config, Device::Cpu);
+                let metrics = tuner.fine_tune()?;
+                println!(
+                    "Best epoch {}: val_loss={:.4}, val_acc={:.4}",
+                    metrics.epoch, metrics.val_loss, metrics.val_accuracy
+                );
+                let test = tuner.test()?;
+                match test {
+                    Some((loss, accuracy)) => {
+                        println!("Test loss {loss:.4}, accuracy {accuracy:.4}")
+                    }
+                    None => println!("No test matches held out, the model is untested"),
+                }
+                if let Some(name) = register {
+                    let mut metrics = BTreeMap::from([
+                        ("epoch".to_string(), metrics.epoch as f64),
+                        ("train_loss".to_string(), metrics.train_loss as f64),
+                        ("val_loss".to_string(), metrics.val_loss as f64),
+                        ("train_accuracy".to_string(), metrics.train_accuracy as f64),
+                        ("val_accuracy".to_string(), metrics.val_accuracy as f64),
+                    ]);
+                    if let Some((loss, accuracy)) = test {
+                        metrics.insert("test_loss".to_string(), loss as f64);
+                        metrics.insert("test_accuracy".to_string(), accuracy as f64);
+                    }
+                    let provenance = Provenance {
+                        dataset_fingerprint: Some(tuner.dataset_fingerprint()?),
+                        seed: Some(seed),
+                        metrics,
+                    };
+                    let entry = ModelRegistry::open(&registry)?.register(
+                        &name,
+                        ModelKind::Mlmove,
+                        &model_out,
+                        provenance,
+                    )?;
+                    println!("Registered {}", entry.reference());
+                }
+                return Ok(());
             }
+
+            // The aim policy learns from single frames, split by match like the sequences
+            let dataset = SequenceDataset::open(
+                &data,
+                DatasetConfig {
+                    sequence_length: 1,
+                    window_stride: 1,
+                    seed,
+                    ..Default::default()
+                },
+            )?;
+            let frames = |split| -> anyhow::Result<Vec<(Vec<f32>, Vec<f32>)>> {
+                Ok(dataset
+                    .windows(split)?
+                    .iter()
+                    .map(|w| {
+                        let v = &w.vectors[0];
+                        let input = cs2_common::InputVector::from_behavioral(v);
+                        (
+                            model::behavior_features(&input),
+                            vec![v.delta_yaw, v.delta_pitch],
+                        )
+                    })
+                    .collect())
+            };
+            let (train, validation, test) = (
+                frames(Split::Train)?,
+                frames(Split::Validation)?,
+                frames(Split::Test)?,
+            );
+            println!(
+                "{} training, {} validation and {} test frames",
+                train.len(),
+                validation.len(),
+                test.len()
+            );
+
             // Use Candle instead of PyTorch
-            use candle_core::Device;
-            let mut net = model::BehaviorNet::new(12, 2, Device::Cpu)?;
-            net.train(&dataset)?;
+            let mut net = model::BehaviorNet::new(
+                model::BEHAVIOR_INPUT_DIM,
+                model::BEHAVIOR_OUTPUT_DIM,
+                Device::Cpu,
+            )?;
+            let config = model::TrainingConfig {
+                epochs,
+                seed,
+                ..Default::default()
+            };
+            let report = net.train_with_validation(&train, &validation, &config)?;
+            println!(
+                "Best validation loss {:.4} at epoch {} of {}{}",
+                report.best_val_loss,
+                report.best_epoch + 1,
+                report.val_loss.len(),
+                if report.stopped_early {
+                    " (stopped early)"
+                } else {
+                    ""
+                }
+            );
+            let test_loss = if test.is_empty() {
+                None
+            } else {
+                let loss = net.loss(&test)?;
+                println!("Test loss {loss:.4}");
+                Some(loss)
+            };
             net.save(model_out.to_str().unwrap())?;
             println!("Model saved to {}", model_out.display());
+            if let Some(name) = register {
+                let mut metrics = BTreeMap::from([
+                    ("best_epoch".to_string(), report.best_epoch as f64 + 1.0),
+                    ("best_val_loss".to_string(), report.best_val_loss as f64),
+                ]);
+                if let Some(loss) = test_loss {
+                    metrics.insert("test_loss".to_string(), loss as f64);
+                }
+                let provenance = Provenance {
+                    dataset_fingerprint: Some(dataset.fingerprint()?),
+                    seed: Some(seed),
+                    metrics,
+                };
+                let entry = ModelRegistry::open(&registry)?.register(
+                    &name,
+                    ModelKind::Behavior,
+                    &model_out,
+                    provenance,
+                )?;
+                println!("Registered {}", entry.reference());
+            }
+        }
+        Commands::Serve {
+            model,
+            movement_model,
+            registry,
+            port,
+            batch_wait_us,
+            max_batch,
+            watch,
+            quantize,
+        } => {
+            let registry = registry.map(ModelRegistry::open).transpose()?;
+            let resolve = |reference: &str| -> anyhow::Result<PathBuf> {
+                match &registry {
+                    Some(registry) => {
+                        let entry = registry.resolve(reference)?;
+                        println!("{reference} is {}", entry.reference());
+                        Ok(registry.weights_path(&entry))
+                    }
+                    None => Ok(PathBuf::from(reference)),
+                }
+            };
+            let model_path = resolve(&model)?;
+            let movement_model = movement_model.as_deref().map(resolve).transpose()?;
+            let batching = batch_wait_us.map(|us| cs2_ml::BatchConfig {
+                max_wait: std::time::Duration::from_micros(us),
+                max_batch,
+            });
+            let reload = watch.then(|| match registry {
+                Some(registry) => ModelSource::Registry {
+                    registry,
+                    reference: model,
+                },
+                None => ModelSource::Path(model_path.clone()),
+            });
+            let options = ServeOptions {
+                port,
+                batching,
+                reload,
+                quantize,
+            };
+            server::serve(&model_path, movement_model.as_deref(), options)?;
         }
-        Commands::Serve { model: _, port } => {
-            server::serve(port)?;
+        Commands::Quantize {
+            checkpoint,
+            output,
+            format,
+            mlmove,
+            data,
+            seed,
+            report,
+        } => {
+            // Compare on the matches training held out
+            let kind = if mlmove {
+                ModelKind::Mlmove
+            } else {
+                ModelKind::Behavior
+            };
+            let (config, split) = evaluation::held_out(kind, &checkpoint, seed)?;
+            let windows = match data {
+                Some(data) => {
+                    cs2_ml::dataset::SequenceDataset::open(&data, config)?.windows(split)?
+                }
+                None => Vec::new(),
+            };
+            let quantization_report =
+                quantization::quantize_checkpoint(&checkpoint, &output, kind, format, &windows)?;
+            println!("Wrote {}", output.display());
+            println!("{quantization_report}");
+            if let Some(path) = report {
+                std::fs::write(&path, serde_json::to_string_pretty(&quantization_report)?)?;
+                println!("Report written to {}", path.display());
+            }
+        }
+        Commands::Evaluate {
+            checkpoint,
+            data,
+            mlmove,
+            seed,
+            json,
+            markdown,
+        } => {
+            let kind = if mlmove {
+                ModelKind::Mlmove
+            } else {
+                ModelKind::Behavior
+            };
+            let report = evaluation::evaluate(&checkpoint, kind, &data, seed)?;
+            match markdown {
+                Some(path) => {
+                    std::fs::write(&path, report.to_markdown())?;
+                    println!("{report}");
+                    println!("Markdown summary written to {}", path.display());
+                }
+                None => print!("{}", report.to_markdown()),
+            }
+            if let Some(path) = json {
+                std::fs::write(&path, serde_json::to_string_pretty(&report)?)?;
+                println!("Report written to {}", path.display());
+            }
+        }
+        Commands::Rollout {
+            checkpoint,
+            data,
+            horizon,
+            stride,
+            sample,
+            seed,
+            cell_size,
+            report,
+        } => {
+            let config = rollout::RolloutConfig {
+                horizon,
+                stride,
+                sample,
+                seed,
+                cell_size,
+                ..Default::default()
+            };
+            let rollout_report = rollout::rollout_checkpoint(&checkpoint, &data, seed, &config)?;
+            println!("{rollout_report}");
+            if let Some(path) = report {
+                std::fs::write(&path, serde_json::to_string_pretty(&rollout_report)?)?;
+                println!("Report written to {}", path.display());
+            }
+        }
+        Commands::Registry { root, command } => {
+            let registry = ModelRegistry::open(&root)?;
+            match command {
+                RegistryCommands::List => {
+                    let aliases = registry.aliases()?;
+                    for entry in registry.list()? {
+                        let reference = entry.reference();
+                        let pointed: Vec<&str> = aliases
+                            .iter()
+                            .filter(|(_, target)| **target == reference)
+                            .map(|(alias, _)| alias.as_str())
+                            .collect();
+                        let metrics: Vec<String> = entry
+                            .metrics
+                            .iter()
+                            .map(|(k, v)| format!("{k}={v:.4}"))
+                            .collect();
+                        println!(
+                            "{reference:<24} {:?} schema v{} data {} seed {} {}{}",
+                            entry.config.kind(),
+                            entry.feature_schema_version,
+                            entry.dataset_fingerprint.as_deref().unwrap_or("-"),
+                            entry.seed.map_or("-".to_string(), |seed| seed.to_string()),
+                            metrics.join(" "),
+                            if pointed.is_empty() {
+                                String::new()
+                            } else {
+                                format!(" [{}]", pointed.join(", "))
+                            }
+                        );
+                    }
+                }
+                RegistryCommands::Register {
+                    name,
+                    checkpoint,
+                    mlmove,
+                } => {
+                    let kind = if mlmove {
+                        ModelKind::Mlmove
+                    } else {
+                        ModelKind::Behavior
+                    };
+                    let entry =
+                        registry.register(&name, kind, &checkpoint, Provenance::default())?;
+                    println!("Registered {}", entry.reference());
+                }
+                RegistryCommands::Promote { model, alias } => {
+                    let entry = registry.promote(&model, &alias)?;
+                    println!("{alias} is now {}", entry.reference());
+                }
+                RegistryCommands::Rollback { alias } => {
+                    let entry = registry.rollback(&alias)?;
+                    println!("{alias} rolled back to {}", entry.reference());
+                }
+            }
         }
     }
     Ok(())
@@ -2,6 +2,39 @@ use anyhow::Result;
 use candle_core::{DType, Device, Tensor};
 use candle_nn::{linear, Linear, Module, VarBuilder, VarMap};
 use cs2_common::feature_extraction::*;
+use cs2_common::feature_schema::FeatureSchema;
+
+/// Schema features fed to the mechanics encoder of [`PlayerStyleClassifier`]
+pub const STYLE_MECHANICS_INPUTS: &[&str] = &[
+    "player_mechanics.headshot_percentage",
+    "player_mechanics.flick_accuracy",
+    "player_mechanics.target_acquisition_time",
+    "player_mechanics.spray_control_deviation",
+    "player_mechanics.crosshair_placement_height",
+    "player_mechanics.pre_aim_accuracy",
+    "player_mechanics.counter_strafe_effectiveness",
+    "player_mechanics.peek_technique_score",
+    "player_mechanics.movement_efficiency",
+    "player_mechanics.position_transition_smoothness",
+    "player_mechanics.crouch_usage_pattern",
+    "player_mechanics.jump_usage_pattern",
+    "player_mechanics.air_strafe_control",
+    "player_mechanics.recoil_control_consistency",
+    "player_mechanics.burst_vs_spray_preference",
+    "player_mechanics.weapon_switch_speed",
+    "player_mechanics.positioning_vs_weapon_range",
+    "player_mechanics.first_bullet_accuracy",
+];
+
+/// Schema features fed to the context encoder of [`PlayerStyleClassifier`]
+pub const STYLE_CONTEXT_INPUTS: &[&str] = &[
+    "temporal_context.clutch_performance_metrics",
+    "temporal_context.counter_strategy_effectiveness",
+    "temporal_context.adaptation_to_opponent_patterns",
+    "temporal_context.anti_strategy_timing",
+    "temporal_context.information_denial_effectiveness",
+    "temporal_context.map_specific_tendencies.mean",
+];
 
 /// Player Style Classifier - Identifies playing styles and patterns
 #[derive(Debug)]
@@ -355,8 +388,8 @@ impl PlayerStyleClassifier {
         features: &ExtractedFeatures,
     ) -> Result<PlayerStylePrediction> {
         // Convert features to tensors
-        let mechanics_tensor = self.mechanics_features_to_tensor(&features.player_mechanics)?;
-        let context_tensor = self.context_features_to_tensor(&features.temporal_context)?;
+        let mechanics_tensor = self.schema_features_to_tensor(features, STYLE_MECHANICS_INPUTS)?;
+        let context_tensor = self.schema_features_to_tensor(features, STYLE_CONTEXT_INPUTS)?;
 
         // Forward pass - simplified without softmax for now
         let predictions = self.forward(&mechanics_tensor, &context_tensor, false)?;
@@ -388,50 +421,17 @@ impl PlayerStyleClassifier {
         })
     }
 
-    fn mechanics_features_to_tensor(&self, mechanics: &PlayerMechanicsFeatures) -> Result<Tensor> {
-        let features = vec![
-            mechanics.headshot_percentage,
-            mechanics.flick_accuracy,
-            mechanics.target_acquisition_time,
-            mechanics.spray_control_deviation,
-            mechanics.crosshair_placement_height,
-            mechanics.pre_aim_accuracy,
-            mechanics.counter_strafe_effectiveness,
-            mechanics.peek_technique_score,
-            mechanics.movement_efficiency,
-            mechanics.position_transition_smoothness,
-            mechanics.crouch_usage_pattern,
-            mechanics.jump_usage_pattern,
-            mechanics.air_strafe_control,
-            mechanics.recoil_control_consistency,
-            mechanics.burst_vs_spray_preference,
-            mechanics.weapon_switch_speed,
-            mechanics.positioning_vs_weapon_range,
-            mechanics.first_bullet_accuracy,
-        ];
-
-        Ok(Tensor::from_slice(
-            &features,
-            (1, features.len()),
-            &self.device,
-        )?)
-    }
-
-    fn context_features_to_tensor(&self, context: &TemporalContextFeatures) -> Result<Tensor> {
-        let features = vec![
-            context.clutch_performance_metrics,
-            context.counter_strategy_effectiveness,
-            context.adaptation_to_opponent_patterns,
-            context.anti_strategy_timing,
-            context.information_denial_effectiveness,
-            // Add map-specific tendency aggregates
-            context.map_specific_tendencies.values().sum::<f32>()
-                / context.map_specific_tendencies.len().max(1) as f32,
-        ];
+    /// Select the named schema features into a `(1, n)` input tensor
+    fn schema_features_to_tensor(
+        &self,
+        features: &ExtractedFeatures,
+        names: &[&str],
+    ) -> Result<Tensor> {
+        let values = FeatureSchema::current().select(&features.to_flat_vector(), names)?;
 
         Ok(Tensor::from_slice(
-            &features,
-            (1, features.len()),
+            &values,
+            (1, values.len()),
             &self.device,
         )?)
     }
@@ -925,6 +925,20 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_style_inputs_come_from_schema() -> Result<()> {
+        let classifier = PlayerStyleClassifier::new(
+            STYLE_MECHANICS_INPUTS.len(),
+            STYLE_CONTEXT_INPUTS.len(),
+            5,
+            Device::Cpu,
+        )?;
+
+        let prediction = classifier.classify_player_style(&ExtractedFeatures::default())?;
+        assert_eq!(prediction.style_probabilities.len(), 5);
+        Ok(())
+    }
+
     #[test]
     fn test_team_dynamics_transformer() -> Result<()> {
         let transformer = TeamDynamicsTransformer::new(16, 8, 4, Device::Cpu)?;
@@ -2,11 +2,20 @@
 ///
 /// Implements the 4-layer, 1-head, 256-d transformer from the MLMOVE research paper
 /// for 0.5ms/tick professional player movement prediction and behavior cloning.
-use anyhow::Result;
-use candle_core::{DType, Device, IndexOp, Tensor};
-use candle_nn::{embedding, linear, Embedding, Linear, Module, VarBuilder, VarMap};
+use anyhow::{bail, Context, Result};
+use candle_core::quantized::GgmlDType;
+use candle_core::{DType, Device, IndexOp, Tensor, Var};
+use candle_nn::{embedding, linear, Embedding, Module, VarBuilder, VarMap};
+use cs2_common::player_frames::wrap_degrees;
 use cs2_common::BehavioralVector;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::quantization::{self, Dense};
+
+/// Horizontal speed of a player running with a knife, in units per second
+pub const RUN_SPEED: f32 = 250.0;
 
 /// MLMOVE Transformer Configuration
 ///
@@ -48,26 +57,66 @@ impl Default for MLMOVEConfig {
     }
 }
 
+impl MLMOVEConfig {
+    /// Recover the architecture from weights in our var layout
+    pub fn from_weights(tensors: &HashMap<String, Tensor>) -> Result<Self> {
+        let dims = |name: &str| -> Result<(usize, usize)> {
+            let tensor = tensors
+                .get(name)
+                .with_context(|| format!("checkpoint has no {name}"))?;
+            Ok(tensor.dims2()?)
+        };
+        let (model_dim, input_dim) = dims("input_embedding.weight")?;
+        let (sequence_length, _) = dims("pos_embedding.weight")?;
+        let (ff_dim, _) = dims("layer_0.ff.linear1.weight")?;
+        let (action_space_size, _) = dims("output.weight")?;
+        let num_layers = (0..)
+            .take_while(|i| tensors.contains_key(&format!("layer_{i}.ln1.weight")))
+            .count();
+
+        Ok(Self {
+            num_layers,
+            num_heads: 1,
+            model_dim,
+            ff_dim,
+            sequence_length,
+            action_space_size,
+            input_dim,
+        })
+    }
+}
+
 /// MLMOVE Transformer for professional movement prediction
 ///
 /// Implements the architecture from "Learning to Move Like Professional Counter-Strike Players"
 /// Optimized for real-time inference with 0.5ms per tick performance target
-#[derive(Debug)]
 pub struct MLMOVETransformer {
     /// Input feature embedding layer
-    input_embedding: Linear,
+    input_embedding: Dense,
     /// Positional embeddings for sequence modeling
     position_embedding: Embedding,
     /// Transformer layers
     transformer_layers: Vec<TransformerLayer>,
     /// Output projection to action space
-    output_projection: Linear,
+    output_projection: Dense,
     /// Configuration
     config: MLMOVEConfig,
+    /// Trainable parameters backing every layer
+    varmap: VarMap,
     /// Device for tensor operations
     device: Device,
 }
 
+impl std::fmt::Debug for MLMOVETransformer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MLMOVETransformer")
+            .field("config", &self.config)
+            .field("quantization", &self.quantization())
+            .field("device", &self.device)
+            .finish()
+    }
+}
+
 /// Single transformer layer with self-attention and feed-forward
 #[derive(Debug)]
 struct TransformerLayer {
@@ -85,13 +134,13 @@ struct TransformerLayer {
 #[derive(Debug)]
 struct SelfAttention {
     /// Query projection
-    query: Linear,
+    query: Dense,
     /// Key projection
-    key: Linear,
+    key: Dense,
     /// Value projection
-    value: Linear,
+    value: Dense,
     /// Output projection
-    output: Linear,
+    output: Dense,
     /// Model dimension
     model_dim: usize,
 }
@@ -100,27 +149,30 @@ struct SelfAttention {
 #[derive(Debug)]
 struct FeedForward {
     /// First linear layer
-    linear1: Linear,
+    linear1: Dense,
     /// Second linear layer
-    linear2: Linear,
+    linear2: Dense,
 }
 
-/// Layer normalization (simplified)
+/// Layer normalization over the model dimension, as `torch.nn.LayerNorm`
 #[derive(Debug)]
 struct LayerNorm {
     /// Scale parameter
-    weight: Linear,
-    /// Bias parameter  
-    bias: Linear,
+    weight: Tensor,
+    /// Bias parameter
+    bias: Tensor,
 }
 
+/// Epsilon of `torch.nn.LayerNorm`
+const LAYER_NORM_EPS: f64 = 1e-5;
+
 /// Discrete action space for CS2 movement
 ///
 /// Represents the 97-way discrete action space from the research:
 /// - Movement directions (8 directions)
 /// - Movement speeds (multiple levels)
 /// - Jump actions (jump/no-jump)
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct DiscreteAction {
     /// Movement direction (0-7, representing 8 directions)
     pub direction: u8,
@@ -130,6 +182,80 @@ pub struct DiscreteAction {
     pub jump: u8,
 }
 
+impl DiscreteAction {
+    /// Index of the action that neither moves nor jumps
+    pub const NO_OP_INDEX: usize = 96;
+
+    /// Decode an index of the 97-way action space
+    pub fn from_index(index: usize) -> Self {
+        // 97-way action space: 8 directions × 6 speeds × 2 jump states = 96, + 1 no-op
+        if index == Self::NO_OP_INDEX {
+            // No-op action
+            return DiscreteAction {
+                direction: 8,
+                speed: 0,
+                jump: 0,
+            };
+        }
+
+        let direction = (index % 8) as u8;
+        let speed = ((index / 8) % 6) as u8;
+        let jump = (index / 48) as u8;
+
+        DiscreteAction {
+            direction,
+            speed,
+            jump,
+        }
+    }
+
+    /// Position of this action in the 97-way action space
+    pub fn index(&self) -> usize {
+        if self.direction >= 8 {
+            return Self::NO_OP_INDEX;
+        }
+        self.jump.min(1) as usize * 48 + self.speed.min(5) as usize * 8 + self.direction as usize
+    }
+
+    /// The action a player took to go from `previous` to `current`
+    ///
+    /// Direction is the horizontal velocity relative to the view yaw, speed is
+    /// bucketed against [`RUN_SPEED`], and a jump is leaving the ground.
+    pub fn from_movement(previous: &BehavioralVector, current: &BehavioralVector) -> Self {
+        let jump = u8::from(current.is_airborne > 0.5 && previous.is_airborne <= 0.5);
+        let horizontal = current.vel_x.hypot(current.vel_y);
+        let speed = match horizontal / RUN_SPEED {
+            f if f < 0.04 => 0,
+            f if f < 0.45 => 1,
+            f if f < 0.7 => 2,
+            f if f < 0.9 => 3,
+            f if f <= 1.05 => 4,
+            _ => 5,
+        };
+        if speed == 0 {
+            return if jump == 1 {
+                DiscreteAction {
+                    direction: 0,
+                    speed: 0,
+                    jump,
+                }
+            } else {
+                Self::from_index(Self::NO_OP_INDEX)
+            };
+        }
+
+        // Yaw grows counter-clockwise while directions go clockwise from forward
+        let heading = current.vel_y.atan2(current.vel_x).to_degrees();
+        let relative = wrap_degrees(heading - current.yaw);
+        let direction = ((-relative / 45.0).round() as i32).rem_euclid(8) as u8;
+        DiscreteAction {
+            direction,
+            speed,
+            jump,
+        }
+    }
+}
+
 /// Movement prediction result
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MovementPrediction {
@@ -156,7 +282,11 @@ impl MLMOVETransformer {
         let vs = VarBuilder::from_varmap(&varmap, DType::F32, &device);
 
         // Input embedding layer
-        let input_embedding = linear(config.input_dim, config.model_dim, vs.pp("input_embedding"))?;
+        let input_embedding = Dense::Float(linear(
+            config.input_dim,
+            config.model_dim,
+            vs.pp("input_embedding"),
+        )?);
 
         // Positional embedding
         let position_embedding = embedding(
@@ -177,8 +307,11 @@ impl MLMOVETransformer {
         }
 
         // Output projection to action space
-        let output_projection =
-            linear(config.model_dim, config.action_space_size, vs.pp("output"))?;
+        let output_projection = Dense::Float(linear(
+            config.model_dim,
+            config.action_space_size,
+            vs.pp("output"),
+        )?);
 
         Ok(Self {
             input_embedding,
@@ -186,15 +319,167 @@ impl MLMOVETransformer {
             transformer_layers,
             output_projection,
             config,
+            varmap,
             device,
         })
     }
 
-    /// Load pre-trained MLMOVE weights from safetensors file
-    pub fn load_pretrained(_model_path: &str, device: Device) -> Result<Self> {
-        // TODO: Implement safetensors loading
-        // For now, create a new model with default weights
-        Self::new(device)
+    /// Load MLMOVE weights from safetensors, ONNX, a PyTorch `.pt` state dict or
+    /// the quantized GGUF written by [`MLMOVETransformer::save`]
+    ///
+    /// Parameter names may use either our var paths or the PyTorch layout, see
+    /// [`crate::conversion_utils::map_pytorch_names`]. The architecture comes
+    /// from the `.json` sidecar written by [`MLMOVETransformer::save`], or is
+    /// inferred from the weight shapes when there is none.
+    pub fn load_pretrained(model_path: &str, device: Device) -> Result<Self> {
+        let sidecar = Path::new(model_path).with_extension("json");
+        let config = if sidecar.exists() {
+            Some(
+                serde_json::from_str(&std::fs::read_to_string(&sidecar)?)
+                    .with_context(|| format!("parse {}", sidecar.display()))?,
+            )
+        } else {
+            None
+        };
+        if quantization::is_gguf(model_path) {
+            let mut quantized = quantization::read_gguf(Path::new(model_path), &device)?;
+            let tensors = quantization::dequantize(&quantized, &device)?;
+            let mut model = Self::from_tensors(&tensors, config, device)?;
+            quantization::load_quantized_layers(model.dense_layers_mut(), &mut quantized)
+                .with_context(|| format!("load weights from {model_path}"))?;
+            return Ok(model);
+        }
+        let tensors = crate::conversion_utils::read_checkpoint(model_path, &device)?;
+        let norm_first = crate::conversion_utils::checkpoint_norm_first(model_path)?;
+        let tensors = crate::conversion_utils::map_pytorch_names(tensors, norm_first)?;
+        Self::from_tensors(&tensors, config, device)
+            .with_context(|| format!("load weights from {model_path}"))
+    }
+
+    /// Build a model whose every parameter is taken from `tensors`, keyed by var path
+    pub fn from_tensors(
+        tensors: &HashMap<String, Tensor>,
+        config: Option<MLMOVEConfig>,
+        device: Device,
+    ) -> Result<Self> {
+        let config = match config {
+            Some(config) => config,
+            None => MLMOVEConfig::from_weights(tensors)?,
+        };
+        let model = Self::with_config(config, device)?;
+        {
+            let vars = model.varmap.data().lock().unwrap();
+            let mut missing: Vec<&str> = vars
+                .keys()
+                .filter(|name| !tensors.contains_key(*name))
+                .map(String::as_str)
+                .collect();
+            if !missing.is_empty() {
+                missing.sort();
+                bail!("checkpoint is missing {}", missing.join(", "));
+            }
+            for (name, var) in vars.iter() {
+                let tensor = &tensors[name];
+                if tensor.dims() != var.dims() {
+                    bail!(
+                        "{name} has shape {:?}, the model expects {:?}",
+                        tensor.dims(),
+                        var.dims()
+                    );
+                }
+                var.set(&tensor.to_dtype(DType::F32)?.to_device(&model.device)?)?;
+            }
+        }
+        Ok(model)
+    }
+
+    /// Write the weights and the configuration to a `.json` sidecar
+    ///
+    /// Weights go to a GGUF file for a `.gguf` path and to safetensors
+    /// otherwise; a quantized model can only be written as GGUF.
+    pub fn save(&self, model_path: &str) -> Result<()> {
+        if quantization::is_gguf(model_path) {
+            quantization::write_gguf(Path::new(model_path), &self.varmap, self.dense_layers())
+                .with_context(|| format!("save weights to {model_path}"))?;
+        } else if let Some(format) = self.quantization() {
+            bail!(
+                "a model quantized to {} is saved as .gguf, not {model_path}",
+                quantization::format_name(format)
+            );
+        } else {
+            self.varmap
+                .save(model_path)
+                .with_context(|| format!("save weights to {model_path}"))?;
+        }
+        let sidecar = Path::new(model_path).with_extension("json");
+        std::fs::write(&sidecar, serde_json::to_string_pretty(&self.config)?)
+            .with_context(|| format!("write {}", sidecar.display()))?;
+        Ok(())
+    }
+
+    pub fn config(&self) -> &MLMOVEConfig {
+        &self.config
+    }
+
+    /// Run the linear layers on weights quantized to `format`, for CPU inference
+    ///
+    /// Returns how many layers were quantized; the input embedding stays f32
+    /// unless `input_dim` is a multiple of the format's block size, and so do
+    /// the layer norms and the positional embedding. A quantized model can no
+    /// longer be fine-tuned.
+    pub fn quantize(&mut self, format: GgmlDType) -> Result<usize> {
+        quantization::quantize_layers(self.dense_layers_mut(), format)
+    }
+
+    /// Format of the quantized layers, `None` for an f32 model
+    pub fn quantization(&self) -> Option<GgmlDType> {
+        quantization::layers_format(self.dense_layers().into_iter().map(|(_, layer)| layer))
+    }
+
+    /// Linear layers by var path
+    pub(crate) fn dense_layers(&self) -> Vec<(String, &Dense)> {
+        let mut layers = vec![
+            ("input_embedding".to_string(), &self.input_embedding),
+            ("output".to_string(), &self.output_projection),
+        ];
+        for (i, layer) in self.transformer_layers.iter().enumerate() {
+            let attention = &layer.self_attention;
+            let ff = &layer.feed_forward;
+            layers.extend([
+                (format!("layer_{i}.attention.query"), &attention.query),
+                (format!("layer_{i}.attention.key"), &attention.key),
+                (format!("layer_{i}.attention.value"), &attention.value),
+                (format!("layer_{i}.attention.output"), &attention.output),
+                (format!("layer_{i}.ff.linear1"), &ff.linear1),
+                (format!("layer_{i}.ff.linear2"), &ff.linear2),
+            ]);
+        }
+        layers
+    }
+
+    fn dense_layers_mut(&mut self) -> Vec<(String, &mut Dense)> {
+        let mut layers = vec![
+            ("input_embedding".to_string(), &mut self.input_embedding),
+            ("output".to_string(), &mut self.output_projection),
+        ];
+        for (i, layer) in self.transformer_layers.iter_mut().enumerate() {
+            let attention = &mut layer.self_attention;
+            let ff = &mut layer.feed_forward;
+            layers.extend([
+                (format!("layer_{i}.attention.query"), &mut attention.query),
+                (format!("layer_{i}.attention.key"), &mut attention.key),
+                (format!("layer_{i}.attention.value"), &mut attention.value),
+                (format!("layer_{i}.attention.output"), &mut attention.output),
+                (format!("layer_{i}.ff.linear1"), &mut ff.linear1),
+                (format!("layer_{i}.ff.linear2"), &mut ff.linear2),
+            ]);
+        }
+        layers
+    }
+
+    /// Parameters to hand to an optimizer
+    pub fn trainable_vars(&self) -> Vec<Var> {
+        self.varmap.all_vars()
     }
 
     /// Forward pass through the transformer
@@ -237,7 +522,7 @@ impl MLMOVETransformer {
         let probabilities = self.softmax(&logits)?;
 
         // Find best action
-        let probs_vec = probabilities.to_vec1::<f32>()?;
+        let probs_vec = probabilities.squeeze(0)?.to_vec1::<f32>()?;
         let best_action_idx = probs_vec
             .iter()
             .enumerate()
@@ -260,77 +545,54 @@ impl MLMOVETransformer {
 
     /// Convert behavioral vectors to input tensor
     fn behavioral_vectors_to_tensor(&self, vectors: &[BehavioralVector]) -> Result<Tensor> {
-        let seq_len = vectors.len().min(self.config.sequence_length);
-        let mut input_data = Vec::new();
-
-        for i in 0..seq_len {
-            let vector = &vectors[vectors.len() - seq_len + i];
-
-            // Extract relevant features for movement prediction
-            let features = vec![
-                vector.pos_x / 1000.0, // Normalize position
-                vector.pos_y / 1000.0,
-                vector.pos_z / 100.0,
-                vector.vel_x / 320.0, // Normalize by max speed
-                vector.vel_y / 320.0,
-                vector.vel_z / 320.0,
-                vector.yaw / 360.0, // Normalize angles
-                vector.pitch / 90.0,
-                vector.health / 100.0, // Normalize health/armor
-                vector.armor / 100.0,
-            ];
-
-            input_data.extend(features);
-        }
+        self.sequences_to_tensor(&[vectors])
+    }
 
-        // Pad if necessary
-        while input_data.len() < self.config.sequence_length * self.config.input_dim {
-            input_data.push(0.0);
+    /// Stack sequences into a `(batch, sequence_length, input_dim)` tensor
+    ///
+    /// Each sequence keeps its latest `sequence_length` vectors. A shorter one
+    /// is padded at the start by repeating its oldest vector, so the last
+    /// position, which the prediction is read from, always holds the latest
+    /// state, as it does for the full windows the model is trained on.
+    pub fn sequences_to_tensor(&self, sequences: &[&[BehavioralVector]]) -> Result<Tensor> {
+        let row_len = self.config.sequence_length * self.config.input_dim;
+        let mut input_data = Vec::with_capacity(sequences.len() * row_len);
+
+        for vectors in sequences {
+            let Some(oldest) = vectors.first() else {
+                bail!("cannot predict from an empty sequence");
+            };
+            let seq_len = vectors.len().min(self.config.sequence_length);
+            let oldest = movement_features(oldest);
+            for _ in seq_len..self.config.sequence_length {
+                input_data.extend_from_slice(&oldest);
+            }
+            for vector in &vectors[vectors.len() - seq_len..] {
+                input_data.extend(movement_features(vector));
+            }
         }
 
-        let tensor = Tensor::from_slice(
-            &input_data,
-            (1, self.config.sequence_length, self.config.input_dim),
+        let tensor = Tensor::from_vec(
+            input_data,
+            (
+                sequences.len(),
+                self.config.sequence_length,
+                self.config.input_dim,
+            ),
             &self.device,
         )?;
 
         Ok(tensor)
     }
 
-    /// Simplified softmax implementation
+    /// Softmax over the action dimension
     fn softmax(&self, input: &Tensor) -> Result<Tensor> {
-        let max_val = input
-            .max(candle_core::D::Minus1)?
-            .broadcast_as(input.shape())?;
-        let shifted = input.sub(&max_val)?;
-        let exp_vals = shifted.exp()?;
-        let sum_exp = exp_vals
-            .sum(candle_core::D::Minus1)?
-            .broadcast_as(input.shape())?;
-        Ok(exp_vals.div(&sum_exp)?)
+        Ok(candle_nn::ops::softmax(input, candle_core::D::Minus1)?)
     }
 
     /// Convert action index to discrete action
     fn index_to_action(&self, index: usize) -> DiscreteAction {
-        // 97-way action space: 8 directions × 6 speeds × 2 jump states = 96, + 1 no-op
-        if index == 96 {
-            // No-op action
-            return DiscreteAction {
-                direction: 8,
-                speed: 0,
-                jump: 0,
-            };
-        }
-
-        let direction = (index % 8) as u8;
-        let speed = ((index / 8) % 6) as u8;
-        let jump = (index / 48) as u8;
-
-        DiscreteAction {
-            direction,
-            speed,
-            jump,
-        }
+        DiscreteAction::from_index(index)
     }
 
     /// Convert discrete action to CS2 movement commands
@@ -366,10 +628,27 @@ impl MLMOVETransformer {
             forward_move: move_y * speed_multiplier,
             side_move: move_x * speed_multiplier,
             jump: action.jump > 0,
+            walk: matches!(action.speed, 1 | 2),
         }
     }
 }
 
+/// Scaled movement inputs of one behavioral vector, matching `MLMOVEConfig::input_dim`
+fn movement_features(vector: &BehavioralVector) -> [f32; 10] {
+    [
+        vector.pos_x / 1000.0, // Normalize position
+        vector.pos_y / 1000.0,
+        vector.pos_z / 100.0,
+        vector.vel_x / 320.0, // Normalize by max speed
+        vector.vel_y / 320.0,
+        vector.vel_z / 320.0,
+        vector.yaw / 360.0, // Normalize angles
+        vector.pitch / 90.0,
+        vector.health / 100.0, // Normalize health/armor
+        vector.armor / 100.0,
+    ]
+}
+
 /// CS2 Movement commands generated from discrete actions
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MovementCommands {
@@ -379,6 +658,8 @@ pub struct MovementCommands {
     pub side_move: f32,
     /// Jump command (true/false)
     pub jump: bool,
+    /// Hold walk for the slow speed buckets
+    pub walk: bool,
 }
 
 impl TransformerLayer {
@@ -412,10 +693,10 @@ impl TransformerLayer {
 
 impl SelfAttention {
     fn new(model_dim: usize, vs: VarBuilder) -> Result<Self> {
-        let query = linear(model_dim, model_dim, vs.pp("query"))?;
-        let key = linear(model_dim, model_dim, vs.pp("key"))?;
-        let value = linear(model_dim, model_dim, vs.pp("value"))?;
-        let output = linear(model_dim, model_dim, vs.pp("output"))?;
+        let query = Dense::Float(linear(model_dim, model_dim, vs.pp("query"))?);
+        let key = Dense::Float(linear(model_dim, model_dim, vs.pp("key"))?);
+        let value = Dense::Float(linear(model_dim, model_dim, vs.pp("value"))?);
+        let output = Dense::Float(linear(model_dim, model_dim, vs.pp("output"))?);
 
         Ok(Self {
             query,
@@ -433,16 +714,9 @@ impl SelfAttention {
 
         // Single-head attention (as per MLMOVE paper)
         let scores = queries.matmul(&keys.transpose(1, 2)?)?;
-        let scaled_scores = scores.div(&Tensor::new(
-            (self.model_dim as f32).sqrt(),
-            input.device(),
-        )?)?;
+        let scaled_scores = (scores / (self.model_dim as f64).sqrt())?;
 
-        // Simplified attention weights (using max normalization instead of softmax)
-        let max_scores = scaled_scores
-            .max(candle_core::D::Minus1)?
-            .broadcast_as(scaled_scores.shape())?;
-        let attention_weights = (scaled_scores - max_scores)?.exp()?;
+        let attention_weights = candle_nn::ops::softmax(&scaled_scores, candle_core::D::Minus1)?;
 
         let attended = attention_weights.matmul(&values)?;
         let output = self.output.forward(&attended)?;
@@ -453,8 +727,8 @@ impl SelfAttention {
 
 impl FeedForward {
     fn new(model_dim: usize, ff_dim: usize, vs: VarBuilder) -> Result<Self> {
-        let linear1 = linear(model_dim, ff_dim, vs.pp("linear1"))?;
-        let linear2 = linear(ff_dim, model_dim, vs.pp("linear2"))?;
+        let linear1 = Dense::Float(linear(model_dim, ff_dim, vs.pp("linear1"))?);
+        let linear2 = Dense::Float(linear(ff_dim, model_dim, vs.pp("linear2"))?);
 
         Ok(Self { linear1, linear2 })
     }
@@ -469,22 +743,23 @@ impl FeedForward {
 
 impl LayerNorm {
     fn new(model_dim: usize, vs: VarBuilder) -> Result<Self> {
-        let weight = linear(model_dim, model_dim, vs.pp("weight"))?;
-        let bias = linear(model_dim, model_dim, vs.pp("bias"))?;
+        let weight = vs.get_with_hints(model_dim, "weight", candle_nn::Init::Const(1.0))?;
+        let bias = vs.get_with_hints(model_dim, "bias", candle_nn::Init::Const(0.0))?;
 
         Ok(Self { weight, bias })
     }
 
     fn forward(&self, input: &Tensor) -> Result<Tensor> {
-        // Simplified layer normalization
-        let mean = input
-            .mean(candle_core::D::Minus1)?
-            .broadcast_as(input.shape())?;
-        let centered = input.sub(&mean)?;
+        // Built from differentiable ops so the weights can be fine-tuned
+        let mean = input.mean_keepdim(candle_core::D::Minus1)?;
+        let centered = input.broadcast_sub(&mean)?;
+        let variance = centered.sqr()?.mean_keepdim(candle_core::D::Minus1)?;
+        let normalized = centered.broadcast_div(&(variance + LAYER_NORM_EPS)?.sqrt()?)?;
 
         // Apply learned scaling and bias
-        let scaled = self.weight.forward(&centered)?;
-        let output = self.bias.forward(&scaled)?;
+        let output = normalized
+            .broadcast_mul(&self.weight)?
+            .broadcast_add(&self.bias)?;
 
         Ok(output)
     }
@@ -523,10 +798,65 @@ mod tests {
         assert_eq!(commands.forward_move, 1.0);
         assert_eq!(commands.side_move, 0.0);
         assert!(commands.jump);
+        assert!(!commands.walk);
+
+        let commands = transformer.action_to_movement_commands(&DiscreteAction {
+            direction: 6, // Left
+            speed: 2,     // Walk
+            jump: 0,
+        });
+        assert_eq!(commands.side_move, -0.6);
+        assert!(commands.walk);
 
         Ok(())
     }
 
+    #[test]
+    fn test_action_from_movement() {
+        let still = BehavioralVector {
+            tick: 1,
+            steamid: 123456789,
+            health: 100.0,
+            armor: 100.0,
+            pos_x: 0.0,
+            pos_y: 0.0,
+            pos_z: 64.0,
+            vel_x: 0.0,
+            vel_y: 0.0,
+            vel_z: 0.0,
+            yaw: 90.0,
+            pitch: 0.0,
+            weapon_id: 7,
+            ammo: 30.0,
+            is_airborne: 0.0,
+            delta_yaw: 0.0,
+            delta_pitch: 0.0,
+        };
+        assert_eq!(
+            DiscreteAction::from_movement(&still, &still).index(),
+            DiscreteAction::NO_OP_INDEX
+        );
+
+        // Looking along +y and running along +x is strafing right
+        let strafing = BehavioralVector {
+            vel_x: 250.0,
+            ..still.clone()
+        };
+        let action = DiscreteAction::from_movement(&still, &strafing);
+        assert_eq!((action.direction, action.speed, action.jump), (2, 4, 0));
+
+        // Leaving the ground while walking forward
+        let jumping = BehavioralVector {
+            vel_y: 150.0,
+            vel_z: 300.0,
+            is_airborne: 1.0,
+            ..still.clone()
+        };
+        let action = DiscreteAction::from_movement(&still, &jumping);
+        assert_eq!((action.direction, action.speed, action.jump), (0, 2, 1));
+        assert_eq!(DiscreteAction::from_index(action.index()), action);
+    }
+
     #[test]
     fn test_behavioral_vector_conversion() -> Result<()> {
         let transformer = MLMOVETransformer::new(Device::Cpu)?;
@@ -554,6 +884,19 @@ mod tests {
         let tensor = transformer.behavioral_vectors_to_tensor(&vectors)?;
         assert_eq!(tensor.shape().dims(), &[1, 32, 10]); // batch, seq_len, features
 
+        // A single state fills the whole window instead of sitting among zeros
+        let rows = tensor.squeeze(0)?.to_vec2::<f32>()?;
+        assert!(rows.iter().all(|row| row == &rows[31]));
+        assert_eq!(rows[31].to_vec(), movement_features(&vectors[0]).to_vec());
+        assert!(transformer.behavioral_vectors_to_tensor(&[]).is_err());
+
+        // Predicting from it is predicting from a window of that state
+        let full = vec![vectors[0].clone(); 32];
+        let short = transformer.predict_movement(&vectors)?;
+        let repeated = transformer.predict_movement(&full)?;
+        assert_eq!(short.action.index(), repeated.action.index());
+        assert_eq!(short.action_probabilities, repeated.action_probabilities);
+
         Ok(())
     }
 
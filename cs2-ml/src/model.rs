@@ -1,25 +1,101 @@
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
+use candle_core::quantized::GgmlDType;
 use candle_core::{DType, Device, Tensor};
-use candle_nn::{linear, Linear, Module, VarBuilder, VarMap};
+use candle_nn::{linear, AdamW, Module, Optimizer, ParamsAdamW, VarBuilder, VarMap};
+use cs2_common::player_frames::wrap_degrees;
+use cs2_common::FeatureStats;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::quantization::{self, Dense};
+
+/// Number of inputs [`BehaviorNet::predict`] builds from an `InputVector`
+pub const BEHAVIOR_INPUT_DIM: usize = 12;
+/// Delta yaw and delta pitch
+pub const BEHAVIOR_OUTPUT_DIM: usize = 2;
+
+/// Commit the binary was built from, recorded in checkpoint sidecars
+pub const GIT_HASH: &str = env!("CS2_ML_GIT_HASH");
 
-#[derive(Debug)]
 pub struct BehaviorNet {
-    layer1: Linear,
-    layer2: Linear,
-    output_layer: Linear,
+    layer1: Dense,
+    layer2: Dense,
+    output_layer: Dense,
     pub input_dim: usize,
     pub output_dim: usize,
+    pub normalization: Vec<FeatureStats>, // Per-input statistics, empty when inputs are used raw
+    varmap: VarMap,
     device: Device,
 }
 
+impl std::fmt::Debug for BehaviorNet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BehaviorNet")
+            .field("input_dim", &self.input_dim)
+            .field("output_dim", &self.output_dim)
+            .field("normalized", &!self.normalization.is_empty())
+            .field("quantization", &self.quantization())
+            .finish()
+    }
+}
+
+/// Settings for [`BehaviorNet::train_with_config`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrainingConfig {
+    pub epochs: usize,
+    pub batch_size: usize,
+    pub learning_rate: f64,
+    pub weight_decay: f64,
+    pub validation_split: f32, // Fraction of samples held out for early stopping
+    pub patience: usize,       // Epochs without validation improvement before stopping
+    pub seed: u64,
+}
+
+impl Default for TrainingConfig {
+    fn default() -> Self {
+        Self {
+            epochs: 100,
+            batch_size: 256,
+            learning_rate: 1e-3,
+            weight_decay: 1e-2,
+            validation_split: 0.1,
+            patience: 10,
+            seed: 42,
+        }
+    }
+}
+
+/// Loss curves of a training run
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TrainingReport {
+    pub train_loss: Vec<f32>,
+    pub val_loss: Vec<f32>,
+    pub best_epoch: usize,
+    pub best_val_loss: f32,
+    pub stopped_early: bool,
+}
+
+/// JSON sidecar written next to the safetensors weights
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BehaviorNetMetadata {
+    pub input_dim: usize,
+    pub output_dim: usize,
+    pub normalization: Vec<FeatureStats>,
+    pub git_hash: String,
+}
+
 impl BehaviorNet {
     pub fn new(input_dim: usize, output_dim: usize, device: Device) -> Result<Self> {
         let varmap = VarMap::new();
         let vs = VarBuilder::from_varmap(&varmap, DType::F32, &device);
 
-        let layer1 = linear(input_dim, 512, vs.pp("layer1"))?;
-        let layer2 = linear(512, 256, vs.pp("layer2"))?;
-        let output_layer = linear(256, output_dim, vs.pp("output"))?;
+        let layer1 = Dense::Float(linear(input_dim, 512, vs.pp("layer1"))?);
+        let layer2 = Dense::Float(linear(512, 256, vs.pp("layer2"))?);
+        let output_layer = Dense::Float(linear(256, output_dim, vs.pp("output"))?);
 
         Ok(BehaviorNet {
             layer1,
@@ -27,6 +103,8 @@ impl BehaviorNet {
             output_layer,
             input_dim,
             output_dim,
+            normalization: Vec::new(),
+            varmap,
             device,
         })
     }
@@ -40,65 +118,398 @@ impl BehaviorNet {
         Ok(output)
     }
 
+    /// Run one raw input through the normalization and the network
     pub fn forward_vec(&self, input: &[f32]) -> Result<Vec<f32>> {
-        let input_tensor = Tensor::from_slice(input, (1, self.input_dim), &self.device)?;
+        let input = self.normalize(input)?;
+        let input_tensor = Tensor::from_slice(&input, (1, self.input_dim), &self.device)?;
         let output_tensor = self.forward(&input_tensor)?;
         let output_vec = output_tensor.to_vec2::<f32>()?;
         Ok(output_vec[0].clone())
     }
 
-    pub fn train(&mut self, _training_data: &[(Vec<f32>, Vec<f32>)]) -> Result<()> {
-        // TODO: Implement training loop with Candle optimizer
-        // This is a placeholder for the training implementation
-        println!("Training with Candle framework - implementation in progress");
+    /// Train with the default [`TrainingConfig`]
+    pub fn train(&mut self, training_data: &[(Vec<f32>, Vec<f32>)]) -> Result<TrainingReport> {
+        self.train_with_config(training_data, &TrainingConfig::default())
+    }
+
+    /// Train on a random `validation_split` of the samples, see [`BehaviorNet::train_with_validation`]
+    pub fn train_with_config(
+        &mut self,
+        training_data: &[(Vec<f32>, Vec<f32>)],
+        config: &TrainingConfig,
+    ) -> Result<TrainingReport> {
+        if training_data.is_empty() {
+            bail!("cannot train on an empty dataset");
+        }
+        let mut rng = StdRng::seed_from_u64(config.seed);
+        let mut indices: Vec<usize> = (0..training_data.len()).collect();
+        indices.shuffle(&mut rng);
+        let val_len = ((training_data.len() as f32 * config.validation_split) as usize)
+            .min(training_data.len() - 1);
+        let (val_idx, train_idx) = indices.split_at(val_len);
+        self.fit(training_data, train_idx.to_vec(), val_idx, config, &mut rng)
+    }
+
+    /// Mini-batch AdamW on the angular MSE, keeping the weights of the best validation epoch
+    ///
+    /// Targets are angle deltas in degrees and are wrapped into [-180, 180)
+    /// before training. Input normalization is fitted on the training samples.
+    /// Without validation samples the training loss decides the best epoch.
+    pub fn train_with_validation(
+        &mut self,
+        train: &[(Vec<f32>, Vec<f32>)],
+        validation: &[(Vec<f32>, Vec<f32>)],
+        config: &TrainingConfig,
+    ) -> Result<TrainingReport> {
+        if train.is_empty() {
+            bail!("cannot train on an empty dataset");
+        }
+        let data: Vec<(Vec<f32>, Vec<f32>)> = train.iter().chain(validation).cloned().collect();
+        let val_idx: Vec<usize> = (train.len()..data.len()).collect();
+        let mut rng = StdRng::seed_from_u64(config.seed);
+        self.fit(
+            &data,
+            (0..train.len()).collect(),
+            &val_idx,
+            config,
+            &mut rng,
+        )
+    }
+
+    /// Mean angular MSE over `data`
+    pub fn loss(&self, data: &[(Vec<f32>, Vec<f32>)]) -> Result<f32> {
+        if data.is_empty() {
+            bail!("cannot evaluate on an empty dataset");
+        }
+        self.check_shapes(data)?;
+        let indices: Vec<usize> = (0..data.len()).collect();
+        self.evaluate(data, &indices, TrainingConfig::default().batch_size)
+    }
+
+    fn check_shapes(&self, data: &[(Vec<f32>, Vec<f32>)]) -> Result<()> {
+        if let Some((input, target)) = data
+            .iter()
+            .find(|(i, t)| i.len() != self.input_dim || t.len() != self.output_dim)
+        {
+            bail!(
+                "sample has {} inputs and {} targets, network expects {} and {}",
+                input.len(),
+                target.len(),
+                self.input_dim,
+                self.output_dim
+            );
+        }
         Ok(())
     }
 
-    pub fn predict(&self, input: &cs2_common::InputVector) -> cs2_common::OutputVector {
-        // Convert InputVector to Vec<f32> for the model
-        let input_vec = vec![
-            input.pos_x,
-            input.pos_y,
-            input.pos_z,
-            input.vel_x,
-            input.vel_y,
-            input.vel_z,
-            input.health,
-            input.armor,
-            input.yaw,
-            input.pitch,
-            if input.is_airborne > 0.5 { 1.0 } else { 0.0 },
-            input.weapon_id_f32,
-        ];
-
-        match self.forward_vec(&input_vec) {
-            Ok(output) => cs2_common::OutputVector {
+    fn fit(
+        &mut self,
+        training_data: &[(Vec<f32>, Vec<f32>)],
+        mut train_idx: Vec<usize>,
+        val_idx: &[usize],
+        config: &TrainingConfig,
+        rng: &mut StdRng,
+    ) -> Result<TrainingReport> {
+        if let Some(format) = self.quantization() {
+            bail!(
+                "cannot train a network quantized to {}",
+                quantization::format_name(format)
+            );
+        }
+        self.check_shapes(training_data)?;
+
+        self.normalization = (0..self.input_dim)
+            .map(|i| {
+                let column: Vec<f32> = train_idx.iter().map(|&j| training_data[j].0[i]).collect();
+                FeatureStats::from_values(&column)
+                    .with_context(|| format!("no training samples to normalize input {i} with"))
+            })
+            .collect::<Result<_>>()?;
+
+        let mut optimizer = AdamW::new(
+            self.varmap.all_vars(),
+            ParamsAdamW {
+                lr: config.learning_rate,
+                weight_decay: config.weight_decay,
+                ..Default::default()
+            },
+        )?;
+
+        let mut report = TrainingReport {
+            best_val_loss: f32::INFINITY,
+            ..Default::default()
+        };
+        let mut best_weights = self.snapshot()?;
+        let batch_size = config.batch_size.max(1);
+
+        for epoch in 0..config.epochs {
+            train_idx.shuffle(rng);
+            let mut epoch_loss = 0.0;
+            for batch in train_idx.chunks(batch_size) {
+                let (inputs, targets) = self.batch_tensors(training_data, batch)?;
+                let loss = angular_mse(&self.forward(&inputs)?, &targets)?;
+                optimizer.backward_step(&loss)?;
+                epoch_loss += loss.to_scalar::<f32>()? * batch.len() as f32;
+            }
+            let train_loss = epoch_loss / train_idx.len() as f32;
+            let val_loss = if val_idx.is_empty() {
+                train_loss
+            } else {
+                self.evaluate(training_data, val_idx, batch_size)?
+            };
+            report.train_loss.push(train_loss);
+            report.val_loss.push(val_loss);
+
+            if val_loss < report.best_val_loss {
+                report.best_val_loss = val_loss;
+                report.best_epoch = epoch;
+                best_weights = self.snapshot()?;
+            } else if epoch - report.best_epoch >= config.patience {
+                report.stopped_early = true;
+                break;
+            }
+        }
+
+        self.restore(&best_weights)?;
+        Ok(report)
+    }
+
+    pub fn predict(&self, input: &cs2_common::InputVector) -> Result<cs2_common::OutputVector> {
+        let output = self.forward_vec(&behavior_features(input))?;
+        Ok(cs2_common::OutputVector {
+            delta_yaw: output.first().copied().unwrap_or(0.0),
+            delta_pitch: output.get(1).copied().unwrap_or(0.0),
+        })
+    }
+
+    /// Predict several inputs with one forward pass
+    pub fn predict_batch(
+        &self,
+        inputs: &[cs2_common::InputVector],
+    ) -> Result<Vec<cs2_common::OutputVector>> {
+        if inputs.is_empty() {
+            return Ok(Vec::new());
+        }
+        let features: Vec<f32> = inputs
+            .iter()
+            .map(|input| self.normalize(&behavior_features(input)))
+            .collect::<Result<Vec<_>>>()?
+            .concat();
+        let batch = Tensor::from_vec(features, (inputs.len(), self.input_dim), &self.device)?;
+        let outputs = self.forward(&batch)?.to_vec2::<f32>()?;
+        Ok(outputs
+            .iter()
+            .map(|output| cs2_common::OutputVector {
                 delta_yaw: output.first().copied().unwrap_or(0.0),
                 delta_pitch: output.get(1).copied().unwrap_or(0.0),
-            },
-            Err(_) => cs2_common::OutputVector {
-                delta_yaw: 0.0,
-                delta_pitch: 0.0,
-            },
+            })
+            .collect())
+    }
+
+    pub fn metadata(&self) -> BehaviorNetMetadata {
+        BehaviorNetMetadata {
+            input_dim: self.input_dim,
+            output_dim: self.output_dim,
+            normalization: self.normalization.clone(),
+            git_hash: GIT_HASH.to_string(),
         }
     }
 
+    /// Write the weights and the metadata to a `.json` sidecar next to them
+    ///
+    /// Weights go to a GGUF file for a `.gguf` path and to safetensors
+    /// otherwise; a quantized network can only be written as GGUF.
     pub fn save(&self, path: &str) -> Result<()> {
-        // TODO: Implement model saving with Candle
-        println!("Model saving to {path} - implementation in progress");
+        if quantization::is_gguf(path) {
+            quantization::write_gguf(Path::new(path), &self.varmap, self.dense_layers())
+                .with_context(|| format!("save weights to {path}"))?;
+        } else if let Some(format) = self.quantization() {
+            bail!(
+                "a network quantized to {} is saved as .gguf, not {path}",
+                quantization::format_name(format)
+            );
+        } else {
+            self.varmap
+                .save(path)
+                .with_context(|| format!("save weights to {path}"))?;
+        }
+        let sidecar = sidecar_path(path);
+        std::fs::write(&sidecar, serde_json::to_string_pretty(&self.metadata())?)
+            .with_context(|| format!("write {}", sidecar.display()))?;
         Ok(())
     }
 
-    pub fn load(path: &str, input_dim: usize, output_dim: usize, device: Device) -> Result<Self> {
-        // TODO: Implement model loading with Candle
-        println!("Model loading from {path} - implementation in progress");
-        Self::new(input_dim, output_dim, device)
+    /// Restore a network written by [`BehaviorNet::save`]
+    pub fn load(path: &str, device: Device) -> Result<Self> {
+        let sidecar = sidecar_path(path);
+        let metadata: BehaviorNetMetadata = serde_json::from_str(
+            &std::fs::read_to_string(&sidecar)
+                .with_context(|| format!("read {}", sidecar.display()))?,
+        )?;
+        if !metadata.normalization.is_empty() && metadata.normalization.len() != metadata.input_dim
+        {
+            bail!(
+                "{} normalizes {} inputs, the network takes {}",
+                sidecar.display(),
+                metadata.normalization.len(),
+                metadata.input_dim
+            );
+        }
+        let mut net = Self::new(metadata.input_dim, metadata.output_dim, device)?;
+        if quantization::is_gguf(path) {
+            let mut tensors = quantization::read_gguf(Path::new(path), &net.device)?;
+            net.restore(&quantization::dequantize(&tensors, &net.device)?)?;
+            quantization::load_quantized_layers(net.dense_layers_mut(), &mut tensors)
+                .with_context(|| format!("load weights from {path}"))?;
+        } else {
+            net.varmap
+                .load(path)
+                .with_context(|| format!("load weights from {path}"))?;
+        }
+        net.normalization = metadata.normalization;
+        Ok(net)
+    }
+
+    /// Run the linear layers on weights quantized to `format`, for CPU inference
+    ///
+    /// Returns how many layers were quantized; layers whose input width is
+    /// not a multiple of the format's block size stay f32. A quantized
+    /// network can no longer be trained.
+    pub fn quantize(&mut self, format: GgmlDType) -> Result<usize> {
+        quantization::quantize_layers(self.dense_layers_mut(), format)
     }
+
+    /// Format of the quantized layers, `None` for an f32 network
+    pub fn quantization(&self) -> Option<GgmlDType> {
+        quantization::layers_format(self.dense_layers().into_iter().map(|(_, layer)| layer))
+    }
+
+    /// Linear layers by var path
+    pub(crate) fn dense_layers(&self) -> Vec<(String, &Dense)> {
+        vec![
+            ("layer1".to_string(), &self.layer1),
+            ("layer2".to_string(), &self.layer2),
+            ("output".to_string(), &self.output_layer),
+        ]
+    }
+
+    fn dense_layers_mut(&mut self) -> Vec<(String, &mut Dense)> {
+        vec![
+            ("layer1".to_string(), &mut self.layer1),
+            ("layer2".to_string(), &mut self.layer2),
+            ("output".to_string(), &mut self.output_layer),
+        ]
+    }
+
+    /// Standardize `input` with the fitted statistics, if there are any
+    fn normalize(&self, input: &[f32]) -> Result<Vec<f32>> {
+        if self.normalization.is_empty() {
+            return Ok(input.to_vec());
+        }
+        if self.normalization.len() != input.len() {
+            bail!(
+                "input has {} features, the normalization expects {}",
+                input.len(),
+                self.normalization.len()
+            );
+        }
+        Ok(input
+            .iter()
+            .zip(&self.normalization)
+            .map(|(v, s)| {
+                if s.std > f32::EPSILON {
+                    (v - s.mean) / s.std
+                } else {
+                    v - s.mean
+                }
+            })
+            .collect())
+    }
+
+    fn batch_tensors(
+        &self,
+        data: &[(Vec<f32>, Vec<f32>)],
+        batch: &[usize],
+    ) -> Result<(Tensor, Tensor)> {
+        let mut inputs = Vec::with_capacity(batch.len() * self.input_dim);
+        let mut targets = Vec::with_capacity(batch.len() * self.output_dim);
+        for &i in batch {
+            inputs.extend(self.normalize(&data[i].0)?);
+            targets.extend(data[i].1.iter().map(|&t| wrap_degrees(t)));
+        }
+        Ok((
+            Tensor::from_vec(inputs, (batch.len(), self.input_dim), &self.device)?,
+            Tensor::from_vec(targets, (batch.len(), self.output_dim), &self.device)?,
+        ))
+    }
+
+    fn evaluate(
+        &self,
+        data: &[(Vec<f32>, Vec<f32>)],
+        indices: &[usize],
+        batch_size: usize,
+    ) -> Result<f32> {
+        let mut total = 0.0;
+        for batch in indices.chunks(batch_size) {
+            let (inputs, targets) = self.batch_tensors(data, batch)?;
+            let loss = angular_mse(&self.forward(&inputs)?, &targets)?;
+            total += loss.to_scalar::<f32>()? * batch.len() as f32;
+        }
+        Ok(total / indices.len() as f32)
+    }
+
+    fn snapshot(&self) -> Result<HashMap<String, Tensor>> {
+        let vars = self.varmap.data().lock().unwrap();
+        vars.iter()
+            .map(|(name, var)| Ok((name.clone(), var.as_tensor().copy()?)))
+            .collect()
+    }
+
+    fn restore(&self, weights: &HashMap<String, Tensor>) -> Result<()> {
+        let vars = self.varmap.data().lock().unwrap();
+        for (name, var) in vars.iter() {
+            if let Some(tensor) = weights.get(name) {
+                var.set(tensor)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Network inputs for one state, in the order the policy is trained on
+pub fn behavior_features(input: &cs2_common::InputVector) -> Vec<f32> {
+    vec![
+        input.pos_x,
+        input.pos_y,
+        input.pos_z,
+        input.vel_x,
+        input.vel_y,
+        input.vel_z,
+        input.health,
+        input.armor,
+        input.yaw,
+        input.pitch,
+        if input.is_airborne > 0.5 { 1.0 } else { 0.0 },
+        input.weapon_id_f32,
+    ]
+}
+
+/// Mean squared error of angle predictions in degrees, measured the short way round
+pub fn angular_mse(prediction: &Tensor, target: &Tensor) -> Result<Tensor> {
+    let diff = (prediction - target)?;
+    // The wrap term has no gradient, so only the wrapped difference is trained on
+    let turns = ((&diff / 360.0)?.round()? * 360.0)?;
+    Ok((diff - turns)?.sqr()?.mean_all()?)
+}
+
+fn sidecar_path(path: &str) -> std::path::PathBuf {
+    Path::new(path).with_extension("json")
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use tempfile::tempdir;
 
     #[test]
     fn test_forward_shape() {
@@ -112,58 +523,77 @@ mod tests {
     fn test_training() -> Result<()> {
         let mut net = BehaviorNet::new(14, 2, Device::Cpu)?;
 
-        // Generate synthetic training data: identity mapping for simplicity
+        // Constant targets from varied inputs
         let mut dataset = Vec::new();
-        for _ in 0..100 {
-            let input = vec![0.0; 14];
+        for i in 0..40 {
+            let input = vec![i as f32 / 40.0; 14];
             let output = vec![1.0, 0.5]; // Always predict these values
             dataset.push((input, output));
         }
 
-        // Train for a few epochs
-        net.train(&dataset)?;
+        let config = TrainingConfig {
+            epochs: 60,
+            batch_size: 8,
+            learning_rate: 3e-3,
+            patience: 60,
+            ..Default::default()
+        };
+        let report = net.train_with_config(&dataset, &config)?;
+        assert!(report.best_val_loss < report.val_loss[0]);
 
-        // Test that it "learned" something (placeholder check)
-        let input = vec![0.0; 14];
-        let output = net.forward_vec(&input)?;
-
-        // Output should be a vector of zeros (placeholder behavior)
-        assert_eq!(output, vec![0.0, 0.0]);
+        let output = net.forward_vec(&[0.5; 14])?;
+        assert!((output[0] - 1.0).abs() < 0.1, "{output:?}");
+        assert!((output[1] - 0.5).abs() < 0.1, "{output:?}");
 
         Ok(())
     }
 
     #[test]
-    fn test_save_load() -> Result<()> {
-        // Skip this test for now since Candle save/load has not been implemented
-        println!("Skipping save/load test due to unimplemented functionality");
+    fn test_angular_mse_wraps_yaw() -> Result<()> {
+        let prediction = Tensor::new(&[[179.0f32, 0.0]], &Device::Cpu)?;
+        let target = Tensor::new(&[[-179.0f32, 1.0]], &Device::Cpu)?;
+        let loss = angular_mse(&prediction, &target)?.to_scalar::<f32>()?;
+        // 2 degrees the short way round, 1 degree of pitch
+        assert!((loss - 2.5).abs() < 1e-4);
         Ok(())
+    }
 
-        // Original test implementation commented out:
-        /*
+    #[test]
+    fn test_save_load() -> Result<()> {
         let tmp_dir = tempdir()?;
-        let model_path = tmp_dir.path().join("test_model.pt");
+        let model_path = tmp_dir.path().join("test_model.safetensors");
+        let model_path = model_path.to_str().unwrap();
 
-        // Create and save a model
-        let net_save = BehaviorNet::new(14, 2, Device::Cpu);
-        net_save.save(model_path.to_str().unwrap())?;
-
-        // Load the model
-        let net_load = BehaviorNet::load(model_path.to_str().unwrap(), 14, 2, Device::Cpu)?;
+        let dataset: Vec<(Vec<f32>, Vec<f32>)> = (0..32)
+            .map(|i| (vec![i as f32; 14], vec![i as f32 * 0.1, -0.2]))
+            .collect();
+        let mut net_save = BehaviorNet::new(14, 2, Device::Cpu)?;
+        net_save.train_with_config(
+            &dataset,
+            &TrainingConfig {
+                epochs: 2,
+                ..Default::default()
+            },
+        )?;
+        net_save.save(model_path)?;
 
-        // Verify both models produce the same output for the same input
-        let input = Tensor::rand(&[1, 14], (Kind::Float, Device::Cpu));
-        let output_save = net_save.forward(&input);
-        let output_load = net_load.forward(&input);
+        let net_load = BehaviorNet::load(model_path, Device::Cpu)?;
+        assert_eq!(net_load.metadata(), net_save.metadata());
 
-        let diff = output_save - output_load;
-        // Use double_value instead of f64::from
-        let max_diff = diff.abs().max().double_value(&[]);
+        let input = vec![3.0; 14];
+        assert_eq!(net_save.forward_vec(&input)?, net_load.forward_vec(&input)?);
+        assert!(net_load.forward_vec(&input[1..]).is_err());
 
-        // The outputs should be very close (may not be exactly equal due to numerical precision)
-        assert!(max_diff < 1e-5, "Max difference: {}", max_diff);
+        // Statistics for fewer inputs than the network takes are refused
+        let mut metadata = net_save.metadata();
+        metadata.normalization.pop();
+        std::fs::write(
+            tmp_dir.path().join("test_model.json"),
+            serde_json::to_string(&metadata)?,
+        )?;
+        let err = BehaviorNet::load(model_path, Device::Cpu).unwrap_err();
+        assert!(err.to_string().contains("normalizes 13 inputs"), "{err}");
 
         Ok(())
-        */
     }
 }
@@ -0,0 +1,256 @@
+/// Minimal ONNX reader for model weights
+///
+/// Decodes the graph initializers of an ONNX `ModelProto`, which is where
+/// exported PyTorch modules keep their parameters, and the wiring of its nodes.
+/// Message layouts follow `onnx.proto`; unknown fields are skipped by the decoder.
+use anyhow::{bail, Context, Result};
+use candle_core::{Device, Tensor};
+use prost::Message;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+/// `TensorProto.DataType.FLOAT`
+pub const ONNX_FLOAT: i32 = 1;
+/// `TensorProto.DataType.DOUBLE`
+pub const ONNX_DOUBLE: i32 = 11;
+/// `TensorProto.DataLocation.EXTERNAL`
+const ONNX_EXTERNAL: i32 = 1;
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ModelProto {
+    #[prost(message, optional, tag = "7")]
+    pub graph: Option<GraphProto>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct GraphProto {
+    #[prost(message, repeated, tag = "1")]
+    pub node: Vec<NodeProto>,
+    #[prost(message, repeated, tag = "5")]
+    pub initializer: Vec<TensorProto>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct NodeProto {
+    #[prost(string, repeated, tag = "1")]
+    pub input: Vec<String>,
+    #[prost(string, repeated, tag = "2")]
+    pub output: Vec<String>,
+    #[prost(string, tag = "3")]
+    pub name: String,
+    #[prost(string, tag = "4")]
+    pub op_type: String,
+}
+
+impl NodeProto {
+    pub fn new(op_type: &str, inputs: &[&str], outputs: &[&str]) -> Self {
+        Self {
+            input: inputs.iter().map(|i| i.to_string()).collect(),
+            output: outputs.iter().map(|o| o.to_string()).collect(),
+            name: String::new(),
+            op_type: op_type.to_string(),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct TensorProto {
+    #[prost(int64, repeated, tag = "1")]
+    pub dims: Vec<i64>,
+    #[prost(int32, tag = "2")]
+    pub data_type: i32,
+    #[prost(float, repeated, tag = "4")]
+    pub float_data: Vec<f32>,
+    #[prost(string, tag = "8")]
+    pub name: String,
+    #[prost(bytes = "vec", tag = "9")]
+    pub raw_data: Vec<u8>,
+    #[prost(double, repeated, tag = "10")]
+    pub double_data: Vec<f64>,
+    #[prost(int32, tag = "14")]
+    pub data_location: i32,
+}
+
+impl TensorProto {
+    /// A float initializer stored inline as `float_data`
+    pub fn from_f32(name: &str, dims: &[usize], values: Vec<f32>) -> Self {
+        Self {
+            dims: dims.iter().map(|&d| d as i64).collect(),
+            data_type: ONNX_FLOAT,
+            float_data: values,
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Decode the values as an f32 tensor
+    pub fn to_tensor(&self, device: &Device) -> Result<Tensor> {
+        if self.data_location == ONNX_EXTERNAL {
+            bail!(
+                "initializer {} uses external data, which is not supported",
+                self.name
+            );
+        }
+        let values: Vec<f32> = match self.data_type {
+            ONNX_FLOAT if !self.raw_data.is_empty() => self
+                .raw_data
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect(),
+            ONNX_FLOAT => self.float_data.clone(),
+            ONNX_DOUBLE if !self.raw_data.is_empty() => self
+                .raw_data
+                .chunks_exact(8)
+                .map(|b| f64::from_le_bytes(b.try_into().unwrap()) as f32)
+                .collect(),
+            ONNX_DOUBLE => self.double_data.iter().map(|&v| v as f32).collect(),
+            other => bail!(
+                "initializer {} has unsupported data type {other}",
+                self.name
+            ),
+        };
+        let dims: Vec<usize> = self.dims.iter().map(|&d| d as usize).collect();
+        Tensor::from_vec(values, dims, device)
+            .with_context(|| format!("initializer {} does not match its dims", self.name))
+    }
+}
+
+fn read_graph(path: &Path) -> Result<GraphProto> {
+    let bytes = std::fs::read(path).with_context(|| format!("read {}", path.display()))?;
+    let model = ModelProto::decode(bytes.as_slice())
+        .with_context(|| format!("decode ONNX model {}", path.display()))?;
+    match model.graph {
+        Some(graph) => Ok(graph),
+        None => bail!("ONNX model {} has no graph", path.display()),
+    }
+}
+
+/// Every named initializer of an ONNX model
+pub fn read_initializers(
+    path: impl AsRef<Path>,
+    device: &Device,
+) -> Result<HashMap<String, Tensor>> {
+    read_graph(path.as_ref())?
+        .initializer
+        .iter()
+        .map(|init| Ok((init.name.clone(), init.to_tensor(device)?)))
+        .collect()
+}
+
+/// Whether the graph normalizes before its residual connections
+///
+/// A post-norm layer adds the sublayer output to its input and normalizes the
+/// sum, which is then both fed to the next sublayer and added to its output,
+/// so a `LayerNormalization` output is consumed by an `Add`. In a pre-norm
+/// layer the residual `Add` takes the unnormalized input instead. `None` when
+/// the graph has no `LayerNormalization` nodes, e.g. because the export
+/// decomposed them into elementwise ops.
+pub fn read_norm_first(path: impl AsRef<Path>) -> Result<Option<bool>> {
+    let graph = read_graph(path.as_ref())?;
+    let normalized: HashSet<&str> = graph
+        .node
+        .iter()
+        .filter(|node| node.op_type == "LayerNormalization")
+        .filter_map(|node| node.output.first().map(String::as_str))
+        .collect();
+    if normalized.is_empty() {
+        return Ok(None);
+    }
+    let residual_of_normalized = graph
+        .node
+        .iter()
+        .filter(|node| node.op_type == "Add")
+        .any(|node| node.input.iter().any(|i| normalized.contains(i.as_str())));
+    Ok(Some(!residual_of_normalized))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_initializer_roundtrip() -> Result<()> {
+        let raw = TensorProto {
+            dims: vec![2],
+            data_type: ONNX_FLOAT,
+            name: "bias".to_string(),
+            raw_data: [1.5f32, -2.0]
+                .iter()
+                .flat_map(|v| v.to_le_bytes())
+                .collect(),
+            ..Default::default()
+        };
+        let model = ModelProto {
+            graph: Some(GraphProto {
+                node: vec![],
+                initializer: vec![
+                    TensorProto::from_f32("weight", &[2, 3], vec![0.0, 1.0, 2.0, 3.0, 4.0, 5.0]),
+                    raw,
+                ],
+            }),
+        };
+        let tmp = tempfile::tempdir()?;
+        let path = tmp.path().join("model.onnx");
+        std::fs::write(&path, model.encode_to_vec())?;
+
+        let tensors = read_initializers(&path, &Device::Cpu)?;
+        assert_eq!(tensors["weight"].dims(), &[2, 3]);
+        assert_eq!(tensors["weight"].to_vec2::<f32>()?[1], vec![3.0, 4.0, 5.0]);
+        assert_eq!(tensors["bias"].to_vec1::<f32>()?, vec![1.5, -2.0]);
+        Ok(())
+    }
+
+    fn write_graph(path: &Path, node: Vec<NodeProto>) -> Result<()> {
+        let model = ModelProto {
+            graph: Some(GraphProto {
+                node,
+                initializer: vec![],
+            }),
+        };
+        std::fs::write(path, model.encode_to_vec())?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_norm_order_from_graph() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let path = tmp.path().join("model.onnx");
+
+        // x + attn(norm(x))
+        write_graph(
+            &path,
+            vec![
+                NodeProto::new("LayerNormalization", &["x", "g", "b"], &["n"]),
+                NodeProto::new("MatMul", &["n", "w"], &["h"]),
+                NodeProto::new("Add", &["h", "bias"], &["attn"]),
+                NodeProto::new("Add", &["x", "attn"], &["y"]),
+            ],
+        )?;
+        assert_eq!(read_norm_first(&path)?, Some(true));
+
+        // n = norm(x + attn(x)), then n + ff(n)
+        write_graph(
+            &path,
+            vec![
+                NodeProto::new("MatMul", &["x", "w"], &["attn"]),
+                NodeProto::new("Add", &["x", "attn"], &["r"]),
+                NodeProto::new("LayerNormalization", &["r", "g", "b"], &["n"]),
+                NodeProto::new("MatMul", &["n", "w2"], &["ff"]),
+                NodeProto::new("Add", &["n", "ff"], &["y"]),
+            ],
+        )?;
+        assert_eq!(read_norm_first(&path)?, Some(false));
+
+        // Normalization spelled out in elementwise ops cannot be told apart
+        write_graph(
+            &path,
+            vec![
+                NodeProto::new("ReduceMean", &["x"], &["mean"]),
+                NodeProto::new("Sub", &["x", "mean"], &["centered"]),
+                NodeProto::new("Add", &["x", "centered"], &["y"]),
+            ],
+        )?;
+        assert_eq!(read_norm_first(&path)?, None);
+        Ok(())
+    }
+}
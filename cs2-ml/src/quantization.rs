@@ -0,0 +1,516 @@
+/// Quantized CPU inference
+///
+/// Linear layers can trade their f32 weights for GGML block-quantized ones
+/// (int8 `q8_0`, 4-bit `q4_0`, k-quants, ...) and run through candle's
+/// quantized matmul. Quantized models are written as GGUF files with the
+/// usual `.json` sidecar; layers whose input width is not a multiple of the
+/// format's block size, layer norms and embeddings stay f32.
+///
+/// candle only vectorizes the quantized dot products for the SIMD features
+/// enabled at compile time, so build with `-C target-cpu=native` (or at least
+/// `+avx`) before comparing latencies; see the `quantized_inference` bench.
+use anyhow::{bail, Context, Result};
+use candle_core::quantized::{gguf_file, GgmlDType, QTensor};
+use candle_core::{DType, Device, Module, Tensor};
+use candle_nn::{Linear, VarMap};
+use cs2_common::player_frames::wrap_degrees;
+use cs2_common::InputVector;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::dataset::SequenceWindow;
+use crate::mlmove_transformer::MLMOVETransformer;
+use crate::model::BehaviorNet;
+use crate::registry::ModelKind;
+
+/// Formats accepted by `--format`, by name
+pub const QUANTIZATION_FORMATS: [(&str, GgmlDType); 8] = [
+    ("q8_0", GgmlDType::Q8_0),
+    ("q5_0", GgmlDType::Q5_0),
+    ("q5_1", GgmlDType::Q5_1),
+    ("q4_0", GgmlDType::Q4_0),
+    ("q4_1", GgmlDType::Q4_1),
+    ("q6k", GgmlDType::Q6K),
+    ("q5k", GgmlDType::Q5K),
+    ("q4k", GgmlDType::Q4K),
+];
+
+/// Windows compared per forward pass in the accuracy report
+const COMPARE_BATCH: usize = 64;
+
+pub fn parse_format(name: &str) -> Result<GgmlDType> {
+    QUANTIZATION_FORMATS
+        .iter()
+        .find(|(known, _)| known.eq_ignore_ascii_case(name))
+        .map(|(_, format)| *format)
+        .with_context(|| {
+            let known: Vec<&str> = QUANTIZATION_FORMATS.iter().map(|(n, _)| *n).collect();
+            format!(
+                "unknown quantization format {name}, expected {}",
+                known.join(", ")
+            )
+        })
+}
+
+pub fn format_name(format: GgmlDType) -> &'static str {
+    QUANTIZATION_FORMATS
+        .iter()
+        .find(|(_, known)| *known == format)
+        .map(|(name, _)| *name)
+        .unwrap_or("f32")
+}
+
+/// Quantized checkpoints are recognised by their `.gguf` extension
+pub fn is_gguf(path: impl AsRef<Path>) -> bool {
+    path.as_ref().extension().is_some_and(|ext| ext == "gguf")
+}
+
+/// A linear layer running on f32 or on quantized weights
+#[derive(Debug, Clone)]
+pub enum Dense {
+    Float(Linear),
+    Quantized {
+        weight: Arc<QTensor>, // `(out, in)`, as the f32 weight it replaced
+        bias: Option<Tensor>,
+    },
+}
+
+impl Dense {
+    pub fn format(&self) -> Option<GgmlDType> {
+        match self {
+            Self::Float(_) => None,
+            Self::Quantized { weight, .. } => Some(weight.dtype()),
+        }
+    }
+
+    /// Quantize the weight if its rows split into whole blocks of `format`
+    pub fn quantize(&mut self, format: GgmlDType) -> Result<bool> {
+        let Self::Float(linear) = self else {
+            return Ok(false);
+        };
+        if linear.weight().dim(1)? % format.block_size() != 0 {
+            return Ok(false);
+        }
+        let weight = QTensor::quantize(linear.weight(), format)?;
+        let bias = linear.bias().cloned();
+        *self = Self::Quantized {
+            weight: Arc::new(weight),
+            bias,
+        };
+        Ok(true)
+    }
+}
+
+impl Module for Dense {
+    fn forward(&self, xs: &Tensor) -> candle_core::Result<Tensor> {
+        match self {
+            Self::Float(linear) => linear.forward(xs),
+            Self::Quantized { weight, bias } => {
+                let output = xs.contiguous()?.apply_op1_no_bwd(weight.as_ref())?;
+                match bias {
+                    Some(bias) => output.broadcast_add(bias),
+                    None => Ok(output),
+                }
+            }
+        }
+    }
+}
+
+/// Quantize every layer that can be, returning how many were
+pub(crate) fn quantize_layers(
+    layers: Vec<(String, &mut Dense)>,
+    format: GgmlDType,
+) -> Result<usize> {
+    let mut quantized = 0;
+    for (name, layer) in layers {
+        if layer
+            .quantize(format)
+            .with_context(|| format!("quantize {name}"))?
+        {
+            quantized += 1;
+        }
+    }
+    Ok(quantized)
+}
+
+/// Format of the quantized layers, `None` when every layer runs on f32
+pub(crate) fn layers_format<'a>(mut layers: impl Iterator<Item = &'a Dense>) -> Option<GgmlDType> {
+    layers.find_map(Dense::format)
+}
+
+/// Write a model's parameters as GGUF, quantized layers with their quantized weights
+pub(crate) fn write_gguf(path: &Path, vars: &VarMap, layers: Vec<(String, &Dense)>) -> Result<()> {
+    let quantized: HashMap<String, Arc<QTensor>> = layers
+        .into_iter()
+        .filter_map(|(name, layer)| match layer {
+            Dense::Quantized { weight, .. } => Some((format!("{name}.weight"), weight.clone())),
+            Dense::Float(_) => None,
+        })
+        .collect();
+    let mut tensors: Vec<(String, Arc<QTensor>)> = Vec::new();
+    {
+        let vars = vars.data().lock().unwrap();
+        let mut names: Vec<&String> = vars.keys().collect();
+        names.sort();
+        for name in names {
+            let tensor = match quantized.get(name) {
+                Some(weight) => weight.clone(),
+                None => Arc::new(QTensor::quantize(vars[name].as_tensor(), GgmlDType::F32)?),
+            };
+            tensors.push((name.clone(), tensor));
+        }
+    }
+    let tensors: Vec<(&str, &QTensor)> = tensors
+        .iter()
+        .map(|(name, tensor)| (name.as_str(), tensor.as_ref()))
+        .collect();
+    let mut file = std::io::BufWriter::new(
+        std::fs::File::create(path).with_context(|| format!("create {}", path.display()))?,
+    );
+    gguf_file::write(&mut file, &[], &tensors)?;
+    Ok(())
+}
+
+/// Every tensor of a GGUF file as written by [`write_gguf`]
+pub(crate) fn read_gguf(path: &Path, device: &Device) -> Result<HashMap<String, QTensor>> {
+    let mut file = std::io::BufReader::new(
+        std::fs::File::open(path).with_context(|| format!("open {}", path.display()))?,
+    );
+    let content =
+        gguf_file::Content::read(&mut file).with_context(|| format!("read {}", path.display()))?;
+    content
+        .tensor_infos
+        .keys()
+        .map(|name| Ok((name.clone(), content.tensor(&mut file, name, device)?)))
+        .collect()
+}
+
+/// f32 copies of GGUF tensors, to build the model the quantized layers go into
+pub(crate) fn dequantize(
+    tensors: &HashMap<String, QTensor>,
+    device: &Device,
+) -> Result<HashMap<String, Tensor>> {
+    tensors
+        .iter()
+        .map(|(name, tensor)| {
+            Ok((
+                name.clone(),
+                tensor.dequantize(device)?.to_dtype(DType::F32)?,
+            ))
+        })
+        .collect()
+}
+
+/// Put the quantized weights of a GGUF file back into the layers they came from
+pub(crate) fn load_quantized_layers(
+    layers: Vec<(String, &mut Dense)>,
+    tensors: &mut HashMap<String, QTensor>,
+) -> Result<()> {
+    for (name, layer) in layers {
+        let Some(weight) = tensors.remove(&format!("{name}.weight")) else {
+            bail!("checkpoint is missing {name}.weight");
+        };
+        if weight.dtype() == GgmlDType::F32 {
+            continue;
+        }
+        let bias = match layer {
+            Dense::Float(linear) => linear.bias().cloned(),
+            Dense::Quantized { bias, .. } => bias.clone(),
+        };
+        *layer = Dense::Quantized {
+            weight: Arc::new(weight),
+            bias,
+        };
+    }
+    Ok(())
+}
+
+/// How far a quantized checkpoint drifts from the f32 one it was made from
+#[derive(Debug, Clone, Serialize)]
+pub struct QuantizationReport {
+    pub format: String,
+    pub quantized_layers: usize,
+    pub layers: usize,
+    pub f32_bytes: u64, // Checkpoint sizes on disk
+    pub quantized_bytes: u64,
+    pub samples: usize, // Held-out samples the metrics are measured on, 0 without data
+    pub metrics: BTreeMap<String, f64>,
+}
+
+impl fmt::Display for QuantizationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{}: {} of {} linear layers quantized, {:.1} KiB -> {:.1} KiB",
+            self.format,
+            self.quantized_layers,
+            self.layers,
+            self.f32_bytes as f64 / 1024.0,
+            self.quantized_bytes as f64 / 1024.0
+        )?;
+        if self.samples == 0 {
+            return write!(f, "No held-out data to compare predictions on");
+        }
+        write!(f, "Compared on {} held-out samples:", self.samples)?;
+        for (name, value) in &self.metrics {
+            write!(f, "\n  {name}: {value:.4}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Write `checkpoint` quantized to `output` and measure the accuracy drop on `windows`
+///
+/// The aim policy is compared on the last vector of every window, the
+/// movement model on whole windows. Pass windows of matches the model was
+/// not trained on.
+pub fn quantize_checkpoint(
+    checkpoint: &Path,
+    output: &Path,
+    kind: ModelKind,
+    format: GgmlDType,
+    windows: &[SequenceWindow],
+) -> Result<QuantizationReport> {
+    if !is_gguf(output) {
+        bail!(
+            "quantized checkpoints are written as .gguf, not {}",
+            output.display()
+        );
+    }
+    let checkpoint_str = checkpoint.to_str().context("path is not valid UTF-8")?;
+    let output_str = output.to_str().context("path is not valid UTF-8")?;
+    let (quantized_layers, layers, samples, metrics) = match kind {
+        ModelKind::Behavior => {
+            let reference = BehaviorNet::load(checkpoint_str, Device::Cpu)?;
+            let mut quantized = BehaviorNet::load(checkpoint_str, Device::Cpu)?;
+            let count = quantized.quantize(format)?;
+            quantized.save(output_str)?;
+            let inputs: Vec<InputVector> = windows
+                .iter()
+                .filter_map(|w| w.vectors.last())
+                .map(InputVector::from_behavioral)
+                .collect();
+            let metrics = compare_behavior(&reference, &quantized, &inputs)?;
+            let layers = reference.dense_layers().len();
+            (count, layers, inputs.len(), metrics)
+        }
+        ModelKind::Mlmove => {
+            let reference = MLMOVETransformer::load_pretrained(checkpoint_str, Device::Cpu)?;
+            let mut quantized = MLMOVETransformer::load_pretrained(checkpoint_str, Device::Cpu)?;
+            let count = quantized.quantize(format)?;
+            quantized.save(output_str)?;
+            let metrics = compare_mlmove(&reference, &quantized, windows)?;
+            let layers = reference.dense_layers().len();
+            (count, layers, windows.len(), metrics)
+        }
+    };
+    Ok(QuantizationReport {
+        format: format_name(format).to_string(),
+        quantized_layers,
+        layers,
+        f32_bytes: std::fs::metadata(checkpoint)?.len(),
+        quantized_bytes: std::fs::metadata(output)?.len(),
+        samples,
+        metrics,
+    })
+}
+
+/// Aim error of a quantized policy against its f32 original, in degrees
+pub fn compare_behavior(
+    reference: &BehaviorNet,
+    quantized: &BehaviorNet,
+    inputs: &[InputVector],
+) -> Result<BTreeMap<String, f64>> {
+    let mut errors = Vec::with_capacity(inputs.len() * 2);
+    let mut magnitude = 0.0;
+    for chunk in inputs.chunks(COMPARE_BATCH) {
+        let expected = reference.predict_batch(chunk)?;
+        let actual = quantized.predict_batch(chunk)?;
+        for (e, a) in expected.iter().zip(&actual) {
+            errors.push(wrap_degrees(a.delta_yaw - e.delta_yaw).abs() as f64);
+            errors.push(wrap_degrees(a.delta_pitch - e.delta_pitch).abs() as f64);
+            magnitude += (e.delta_yaw.abs() + e.delta_pitch.abs()) as f64;
+        }
+    }
+    if errors.is_empty() {
+        return Ok(BTreeMap::new());
+    }
+    let mean = errors.iter().sum::<f64>() / errors.len() as f64;
+    errors.sort_by(f64::total_cmp);
+    let p99 = errors[((errors.len() - 1) as f64 * 0.99).round() as usize];
+    Ok(BTreeMap::from([
+        ("mean_abs_error_deg".to_string(), mean),
+        ("p99_abs_error_deg".to_string(), p99),
+        ("max_abs_error_deg".to_string(), errors[errors.len() - 1]),
+        (
+            "reference_mean_abs_deg".to_string(),
+            magnitude / errors.len() as f64,
+        ),
+    ]))
+}
+
+/// Action agreement and accuracy of a quantized movement model against its f32 original
+pub fn compare_mlmove(
+    reference: &MLMOVETransformer,
+    quantized: &MLMOVETransformer,
+    windows: &[SequenceWindow],
+) -> Result<BTreeMap<String, f64>> {
+    let (mut agree, mut reference_correct, mut quantized_correct) = (0, 0, 0);
+    let mut total_variation = 0.0;
+    for chunk in windows.chunks(COMPARE_BATCH) {
+        let sequences: Vec<&[cs2_common::BehavioralVector]> =
+            chunk.iter().map(|w| w.vectors.as_slice()).collect();
+        let input = reference.sequences_to_tensor(&sequences)?;
+        let probabilities = |model: &MLMOVETransformer| -> Result<Vec<Vec<f32>>> {
+            let logits = model.forward(&input)?;
+            Ok(candle_nn::ops::softmax(&logits, candle_core::D::Minus1)?.to_vec2()?)
+        };
+        let expected = probabilities(reference)?;
+        let actual = probabilities(quantized)?;
+        for ((window, e), a) in chunk.iter().zip(&expected).zip(&actual) {
+            let (e_best, a_best) = (argmax(e), argmax(a));
+            agree += usize::from(e_best == a_best);
+            reference_correct += usize::from(e_best == window.action_index());
+            quantized_correct += usize::from(a_best == window.action_index());
+            total_variation += 0.5
+                * e.iter()
+                    .zip(a)
+                    .map(|(p, q)| (p - q).abs() as f64)
+                    .sum::<f64>();
+        }
+    }
+    if windows.is_empty() {
+        return Ok(BTreeMap::new());
+    }
+    let n = windows.len() as f64;
+    Ok(BTreeMap::from([
+        ("top1_agreement".to_string(), agree as f64 / n),
+        ("mean_total_variation".to_string(), total_variation / n),
+        (
+            "reference_accuracy".to_string(),
+            reference_correct as f64 / n,
+        ),
+        (
+            "quantized_accuracy".to_string(),
+            quantized_correct as f64 / n,
+        ),
+    ]))
+}
+
+fn argmax(values: &[f32]) -> usize {
+    values
+        .iter()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(i, _)| i)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mlmove_transformer::MLMOVEConfig;
+    use crate::model::{BEHAVIOR_INPUT_DIM, BEHAVIOR_OUTPUT_DIM};
+    use bytemuck::Zeroable;
+    use cs2_common::BehavioralVector;
+
+    #[test]
+    fn test_format_names() {
+        for (name, format) in QUANTIZATION_FORMATS {
+            assert_eq!(parse_format(name).unwrap(), format);
+            assert_eq!(format_name(format), name);
+        }
+        assert_eq!(parse_format("Q8_0").unwrap(), GgmlDType::Q8_0);
+        assert!(parse_format("int3").is_err());
+    }
+
+    #[test]
+    fn test_quantized_behavior_net_round_trip() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let reference = BehaviorNet::new(BEHAVIOR_INPUT_DIM, BEHAVIOR_OUTPUT_DIM, Device::Cpu)?;
+        let f32_path = tmp.path().join("policy.safetensors");
+        reference.save(f32_path.to_str().unwrap())?;
+
+        let q8_path = tmp.path().join("policy.gguf");
+        let report = quantize_checkpoint(
+            &f32_path,
+            &q8_path,
+            ModelKind::Behavior,
+            GgmlDType::Q8_0,
+            &[],
+        )?;
+        // The first layer reads 12 inputs, fewer than a block, and stays f32
+        assert_eq!((report.quantized_layers, report.layers), (2, 3));
+        assert!(report.quantized_bytes < report.f32_bytes);
+
+        let quantized = BehaviorNet::load(q8_path.to_str().unwrap(), Device::Cpu)?;
+        assert_eq!(quantized.quantization(), Some(GgmlDType::Q8_0));
+        let inputs: Vec<InputVector> = (0..32)
+            .map(|i| InputVector {
+                yaw: i as f32 * 11.0,
+                pitch: 5.0,
+                health: 100.0,
+                pos_x: i as f32 * 40.0,
+                ..InputVector::zeroed()
+            })
+            .collect();
+        let metrics = compare_behavior(&reference, &quantized, &inputs)?;
+        // int8 weights and activations stay within a few percent of the f32 outputs
+        assert!(metrics["max_abs_error_deg"] > 0.0);
+        assert!(metrics["mean_abs_error_deg"] < 0.1 * metrics["reference_mean_abs_deg"]);
+        // A quantized network is only ever saved quantized
+        assert!(quantized
+            .save(tmp.path().join("again.safetensors").to_str().unwrap())
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_quantized_mlmove_round_trip() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let config = MLMOVEConfig {
+            num_layers: 2,
+            model_dim: 64,
+            ff_dim: 128,
+            sequence_length: 8,
+            ..Default::default()
+        };
+        let mut model = MLMOVETransformer::with_config(config, Device::Cpu)?;
+        let sequence: Vec<BehavioralVector> = (0..8)
+            .map(|i| BehavioralVector {
+                pos_x: i as f32 * 10.0,
+                vel_x: 200.0,
+                yaw: 90.0,
+                health: 100.0,
+                ..BehavioralVector::new(i, 1)
+            })
+            .collect();
+        let expected = model.predict_movement(&sequence)?;
+
+        assert_eq!(model.quantize(GgmlDType::Q8_0)?, 13);
+        let path = tmp.path().join("mlmove.gguf");
+        model.save(path.to_str().unwrap())?;
+        let loaded = MLMOVETransformer::load_pretrained(path.to_str().unwrap(), Device::Cpu)?;
+        assert_eq!(loaded.quantization(), Some(GgmlDType::Q8_0));
+
+        // The file holds exactly the quantized weights, which stay close to f32
+        let in_memory = model.predict_movement(&sequence)?;
+        let from_disk = loaded.predict_movement(&sequence)?;
+        assert_eq!(
+            in_memory.action_probabilities,
+            from_disk.action_probabilities
+        );
+        let total_variation: f32 = expected
+            .action_probabilities
+            .iter()
+            .zip(&from_disk.action_probabilities)
+            .map(|(p, q)| (p - q).abs())
+            .sum::<f32>()
+            / 2.0;
+        assert!(total_variation < 0.1);
+        Ok(())
+    }
+}
@@ -0,0 +1,458 @@
+/// Local registry of trained models
+///
+/// Every registered checkpoint becomes an immutable version of a named model:
+///
+/// ```text
+/// <root>/registry.json                  aliases and their promotion history
+/// <root>/<name>/<version>/entry.json    a `ModelEntry`
+/// <root>/<name>/<version>/<name>@<version>.<ext> (+ .json sidecar)
+/// ```
+///
+/// Models are referred to as `name@version`, as `name` for the latest
+/// version, or by an alias such as `production`. Checkpoints keep the
+/// extension they were registered with, `.safetensors` or a quantized `.gguf`,
+/// since the loaders pick the format by it.
+use anyhow::{bail, Context, Result};
+use cs2_common::FEATURE_SCHEMA_VERSION;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::mlmove_transformer::MLMOVEConfig;
+use crate::model::BehaviorNetMetadata;
+
+/// Alias the policy server is usually pointed at
+pub const PRODUCTION: &str = "production";
+
+const INDEX_FILE: &str = "registry.json";
+const ENTRY_FILE: &str = "entry.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModelKind {
+    Behavior, // `BehaviorNet` aim policy
+    Mlmove,   // `MLMOVETransformer` movement model
+}
+
+/// Architecture of a registered model, read from its checkpoint sidecar
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ModelConfig {
+    Behavior { input_dim: usize, output_dim: usize },
+    Mlmove(MLMOVEConfig),
+}
+
+impl ModelConfig {
+    pub fn kind(&self) -> ModelKind {
+        match self {
+            Self::Behavior { .. } => ModelKind::Behavior,
+            Self::Mlmove(_) => ModelKind::Mlmove,
+        }
+    }
+}
+
+/// One immutable version of a named model
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelEntry {
+    pub name: String,
+    pub version: u32,
+    pub config: ModelConfig,
+    pub feature_schema_version: u32,
+    pub dataset_fingerprint: Option<String>, // `SequenceDataset::fingerprint` of the training data
+    #[serde(default)]
+    pub seed: Option<u64>, // Split assignment and training order seed
+    pub metrics: BTreeMap<String, f64>,
+    pub created_at: u64,  // Seconds since the Unix epoch
+    pub git_hash: String, // Commit of the code that wrote the checkpoint, empty when unknown
+    #[serde(default = "default_weights_extension")]
+    pub weights_extension: String, // Of the stored checkpoint, e.g. `safetensors` or `gguf`
+}
+
+fn default_weights_extension() -> String {
+    "safetensors".to_string()
+}
+
+impl ModelEntry {
+    /// `name@version`
+    pub fn reference(&self) -> String {
+        format!("{}@{}", self.name, self.version)
+    }
+
+    fn weights_file(&self) -> String {
+        format!("{}.{}", self.reference(), self.weights_extension)
+    }
+}
+
+/// What to record about a checkpoint besides its weights
+#[derive(Debug, Clone, Default)]
+pub struct Provenance {
+    pub dataset_fingerprint: Option<String>,
+    pub seed: Option<u64>,
+    pub metrics: BTreeMap<String, f64>,
+}
+
+/// Aliases and the targets they pointed at before each promotion
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct RegistryIndex {
+    aliases: BTreeMap<String, String>,      // Alias to `name@version`
+    history: BTreeMap<String, Vec<String>>, // Earlier targets of each alias, oldest first
+}
+
+/// A directory of versioned checkpoints with aliases
+#[derive(Debug, Clone)]
+pub struct ModelRegistry {
+    root: PathBuf,
+}
+
+impl ModelRegistry {
+    /// Open the registry at `root`, creating the directory if needed
+    pub fn open(root: impl AsRef<Path>) -> Result<Self> {
+        let root = root.as_ref().to_path_buf();
+        std::fs::create_dir_all(&root)
+            .with_context(|| format!("create registry {}", root.display()))?;
+        Ok(Self { root })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Copy a checkpoint written by `save` into the next version of `name`
+    pub fn register(
+        &self,
+        name: &str,
+        kind: ModelKind,
+        checkpoint: &Path,
+        provenance: Provenance,
+    ) -> Result<ModelEntry> {
+        validate_name(name)?;
+        let sidecar = checkpoint.with_extension("json");
+        let sidecar_json = std::fs::read_to_string(&sidecar)
+            .with_context(|| format!("read {}", sidecar.display()))?;
+        let (config, git_hash) = match kind {
+            ModelKind::Behavior => {
+                let metadata: BehaviorNetMetadata = serde_json::from_str(&sidecar_json)?;
+                let config = ModelConfig::Behavior {
+                    input_dim: metadata.input_dim,
+                    output_dim: metadata.output_dim,
+                };
+                (config, metadata.git_hash)
+            }
+            ModelKind::Mlmove => {
+                // MLMOVE sidecars hold only the architecture, unless written with a commit
+                let sidecar: serde_json::Value = serde_json::from_str(&sidecar_json)?;
+                let git_hash = sidecar["git_hash"].as_str().unwrap_or_default().to_string();
+                (
+                    ModelConfig::Mlmove(serde_json::from_value(sidecar)?),
+                    git_hash,
+                )
+            }
+        };
+
+        let version = self.versions(name)?.last().map_or(1, |v| v + 1);
+        let entry = ModelEntry {
+            name: name.to_string(),
+            version,
+            config,
+            feature_schema_version: FEATURE_SCHEMA_VERSION,
+            dataset_fingerprint: provenance.dataset_fingerprint,
+            seed: provenance.seed,
+            metrics: provenance.metrics,
+            created_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+            git_hash,
+            weights_extension: checkpoint
+                .extension()
+                .map(|ext| ext.to_string_lossy().into_owned())
+                .unwrap_or_else(default_weights_extension),
+        };
+        let dir = self.entry_dir(name, version);
+        std::fs::create_dir_all(&dir)?;
+        let weights = dir.join(entry.weights_file());
+        std::fs::copy(checkpoint, &weights)
+            .with_context(|| format!("copy {}", checkpoint.display()))?;
+        std::fs::write(weights.with_extension("json"), sidecar_json)?;
+        std::fs::write(dir.join(ENTRY_FILE), serde_json::to_string_pretty(&entry)?)?;
+        Ok(entry)
+    }
+
+    /// Every version of every model, by name then version
+    pub fn list(&self) -> Result<Vec<ModelEntry>> {
+        let mut names = Vec::new();
+        for dir in std::fs::read_dir(&self.root)? {
+            let dir = dir?;
+            if dir.file_type()?.is_dir() {
+                names.push(dir.file_name().to_string_lossy().into_owned());
+            }
+        }
+        names.sort();
+        let mut entries = Vec::new();
+        for name in names {
+            for version in self.versions(&name)? {
+                entries.push(self.entry(&name, version)?);
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Aliases and the `name@version` each points at
+    pub fn aliases(&self) -> Result<BTreeMap<String, String>> {
+        Ok(self.read_index()?.aliases)
+    }
+
+    /// Find the entry for an alias, `name@version` or `name`
+    pub fn resolve(&self, reference: &str) -> Result<ModelEntry> {
+        let index = self.read_index()?;
+        let target = index
+            .aliases
+            .get(reference)
+            .map(String::as_str)
+            .unwrap_or(reference);
+        match target.split_once('@') {
+            Some((name, version)) => {
+                let version: u32 = version
+                    .parse()
+                    .with_context(|| format!("bad version in {target}"))?;
+                self.entry(name, version)
+            }
+            None => match self.versions(target)?.last() {
+                Some(&version) => self.entry(target, version),
+                None => bail!("no model or alias named {target}"),
+            },
+        }
+    }
+
+    /// Checkpoint file of an entry, loadable by `BehaviorNet::load` or
+    /// `MLMOVETransformer::load_pretrained`
+    pub fn weights_path(&self, entry: &ModelEntry) -> PathBuf {
+        self.entry_dir(&entry.name, entry.version)
+            .join(entry.weights_file())
+    }
+
+    /// Point `alias` at a model, remembering its previous target for rollback
+    pub fn promote(&self, reference: &str, alias: &str) -> Result<ModelEntry> {
+        validate_name(alias)?;
+        let entry = self.resolve(reference)?;
+        let mut index = self.read_index()?;
+        let target = entry.reference();
+        if let Some(previous) = index.aliases.insert(alias.to_string(), target.clone()) {
+            if previous != target {
+                index
+                    .history
+                    .entry(alias.to_string())
+                    .or_default()
+                    .push(previous);
+            }
+        }
+        self.write_index(&index)?;
+        Ok(entry)
+    }
+
+    /// Point `alias` back at the model it had before its latest promotion
+    pub fn rollback(&self, alias: &str) -> Result<ModelEntry> {
+        let mut index = self.read_index()?;
+        let previous = index
+            .history
+            .get_mut(alias)
+            .and_then(Vec::pop)
+            .with_context(|| format!("{alias} has no earlier model to roll back to"))?;
+        index.aliases.insert(alias.to_string(), previous.clone());
+        self.write_index(&index)?;
+        self.resolve(&previous)
+    }
+
+    fn entry(&self, name: &str, version: u32) -> Result<ModelEntry> {
+        let path = self.entry_dir(name, version).join(ENTRY_FILE);
+        let json = std::fs::read_to_string(&path)
+            .with_context(|| format!("no model {name}@{version} in {}", self.root.display()))?;
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    /// Registered versions of `name`, ascending
+    fn versions(&self, name: &str) -> Result<Vec<u32>> {
+        let dir = self.root.join(name);
+        if !dir.is_dir() {
+            return Ok(Vec::new());
+        }
+        let mut versions = Vec::new();
+        for version in std::fs::read_dir(dir)? {
+            let version = version?;
+            if let Ok(v) = version.file_name().to_string_lossy().parse::<u32>() {
+                if version.path().join(ENTRY_FILE).exists() {
+                    versions.push(v);
+                }
+            }
+        }
+        versions.sort();
+        Ok(versions)
+    }
+
+    fn entry_dir(&self, name: &str, version: u32) -> PathBuf {
+        self.root.join(name).join(version.to_string())
+    }
+
+    fn read_index(&self) -> Result<RegistryIndex> {
+        let path = self.root.join(INDEX_FILE);
+        if !path.exists() {
+            return Ok(RegistryIndex::default());
+        }
+        serde_json::from_str(&std::fs::read_to_string(&path)?)
+            .with_context(|| format!("parse {}", path.display()))
+    }
+
+    /// Replace the index in one rename, so readers never see half of it
+    fn write_index(&self, index: &RegistryIndex) -> Result<()> {
+        let path = self.root.join(INDEX_FILE);
+        let staging = path.with_extension("json.tmp");
+        std::fs::write(&staging, serde_json::to_string_pretty(index)?)?;
+        std::fs::rename(&staging, &path)?;
+        Ok(())
+    }
+}
+
+/// Names and aliases become directory names and appear in `name@version`
+fn validate_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        && !name.starts_with('.');
+    if !valid {
+        bail!("{name:?} is not a valid model name, use letters, digits, '-', '_' and '.'");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{BehaviorNet, BEHAVIOR_INPUT_DIM, BEHAVIOR_OUTPUT_DIM};
+    use crate::server::ServedModel;
+    use candle_core::Device;
+
+    fn checkpoint(dir: &Path, file: &str) -> PathBuf {
+        let path = dir.join(file);
+        BehaviorNet::new(BEHAVIOR_INPUT_DIM, BEHAVIOR_OUTPUT_DIM, Device::Cpu)
+            .unwrap()
+            .save(path.to_str().unwrap())
+            .unwrap();
+        path
+    }
+
+    #[test]
+    fn test_register_promote_and_rollback() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let registry = ModelRegistry::open(tmp.path().join("registry"))?;
+        let provenance = Provenance {
+            dataset_fingerprint: Some("00ff".to_string()),
+            seed: Some(7),
+            metrics: BTreeMap::from([("val_loss".to_string(), 0.25)]),
+        };
+        let first = registry.register(
+            "aim",
+            ModelKind::Behavior,
+            &checkpoint(tmp.path(), "a.safetensors"),
+            provenance.clone(),
+        )?;
+        let second = registry.register(
+            "aim",
+            ModelKind::Behavior,
+            &checkpoint(tmp.path(), "b.safetensors"),
+            provenance,
+        )?;
+        assert_eq!((first.version, second.version), (1, 2));
+        assert_eq!(first.feature_schema_version, FEATURE_SCHEMA_VERSION);
+        assert!(matches!(
+            first.config,
+            ModelConfig::Behavior {
+                input_dim: BEHAVIOR_INPUT_DIM,
+                ..
+            }
+        ));
+
+        let listed = registry.list()?;
+        assert_eq!(listed.len(), 2);
+        assert_eq!(listed[1].metrics["val_loss"], 0.25);
+        assert_eq!(listed[1].seed, Some(7));
+        assert_eq!(registry.resolve("aim")?.version, 2);
+        assert_eq!(registry.resolve("aim@1")?.version, 1);
+        assert!(registry.resolve("aim@3").is_err());
+        assert!(registry.resolve(PRODUCTION).is_err());
+
+        registry.promote("aim@1", PRODUCTION)?;
+        registry.promote("aim", PRODUCTION)?;
+        assert_eq!(registry.aliases()?[PRODUCTION], "aim@2");
+        assert_eq!(registry.rollback(PRODUCTION)?.version, 1);
+        assert_eq!(registry.resolve(PRODUCTION)?.version, 1);
+        assert!(registry.rollback(PRODUCTION).is_err());
+
+        // The server loads by alias, named after the version it got
+        let entry = registry.resolve(PRODUCTION)?;
+        let served = ServedModel::load(&registry.weights_path(&entry))?;
+        assert_eq!(served.name, "aim@1");
+        Ok(())
+    }
+
+    #[test]
+    fn test_quantized_checkpoint_round_trip() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let registry = ModelRegistry::open(tmp.path().join("registry"))?;
+        let mut net = BehaviorNet::new(BEHAVIOR_INPUT_DIM, BEHAVIOR_OUTPUT_DIM, Device::Cpu)?;
+        net.quantize(candle_core::quantized::GgmlDType::Q8_0)?;
+        let path = tmp.path().join("aim.gguf");
+        net.save(path.to_str().unwrap())?;
+
+        let entry = registry.register("aim", ModelKind::Behavior, &path, Provenance::default())?;
+        let stored = registry.weights_path(&registry.resolve("aim")?);
+        assert_eq!(stored.file_name().unwrap(), "aim@1.gguf");
+        assert_eq!(entry.weights_extension, "gguf");
+
+        let loaded = BehaviorNet::load(stored.to_str().unwrap(), Device::Cpu)?;
+        assert_eq!(loaded.quantization(), net.quantization());
+        Ok(())
+    }
+
+    #[test]
+    fn test_mlmove_git_hash_comes_from_the_checkpoint() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let registry = ModelRegistry::open(tmp.path().join("registry"))?;
+        let config = MLMOVEConfig {
+            num_layers: 1,
+            model_dim: 16,
+            ff_dim: 32,
+            sequence_length: 4,
+            ..Default::default()
+        };
+        let path = tmp.path().join("move.safetensors");
+        crate::mlmove_transformer::MLMOVETransformer::with_config(config.clone(), Device::Cpu)?
+            .save(path.to_str().unwrap())?;
+
+        // Nothing records which code wrote this one
+        let entry = registry.register("move", ModelKind::Mlmove, &path, Provenance::default())?;
+        assert_eq!(entry.git_hash, "");
+        assert!(matches!(entry.config, ModelConfig::Mlmove(ref c) if c.model_dim == 16));
+
+        let mut sidecar = serde_json::to_value(&config)?;
+        sidecar["git_hash"] = "abc1234".into();
+        std::fs::write(path.with_extension("json"), sidecar.to_string())?;
+        let entry = registry.register("move", ModelKind::Mlmove, &path, Provenance::default())?;
+        assert_eq!(entry.git_hash, "abc1234");
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_bad_names() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let registry = ModelRegistry::open(tmp.path())?;
+        let path = checkpoint(tmp.path(), "a.safetensors");
+        for name in ["", "a/b", "a@1", ".."] {
+            assert!(registry
+                .register(name, ModelKind::Behavior, &path, Provenance::default())
+                .is_err());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,217 @@
+/// Hot reload of the served model
+///
+/// A [`ModelWatcher`] polls a checkpoint path or a registry reference and
+/// swaps new versions into the server's [`ModelSlot`] while clients stay
+/// connected.
+use anyhow::{Context, Result};
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::registry::ModelRegistry;
+use crate::server::{ModelSlot, ServedModel};
+
+/// Where new versions of the served model come from
+#[derive(Debug, Clone)]
+pub enum ModelSource {
+    Path(PathBuf), // A checkpoint overwritten in place
+    Registry {
+        registry: ModelRegistry,
+        reference: String, // Usually an alias such as `production`
+    },
+}
+
+impl ModelSource {
+    /// Checkpoint to load and a signature that changes with its contents
+    fn locate(&self) -> Result<(PathBuf, String)> {
+        match self {
+            Self::Path(path) => {
+                let mut signature = String::new();
+                for file in [path.clone(), path.with_extension("json")] {
+                    let metadata = std::fs::metadata(&file)
+                        .with_context(|| format!("stat {}", file.display()))?;
+                    let modified = metadata
+                        .modified()?
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default();
+                    signature.push_str(&format!("{}:{} ", metadata.len(), modified.as_nanos()));
+                }
+                Ok((path.clone(), signature))
+            }
+            Self::Registry {
+                registry,
+                reference,
+            } => {
+                let entry = registry.resolve(reference)?;
+                Ok((registry.weights_path(&entry), entry.reference()))
+            }
+        }
+    }
+}
+
+impl fmt::Display for ModelSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Path(path) => write!(f, "{}", path.display()),
+            Self::Registry {
+                registry,
+                reference,
+            } => write!(f, "{reference} in {}", registry.root().display()),
+        }
+    }
+}
+
+/// A model swapped in by [`ModelWatcher::poll`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Swap {
+    pub from: u32, // Model IDs
+    pub to: u32,
+    pub name: String,
+}
+
+/// Follows a [`ModelSource`] and swaps its new versions into a [`ModelSlot`]
+///
+/// A change is only loaded once it has stayed the same for a whole poll, so
+/// a checkpoint that is still being written is never read half-way.
+#[derive(Debug)]
+pub struct ModelWatcher {
+    source: ModelSource,
+    loaded: String,          // Signature of the version being served
+    pending: Option<String>, // Signature of a change seen on the previous poll
+}
+
+impl ModelWatcher {
+    /// Watch `source`, taking its current version as the one being served
+    pub fn new(source: ModelSource) -> Result<Self> {
+        let (_, loaded) = source.locate()?;
+        Ok(Self {
+            source,
+            loaded,
+            pending: None,
+        })
+    }
+
+    /// Check the source once, swapping in a settled new version
+    ///
+    /// A version that fails to load or has other dimensions is reported once
+    /// and skipped until the source changes again.
+    pub fn poll(&mut self, slot: &ModelSlot) -> Result<Option<Swap>> {
+        let (path, signature) = self.source.locate()?;
+        if signature == self.loaded {
+            self.pending = None;
+            return Ok(None);
+        }
+        if self.pending.as_ref() != Some(&signature) {
+            self.pending = Some(signature);
+            return Ok(None);
+        }
+        self.loaded = signature;
+        self.pending = None;
+
+        let model = ServedModel::load(&path)?;
+        if model.id == slot.current().id {
+            return Ok(None);
+        }
+        let previous = slot.swap(model)?;
+        // The slot may have quantized the model, which changes its ID and name
+        let current = slot.current();
+        Ok(Some(Swap {
+            from: previous.id,
+            to: current.id,
+            name: current.name.clone(),
+        }))
+    }
+
+    /// Poll every `interval` until `shutdown` is set, logging swaps and failures
+    pub fn run(mut self, slot: &ModelSlot, interval: Duration, shutdown: &AtomicBool) {
+        let mut last_error = None;
+        while !shutdown.load(Ordering::SeqCst) {
+            match self.poll(slot) {
+                Ok(Some(swap)) => {
+                    println!(
+                        "Reloaded {}: model {:08x} replaced {:08x} ({} swaps so far)",
+                        swap.name,
+                        swap.to,
+                        swap.from,
+                        slot.swaps()
+                    );
+                    last_error = None;
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    let message = format!("{e:#}");
+                    if last_error.as_ref() != Some(&message) {
+                        eprintln!("Not reloading {}: {message}", self.source);
+                        last_error = Some(message);
+                    }
+                }
+            }
+            // Sleep in short steps so shutdown is not held up by a long interval
+            let mut slept = Duration::ZERO;
+            while slept < interval && !shutdown.load(Ordering::SeqCst) {
+                let step = (interval - slept).min(Duration::from_millis(50));
+                thread::sleep(step);
+                slept += step;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{BehaviorNet, BEHAVIOR_INPUT_DIM, BEHAVIOR_OUTPUT_DIM};
+    use crate::registry::{ModelKind, Provenance, PRODUCTION};
+    use candle_core::Device;
+    use std::path::Path;
+
+    fn checkpoint(path: &Path, input_dim: usize) {
+        BehaviorNet::new(input_dim, BEHAVIOR_OUTPUT_DIM, Device::Cpu)
+            .unwrap()
+            .save(path.to_str().unwrap())
+            .unwrap();
+    }
+
+    #[test]
+    fn test_follows_registry_alias() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let registry = ModelRegistry::open(tmp.path().join("registry"))?;
+        let register = |file: &str, input_dim| -> Result<()> {
+            let path = tmp.path().join(file);
+            checkpoint(&path, input_dim);
+            registry.register("aim", ModelKind::Behavior, &path, Provenance::default())?;
+            Ok(())
+        };
+        register("a.safetensors", BEHAVIOR_INPUT_DIM)?;
+        register("b.safetensors", BEHAVIOR_INPUT_DIM)?;
+        register("wide.safetensors", BEHAVIOR_INPUT_DIM + 1)?;
+        registry.promote("aim@1", PRODUCTION)?;
+
+        let first = registry.resolve(PRODUCTION)?;
+        let slot = ModelSlot::new(ServedModel::load(&registry.weights_path(&first))?);
+        let mut watcher = ModelWatcher::new(ModelSource::Registry {
+            registry: registry.clone(),
+            reference: PRODUCTION.to_string(),
+        })?;
+        assert_eq!(watcher.poll(&slot)?, None);
+
+        // A promotion is picked up once it has settled for a poll
+        registry.promote("aim@2", PRODUCTION)?;
+        let served = slot.current().id;
+        assert_eq!(watcher.poll(&slot)?, None);
+        let swap = watcher.poll(&slot)?.unwrap();
+        assert_eq!((swap.from, swap.name.as_str()), (served, "aim@2"));
+        assert_eq!(slot.current().id, swap.to);
+        assert_eq!(slot.swaps(), 1);
+
+        // A model with other dimensions is refused and reported only once
+        registry.promote("aim@3", PRODUCTION)?;
+        assert_eq!(watcher.poll(&slot)?, None);
+        assert!(watcher.poll(&slot).is_err());
+        assert_eq!(watcher.poll(&slot)?, None);
+        assert_eq!(slot.current().id, swap.to);
+        Ok(())
+    }
+}
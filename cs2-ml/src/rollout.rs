@@ -0,0 +1,707 @@
+/// Closed-loop rollouts of the movement policy against demo ground truth
+///
+/// Next-action accuracy scores every prediction from the real history, so it
+/// never sees the policy's errors compound. A rollout starts from a recorded
+/// state and feeds the policy its own simulated positions: each predicted
+/// action moves a kinematic body (Source-style acceleration, friction, max
+/// speed per weapon and jump arc) for one recorded step, while view angles,
+/// weapon and health still come from the demo. The simulated path is then
+/// compared to the real one with average and final displacement error and
+/// the earth mover's distance between their occupancy grids, as in the MLMOVE
+/// paper.
+///
+/// There is no map geometry: the ground stays at the height the body last
+/// stood on and displacements are measured in the horizontal plane.
+use anyhow::{bail, Context, Result};
+use candle_core::{Device, D};
+use cs2_common::match_clock::DEFAULT_TICK_RATE;
+use cs2_common::{weapon_id_from_name, BehavioralVector};
+use rand::distr::weighted::WeightedIndex;
+use rand::distr::Distribution;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+use crate::dataset::{DatasetConfig, SequenceDataset, SequenceWindow, Split};
+use crate::mlmove_transformer::{DiscreteAction, MLMOVETransformer, RUN_SPEED};
+use crate::registry::ModelKind;
+
+/// Ground speed of a player holding each weapon, in units per second
+pub const WEAPON_MAX_SPEEDS: [(&str, f32); 44] = [
+    ("weapon_ak47", 215.0),
+    ("weapon_m4a1", 225.0),
+    ("weapon_m4a1_silencer", 225.0),
+    ("weapon_awp", 200.0),
+    ("weapon_deagle", 230.0),
+    ("weapon_glock", 240.0),
+    ("weapon_usp_silencer", 240.0),
+    ("weapon_hkp2000", 240.0),
+    ("weapon_p250", 240.0),
+    ("weapon_fiveseven", 240.0),
+    ("weapon_tec9", 240.0),
+    ("weapon_cz75a", 240.0),
+    ("weapon_elite", 240.0),
+    ("weapon_revolver", 220.0),
+    ("weapon_famas", 220.0),
+    ("weapon_galilar", 215.0),
+    ("weapon_aug", 220.0),
+    ("weapon_sg556", 210.0),
+    ("weapon_ssg08", 230.0),
+    ("weapon_scar20", 215.0),
+    ("weapon_g3sg1", 215.0),
+    ("weapon_mac10", 240.0),
+    ("weapon_mp9", 240.0),
+    ("weapon_mp7", 220.0),
+    ("weapon_mp5sd", 235.0),
+    ("weapon_ump45", 230.0),
+    ("weapon_p90", 230.0),
+    ("weapon_bizon", 240.0),
+    ("weapon_nova", 220.0),
+    ("weapon_xm1014", 215.0),
+    ("weapon_mag7", 225.0),
+    ("weapon_sawedoff", 210.0),
+    ("weapon_m249", 195.0),
+    ("weapon_negev", 150.0),
+    ("weapon_knife", 250.0),
+    ("weapon_knife_t", 250.0),
+    ("weapon_taser", 220.0),
+    ("weapon_hegrenade", 245.0),
+    ("weapon_flashbang", 245.0),
+    ("weapon_smokegrenade", 245.0),
+    ("weapon_molotov", 245.0),
+    ("weapon_incgrenade", 245.0),
+    ("weapon_decoy", 245.0),
+    ("weapon_c4", 250.0),
+];
+
+/// Max ground speed with a weapon, [`RUN_SPEED`] for unknown IDs
+pub fn max_speed(weapon_id: u16) -> f32 {
+    WEAPON_MAX_SPEEDS
+        .iter()
+        .find(|(name, _)| weapon_id_from_name(name) == weapon_id)
+        .map_or(RUN_SPEED, |&(_, speed)| speed)
+}
+
+/// Wish speed of each speed bucket, as a share of [`RUN_SPEED`]
+///
+/// The middle of each bucket of [`DiscreteAction::from_movement`]; the open
+/// last bucket is capped by the weapon's max speed anyway.
+const SPEED_FRACTIONS: [f32; 6] = [0.0, 0.25, 0.575, 0.8, 0.975, 1.1];
+
+/// Movement constants, defaulting to CS2's competitive settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Kinematics {
+    pub acceleration: f32,     // sv_accelerate
+    pub air_acceleration: f32, // sv_airaccelerate
+    pub air_speed_cap: f32,    // Wish speed air acceleration is limited to
+    pub friction: f32,         // sv_friction
+    pub stop_speed: f32,       // sv_stopspeed
+    pub gravity: f32,          // sv_gravity, units/s²
+    pub jump_speed: f32,       // Upward velocity of a jump
+    pub tick_rate: f32,        // Ticks per second, to turn tick gaps into time
+}
+
+impl Default for Kinematics {
+    fn default() -> Self {
+        Self {
+            acceleration: 5.5,
+            air_acceleration: 12.0,
+            air_speed_cap: 30.0,
+            friction: 5.2,
+            stop_speed: 80.0,
+            gravity: 800.0,
+            jump_speed: 301.993,
+            tick_rate: DEFAULT_TICK_RATE,
+        }
+    }
+}
+
+/// Position and velocity of a simulated player
+#[derive(Debug, Clone, PartialEq)]
+pub struct Body {
+    pub position: [f32; 3],
+    pub velocity: [f32; 3],
+    pub ground_z: f32, // Height the body lands back on
+    pub airborne: bool,
+}
+
+impl Body {
+    /// The state recorded in a behavioral vector
+    pub fn from_vector(vector: &BehavioralVector) -> Self {
+        Self {
+            position: [vector.pos_x, vector.pos_y, vector.pos_z],
+            velocity: [vector.vel_x, vector.vel_y, vector.vel_z],
+            ground_z: vector.pos_z,
+            airborne: vector.is_airborne > 0.5,
+        }
+    }
+
+    /// `template` with this body's position, velocity and ground state
+    pub fn to_vector(&self, template: &BehavioralVector) -> BehavioralVector {
+        BehavioralVector {
+            pos_x: self.position[0],
+            pos_y: self.position[1],
+            pos_z: self.position[2],
+            vel_x: self.velocity[0],
+            vel_y: self.velocity[1],
+            vel_z: self.velocity[2],
+            is_airborne: if self.airborne { 1.0 } else { 0.0 },
+            ..template.clone()
+        }
+    }
+}
+
+impl Kinematics {
+    /// Move `body` for `ticks` ticks, taking `action` relative to view `yaw`
+    ///
+    /// Directions go clockwise from the view like in
+    /// [`DiscreteAction::from_movement`], and the speed bucket sets the
+    /// speed the player accelerates towards, capped at `max_speed`.
+    pub fn step(
+        &self,
+        body: &mut Body,
+        action: &DiscreteAction,
+        yaw: f32,
+        max_speed: f32,
+        ticks: u32,
+    ) {
+        let (wish_dir, wish_speed) = if action.direction >= 8 {
+            ([0.0, 0.0], 0.0)
+        } else {
+            let heading = (yaw - 45.0 * action.direction as f32).to_radians();
+            let speed = SPEED_FRACTIONS[action.speed.min(5) as usize] * RUN_SPEED;
+            ([heading.cos(), heading.sin()], speed.min(max_speed))
+        };
+        if action.jump == 1 && !body.airborne {
+            body.ground_z = body.position[2];
+            body.velocity[2] = self.jump_speed;
+            body.airborne = true;
+        }
+
+        let dt = 1.0 / self.tick_rate;
+        for _ in 0..ticks.max(1) {
+            if body.airborne {
+                self.accelerate(
+                    body,
+                    wish_dir,
+                    wish_speed.min(self.air_speed_cap),
+                    self.air_acceleration * wish_speed,
+                    dt,
+                );
+                body.velocity[2] -= self.gravity * dt;
+            } else {
+                self.apply_friction(body, dt);
+                self.accelerate(
+                    body,
+                    wish_dir,
+                    wish_speed,
+                    self.acceleration * wish_speed,
+                    dt,
+                );
+                body.velocity[2] = 0.0;
+            }
+            for (p, v) in body.position.iter_mut().zip(body.velocity) {
+                *p += v * dt;
+            }
+            if body.airborne && body.velocity[2] <= 0.0 && body.position[2] <= body.ground_z {
+                body.position[2] = body.ground_z;
+                body.velocity[2] = 0.0;
+                body.airborne = false;
+            }
+        }
+    }
+
+    fn apply_friction(&self, body: &mut Body, dt: f32) {
+        let speed = body.velocity[0].hypot(body.velocity[1]);
+        if speed < 0.1 {
+            body.velocity[0] = 0.0;
+            body.velocity[1] = 0.0;
+            return;
+        }
+        let drop = speed.max(self.stop_speed) * self.friction * dt;
+        let scale = (speed - drop).max(0.0) / speed;
+        body.velocity[0] *= scale;
+        body.velocity[1] *= scale;
+    }
+
+    /// Source's accelerate: add speed along `wish_dir` up to `wish_speed`
+    fn accelerate(&self, body: &mut Body, wish_dir: [f32; 2], wish_speed: f32, rate: f32, dt: f32) {
+        let current = body.velocity[0] * wish_dir[0] + body.velocity[1] * wish_dir[1];
+        let add = wish_speed - current;
+        if add <= 0.0 {
+            return;
+        }
+        let gain = (rate * dt).min(add);
+        body.velocity[0] += gain * wish_dir[0];
+        body.velocity[1] += gain * wish_dir[1];
+    }
+}
+
+/// Settings of a rollout evaluation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RolloutConfig {
+    pub horizon: usize,    // Recorded steps each rollout runs for
+    pub stride: usize,     // Steps between the starts of consecutive rollouts
+    pub sample: bool,      // Sample actions instead of taking the most likely one
+    pub seed: u64,         // Drives the sampling
+    pub cell_size: f32,    // Side of the occupancy grid cells, in game units
+    pub batch_size: usize, // Rollouts stepped together
+    pub kinematics: Kinematics,
+}
+
+impl Default for RolloutConfig {
+    fn default() -> Self {
+        Self {
+            horizon: 64,
+            stride: 64,
+            sample: false,
+            seed: 42,
+            cell_size: 128.0,
+            batch_size: 64,
+            kinematics: Kinematics::default(),
+        }
+    }
+}
+
+/// How far closed-loop rollouts drift from the recorded paths, in game units
+#[derive(Debug, Clone, Serialize)]
+pub struct RolloutReport {
+    pub rollouts: usize,
+    pub horizon: usize,
+    pub ade: f64, // Average displacement error over every step of every rollout
+    pub fde: f64, // Mean displacement error at the last step
+    pub occupancy_emd: f64, // Earth mover's distance between simulated and real occupancy grids
+    pub displacement_by_step: Vec<f64>, // Mean displacement error after each step
+    pub ade_by_map: BTreeMap<String, f64>,
+}
+
+impl fmt::Display for RolloutReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} rollouts of {} steps: ADE {:.1}, FDE {:.1}, occupancy EMD {:.1} units",
+            self.rollouts, self.horizon, self.ade, self.fde, self.occupancy_emd
+        )?;
+        for (map, ade) in &self.ade_by_map {
+            write!(f, "\n  {map}: ADE {ade:.1}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Roll a movement checkpoint out on the matches of `data` it was not trained on
+///
+/// `seed` must be the one the model was trained with, so the split holds
+/// unseen matches. Rollouts start every `config.stride` recorded steps.
+pub fn rollout_checkpoint(
+    checkpoint: &Path,
+    data: &Path,
+    seed: u64,
+    config: &RolloutConfig,
+) -> Result<RolloutReport> {
+    let checkpoint_str = checkpoint.to_str().context("path is not valid UTF-8")?;
+    let model = MLMOVETransformer::load_pretrained(checkpoint_str, Device::Cpu)?;
+    let (held_out, split) = crate::evaluation::held_out(ModelKind::Mlmove, checkpoint, seed)?;
+    let dataset = SequenceDataset::open(
+        data,
+        DatasetConfig {
+            sequence_length: model.config().sequence_length + config.horizon,
+            window_stride: config.stride,
+            ..held_out
+        },
+    )?;
+    evaluate_rollouts(&model, &dataset, split, config)
+}
+
+/// Roll the policy out from every window of a split
+///
+/// `dataset` must cut windows of the model's sequence length plus
+/// `config.horizon` vectors: the first part is the history the rollout
+/// starts from, the rest the path it is compared to.
+pub fn evaluate_rollouts(
+    model: &MLMOVETransformer,
+    dataset: &SequenceDataset,
+    split: Split,
+    config: &RolloutConfig,
+) -> Result<RolloutReport> {
+    let history = model.config().sequence_length;
+    if dataset.config.sequence_length != history + config.horizon {
+        bail!(
+            "rollouts need windows of {} vectors, the dataset cuts {}",
+            history + config.horizon,
+            dataset.config.sequence_length
+        );
+    }
+
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    let mut step_errors = vec![0.0; config.horizon];
+    let mut maps: BTreeMap<String, (f64, usize)> = BTreeMap::new();
+    let (mut simulated, mut real) = (Vec::new(), Vec::new());
+    let mut rollouts = 0;
+    for windows in dataset.matches(split) {
+        let windows = windows?;
+        let Some(first) = windows.first() else {
+            continue;
+        };
+        let map = crate::evaluation::match_map(dataset, first.match_index)?.to_string();
+        for chunk in windows.chunks(config.batch_size.max(1)) {
+            let paths = roll_out(model, chunk, config, &mut rng)?;
+            let entry = maps.entry(map.clone()).or_default();
+            for (window, path) in chunk.iter().zip(paths) {
+                for (step, (position, target)) in
+                    path.iter().zip(&window.vectors[history..]).enumerate()
+                {
+                    let error = (position[0] - target.pos_x).hypot(position[1] - target.pos_y);
+                    step_errors[step] += error as f64;
+                    entry.0 += error as f64;
+                    simulated.push([position[0], position[1]]);
+                    real.push([target.pos_x, target.pos_y]);
+                }
+                entry.1 += config.horizon;
+            }
+            rollouts += chunk.len();
+        }
+    }
+    if rollouts == 0 {
+        bail!("the {split:?} split has no windows to roll out from");
+    }
+
+    let displacement_by_step: Vec<f64> = step_errors.iter().map(|e| e / rollouts as f64).collect();
+    Ok(RolloutReport {
+        rollouts,
+        horizon: config.horizon,
+        ade: displacement_by_step.iter().sum::<f64>() / config.horizon as f64,
+        fde: displacement_by_step.last().copied().unwrap_or(0.0),
+        occupancy_emd: occupancy_emd(&simulated, &real, config.cell_size),
+        displacement_by_step,
+        ade_by_map: maps
+            .into_iter()
+            .map(|(map, (total, steps))| (map, total / steps.max(1) as f64))
+            .collect(),
+    })
+}
+
+/// Simulated horizontal positions after each step of a batch of rollouts
+fn roll_out(
+    model: &MLMOVETransformer,
+    windows: &[SequenceWindow],
+    config: &RolloutConfig,
+    rng: &mut StdRng,
+) -> Result<Vec<Vec<[f32; 2]>>> {
+    let history = model.config().sequence_length;
+    let mut tracks: Vec<Vec<BehavioralVector>> = windows
+        .iter()
+        .map(|w| w.vectors[..history].to_vec())
+        .collect();
+    let mut bodies: Vec<Body> = tracks
+        .iter()
+        .map(|track| {
+            let mut body = Body::from_vector(&track[history - 1]);
+            // Land where the body last stood, not mid-jump
+            if let Some(grounded) = track.iter().rev().find(|v| v.is_airborne <= 0.5) {
+                body.ground_z = grounded.pos_z.min(body.position[2]);
+            }
+            body
+        })
+        .collect();
+    let mut paths = vec![Vec::with_capacity(config.horizon); windows.len()];
+
+    for step in 0..config.horizon {
+        let sequences: Vec<&[BehavioralVector]> =
+            tracks.iter().map(|t| &t[t.len() - history..]).collect();
+        let logits = model.forward(&model.sequences_to_tensor(&sequences)?)?;
+        let probabilities: Vec<Vec<f32>> =
+            candle_nn::ops::softmax(&logits, D::Minus1)?.to_vec2()?;
+
+        for (i, window) in windows.iter().enumerate() {
+            let index = if config.sample {
+                WeightedIndex::new(&probabilities[i])?.sample(rng)
+            } else {
+                probabilities[i]
+                    .iter()
+                    .enumerate()
+                    .max_by(|(_, a), (_, b)| a.total_cmp(b))
+                    .map_or(DiscreteAction::NO_OP_INDEX, |(index, _)| index)
+            };
+            let previous = &window.vectors[history + step - 1];
+            let target = &window.vectors[history + step];
+            let body = &mut bodies[i];
+            config.kinematics.step(
+                body,
+                &DiscreteAction::from_index(index),
+                target.yaw,
+                max_speed(target.weapon_id),
+                target.tick.saturating_sub(previous.tick),
+            );
+            paths[i].push([body.position[0], body.position[1]]);
+            tracks[i].push(body.to_vector(target));
+        }
+    }
+    Ok(paths)
+}
+
+/// Earth mover's distance between two sets of positions binned on a grid
+///
+/// Both sets are counted into `cell_size` cells and the cheapest way to turn
+/// one histogram into the other is found exactly, with the distance between
+/// cell centers as ground cost. The result is the mean distance a unit of
+/// occupancy moves; sets of different sizes are compared as distributions.
+pub fn occupancy_emd(a: &[[f32; 2]], b: &[[f32; 2]], cell_size: f32) -> f64 {
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    let cell = |p: &[f32; 2]| {
+        (
+            (p[0] / cell_size).floor() as i64,
+            (p[1] / cell_size).floor() as i64,
+        )
+    };
+    // Scale counts so both sets carry the same integer mass
+    let mut mass: BTreeMap<(i64, i64), i64> = BTreeMap::new();
+    for p in a {
+        *mass.entry(cell(p)).or_default() += b.len() as i64;
+    }
+    for p in b {
+        *mass.entry(cell(p)).or_default() -= a.len() as i64;
+    }
+
+    // Mass shared by a cell stays put; the rest is a transportation problem
+    let (mut supply, mut demand) = (Vec::new(), Vec::new());
+    for (&(x, y), &m) in &mass {
+        let center = [(x as f64 + 0.5), (y as f64 + 0.5)];
+        match m.cmp(&0) {
+            std::cmp::Ordering::Greater => supply.push((center, m)),
+            std::cmp::Ordering::Less => demand.push((center, -m)),
+            std::cmp::Ordering::Equal => {}
+        }
+    }
+    let cost: Vec<Vec<f64>> = supply
+        .iter()
+        .map(|(s, _)| {
+            demand
+                .iter()
+                .map(|(d, _)| (s[0] - d[0]).hypot(s[1] - d[1]) * cell_size as f64)
+                .collect()
+        })
+        .collect();
+    let total = transport_cost(
+        supply.iter().map(|s| s.1).collect(),
+        demand.iter().map(|d| d.1).collect(),
+        &cost,
+    );
+    total / (a.len() * b.len()) as f64
+}
+
+/// Cost of the cheapest transport plan between balanced supplies and demands
+///
+/// Successive shortest paths on the residual network, with Dijkstra over
+/// reduced costs. Every path either empties a supply, fills a demand or
+/// cancels a previous shipment, and the dense graph keeps each search at
+/// O((S + D)²).
+fn transport_cost(mut supply: Vec<i64>, mut demand: Vec<i64>, cost: &[Vec<f64>]) -> f64 {
+    let (s, d) = (supply.len(), demand.len());
+    let mut flow = vec![vec![0i64; d]; s];
+    // Node potentials: supplies first, then demands
+    let mut potential = vec![0.0f64; s + d];
+    let mut total = 0.0;
+
+    while supply.iter().any(|&m| m > 0) {
+        let mut dist = vec![f64::INFINITY; s + d];
+        let mut previous = vec![usize::MAX; s + d];
+        let mut done = vec![false; s + d];
+        for i in (0..s).filter(|&i| supply[i] > 0) {
+            dist[i] = 0.0;
+        }
+        let mut sink = None;
+        while let Some(u) = (0..s + d)
+            .filter(|&n| !done[n] && dist[n].is_finite())
+            .min_by(|&x, &y| dist[x].total_cmp(&dist[y]))
+        {
+            done[u] = true;
+            if u >= s && demand[u - s] > 0 {
+                sink = Some(u);
+                break;
+            }
+            if u < s {
+                for j in (0..d).filter(|&j| !done[s + j]) {
+                    let reduced = cost[u][j] + potential[u] - potential[s + j];
+                    if dist[u] + reduced < dist[s + j] {
+                        dist[s + j] = dist[u] + reduced;
+                        previous[s + j] = u;
+                    }
+                }
+            } else {
+                let j = u - s;
+                for i in (0..s).filter(|&i| !done[i] && flow[i][j] > 0) {
+                    let reduced = -cost[i][j] + potential[u] - potential[i];
+                    if dist[u] + reduced < dist[i] {
+                        dist[i] = dist[u] + reduced;
+                        previous[i] = u;
+                    }
+                }
+            }
+        }
+        let Some(sink) = sink else {
+            break;
+        };
+        // Settled nodes keep reduced costs non-negative for the next search
+        let reached = dist[sink];
+        for (n, p) in potential.iter_mut().enumerate() {
+            *p += dist[n].min(reached);
+        }
+
+        // Walk back to the supply, finding how much the path can carry
+        let mut amount = demand[sink - s];
+        let mut node = sink;
+        while previous[node] != usize::MAX {
+            let from = previous[node];
+            if from >= s {
+                amount = amount.min(flow[node][from - s]);
+            }
+            node = from;
+        }
+        amount = amount.min(supply[node]);
+        supply[node] -= amount;
+        demand[sink - s] -= amount;
+        let mut node = sink;
+        while previous[node] != usize::MAX {
+            let from = previous[node];
+            if from < s {
+                flow[from][node - s] += amount;
+                total += amount as f64 * cost[from][node - s];
+            } else {
+                flow[node][from - s] -= amount;
+                total -= amount as f64 * cost[node][from - s];
+            }
+            node = from;
+        }
+    }
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mlmove_transformer::MLMOVEConfig;
+
+    fn run(speed: u8, direction: u8) -> DiscreteAction {
+        DiscreteAction {
+            direction,
+            speed,
+            jump: 0,
+        }
+    }
+
+    #[test]
+    fn test_kinematics() {
+        let kinematics = Kinematics::default();
+        let mut body = Body::from_vector(&BehavioralVector::new(0, 1));
+        let awp = max_speed(weapon_id_from_name("weapon_awp"));
+        assert_eq!(awp, 200.0);
+
+        // Running forward while looking along +y tops out at the weapon's speed
+        kinematics.step(&mut body, &run(4, 0), 90.0, awp, 128);
+        assert!((body.velocity[1] - awp).abs() < 1.0);
+        assert!(body.velocity[0].abs() < 1e-3);
+        // Strafing right turns towards +x; letting go stops within half a second
+        kinematics.step(&mut body, &run(4, 2), 180.0, awp, 128);
+        assert!(body.velocity[1] > awp - 1.0 && body.velocity[0].abs() < 1.0);
+        kinematics.step(&mut body, &DiscreteAction::from_index(96), 90.0, awp, 32);
+        assert_eq!(body.velocity, [0.0; 3]);
+
+        // A jump lands back on the ground after its arc of about 0.75 s
+        let start = body.position;
+        let jump = DiscreteAction {
+            jump: 1,
+            ..run(0, 0)
+        };
+        kinematics.step(&mut body, &jump, 90.0, awp, 24);
+        assert!(body.airborne && body.position[2] > start[2] + 50.0);
+        kinematics.step(&mut body, &run(0, 0), 90.0, awp, 26);
+        assert!(!body.airborne);
+        assert_eq!(body.position[2], start[2]);
+    }
+
+    #[test]
+    fn test_occupancy_emd() {
+        let cell = 100.0;
+        let at = |cells: &[f32]| -> Vec<[f32; 2]> {
+            cells.iter().map(|&x| [x * cell + 1.0, 1.0]).collect()
+        };
+        assert_eq!(occupancy_emd(&at(&[0.0, 3.0]), &at(&[3.0, 0.0]), cell), 0.0);
+        assert!((occupancy_emd(&at(&[0.0]), &at(&[10.0]), cell) - 10.0 * cell as f64).abs() < 1e-6);
+        // Half the mass moves three cells
+        let split = occupancy_emd(&at(&[0.0, 0.0]), &at(&[0.0, 3.0]), cell);
+        assert!((split - 1.5 * cell as f64).abs() < 1e-6);
+        // The nearest demand of the middle supply is the wrong pairing
+        let interleaved = occupancy_emd(&at(&[0.0, 2.0]), &at(&[1.0, 3.0]), cell);
+        assert!((interleaved - cell as f64).abs() < 1e-6);
+        // Uneven sets are compared as distributions
+        let uneven = occupancy_emd(&at(&[0.0]), &at(&[0.0, 0.0, 4.0, 4.0]), cell);
+        assert!((uneven - 2.0 * cell as f64).abs() < 1e-6);
+    }
+
+    fn write_match(path: &Path) -> Result<()> {
+        let vectors: Vec<BehavioralVector> = (0..40u32)
+            .map(|tick| BehavioralVector {
+                pos_x: tick as f32 * 4.0,
+                vel_x: 250.0,
+                weapon_id: weapon_id_from_name("weapon_knife"),
+                ..BehavioralVector::new(tick, 76561198000000001)
+            })
+            .collect();
+        crate::data::write_match_parquet(&vectors, "de_inferno", path)
+    }
+
+    #[test]
+    fn test_rollouts() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        write_match(&tmp.path().join("a_vs_b.parquet"))?;
+        write_match(&tmp.path().join("c_vs_d.parquet"))?;
+        let model = MLMOVETransformer::with_config(
+            MLMOVEConfig {
+                num_layers: 1,
+                model_dim: 32,
+                ff_dim: 64,
+                sequence_length: 8,
+                ..Default::default()
+            },
+            Device::Cpu,
+        )?;
+        let config = RolloutConfig {
+            horizon: 12,
+            stride: 10,
+            sample: true,
+            ..Default::default()
+        };
+        let dataset = |sequence_length| {
+            SequenceDataset::open(
+                tmp.path(),
+                DatasetConfig {
+                    sequence_length,
+                    window_stride: config.stride,
+                    validation_fraction: 0.0,
+                    test_fraction: 0.5,
+                    ..Default::default()
+                },
+            )
+        };
+        assert!(evaluate_rollouts(&model, &dataset(8)?, Split::Test, &config).is_err());
+
+        let report = evaluate_rollouts(&model, &dataset(20)?, Split::Test, &config)?;
+        // One held-out match of 40 ticks starts rollouts at ticks 0 and 10
+        assert_eq!(report.rollouts, 2);
+        assert_eq!(report.displacement_by_step.len(), 12);
+        assert_eq!(report.fde, report.displacement_by_step[11]);
+        assert!((report.ade_by_map["de_inferno"] - report.ade).abs() < 1e-9);
+        assert!(report.ade.is_finite() && report.occupancy_emd.is_finite());
+
+        // The same seed samples the same rollouts
+        let again = evaluate_rollouts(&model, &dataset(20)?, Split::Test, &config)?;
+        assert_eq!(again.displacement_by_step, report.displacement_by_step);
+        Ok(())
+    }
+}
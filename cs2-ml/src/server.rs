@@ -1,135 +1,781 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use candle_core::quantized::GgmlDType;
 use candle_core::Device;
-use cs2_common::InputVector;
-use std::io::{Read, Write};
-use std::net::TcpListener;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use cs2_common::policy_protocol::{
+    decode_inputs, encode_movement, encode_outputs, INPUT_VECTOR_FIELDS,
+};
+use cs2_common::{
+    BehavioralVector, CS2Error, ErrorCode, ErrorReply, Frame, Handshake, InputVector, MessageType,
+    MovementVector, OutputVector, PROTOCOL_MAGIC, PROTOCOL_VERSION,
+};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::io::{BufReader, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
 
+use crate::batching::{BatchConfig, ClientRegistration, MicroBatcher};
+use crate::mlmove_transformer::MLMOVETransformer;
 use crate::model::BehaviorNet;
+use crate::quantization;
+use crate::reload::{ModelSource, ModelWatcher};
 
-pub fn serve(port: u16) -> Result<()> {
-    let net = BehaviorNet::new(12, 2, Device::Cpu)?; // Fixed: Added Device and proper error handling
-    serve_with_model(net, port)
+/// Most recent request latencies kept for the percentiles
+const LATENCY_WINDOW: usize = 4096;
+
+/// How often the accept loop prints the latency summary
+const STATS_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How often a watched model source is checked for a new version
+pub const RELOAD_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Everything about serving besides the models themselves
+#[derive(Debug, Clone)]
+pub struct ServeOptions {
+    pub port: u16,
+    pub batching: Option<BatchConfig>, // Gather concurrent predictions into micro-batches
+    pub reload: Option<ModelSource>,   // Swap in new versions of the model from here
+    pub quantize: Option<GgmlDType>,   // Run f32 checkpoints on weights quantized to this format
 }
 
-// Separated for testing
-pub fn serve_with_model(_net: crate::model::BehaviorNet, port: u16) -> Result<()> {
-    let listener = TcpListener::bind(format!("0.0.0.0:{port}"))?;
-    println!("Policy server listening on port {port}");
-    for stream in listener.incoming() {
-        let mut stream = stream?;
-        let mut buf = [0u8; std::mem::size_of::<InputVector>()];
-        match stream.read_exact(&mut buf) {
-            Ok(_) => {
-                let _input_vec: &InputVector = bytemuck::from_bytes(&buf);
-                // Temporarily use placeholder prediction
-                let output = cs2_common::OutputVector {
-                    delta_yaw: 0.0,
-                    delta_pitch: 0.0,
-                };
-                let out_bytes = bytemuck::bytes_of(&output);
-                if let Err(e) = stream.write_all(out_bytes) {
-                    eprintln!("Error writing response: {e}");
-                }
-            }
-            Err(e) => {
-                eprintln!("Error reading from client: {e}");
-            }
+impl Default for ServeOptions {
+    fn default() -> Self {
+        Self {
+            port: 8123,
+            batching: None,
+            reload: None,
+            quantize: None,
         }
     }
-    Ok(())
 }
 
-// Modified serve function that checks shutdown flag
+/// Load a checkpoint written by `cs2-ml train` and serve it until the process exits
+///
+/// With `movement_path`, framed clients can also ask an MLMOVE checkpoint for
+/// movement commands.
+pub fn serve(model_path: &Path, movement_path: Option<&Path>, options: ServeOptions) -> Result<()> {
+    let mut model = ServedModel::load(model_path)?;
+    println!(
+        "Loaded {} as model {:08x} ({} inputs, {} outputs)",
+        model_path.display(),
+        model.id,
+        model.net.input_dim,
+        model.net.output_dim
+    );
+    if let Some(path) = movement_path {
+        let path_str = path
+            .to_str()
+            .context("movement model path is not valid UTF-8")?;
+        let movement = MLMOVETransformer::load_pretrained(path_str, Device::Cpu)?;
+        println!(
+            "Loaded movement model {} ({} ticks of history)",
+            path.display(),
+            movement.config().sequence_length
+        );
+        model = model.with_movement(movement);
+    }
+    if let Some(format) = options.quantize {
+        model.quantize(format)?;
+        println!(
+            "Quantized to {}, now model {:08x}",
+            quantization::format_name(format),
+            model.id
+        );
+    }
+    let port = options.port;
+    let mut server = PolicyServer::bind(model, ("0.0.0.0", port))?;
+    if let Some(config) = options.batching {
+        println!(
+            "Micro-batching up to {} inputs within {}us",
+            config.max_batch,
+            config.max_wait.as_micros()
+        );
+        server = server.with_batching(config);
+    }
+    if let Some(source) = options.reload {
+        println!(
+            "Watching {source} for new models every {}s",
+            RELOAD_INTERVAL.as_secs()
+        );
+        server = server.with_reload(source, RELOAD_INTERVAL)?;
+    }
+    println!("Policy server listening on port {port}");
+    server.run(Arc::new(AtomicBool::new(false)))
+}
+
+// Separated for testing
+pub fn serve_with_model(model: impl Into<ServedModel>, port: u16) -> Result<()> {
+    serve_with_model_with_shutdown(model, port, Arc::new(AtomicBool::new(false)))
+}
+
+// Serve function that checks shutdown flag
 pub fn serve_with_model_with_shutdown(
-    _net: crate::model::BehaviorNet,
+    model: impl Into<ServedModel>,
     port: u16,
     shutdown: Arc<AtomicBool>,
 ) -> Result<()> {
-    let listener = TcpListener::bind(format!("0.0.0.0:{port}"))?;
-    listener.set_nonblocking(true)?;
+    let server = PolicyServer::bind(model, ("0.0.0.0", port))?;
     println!("Policy server listening on port {port}");
+    server.run(shutdown)
+}
+
+/// Request latencies of a running server
+#[derive(Debug, Default)]
+pub struct LatencyStats {
+    requests: u64,
+    total: Duration,
+    max: Duration,
+    recent: VecDeque<Duration>,
+}
 
-    while !shutdown.load(Ordering::SeqCst) {
-        match listener.accept() {
-            Ok((mut stream, _)) => {
-                // Handle client
-                let mut buf = [0u8; std::mem::size_of::<InputVector>()];
-                match stream.read_exact(&mut buf) {
-                    Ok(_) => {
-                        let _input_vec: &InputVector = bytemuck::from_bytes(&buf);
-                        // Temporarily use placeholder prediction
-                        let output = cs2_common::OutputVector {
-                            delta_yaw: 0.0,
-                            delta_pitch: 0.0,
-                        };
-                        let out_bytes = bytemuck::bytes_of(&output);
-                        if let Err(e) = stream.write_all(out_bytes) {
-                            eprintln!("Error writing response: {e}");
+/// Latency summary in microseconds, percentiles over the last requests
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct LatencySummary {
+    pub requests: u64,
+    pub mean_us: f64,
+    pub p50_us: f64,
+    pub p95_us: f64,
+    pub p99_us: f64,
+    pub max_us: f64,
+}
+
+impl LatencyStats {
+    pub fn record(&mut self, latency: Duration) {
+        self.requests += 1;
+        self.total += latency;
+        self.max = self.max.max(latency);
+        if self.recent.len() == LATENCY_WINDOW {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(latency);
+    }
+
+    pub fn summary(&self) -> LatencySummary {
+        if self.requests == 0 {
+            return LatencySummary::default();
+        }
+        let mut sorted: Vec<Duration> = self.recent.iter().copied().collect();
+        sorted.sort();
+        let percentile = |p: f64| {
+            let rank = ((sorted.len() - 1) as f64 * p).round() as usize;
+            micros(sorted[rank])
+        };
+        LatencySummary {
+            requests: self.requests,
+            mean_us: micros(self.total) / self.requests as f64,
+            p50_us: percentile(0.5),
+            p95_us: percentile(0.95),
+            p99_us: percentile(0.99),
+            max_us: micros(self.max),
+        }
+    }
+}
+
+impl std::fmt::Display for LatencySummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} requests, mean {:.0}us, p50 {:.0}us, p95 {:.0}us, p99 {:.0}us, max {:.0}us",
+            self.requests, self.mean_us, self.p50_us, self.p95_us, self.p99_us, self.max_us
+        )
+    }
+}
+
+fn micros(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1e6
+}
+
+/// Largest batch accepted in one framed `Predict` request
+pub const MAX_BATCH: usize = 1024;
+
+/// A model together with the identity reported to clients
+#[derive(Debug)]
+pub struct ServedModel {
+    pub net: BehaviorNet,
+    pub movement: Option<Arc<MLMOVETransformer>>, // Answers `PredictMovement` when present
+    pub id: u32, // Sent in the handshake and in the header of every reply
+    pub name: String,
+}
+
+impl ServedModel {
+    /// Load a checkpoint, identified by the hash of its weights
+    pub fn load(path: &Path) -> Result<Self> {
+        let path_str = path.to_str().context("model path is not valid UTF-8")?;
+        let net = BehaviorNet::load(path_str, Device::Cpu)?;
+        let weights = std::fs::read(path).with_context(|| format!("read {}", path.display()))?;
+        Ok(Self {
+            net,
+            movement: None,
+            id: fnv1a(&weights),
+            name: path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+        })
+    }
+
+    pub fn handshake(&self) -> Handshake {
+        Handshake {
+            model_id: self.id,
+            model_name: self.name.clone(),
+            input_schema: INPUT_VECTOR_FIELDS.iter().map(|f| f.to_string()).collect(),
+            input_dim: self.net.input_dim,
+            output_dim: self.net.output_dim,
+            max_batch: MAX_BATCH,
+            sequence_length: self
+                .movement
+                .as_ref()
+                .map(|movement| movement.config().sequence_length),
+        }
+    }
+
+    /// Run on weights quantized to `format` where they are still f32
+    ///
+    /// A quantized model answers slightly differently, so it gets an ID of its
+    /// own. A movement model shared with other models is left as it is.
+    pub fn quantize(&mut self, format: GgmlDType) -> Result<()> {
+        if let Some(movement) = self.movement.as_mut().and_then(Arc::get_mut) {
+            if movement.quantization().is_none() {
+                movement.quantize(format)?;
+            }
+        }
+        if self.net.quantization().is_some() {
+            return Ok(());
+        }
+        self.net.quantize(format)?;
+        let name = quantization::format_name(format);
+        self.id = fnv1a(format!("{:08x}.{name}", self.id).as_bytes());
+        self.name = format!("{}.{name}", self.name);
+        Ok(())
+    }
+
+    /// Serve movement predictions from an MLMOVE model as well
+    pub fn with_movement(mut self, movement: MLMOVETransformer) -> Self {
+        self.movement = Some(Arc::new(movement));
+        self
+    }
+
+    /// Movement for the latest of a player's recent inputs, oldest first
+    ///
+    /// Only the last `sequence_length` inputs are used.
+    pub fn predict_movement(
+        &self,
+        history: &[InputVector],
+    ) -> std::result::Result<MovementVector, ErrorReply> {
+        let movement = self
+            .movement
+            .as_ref()
+            .ok_or_else(|| ErrorReply::new(ErrorCode::Unsupported, "no movement model loaded"))?;
+        if history.is_empty() {
+            return Err(ErrorReply::new(
+                ErrorCode::MalformedRequest,
+                "movement needs at least one state",
+            ));
+        }
+        // A history shorter than the window is padded with its oldest state
+        let keep = history.len().min(movement.config().sequence_length);
+        let sequence: Vec<BehavioralVector> = history[history.len() - keep..]
+            .iter()
+            .enumerate()
+            .map(|(tick, input)| BehavioralVector::from_input(input, tick as u32, 0))
+            .collect();
+        let prediction = movement
+            .predict_movement(&sequence)
+            .map_err(|e| ErrorReply::new(ErrorCode::ModelError, e.to_string()))?;
+        let commands = movement.action_to_movement_commands(&prediction.action);
+        Ok(MovementVector {
+            forward_move: commands.forward_move,
+            side_move: commands.side_move,
+            jump: if commands.jump { 1.0 } else { 0.0 },
+            walk: if commands.walk { 1.0 } else { 0.0 },
+            confidence: prediction.confidence,
+            action_index: prediction.action.index() as u32,
+        })
+    }
+}
+
+impl From<BehaviorNet> for ServedModel {
+    /// An in-memory model, identified by its metadata
+    fn from(net: BehaviorNet) -> Self {
+        let metadata = serde_json::to_vec(&net.metadata()).unwrap_or_default();
+        Self {
+            net,
+            movement: None,
+            id: fnv1a(&metadata),
+            name: "in-memory".to_string(),
+        }
+    }
+}
+
+/// 32-bit FNV-1a, stable across runs and platforms
+fn fnv1a(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c_9dc5, |hash, &b| {
+        (hash ^ b as u32).wrapping_mul(0x0100_0193)
+    })
+}
+
+/// The model answering requests, swappable while clients stay connected
+///
+/// Each request takes the model that is current when it starts and keeps it
+/// until its reply is written, so a swap never changes the model under a
+/// request in flight; the old model is dropped with its last request.
+#[derive(Debug, Clone)]
+pub struct ModelSlot {
+    current: Arc<RwLock<Arc<ServedModel>>>,
+    swaps: Arc<AtomicU64>,
+}
+
+impl ModelSlot {
+    pub fn new(model: impl Into<ServedModel>) -> Self {
+        Self {
+            current: Arc::new(RwLock::new(Arc::new(model.into()))),
+            swaps: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    pub fn current(&self) -> Arc<ServedModel> {
+        self.current.read().unwrap().clone()
+    }
+
+    /// Replace the model if it reads and writes vectors of the same sizes
+    ///
+    /// A model without a movement model of its own keeps the current one, and
+    /// an f32 model replacing a quantized one is quantized the same way.
+    /// Returns the model that was replaced.
+    pub fn swap(&self, mut model: ServedModel) -> Result<Arc<ServedModel>> {
+        if let Some(format) = self.current().net.quantization() {
+            model.quantize(format)?;
+        }
+        let mut current = self.current.write().unwrap();
+        let (old, new) = (&current.net, &model.net);
+        if (old.input_dim, old.output_dim) != (new.input_dim, new.output_dim) {
+            anyhow::bail!(
+                "model {:08x} maps {} inputs to {} outputs, the served model {} to {}",
+                model.id,
+                new.input_dim,
+                new.output_dim,
+                old.input_dim,
+                old.output_dim
+            );
+        }
+        if model.movement.is_none() {
+            model.movement = current.movement.clone();
+        }
+        let previous = std::mem::replace(&mut *current, Arc::new(model));
+        self.swaps.fetch_add(1, Ordering::SeqCst);
+        Ok(previous)
+    }
+
+    /// Models swapped in since the server started
+    pub fn swaps(&self) -> u64 {
+        self.swaps.load(Ordering::SeqCst)
+    }
+}
+
+/// Policy server answering `InputVector` requests with model predictions
+///
+/// Every client gets its own thread and keeps its connection open for as many
+/// request/response pairs as it likes. Clients that open with
+/// [`PROTOCOL_MAGIC`] speak the framed protocol of
+/// [`cs2_common::policy_protocol`]; any other client is served in the legacy
+/// raw mode, one 56-byte `InputVector` in and one 8-byte `OutputVector` out.
+/// A legacy prediction that fails is answered with [`PROTOCOL_MAGIC`] and an
+/// error frame instead.
+pub struct PolicyServer {
+    listener: TcpListener,
+    model: ModelSlot,
+    stats: Arc<Mutex<LatencyStats>>,
+    batcher: Option<Arc<MicroBatcher>>, // Shared by all clients when micro-batching
+    watcher: Option<(ModelWatcher, Duration)>, // Source of new models and how often to poll it
+}
+
+impl PolicyServer {
+    pub fn bind(model: impl Into<ServedModel>, addr: impl ToSocketAddrs) -> Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener,
+            model: ModelSlot::new(model),
+            stats: Arc::new(Mutex::new(LatencyStats::default())),
+            batcher: None,
+            watcher: None,
+        })
+    }
+
+    /// Swap in new versions of the model from `source` while running
+    ///
+    /// The version the source holds now counts as the one being served.
+    pub fn with_reload(mut self, source: ModelSource, interval: Duration) -> Result<Self> {
+        self.watcher = Some((ModelWatcher::new(source)?, interval));
+        Ok(self)
+    }
+
+    /// Gather concurrent `Predict` requests of all clients into micro-batches
+    pub fn with_batching(mut self, config: BatchConfig) -> Self {
+        self.batcher = Some(Arc::new(MicroBatcher::spawn(self.model.clone(), config)));
+        self
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    pub fn model_id(&self) -> u32 {
+        self.model.current().id
+    }
+
+    /// Handle to the served model, for swapping it from outside the server
+    pub fn model(&self) -> ModelSlot {
+        self.model.clone()
+    }
+
+    /// Shared handle to the latency stats, updated by every client thread
+    pub fn stats(&self) -> Arc<Mutex<LatencyStats>> {
+        self.stats.clone()
+    }
+
+    /// Accept clients until `shutdown` is set
+    pub fn run(self, shutdown: Arc<AtomicBool>) -> Result<()> {
+        if let Some((watcher, interval)) = self.watcher {
+            let slot = self.model.clone();
+            let shutdown = shutdown.clone();
+            thread::spawn(move || watcher.run(&slot, interval, &shutdown));
+        }
+        let mut last_report = Instant::now();
+        let mut reported = 0;
+        while !shutdown.load(Ordering::SeqCst) {
+            match self.listener.accept() {
+                Ok((stream, peer)) => {
+                    let model = self.model.clone();
+                    let stats = self.stats.clone();
+                    let batcher = self.batcher.clone();
+                    thread::spawn(move || {
+                        // Batches stop waiting for this client once it hangs up
+                        let client = batcher.as_ref().map(|b| b.register_client());
+                        if let Err(e) = handle_client(stream, &model, client.as_ref(), &stats) {
+                            eprintln!("Error serving {peer}: {e}");
                         }
+                    });
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                    // No connections available, sleep briefly
+                    thread::sleep(Duration::from_millis(10));
+                }
+                Err(e) => {
+                    eprintln!("Error accepting connection: {e}");
+                    break;
+                }
+            }
+
+            if last_report.elapsed() >= STATS_INTERVAL {
+                let summary = self.stats.lock().unwrap().summary();
+                if summary.requests > reported {
+                    match &self.batcher {
+                        Some(batcher) => println!(
+                            "Latency: {summary}, {:.1} inputs per batch",
+                            batcher.mean_batch_size()
+                        ),
+                        None => println!("Latency: {summary}"),
                     }
-                    Err(e) => {
-                        eprintln!("Error reading from client: {e}");
-                    }
+                    reported = summary.requests;
                 }
+                last_report = Instant::now();
             }
-            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
-                // No connections available, sleep briefly
-                std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        Ok(())
+    }
+}
+
+/// Answer requests on one connection until the client hangs up
+fn handle_client(
+    stream: TcpStream,
+    model: &ModelSlot,
+    client: Option<&ClientRegistration>,
+    stats: &Mutex<LatencyStats>,
+) -> Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_nodelay(true)?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut opening = [0u8; 4];
+    if !read_or_eof(&mut reader, &mut opening)? {
+        return Ok(());
+    }
+    if opening == PROTOCOL_MAGIC {
+        serve_framed(reader, stream, model, client, stats)
+    } else {
+        serve_legacy(reader, stream, opening, model, client, stats)
+    }
+}
+
+/// Predict through the micro-batcher when the server has one
+///
+/// Returns the ID of the model that answered, which can be newer than `model`
+/// when a batch ran after a swap.
+fn predict_inputs(
+    model: &ServedModel,
+    client: Option<&ClientRegistration>,
+    inputs: Vec<InputVector>,
+) -> Result<(u32, Vec<OutputVector>)> {
+    match client {
+        Some(client) => client.predict(inputs),
+        None => Ok((model.id, model.net.predict_batch(&inputs)?)),
+    }
+}
+
+/// Fill `buf`, returning false if the client hung up before sending anything
+fn read_or_eof(reader: &mut impl Read, buf: &mut [u8]) -> Result<bool> {
+    match reader.read_exact(buf) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Raw `InputVector`s in, raw `OutputVector`s out
+fn serve_legacy(
+    mut reader: impl Read,
+    mut stream: TcpStream,
+    opening: [u8; 4],
+    model: &ModelSlot,
+    client: Option<&ClientRegistration>,
+    stats: &Mutex<LatencyStats>,
+) -> Result<()> {
+    let mut buf = [0u8; std::mem::size_of::<InputVector>()];
+    buf[..4].copy_from_slice(&opening);
+    let mut filled = read_or_eof(&mut reader, &mut buf[4..])?;
+    while filled {
+        let start = Instant::now();
+        let input: InputVector = bytemuck::pod_read_unaligned(&buf);
+        let served = model.current();
+        match predict_inputs(&served, client, vec![input]) {
+            Ok((_, outputs)) => {
+                // Recorded before replying so a client never sees its answer ahead of the stats
+                stats.lock().unwrap().record(start.elapsed());
+                stream.write_all(bytemuck::bytes_of(&outputs[0]))?;
             }
             Err(e) => {
-                eprintln!("Error accepting connection: {e}");
-                break;
+                // No output starts with the magic, so it marks an error frame instead
+                let error = ErrorReply::new(ErrorCode::ModelError, e.to_string());
+                stream.write_all(&PROTOCOL_MAGIC)?;
+                error_frame(served.id, 0, error).write_to(&mut stream)?;
             }
         }
+        filled = read_or_eof(&mut reader, &mut buf)?;
     }
-
     Ok(())
 }
 
+/// Handshake, then framed requests until the client hangs up
+fn serve_framed(
+    mut reader: impl Read,
+    mut stream: TcpStream,
+    slot: &ModelSlot,
+    client: Option<&ClientRegistration>,
+    stats: &Mutex<LatencyStats>,
+) -> Result<()> {
+    let model = slot.current();
+    let hello = Frame::read_from(&mut reader)?;
+    if hello.version != PROTOCOL_VERSION {
+        let message = format!(
+            "client speaks version {}, server speaks {PROTOCOL_VERSION}",
+            hello.version
+        );
+        let error = ErrorReply::new(ErrorCode::UnsupportedVersion, message);
+        error_frame(model.id, hello.request_id, error).write_to(&mut stream)?;
+        return Ok(());
+    }
+    if hello.kind != MessageType::Hello {
+        let error = ErrorReply::new(ErrorCode::UnexpectedMessage, "expected Hello");
+        error_frame(model.id, hello.request_id, error).write_to(&mut stream)?;
+        return Ok(());
+    }
+    Frame::new(
+        MessageType::Handshake,
+        model.id,
+        hello.request_id,
+        model.handshake().encode(),
+    )
+    .write_to(&mut stream)?;
+
+    // Predictions run on threads of their own so a slow request does not hold
+    // up the ones behind it, and every reply goes out through one writer
+    let (replies, outbox) = mpsc::channel::<Frame>();
+    thread::scope(|scope| {
+        let writer = scope.spawn(move || -> Result<()> {
+            for frame in outbox {
+                frame.write_to(&mut stream)?;
+            }
+            Ok(())
+        });
+
+        let mut in_flight = VecDeque::new();
+        let session = loop {
+            let frame = match Frame::read_from(&mut reader) {
+                Ok(frame) => frame,
+                Err(CS2Error::IoError(e)) if e.kind() == ErrorKind::UnexpectedEof => break Ok(()),
+                Err(CS2Error::IoError(e)) => break Err(e.into()),
+                Err(e) => {
+                    // The stream can no longer be trusted to be aligned on frames
+                    let error = ErrorReply::new(ErrorCode::MalformedRequest, e.to_string());
+                    let _ = replies.send(error_frame(slot.current().id, 0, error));
+                    break Ok(());
+                }
+            };
+            // Held until the reply is written, even if the slot is swapped meanwhile
+            let model = slot.current();
+            let reply = if frame.version != PROTOCOL_VERSION {
+                let error = ErrorReply::new(
+                    ErrorCode::UnsupportedVersion,
+                    format!(
+                        "version {} in a version {PROTOCOL_VERSION} session",
+                        frame.version
+                    ),
+                );
+                error_frame(model.id, frame.request_id, error)
+            } else {
+                match frame.kind {
+                    MessageType::Predict | MessageType::PredictMovement => {
+                        in_flight.retain(|p: &thread::ScopedJoinHandle<()>| !p.is_finished());
+                        if in_flight.len() >= MAX_IN_FLIGHT {
+                            let _ = in_flight.pop_front().map(|p| p.join());
+                        }
+                        let replies = replies.clone();
+                        in_flight.push_back(scope.spawn(move || {
+                            let _ = replies.send(answer_prediction(frame, &model, client, stats));
+                        }));
+                        continue;
+                    }
+                    MessageType::Heartbeat => Frame::new(
+                        MessageType::Heartbeat,
+                        model.id,
+                        frame.request_id,
+                        frame.payload,
+                    ),
+                    MessageType::Unknown(code) => {
+                        let error = ErrorReply::new(
+                            ErrorCode::Unsupported,
+                            format!("unknown message type {code}"),
+                        );
+                        error_frame(model.id, frame.request_id, error)
+                    }
+                    other => {
+                        let error = ErrorReply::new(
+                            ErrorCode::UnexpectedMessage,
+                            format!("unexpected {other:?}"),
+                        );
+                        error_frame(model.id, frame.request_id, error)
+                    }
+                }
+            };
+            if replies.send(reply).is_err() {
+                // The writer failed, its error ends the session
+                break Ok(());
+            }
+        };
+
+        // The writer finishes once the last prediction has handed over its reply
+        drop(replies);
+        let written = writer.join().expect("reply writer panicked");
+        session.and(written)
+    })
+}
+
+/// Most prediction frames of one session computed at once
+///
+/// Further frames are read only once one of them has finished.
+const MAX_IN_FLIGHT: usize = 64;
+
+fn error_frame(model_id: u32, request_id: u32, error: ErrorReply) -> Frame {
+    Frame::new(MessageType::Error, model_id, request_id, error.encode())
+}
+
+/// Reply to a `Predict` or `PredictMovement` frame
+fn answer_prediction(
+    frame: Frame,
+    model: &ServedModel,
+    client: Option<&ClientRegistration>,
+    stats: &Mutex<LatencyStats>,
+) -> Frame {
+    let start = Instant::now();
+    let reply = match frame.kind {
+        MessageType::PredictMovement => decode_inputs(&frame.payload)
+            .map_err(|e| ErrorReply::new(ErrorCode::MalformedRequest, e.to_string()))
+            .and_then(|history| model.predict_movement(&history))
+            .map(|movement| {
+                Frame::new(
+                    MessageType::Movement,
+                    model.id,
+                    frame.request_id,
+                    encode_movement(&movement),
+                )
+            }),
+        _ => decode_inputs(&frame.payload)
+            .map_err(|e| ErrorReply::new(ErrorCode::MalformedRequest, e.to_string()))
+            .and_then(|inputs| {
+                if inputs.len() > MAX_BATCH {
+                    return Err(ErrorReply::new(
+                        ErrorCode::BatchTooLarge,
+                        format!("{} inputs, at most {MAX_BATCH}", inputs.len()),
+                    ));
+                }
+                predict_inputs(model, client, inputs)
+                    .map_err(|e| ErrorReply::new(ErrorCode::ModelError, e.to_string()))
+            })
+            .map(|(answered_by, outputs)| {
+                Frame::new(
+                    MessageType::Prediction,
+                    answered_by,
+                    frame.request_id,
+                    encode_outputs(&outputs),
+                )
+            }),
+    };
+    match reply {
+        Ok(reply) => {
+            stats.lock().unwrap().record(start.elapsed());
+            reply
+        }
+        Err(error) => error_frame(model.id, frame.request_id, error),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use cs2_common::OutputVector;
     use std::net::TcpStream;
-    use std::sync::{
-        atomic::{AtomicBool, Ordering},
-        Arc,
-    };
-    use std::thread;
-    use std::time::Duration;
 
     struct TestServer {
-        port: u16,
+        addr: SocketAddr,
+        stats: Arc<Mutex<LatencyStats>>,
         handle: Option<thread::JoinHandle<()>>,
         shutdown: Arc<AtomicBool>,
     }
 
     impl TestServer {
-        fn start() -> Self {
-            // Find an available port
-            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
-            let addr = listener.local_addr().unwrap();
-            let port = addr.port();
-            drop(listener);
+        fn start(model: impl Into<ServedModel>) -> Self {
+            // Bound before the thread starts, so clients can connect right away
+            Self::run(PolicyServer::bind(model, "127.0.0.1:0").unwrap())
+        }
+
+        fn run(server: PolicyServer) -> Self {
+            let addr = server.local_addr().unwrap();
+            let stats = server.stats();
 
             let shutdown = Arc::new(AtomicBool::new(false));
             let shutdown_clone = shutdown.clone();
-
-            // Create a simple model - handle the Result properly
-            let model = crate::model::BehaviorNet::new(14, 2, candle_core::Device::Cpu).unwrap();
-
             let handle = thread::spawn(move || {
-                // Run server in a separate thread until shutdown
-                let server_result = serve_with_model_with_shutdown(model, port, shutdown_clone);
-                if let Err(e) = server_result {
+                if let Err(e) = server.run(shutdown_clone) {
                     eprintln!("Server error: {}", e);
                 }
             });
 
             TestServer {
-                port,
+                addr,
+                stats,
                 shutdown,
                 handle: Some(handle),
             }
@@ -145,56 +791,8 @@ mod tests {
         }
     }
 
-    // Modified serve function that checks shutdown flag
-    fn serve_with_model_with_shutdown(
-        _net: crate::model::BehaviorNet,
-        port: u16,
-        shutdown: Arc<AtomicBool>,
-    ) -> Result<()> {
-        let listener = TcpListener::bind(format!("127.0.0.1:{}", port))?;
-        listener.set_nonblocking(true)?;
-
-        while !shutdown.load(Ordering::SeqCst) {
-            match listener.accept() {
-                Ok((mut stream, _)) => {
-                    stream.set_nonblocking(false)?;
-
-                    let mut buf = [0u8; std::mem::size_of::<InputVector>()];
-                    match stream.read_exact(&mut buf) {
-                        Ok(_) => {
-                            let _input_vec: &InputVector = bytemuck::from_bytes(&buf);
-                            // Temporarily use placeholder prediction
-                            let output = cs2_common::OutputVector {
-                                delta_yaw: 0.0,
-                                delta_pitch: 0.0,
-                            };
-                            let out_bytes = bytemuck::bytes_of(&output);
-                            let _ = stream.write_all(out_bytes);
-                        }
-                        Err(_) => continue,
-                    }
-                }
-                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
-                    // No connections available, sleep a bit
-                    thread::sleep(Duration::from_millis(10));
-                }
-                Err(_) => break,
-            }
-        }
-
-        Ok(())
-    }
-
-    #[test]
-    fn test_server_client_communication() {
-        let server = TestServer::start();
-
-        // Connect to server
-        let addr = format!("127.0.0.1:{}", server.port);
-        let mut stream = TcpStream::connect(addr).unwrap();
-
-        // Send input vector
-        let input = InputVector {
+    fn input(yaw: f32) -> InputVector {
+        InputVector {
             health: 100.0,
             armor: 50.0,
             pos_x: 1.0,
@@ -203,25 +801,399 @@ mod tests {
             vel_x: 0.1,
             vel_y: 0.2,
             vel_z: 0.3,
-            yaw: 90.0,
+            yaw,
             pitch: 45.0,
             weapon_id_f32: 42.0,
             ammo: 30.0,
             is_airborne: 0.0,
             padding: 0.0,
-        };
-        let input_bytes = bytemuck::bytes_of(&input);
-        stream.write_all(input_bytes).unwrap();
+        }
+    }
 
-        // Read response
+    fn request(stream: &mut TcpStream, input: &InputVector) -> OutputVector {
+        stream.write_all(bytemuck::bytes_of(input)).unwrap();
         let mut output_bytes = [0u8; std::mem::size_of::<OutputVector>()];
         stream.read_exact(&mut output_bytes).unwrap();
+        bytemuck::pod_read_unaligned(&output_bytes)
+    }
 
-        // Parse output
-        let output: OutputVector = *bytemuck::from_bytes(&output_bytes);
+    #[test]
+    fn test_server_client_communication() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("policy.safetensors");
+        let path = path.to_str().unwrap();
+        let trained = BehaviorNet::new(
+            crate::model::BEHAVIOR_INPUT_DIM,
+            crate::model::BEHAVIOR_OUTPUT_DIM,
+            Device::Cpu,
+        )
+        .unwrap();
+        trained.save(path).unwrap();
+        let server = TestServer::start(BehaviorNet::load(path, Device::Cpu).unwrap());
 
-        // Verify output is valid (not checking exact values since the model is random)
-        assert!(output.delta_yaw.is_finite());
-        assert!(output.delta_pitch.is_finite());
+        // Several requests share one connection, and two clients run at once
+        let mut clients: Vec<_> = (0..2)
+            .map(|_| TcpStream::connect(server.addr).unwrap())
+            .collect();
+        for step in 0..5 {
+            for (i, stream) in clients.iter_mut().enumerate() {
+                let input = input(90.0 + (step * 2 + i) as f32);
+                let output = request(stream, &input);
+                let expected = trained.predict(&input).unwrap();
+                assert!((output.delta_yaw - expected.delta_yaw).abs() < 1e-6);
+                assert!((output.delta_pitch - expected.delta_pitch).abs() < 1e-6);
+            }
+        }
+
+        let summary = server.stats.lock().unwrap().summary();
+        assert_eq!(summary.requests, 10);
+        assert!(summary.p50_us <= summary.p99_us && summary.p99_us <= summary.max_us);
+    }
+
+    #[test]
+    fn test_micro_batched_server() {
+        let net = BehaviorNet::new(
+            crate::model::BEHAVIOR_INPUT_DIM,
+            crate::model::BEHAVIOR_OUTPUT_DIM,
+            Device::Cpu,
+        )
+        .unwrap();
+        let expected: Vec<OutputVector> = (0..20)
+            .map(|i| net.predict(&input(i as f32)))
+            .collect::<Result<_>>()
+            .unwrap();
+        let server = PolicyServer::bind(net, "127.0.0.1:0")
+            .unwrap()
+            .with_batching(BatchConfig {
+                max_wait: Duration::from_millis(2),
+                max_batch: 8,
+            });
+        let server = TestServer::run(server);
+
+        // Legacy clients share batches and still get their own answers back
+        let close = |a: f32, b: f32| (a - b).abs() <= 1e-4 * a.abs().max(1.0);
+        thread::scope(|s| {
+            for client in 0..4 {
+                let expected = &expected;
+                let addr = server.addr;
+                s.spawn(move || {
+                    let mut stream = TcpStream::connect(addr).unwrap();
+                    for step in 0..5 {
+                        let i = step * 4 + client;
+                        let output = request(&mut stream, &input(i as f32));
+                        assert!(close(output.delta_yaw, expected[i].delta_yaw));
+                        assert!(close(output.delta_pitch, expected[i].delta_pitch));
+                    }
+                });
+            }
+        });
+        assert_eq!(server.stats.lock().unwrap().summary().requests, 20);
+    }
+
+    #[test]
+    fn test_framed_session() {
+        let net = BehaviorNet::new(
+            crate::model::BEHAVIOR_INPUT_DIM,
+            crate::model::BEHAVIOR_OUTPUT_DIM,
+            Device::Cpu,
+        )
+        .unwrap();
+        let inputs = [input(10.0), input(20.0), input(30.0)];
+        let expected = net.predict_batch(&inputs).unwrap();
+        let server = TestServer::start(net);
+
+        let mut stream = TcpStream::connect(server.addr).unwrap();
+        stream.write_all(&PROTOCOL_MAGIC).unwrap();
+        let mut exchange = |frame: Frame| {
+            frame.write_to(&mut stream).unwrap();
+            Frame::read_from(&mut stream).unwrap()
+        };
+
+        let reply = exchange(Frame::new(MessageType::Hello, 0, 1, vec![]));
+        assert_eq!(reply.kind, MessageType::Handshake);
+        let handshake = Handshake::decode(&reply.payload).unwrap();
+        assert_eq!(handshake.input_dim, crate::model::BEHAVIOR_INPUT_DIM);
+        assert_eq!(handshake.input_schema, INPUT_VECTOR_FIELDS);
+
+        // A batch answers every input, tagged with the model and request IDs
+        let reply = exchange(Frame::new(
+            MessageType::Predict,
+            0,
+            2,
+            cs2_common::policy_protocol::encode_inputs(&inputs),
+        ));
+        assert_eq!(reply.kind, MessageType::Prediction);
+        assert_eq!((reply.model_id, reply.request_id), (handshake.model_id, 2));
+        let outputs = cs2_common::policy_protocol::decode_outputs(&reply.payload).unwrap();
+        // The server runs the same batched forward pass, so results match exactly
+        assert_eq!(outputs.len(), expected.len());
+        for (output, expected) in outputs.iter().zip(&expected) {
+            assert_eq!(
+                (output.delta_yaw, output.delta_pitch),
+                (expected.delta_yaw, expected.delta_pitch)
+            );
+        }
+
+        let reply = exchange(Frame::new(MessageType::Heartbeat, 0, 3, b"ping".to_vec()));
+        assert_eq!(
+            (reply.kind, reply.payload.as_slice()),
+            (MessageType::Heartbeat, &b"ping"[..])
+        );
+
+        // Bad requests get error replies and the session stays open
+        let reply = exchange(Frame::new(MessageType::Predict, 0, 4, vec![0; 10]));
+        assert_eq!(reply.kind, MessageType::Error);
+        let error = ErrorReply::decode(&reply.payload).unwrap();
+        assert_eq!(
+            (error.code, reply.request_id),
+            (ErrorCode::MalformedRequest, 4)
+        );
+
+        let mut future = Frame::new(MessageType::Heartbeat, 0, 5, vec![]);
+        future.version = PROTOCOL_VERSION + 1;
+        let reply = exchange(future);
+        let error = ErrorReply::decode(&reply.payload).unwrap();
+        assert_eq!(error.code, ErrorCode::UnsupportedVersion);
+
+        let reply = exchange(Frame::new(MessageType::Unknown(99), 0, 6, b"new".to_vec()));
+        let error = ErrorReply::decode(&reply.payload).unwrap();
+        assert_eq!(
+            (reply.kind, error.code, reply.request_id),
+            (MessageType::Error, ErrorCode::Unsupported, 6)
+        );
+
+        let reply = exchange(Frame::new(MessageType::Heartbeat, 0, 7, vec![]));
+        assert_eq!(reply.kind, MessageType::Heartbeat);
+    }
+
+    #[test]
+    fn test_pipelined_session() {
+        let net = BehaviorNet::new(
+            crate::model::BEHAVIOR_INPUT_DIM,
+            crate::model::BEHAVIOR_OUTPUT_DIM,
+            Device::Cpu,
+        )
+        .unwrap();
+        let expected: Vec<OutputVector> = (0..16)
+            .map(|i| net.predict(&input(i as f32)))
+            .collect::<Result<_>>()
+            .unwrap();
+        let server = PolicyServer::bind(net, "127.0.0.1:0")
+            .unwrap()
+            .with_batching(BatchConfig {
+                max_wait: Duration::from_millis(2),
+                max_batch: 8,
+            });
+        let server = TestServer::run(server);
+
+        let mut stream = TcpStream::connect(server.addr).unwrap();
+        stream.write_all(&PROTOCOL_MAGIC).unwrap();
+        Frame::new(MessageType::Hello, 0, 0, vec![])
+            .write_to(&mut stream)
+            .unwrap();
+        assert_eq!(
+            Frame::read_from(&mut stream).unwrap().kind,
+            MessageType::Handshake
+        );
+
+        // Every request is sent before the first reply is read
+        for (i, input) in (0..16).map(|i| (i, input(i as f32))) {
+            Frame::new(
+                MessageType::Predict,
+                0,
+                i as u32 + 1,
+                cs2_common::policy_protocol::encode_inputs(&[input]),
+            )
+            .write_to(&mut stream)
+            .unwrap();
+        }
+
+        // Replies may arrive in any order, each under its own request ID
+        let close = |a: f32, b: f32| (a - b).abs() <= 1e-4 * a.abs().max(1.0);
+        let mut answered = [false; 16];
+        for _ in 0..16 {
+            let reply = Frame::read_from(&mut stream).unwrap();
+            assert_eq!(reply.kind, MessageType::Prediction);
+            let i = reply.request_id as usize - 1;
+            assert!(!answered[i]);
+            answered[i] = true;
+            let outputs = cs2_common::policy_protocol::decode_outputs(&reply.payload).unwrap();
+            assert!(close(outputs[0].delta_yaw, expected[i].delta_yaw));
+            assert!(close(outputs[0].delta_pitch, expected[i].delta_pitch));
+        }
+        assert_eq!(server.stats.lock().unwrap().summary().requests, 16);
+    }
+
+    #[test]
+    fn test_hot_reload_keeps_sessions() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("policy.safetensors");
+        let new_net = || {
+            BehaviorNet::new(
+                crate::model::BEHAVIOR_INPUT_DIM,
+                crate::model::BEHAVIOR_OUTPUT_DIM,
+                Device::Cpu,
+            )
+            .unwrap()
+        };
+        new_net().save(path.to_str().unwrap()).unwrap();
+        let server = PolicyServer::bind(ServedModel::load(&path).unwrap(), "127.0.0.1:0")
+            .unwrap()
+            .with_reload(ModelSource::Path(path.clone()), Duration::from_millis(20))
+            .unwrap();
+        let slot = server.model();
+        let server = TestServer::run(server);
+
+        let mut stream = TcpStream::connect(server.addr).unwrap();
+        stream.write_all(&PROTOCOL_MAGIC).unwrap();
+        let mut exchange = |frame: Frame| {
+            frame.write_to(&mut stream).unwrap();
+            Frame::read_from(&mut stream).unwrap()
+        };
+        let reply = exchange(Frame::new(MessageType::Hello, 0, 1, vec![]));
+        let first = Handshake::decode(&reply.payload).unwrap().model_id;
+
+        // Overwriting the checkpoint swaps models under the open session
+        let retrained = new_net();
+        retrained.save(path.to_str().unwrap()).unwrap();
+        let payload = cs2_common::policy_protocol::encode_inputs(&[input(30.0)]);
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let reply = loop {
+            let reply = exchange(Frame::new(MessageType::Predict, 0, 2, payload.clone()));
+            assert_eq!(reply.kind, MessageType::Prediction);
+            if reply.model_id != first || Instant::now() > deadline {
+                break reply;
+            }
+            thread::sleep(Duration::from_millis(10));
+        };
+        assert_eq!(reply.model_id, slot.current().id);
+        assert_ne!(reply.model_id, first);
+        assert_eq!(slot.swaps(), 1);
+        let outputs = cs2_common::policy_protocol::decode_outputs(&reply.payload).unwrap();
+        let expected = retrained.predict(&input(30.0)).unwrap();
+        assert!((outputs[0].delta_yaw - expected.delta_yaw).abs() < 1e-5);
+
+        // Models of other sizes are refused and the served one stays
+        let wide = BehaviorNet::new(
+            crate::model::BEHAVIOR_INPUT_DIM + 1,
+            crate::model::BEHAVIOR_OUTPUT_DIM,
+            Device::Cpu,
+        )
+        .unwrap();
+        assert!(slot.swap(ServedModel::from(wide)).is_err());
+        assert_eq!(slot.current().id, reply.model_id);
+    }
+
+    #[test]
+    fn test_quantized_slot_stays_quantized() {
+        let net = || {
+            BehaviorNet::new(
+                crate::model::BEHAVIOR_INPUT_DIM,
+                crate::model::BEHAVIOR_OUTPUT_DIM,
+                Device::Cpu,
+            )
+            .unwrap()
+        };
+        let mut model = ServedModel::from(net());
+        let f32_id = model.id;
+        model.quantize(GgmlDType::Q8_0).unwrap();
+        assert_ne!(model.id, f32_id);
+        assert_eq!(model.name, "in-memory.q8_0");
+
+        // An f32 replacement is quantized like the model it replaces
+        let slot = ModelSlot::new(model);
+        slot.swap(ServedModel::from(net())).unwrap();
+        assert_eq!(slot.current().net.quantization(), Some(GgmlDType::Q8_0));
+    }
+
+    #[test]
+    fn test_movement_endpoint() {
+        let net = BehaviorNet::new(
+            crate::model::BEHAVIOR_INPUT_DIM,
+            crate::model::BEHAVIOR_OUTPUT_DIM,
+            Device::Cpu,
+        )
+        .unwrap();
+        let movement = MLMOVETransformer::with_config(
+            crate::mlmove_transformer::MLMOVEConfig {
+                num_layers: 1,
+                model_dim: 16,
+                ff_dim: 32,
+                sequence_length: 4,
+                ..Default::default()
+            },
+            Device::Cpu,
+        )
+        .unwrap();
+        let model = ServedModel::from(net).with_movement(movement);
+        let history: Vec<InputVector> = (0..6).map(|i| input(i as f32 * 10.0)).collect();
+        let expected = model.predict_movement(&history[2..]).unwrap();
+        let expected_single = model.predict_movement(&[history[0]; 4]).unwrap();
+        let server = TestServer::start(model);
+
+        let mut stream = TcpStream::connect(server.addr).unwrap();
+        stream.write_all(&PROTOCOL_MAGIC).unwrap();
+        let mut exchange = |frame: Frame| {
+            frame.write_to(&mut stream).unwrap();
+            Frame::read_from(&mut stream).unwrap()
+        };
+        let reply = exchange(Frame::new(MessageType::Hello, 0, 1, vec![]));
+        let handshake = Handshake::decode(&reply.payload).unwrap();
+        assert_eq!(handshake.sequence_length, Some(4));
+
+        // Only the last four inputs of the history are used
+        let reply = exchange(Frame::new(
+            MessageType::PredictMovement,
+            0,
+            2,
+            cs2_common::policy_protocol::encode_inputs(&history),
+        ));
+        assert_eq!(reply.kind, MessageType::Movement);
+        let movement = cs2_common::policy_protocol::decode_movement(&reply.payload).unwrap();
+        assert_eq!(movement.action_index, expected.action_index);
+        assert!((movement.confidence - expected.confidence).abs() < 1e-5);
+        assert!(movement.forward_move.abs() <= 1.0 && movement.side_move.abs() <= 1.0);
+
+        // Right after connecting there is one state, answered as a full window of it
+        let reply = exchange(Frame::new(
+            MessageType::PredictMovement,
+            0,
+            3,
+            cs2_common::policy_protocol::encode_inputs(&history[..1]),
+        ));
+        let movement = cs2_common::policy_protocol::decode_movement(&reply.payload).unwrap();
+        assert_eq!(movement.action_index, expected_single.action_index);
+        assert!((movement.confidence - expected_single.confidence).abs() < 1e-5);
+
+        let reply = exchange(Frame::new(MessageType::PredictMovement, 0, 4, vec![]));
+        let error = ErrorReply::decode(&reply.payload).unwrap();
+        assert_eq!(error.code, ErrorCode::MalformedRequest);
+    }
+
+    #[test]
+    fn test_movement_needs_a_movement_model() {
+        let net = BehaviorNet::new(
+            crate::model::BEHAVIOR_INPUT_DIM,
+            crate::model::BEHAVIOR_OUTPUT_DIM,
+            Device::Cpu,
+        )
+        .unwrap();
+        let model = ServedModel::from(net);
+        assert!(model.handshake().sequence_length.is_none());
+        let error = model.predict_movement(&[input(0.0)]).unwrap_err();
+        assert_eq!(error.code, ErrorCode::Unsupported);
+    }
+
+    #[test]
+    fn test_latency_window() {
+        let mut stats = LatencyStats::default();
+        for us in 1..=LATENCY_WINDOW as u64 + 100 {
+            stats.record(Duration::from_micros(us));
+        }
+        let summary = stats.summary();
+        assert_eq!(summary.requests, LATENCY_WINDOW as u64 + 100);
+        // Percentiles only see the last window, the maximum sees everything
+        assert!(summary.p50_us > 2000.0);
+        assert!((summary.max_us - (LATENCY_WINDOW + 100) as f64).abs() < 1e-6);
     }
 }
@@ -0,0 +1,275 @@
+#!/usr/bin/env python3
+"""Regenerate the MLMOVE parity fixture.
+
+Builds a tiny MLMOVE transformer from PyTorch modules
+(`nn.TransformerEncoderLayer` with `norm_first=True`, `batch_first=True`, one
+head, ReLU, no dropout) with deterministic weights and writes:
+
+- `weights.safetensors`: its state_dict, with `norm_first` in the header metadata
+- `weights.pt`: the same state_dict saved with `torch.save`
+- `post_norm.pt`: `{"state_dict": ..., "norm_first": False}` for a post-norm
+  copy of the model, which the loader must reject
+- `reference.json`: an input sequence, the logits the PyTorch model computes
+  for it and the torch version that computed them
+
+Run it with torch installed. `--without-torch` spells out the same forward
+pass with the standard library instead (`nn.Linear` is `x @ W.T + b`,
+`nn.LayerNorm` uses the biased variance with eps 1e-5, attention is
+softmax(QK^T / sqrt(d)) V) and writes the `.pt` archives by hand; the
+reference then records `"generator": "formulas"` instead of a torch version.
+
+The embedding and output modules are named after the Candle var paths
+(`input_embedding`, `pos_embedding`, `output`), and the encoder layers keep
+the names `nn.TransformerEncoderLayer` gives its parameters.
+
+Nothing in the build or at run time depends on this script.
+"""
+
+import argparse
+import copy
+import json
+import math
+import struct
+import zipfile
+from collections import OrderedDict
+from pathlib import Path
+
+CONFIG = {
+    "num_layers": 2,
+    "num_heads": 1,
+    "model_dim": 8,
+    "ff_dim": 16,
+    "sequence_length": 4,
+    "action_space_size": 97,
+    "input_dim": 10,
+}
+EPS = 1e-5
+HERE = Path(__file__).parent
+
+state = 0x2545F491
+
+
+def rand():
+    """Deterministic uniform values in [-0.5, 0.5)"""
+    global state
+    state = (state * 1103515245 + 12345) % (1 << 31)
+    return state / (1 << 31) - 0.5
+
+
+def f32(value):
+    """Round to the precision the fixture stores"""
+    return struct.unpack("<f", struct.pack("<f", value))[0]
+
+
+def matrix(rows, cols, scale):
+    return [[f32(rand() * scale) for _ in range(cols)] for _ in range(rows)]
+
+
+def vector(size, scale, offset=0.0):
+    return [f32(offset + rand() * scale) for _ in range(size)]
+
+
+def make_weights():
+    """The state_dict of the fixture model, as nested lists"""
+    d, ff = CONFIG["model_dim"], CONFIG["ff_dim"]
+    seq, inputs = CONFIG["sequence_length"], CONFIG["input_dim"]
+    actions = CONFIG["action_space_size"]
+
+    weights = OrderedDict()
+    weights["input_embedding.weight"] = matrix(d, inputs, 0.6)
+    weights["input_embedding.bias"] = vector(d, 0.2)
+    weights["pos_embedding.weight"] = matrix(seq, d, 0.5)
+    for i in range(CONFIG["num_layers"]):
+        p = f"layers.{i}."
+        weights[p + "self_attn.in_proj_weight"] = matrix(3 * d, d, 0.8)
+        weights[p + "self_attn.in_proj_bias"] = vector(3 * d, 0.1)
+        weights[p + "self_attn.out_proj.weight"] = matrix(d, d, 0.6)
+        weights[p + "self_attn.out_proj.bias"] = vector(d, 0.1)
+        weights[p + "linear1.weight"] = matrix(ff, d, 0.6)
+        weights[p + "linear1.bias"] = vector(ff, 0.1)
+        weights[p + "linear2.weight"] = matrix(d, ff, 0.4)
+        weights[p + "linear2.bias"] = vector(d, 0.1)
+        weights[p + "norm1.weight"] = vector(d, 0.4, 1.0)
+        weights[p + "norm1.bias"] = vector(d, 0.2)
+        weights[p + "norm2.weight"] = vector(d, 0.4, 1.0)
+        weights[p + "norm2.bias"] = vector(d, 0.2)
+    weights["output.weight"] = matrix(actions, d, 0.6)
+    weights["output.bias"] = vector(actions, 0.1)
+    return weights
+
+
+def torch_reference(weights, x_in):
+    """Logits of the PyTorch model, written out with `torch.save`"""
+    import torch
+    from torch import nn
+
+    class MLMOVE(nn.Module):
+        def __init__(self, norm_first):
+            super().__init__()
+            d = CONFIG["model_dim"]
+            self.input_embedding = nn.Linear(CONFIG["input_dim"], d)
+            self.pos_embedding = nn.Embedding(CONFIG["sequence_length"], d)
+            layer = nn.TransformerEncoderLayer(
+                d,
+                CONFIG["num_heads"],
+                CONFIG["ff_dim"],
+                dropout=0.0,
+                batch_first=True,
+                norm_first=norm_first,
+            )
+            self.layers = nn.ModuleList(copy.deepcopy(layer) for _ in range(CONFIG["num_layers"]))
+            self.output = nn.Linear(d, CONFIG["action_space_size"])
+
+        def forward(self, x):
+            x = self.input_embedding(x) + self.pos_embedding(torch.arange(x.shape[1]))
+            for layer in self.layers:
+                x = layer(x)
+            return self.output(x[:, -1])
+
+    state_dict = OrderedDict((name, torch.tensor(value)) for name, value in weights.items())
+    model = MLMOVE(norm_first=True)
+    model.load_state_dict(state_dict)
+    model.eval()
+    with torch.no_grad():
+        logits = model(torch.tensor([x_in]))[0].tolist()
+    torch.save(model.state_dict(), HERE / "weights.pt")
+
+    post_norm = MLMOVE(norm_first=False)
+    post_norm.load_state_dict(state_dict)
+    torch.save({"state_dict": post_norm.state_dict(), "norm_first": False}, HERE / "post_norm.pt")
+
+    return logits, {"torch_version": torch.__version__}
+
+
+def linear(x, weight, bias):
+    return [sum(w * v for w, v in zip(row, x)) + b for row, b in zip(weight, bias)]
+
+
+def layer_norm(x, weight, bias):
+    mean = sum(x) / len(x)
+    var = sum((v - mean) ** 2 for v in x) / len(x)
+    return [(v - mean) / math.sqrt(var + EPS) * w + b for v, w, b in zip(x, weight, bias)]
+
+
+def softmax(x):
+    top = max(x)
+    exps = [math.exp(v - top) for v in x]
+    total = sum(exps)
+    return [e / total for e in exps]
+
+
+def formula_reference(weights, x_in):
+    """The PyTorch forward pass spelled out, with the `.pt` archives written by hand"""
+    d = CONFIG["model_dim"]
+    x = [
+        [e + p for e, p in zip(linear(row, weights["input_embedding.weight"], weights["input_embedding.bias"]), pos)]
+        for row, pos in zip(x_in, weights["pos_embedding.weight"])
+    ]
+    for i in range(CONFIG["num_layers"]):
+        p = f"layers.{i}."
+        in_w, in_b = weights[p + "self_attn.in_proj_weight"], weights[p + "self_attn.in_proj_bias"]
+        normed = [layer_norm(row, weights[p + "norm1.weight"], weights[p + "norm1.bias"]) for row in x]
+        q = [linear(row, in_w[:d], in_b[:d]) for row in normed]
+        k = [linear(row, in_w[d : 2 * d], in_b[d : 2 * d]) for row in normed]
+        v = [linear(row, in_w[2 * d :], in_b[2 * d :]) for row in normed]
+        attended = []
+        for qi in q:
+            probs = softmax([sum(a * b for a, b in zip(qi, kj)) / math.sqrt(d) for kj in k])
+            attended.append([sum(pj * vj[c] for pj, vj in zip(probs, v)) for c in range(d)])
+        out = [linear(row, weights[p + "self_attn.out_proj.weight"], weights[p + "self_attn.out_proj.bias"]) for row in attended]
+        x = [[a + b for a, b in zip(r, o)] for r, o in zip(x, out)]
+
+        normed = [layer_norm(row, weights[p + "norm2.weight"], weights[p + "norm2.bias"]) for row in x]
+        hidden = [[max(0.0, h) for h in linear(row, weights[p + "linear1.weight"], weights[p + "linear1.bias"])] for row in normed]
+        out = [linear(row, weights[p + "linear2.weight"], weights[p + "linear2.bias"]) for row in hidden]
+        x = [[a + b for a, b in zip(r, o)] for r, o in zip(x, out)]
+
+    write_pt(HERE / "weights.pt", weights)
+    write_pt(HERE / "post_norm.pt", weights, norm_first=False)
+    logits = linear(x[-1], weights["output.weight"], weights["output.bias"])
+    return logits, {"generator": "formulas"}
+
+
+def flatten(value):
+    rows = value if isinstance(value[0], list) else [value]
+    shape = [len(rows), len(rows[0])] if isinstance(value[0], list) else [len(value)]
+    return shape, b"".join(struct.pack("<f", v) for row in rows for v in row)
+
+
+def write_pt(path, weights, norm_first=None):
+    """A `torch.save` zip archive of `weights` as float32 tensors, pickled with protocol 2
+
+    With `norm_first` set the state_dict is nested in a dict next to that flag.
+    """
+
+    def unicode(text):
+        data = text.encode()
+        return b"X" + struct.pack("<I", len(data)) + data
+
+    def integer(value):
+        return b"J" + struct.pack("<i", value)
+
+    def ints(values):
+        return b"(" + b"".join(integer(v) for v in values) + b"t"
+
+    def ordered_dict():
+        return b"ccollections\nOrderedDict\n)R"
+
+    prefix = path.stem
+    pickle = b"\x80\x02"
+    if norm_first is not None:
+        pickle += b"}(" + unicode("state_dict")
+    pickle += ordered_dict() + b"("
+    storages = []
+    for key, (name, value) in enumerate(weights.items()):
+        shape, data = flatten(value)
+        strides = [shape[1], 1] if len(shape) == 2 else [1]
+        storage = b"(" + unicode("storage") + b"ctorch\nFloatStorage\n" + unicode(str(key)) + unicode("cpu")
+        storage += integer(len(data) // 4) + b"tQ"
+        pickle += unicode(name) + b"ctorch._utils\n_rebuild_tensor_v2\n("
+        pickle += storage + integer(0) + ints(shape) + ints(strides) + b"\x89" + ordered_dict() + b"tR"
+        storages.append(data)
+    pickle += b"u"
+    if norm_first is not None:
+        pickle += unicode("norm_first") + (b"\x88" if norm_first else b"\x89") + b"u"
+    pickle += b"."
+
+    with zipfile.ZipFile(path, "w", zipfile.ZIP_STORED) as archive:
+        archive.writestr(f"{prefix}/data.pkl", pickle)
+        archive.writestr(f"{prefix}/byteorder", "little")
+        for key, data in enumerate(storages):
+            archive.writestr(f"{prefix}/data/{key}", data)
+        archive.writestr(f"{prefix}/version", "3\n")
+
+
+def write_safetensors(path, weights, metadata):
+    header, blob = {"__metadata__": metadata}, b""
+    for name, value in weights.items():
+        shape, data = flatten(value)
+        header[name] = {"dtype": "F32", "shape": shape, "data_offsets": [len(blob), len(blob) + len(data)]}
+        blob += data
+    header_bytes = json.dumps(header, separators=(",", ":")).encode()
+    header_bytes += b" " * (-len(header_bytes) % 8)
+    path.write_bytes(struct.pack("<Q", len(header_bytes)) + header_bytes + blob)
+
+
+def main():
+    parser = argparse.ArgumentParser(description=__doc__.splitlines()[0])
+    parser.add_argument("--without-torch", action="store_true", help="compute the reference without PyTorch")
+    args = parser.parse_args()
+
+    weights = make_weights()
+    x_in = [[f32(rand() * 2.0) for _ in range(CONFIG["input_dim"])] for _ in range(CONFIG["sequence_length"])]
+
+    if args.without_torch:
+        logits, source = formula_reference(weights, x_in)
+    else:
+        logits, source = torch_reference(weights, x_in)
+
+    write_safetensors(HERE / "weights.safetensors", weights, {"norm_first": "True"})
+    reference = {**source, "config": CONFIG, "input": x_in, "logits": logits}
+    (HERE / "reference.json").write_text(json.dumps(reference, indent=2) + "\n")
+
+
+if __name__ == "__main__":
+    main()